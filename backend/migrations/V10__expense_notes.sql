@@ -0,0 +1,2 @@
+-- Optional longer free-text context for an expense
+ALTER TABLE expenses ADD COLUMN notes TEXT;
@@ -0,0 +1,3 @@
+-- Optional email address for expense notifications
+ALTER TABLE members ADD COLUMN email VARCHAR(255);
+ALTER TABLE members ADD COLUMN notify_on_expense BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,43 @@
+-- Cheap per-group change counter used for ETags.
+-- Bumped by triggers on every change to the group or its members, expenses and splits.
+ALTER TABLE groups ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
+
+CREATE OR REPLACE FUNCTION bump_group_version_on_group() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER groups_bump_version
+    BEFORE UPDATE OF name, currency ON groups
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version_on_group();
+
+CREATE OR REPLACE FUNCTION bump_group_version() RETURNS TRIGGER AS $$
+DECLARE
+    gid UUID;
+BEGIN
+    IF TG_TABLE_NAME = 'expense_splits' THEN
+        SELECT group_id INTO gid FROM expenses
+        WHERE id = COALESCE(NEW.expense_id, OLD.expense_id);
+    ELSIF TG_OP = 'DELETE' THEN
+        gid := OLD.group_id;
+    ELSE
+        gid := NEW.group_id;
+    END IF;
+    UPDATE groups SET version = version + 1 WHERE id = gid;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER members_bump_version
+    AFTER INSERT OR UPDATE OR DELETE ON members
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version();
+
+CREATE TRIGGER expenses_bump_version
+    AFTER INSERT OR UPDATE OR DELETE ON expenses
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version();
+
+CREATE TRIGGER expense_splits_bump_version
+    AFTER INSERT OR UPDATE OR DELETE ON expense_splits
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version();
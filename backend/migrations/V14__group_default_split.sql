@@ -0,0 +1,9 @@
+-- Split used by new expenses that don't specify split_between:
+-- {"split_type": "equal" | "shares" | ..., "splits": [{"member_id": ..., "share": ...}]}
+ALTER TABLE groups ADD COLUMN default_split JSONB;
+
+-- default_split is part of the group response, so changes must invalidate its ETag
+DROP TRIGGER groups_bump_version ON groups;
+CREATE TRIGGER groups_bump_version
+    BEFORE UPDATE OF name, currency, default_split ON groups
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version_on_group();
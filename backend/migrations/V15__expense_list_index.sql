@@ -0,0 +1,7 @@
+-- Expense lists filter by group and sort newest first (expense_date DESC, created_at DESC).
+-- members(group_id), expense_splits(expense_id) and expense_splits(member_id) are
+-- already indexed by V1; this composite index replaces the plain expenses(group_id)
+-- one, which it covers as a prefix.
+CREATE INDEX IF NOT EXISTS idx_expenses_group_date
+    ON expenses(group_id, expense_date DESC, created_at DESC);
+DROP INDEX IF EXISTS idx_expenses_group_id;
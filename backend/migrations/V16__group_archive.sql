@@ -0,0 +1,7 @@
+-- Archived groups are read-only and exempt from inactivity cleanup
+ALTER TABLE groups ADD COLUMN archived_at TIMESTAMPTZ;
+
+DROP TRIGGER groups_bump_version ON groups;
+CREATE TRIGGER groups_bump_version
+    BEFORE UPDATE OF name, currency, default_split, archived_at ON groups
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version_on_group();
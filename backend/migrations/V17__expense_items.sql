@@ -0,0 +1,12 @@
+-- Line items of an itemized expense. The expense amount is the item sum and its
+-- splits are the derived exact per-member shares; items are kept for display.
+CREATE TABLE expense_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    expense_id UUID NOT NULL REFERENCES expenses(id) ON DELETE CASCADE,
+    position INT NOT NULL,
+    description VARCHAR(255) NOT NULL,
+    amount DECIMAL(12, 2) NOT NULL,
+    member_ids UUID[] NOT NULL
+);
+
+CREATE INDEX idx_expense_items_expense_id ON expense_items(expense_id);
@@ -0,0 +1,5 @@
+-- Richer payment info: preferred method ('paypal', 'iban' or 'venmo'), Venmo handle
+-- and a free-text note shown to whoever pays this member
+ALTER TABLE members ADD COLUMN preferred_payment_method VARCHAR(20);
+ALTER TABLE members ADD COLUMN venmo_handle VARCHAR(64);
+ALTER TABLE members ADD COLUMN payment_note VARCHAR(255);
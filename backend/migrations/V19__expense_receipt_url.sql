@@ -0,0 +1,2 @@
+-- Optional link to a photo of the receipt, stored elsewhere
+ALTER TABLE expenses ADD COLUMN receipt_url TEXT;
@@ -0,0 +1,8 @@
+-- Uploaded receipt files (the file itself lives in the configured storage backend)
+CREATE TABLE expense_receipts (
+    expense_id UUID PRIMARY KEY REFERENCES expenses(id) ON DELETE CASCADE,
+    storage_key TEXT NOT NULL,
+    content_type VARCHAR(50) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    uploaded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
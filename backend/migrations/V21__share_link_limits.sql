@@ -0,0 +1,4 @@
+-- Optional limits on share links: expiry time and number of redemptions
+ALTER TABLE share_links ADD COLUMN expires_at TIMESTAMPTZ;
+ALTER TABLE share_links ADD COLUMN max_uses INTEGER;
+ALTER TABLE share_links ADD COLUMN use_count INTEGER NOT NULL DEFAULT 0;
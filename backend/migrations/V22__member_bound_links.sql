@@ -0,0 +1,5 @@
+-- Share links (and the tokens they issue) can be bound to a member; actions taken
+-- with such a token are attributed to that member.
+ALTER TABLE share_links ADD COLUMN member_id UUID REFERENCES members(id) ON DELETE CASCADE;
+ALTER TABLE expenses ADD COLUMN created_by UUID REFERENCES members(id) ON DELETE SET NULL;
+ALTER TABLE expenses ADD COLUMN updated_by UUID REFERENCES members(id) ON DELETE SET NULL;
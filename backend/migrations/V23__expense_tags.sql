@@ -0,0 +1,30 @@
+-- Free-form tags on expenses (normalized: trimmed, lowercase, without '#')
+CREATE TABLE expense_tags (
+    expense_id UUID NOT NULL REFERENCES expenses(id) ON DELETE CASCADE,
+    tag VARCHAR(50) NOT NULL,
+    PRIMARY KEY (expense_id, tag)
+);
+
+CREATE INDEX idx_expense_tags_tag ON expense_tags(tag);
+
+-- Tag changes bump the group version like split changes do
+CREATE OR REPLACE FUNCTION bump_group_version() RETURNS TRIGGER AS $$
+DECLARE
+    gid UUID;
+BEGIN
+    IF TG_TABLE_NAME IN ('expense_splits', 'expense_tags') THEN
+        SELECT group_id INTO gid FROM expenses
+        WHERE id = COALESCE(NEW.expense_id, OLD.expense_id);
+    ELSIF TG_OP = 'DELETE' THEN
+        gid := OLD.group_id;
+    ELSE
+        gid := NEW.group_id;
+    END IF;
+    UPDATE groups SET version = version + 1 WHERE id = gid;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER expense_tags_bump_version
+    AFTER INSERT OR UPDATE OR DELETE ON expense_tags
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version();
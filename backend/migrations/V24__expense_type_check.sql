@@ -0,0 +1,6 @@
+-- Unknown expense types were always balanced as regular expenses; make that explicit
+UPDATE expenses SET expense_type = 'expense'
+WHERE expense_type NOT IN ('expense', 'transfer', 'income');
+
+ALTER TABLE expenses ADD CONSTRAINT expenses_expense_type_check
+    CHECK (expense_type IN ('expense', 'transfer', 'income'));
@@ -0,0 +1,9 @@
+-- Exchange rates by publication date: 1 unit of base = rate units of quote
+CREATE TABLE exchange_rates (
+    base VARCHAR(3) NOT NULL,
+    quote VARCHAR(3) NOT NULL,
+    date DATE NOT NULL,
+    rate DOUBLE PRECISION NOT NULL CHECK (rate > 0),
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (base, quote, date)
+);
@@ -0,0 +1,17 @@
+-- Expense changes with the state before and after, so recent actions can be undone
+CREATE TABLE activity_log (
+    id BIGSERIAL PRIMARY KEY,
+    group_id UUID NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    action VARCHAR(30) NOT NULL,
+    expense_id UUID NOT NULL,
+    -- SHA-256 of the token that performed the action (tokens themselves aren't stored)
+    token_hash VARCHAR(64) NOT NULL,
+    member_id UUID REFERENCES members(id) ON DELETE SET NULL,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    undone_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_activity_log_group ON activity_log(group_id, created_at DESC);
+CREATE INDEX idx_activity_log_expense ON activity_log(expense_id);
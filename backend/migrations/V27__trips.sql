@@ -0,0 +1,17 @@
+-- Optional buckets (trips, billing periods) for the expenses of long-lived groups
+CREATE TABLE trips (
+    id UUID PRIMARY KEY,
+    group_id UUID NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    start_date DATE NOT NULL,
+    end_date DATE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (end_date IS NULL OR end_date >= start_date)
+);
+
+CREATE INDEX idx_trips_group_id ON trips(group_id);
+
+-- Deleting a trip keeps its expenses, they just no longer belong to a trip
+ALTER TABLE expenses ADD COLUMN trip_id UUID REFERENCES trips(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_expenses_trip_id ON expenses(trip_id);
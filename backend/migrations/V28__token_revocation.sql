@@ -0,0 +1,2 @@
+-- Tokens issued (iat) before this instant are rejected; set when the owner resets sharing
+ALTER TABLE groups ADD COLUMN tokens_revoked_before TIMESTAMPTZ;
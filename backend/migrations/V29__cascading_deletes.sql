@@ -0,0 +1,18 @@
+-- Deleting a group or expense relies on these foreign keys to remove the child rows.
+-- The initial schema used CREATE TABLE IF NOT EXISTS, so a database whose tables
+-- predate it may lack them: drop orphaned rows, then (re)create the constraints.
+DELETE FROM expense_splits s WHERE NOT EXISTS (SELECT 1 FROM expenses e WHERE e.id = s.expense_id);
+DELETE FROM expenses e WHERE NOT EXISTS (SELECT 1 FROM groups g WHERE g.id = e.group_id);
+DELETE FROM members m WHERE NOT EXISTS (SELECT 1 FROM groups g WHERE g.id = m.group_id);
+
+ALTER TABLE expense_splits DROP CONSTRAINT IF EXISTS expense_splits_expense_id_fkey;
+ALTER TABLE expense_splits ADD CONSTRAINT expense_splits_expense_id_fkey
+    FOREIGN KEY (expense_id) REFERENCES expenses(id) ON DELETE CASCADE;
+
+ALTER TABLE expenses DROP CONSTRAINT IF EXISTS expenses_group_id_fkey;
+ALTER TABLE expenses ADD CONSTRAINT expenses_group_id_fkey
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE;
+
+ALTER TABLE members DROP CONSTRAINT IF EXISTS members_group_id_fkey;
+ALTER TABLE members ADD CONSTRAINT members_group_id_fkey
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE;
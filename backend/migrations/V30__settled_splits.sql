@@ -0,0 +1,3 @@
+-- A member's share of an expense can be settled on its own, outside the net balances
+ALTER TABLE expense_splits ADD COLUMN settled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE expense_splits ADD COLUMN settled_at TIMESTAMPTZ;
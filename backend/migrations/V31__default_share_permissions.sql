@@ -0,0 +1,9 @@
+-- Permissions new share links get for fields the request leaves out:
+-- {"can_delete_group": false, "can_manage_members": false, ...}
+ALTER TABLE groups ADD COLUMN default_share_permissions JSONB;
+
+-- default_share_permissions is part of the group response, so changes must invalidate its ETag
+DROP TRIGGER groups_bump_version ON groups;
+CREATE TRIGGER groups_bump_version
+    BEFORE UPDATE OF name, currency, default_split, archived_at, default_share_permissions ON groups
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version_on_group();
@@ -0,0 +1,4 @@
+-- A refund is recorded as income linked to the expense it (partly) reverses.
+-- Refunds go away with their expense.
+ALTER TABLE expenses ADD COLUMN refund_of UUID REFERENCES expenses(id) ON DELETE CASCADE;
+CREATE INDEX idx_expenses_refund_of ON expenses(refund_of);
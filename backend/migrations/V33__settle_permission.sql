@@ -0,0 +1,9 @@
+-- Recording transfers gets its own permission. Until now it came with
+-- can_add_expenses, so existing links and group defaults keep that value.
+ALTER TABLE share_links ADD COLUMN can_settle BOOLEAN NOT NULL DEFAULT TRUE;
+UPDATE share_links SET can_settle = can_add_expenses;
+
+UPDATE groups
+SET default_share_permissions = default_share_permissions
+    || jsonb_build_object('can_settle', COALESCE(default_share_permissions->'can_add_expenses', 'true'::jsonb))
+WHERE default_share_permissions IS NOT NULL;
@@ -0,0 +1,20 @@
+-- Custom display order of a group's members. New members get the next value of
+-- a shared sequence, so they are listed after everyone else (in creation order);
+-- reordering renumbers a group's members from 1.
+ALTER TABLE members ADD COLUMN display_order BIGINT;
+
+UPDATE members m SET display_order = o.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY group_id ORDER BY created_at, id) AS position
+    FROM members
+) o
+WHERE o.id = m.id;
+
+CREATE SEQUENCE members_display_order_seq OWNED BY members.display_order;
+SELECT setval('members_display_order_seq', COALESCE((SELECT MAX(display_order) FROM members), 0) + 1, false);
+
+ALTER TABLE members
+    ALTER COLUMN display_order SET DEFAULT nextval('members_display_order_seq'),
+    ALTER COLUMN display_order SET NOT NULL;
+
+CREATE INDEX idx_members_display_order ON members(group_id, display_order);
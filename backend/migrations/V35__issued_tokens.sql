@@ -0,0 +1,19 @@
+-- Registry of issued tokens, so a group can list and revoke them one by one.
+-- Tokens issued before it existed are not listed and stay valid.
+CREATE TABLE issued_tokens (
+    jti TEXT PRIMARY KEY,
+    group_id UUID NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    member_id UUID REFERENCES members(id) ON DELETE SET NULL,
+    can_delete_group BOOLEAN NOT NULL,
+    can_manage_members BOOLEAN NOT NULL,
+    can_update_payment BOOLEAN NOT NULL,
+    can_add_expenses BOOLEAN NOT NULL,
+    can_edit_expenses BOOLEAN NOT NULL,
+    can_settle BOOLEAN NOT NULL,
+    issued_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_issued_tokens_group_id ON issued_tokens(group_id);
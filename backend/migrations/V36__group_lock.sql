@@ -0,0 +1,8 @@
+-- Locked groups stay readable but reject every edit until unlocked
+ALTER TABLE groups ADD COLUMN locked BOOLEAN NOT NULL DEFAULT false;
+
+-- locked is part of the group response, so changes must invalidate its ETag
+DROP TRIGGER groups_bump_version ON groups;
+CREATE TRIGGER groups_bump_version
+    BEFORE UPDATE OF name, currency, default_split, archived_at, default_share_permissions, locked ON groups
+    FOR EACH ROW EXECUTE FUNCTION bump_group_version_on_group();
@@ -0,0 +1,15 @@
+-- Banker's rounding of a double to `decimals` places, exactly like
+-- currency::round_half_even in the backend, so balances computed in SQL round
+-- the same way as those computed in Rust
+CREATE FUNCTION round_half_even(value DOUBLE PRECISION, decimals INTEGER)
+RETURNS DOUBLE PRECISION
+LANGUAGE sql IMMUTABLE STRICT AS $$
+    SELECT CASE
+        -- Values within float noise of .5 are exact ties
+        WHEN abs((scaled - floor(scaled)) - 0.5) < 1e-9 THEN
+            CASE WHEN floor(scaled)::numeric % 2 = 0 THEN floor(scaled) ELSE floor(scaled) + 1 END
+        ELSE round(scaled)
+    END / factor
+    FROM (SELECT value * factor AS scaled, factor
+          FROM (SELECT power(10::float8, decimals) AS factor) f) s
+$$;
@@ -0,0 +1,3 @@
+-- Version of the group's signing key; bumping it invalidates every token signed
+-- with an older one
+ALTER TABLE groups ADD COLUMN key_version INTEGER NOT NULL DEFAULT 1;
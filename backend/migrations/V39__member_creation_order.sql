@@ -0,0 +1,18 @@
+-- Order in which members were added. Members created together (e.g. with their
+-- group) share a created_at, so splits are ordered, and odd minor units handed
+-- out, by this instead. Existing members keep the order they had (created_at, id).
+ALTER TABLE members ADD COLUMN creation_order BIGINT;
+
+UPDATE members m SET creation_order = o.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (ORDER BY created_at, id) AS position
+    FROM members
+) o
+WHERE o.id = m.id;
+
+CREATE SEQUENCE members_creation_order_seq OWNED BY members.creation_order;
+SELECT setval('members_creation_order_seq', COALESCE((SELECT MAX(creation_order) FROM members), 0) + 1, false);
+
+ALTER TABLE members
+    ALTER COLUMN creation_order SET DEFAULT nextval('members_creation_order_seq'),
+    ALTER COLUMN creation_order SET NOT NULL;
@@ -0,0 +1,135 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::auth::GroupAuth;
+use crate::db;
+use crate::models::Expense;
+
+pub const EXPENSE_CREATED: &str = "expense_created";
+pub const EXPENSE_UPDATED: &str = "expense_updated";
+pub const EXPENSE_DELETED: &str = "expense_deleted";
+
+/// Every action that gets logged.
+pub const ACTIONS: [&str; 3] = [EXPENSE_CREATED, EXPENSE_UPDATED, EXPENSE_DELETED];
+
+/// How long after an action it can still be undone. Defaults to 5 minutes;
+/// override with `UNDO_WINDOW_SECS`.
+static UNDO_WINDOW_SECS: Lazy<i64> = Lazy::new(|| {
+    std::env::var("UNDO_WINDOW_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(5 * 60)
+});
+
+/// One logged change. `before`/`after` are full `Expense` snapshots
+/// (`before` is absent for creations, `after` for deletions).
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ActivityEntry {
+    pub id: i64,
+    pub action: String,
+    pub expense_id: Uuid,
+    pub member_id: Option<Uuid>,
+    pub before: Option<sqlx::types::Json<Expense>>,
+    pub after: Option<sqlx::types::Json<Expense>>,
+    pub created_at: DateTime<Utc>,
+    pub undone_at: Option<DateTime<Utc>>,
+}
+
+/// One page of a group's activity log, newest first.
+#[derive(Debug, Serialize)]
+pub struct ActivityPage {
+    /// Number of matching entries across all pages.
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub entries: Vec<ActivityEntry>,
+}
+
+/// Log a change to an expense. The change itself has already been made, so a
+/// failure here is only reported, never returned.
+pub async fn record(
+    auth: &GroupAuth,
+    action: &str,
+    expense_id: Uuid,
+    before: Option<&Expense>,
+    after: Option<&Expense>,
+) {
+    let result = sqlx::query(
+        "INSERT INTO activity_log (group_id, action, expense_id, token_hash, member_id, before, after)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(auth.group_id)
+    .bind(action)
+    .bind(expense_id)
+    .bind(&auth.token_hash)
+    .bind(auth.member_id)
+    .bind(before.map(sqlx::types::Json))
+    .bind(after.map(sqlx::types::Json))
+    .execute(db::get_pool())
+    .await;
+    if let Err(e) = result {
+        eprintln!("Failed to record activity: {}", e);
+    }
+}
+
+/// The newest action of this token that is still inside the undo window and not undone yet.
+pub async fn last_undoable(auth: &GroupAuth) -> Result<Option<ActivityEntry>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, action, expense_id, member_id, before, after, created_at, undone_at
+         FROM activity_log
+         WHERE group_id = $1 AND token_hash = $2 AND undone_at IS NULL
+           AND created_at > NOW() - make_interval(secs => $3)
+         ORDER BY created_at DESC, id DESC LIMIT 1",
+    )
+    .bind(auth.group_id)
+    .bind(&auth.token_hash)
+    .bind(*UNDO_WINDOW_SECS as f64)
+    .fetch_optional(db::get_pool())
+    .await
+}
+
+/// A page of the group's log, newest first, optionally only one action and only
+/// entries made between `from` and `to` (inclusive, UTC dates).
+pub async fn list(
+    group_id: Uuid,
+    action: Option<&str>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    limit: i64,
+    offset: i64,
+) -> Result<ActivityPage, sqlx::Error> {
+    const FILTER: &str = "group_id = $1 AND ($2::text IS NULL OR action = $2)
+         AND ($3::date IS NULL OR (created_at AT TIME ZONE 'UTC')::date >= $3)
+         AND ($4::date IS NULL OR (created_at AT TIME ZONE 'UTC')::date <= $4)";
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM activity_log WHERE {}", FILTER))
+        .bind(group_id)
+        .bind(action)
+        .bind(from)
+        .bind(to)
+        .fetch_one(db::get_pool())
+        .await?;
+    let entries = sqlx::query_as(&format!(
+        "SELECT id, action, expense_id, member_id, before, after, created_at, undone_at
+         FROM activity_log WHERE {}
+         ORDER BY created_at DESC, id DESC LIMIT $5 OFFSET $6",
+        FILTER
+    ))
+    .bind(group_id)
+    .bind(action)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db::get_pool())
+    .await?;
+    Ok(ActivityPage {
+        total,
+        limit,
+        offset,
+        entries,
+    })
+}
@@ -1,14 +1,176 @@
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+};
 use once_cell::sync::Lazy;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
+use std::collections::HashMap;
 use uuid::Uuid;
 
-// In production, load this from environment variable
-static JWT_SECRET: Lazy<String> = Lazy::new(|| {
-    std::env::var("JWT_SECRET").unwrap_or_else(|_| "dev-secret-change-in-production".to_string())
-});
+use crate::db;
+
+/// Signing and verification keys, selected by `JWT_ALG`:
+/// - `HS256` (default): shared secret from `JWT_SECRET`.
+/// - `RS256`: private key from `JWT_PRIVATE_KEY` for signing, public key from
+///   `JWT_PUBLIC_KEY` for verification. Each is either an inline PEM or a path to a PEM file.
+///
+/// Issued tokens carry a `kid` header naming the current key (`JWT_KEY_ID`, default `1`).
+/// To rotate, set a new key and id and move the old one to `JWT_PREVIOUS_KEYS`
+/// (comma-separated `kid=secret`, or `kid=public-key-pem-or-path` for RS256):
+/// old tokens keep validating until they expire or the entry is removed.
+///
+/// With HS256, tokens are not signed with the secret itself but with a key derived
+/// from it per group and key version (see `group_key`), marked by a `kid` of the form
+/// `<kid>+g<version>`. Bumping `groups.key_version` invalidates every token the group
+/// had. Tokens issued before that are still accepted with the plain secret, and
+/// `<kid>+g` tokens from before key versions count as version 1.
+///
+/// `JWT_ISSUER` and `JWT_AUDIENCE` tie tokens to one deployment: when set, issued
+/// tokens carry them as `iss`/`aud` and tokens without matching claims are rejected.
+/// Unset, the claims are neither added nor checked. Tokens issued before they were
+/// set have no claims and stop working once they are, so turn them on for a new
+/// deployment or together with a key rotation that is meant to end old sessions
+/// anyway; unsetting them again accepts every token once more.
+struct JwtKeys {
+    algorithm: Algorithm,
+    current_kid: String,
+    encoding: EncodingKey,
+    /// Verification keys by key id, including the current one.
+    decoding: HashMap<String, DecodingKey>,
+    /// HS256 secrets by key id, for deriving per-group keys. Empty for RS256.
+    secrets: HashMap<String, Vec<u8>>,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+/// Marks a `kid` whose token is signed with a per-group key; the key version follows.
+const GROUP_KID_SUFFIX: &str = "+g";
+
+static JWT_KEYS: Lazy<JwtKeys> =
+    Lazy::new(|| load_keys().unwrap_or_else(|e| panic!("Invalid JWT configuration: {}", e)));
+
+/// Load the JWT keys eagerly so a misconfiguration fails at startup.
+pub fn init_keys() {
+    Lazy::force(&JWT_KEYS);
+}
+
+/// Turn a PEM held inline or in a file into bytes.
+fn pem_bytes(name: &str, value: &str) -> Result<Vec<u8>, String> {
+    if value.trim_start().starts_with("-----BEGIN") {
+        // Allow `\n` escapes for env files that can't hold multi-line values
+        Ok(value.replace("\\n", "\n").into_bytes())
+    } else {
+        std::fs::read(value.trim()).map_err(|e| format!("Failed to read {}: {}", name, e))
+    }
+}
+
+/// Read a PEM from an env var holding either the PEM itself or a path to it.
+fn read_pem(var: &str) -> Result<Vec<u8>, String> {
+    let value = std::env::var(var).map_err(|_| format!("{} must be set", var))?;
+    pem_bytes(var, &value)
+}
+
+fn decoding_key(algorithm: Algorithm, name: &str, value: &str) -> Result<DecodingKey, String> {
+    match algorithm {
+        Algorithm::RS256 => DecodingKey::from_rsa_pem(&pem_bytes(name, value)?)
+            .map_err(|e| format!("Invalid {}: {}", name, e)),
+        _ => Ok(DecodingKey::from_secret(value.as_bytes())),
+    }
+}
+
+fn load_keys() -> Result<JwtKeys, String> {
+    let alg = std::env::var("JWT_ALG").unwrap_or_else(|_| "HS256".to_string());
+    let mut current_secret = None;
+    let (algorithm, encoding, current_decoding) = match alg.to_ascii_uppercase().as_str() {
+        "HS256" => {
+            // In production, load this from environment variable
+            let secret = std::env::var("JWT_SECRET")
+                .unwrap_or_else(|_| "dev-secret-change-in-production".to_string());
+            current_secret = Some(secret.clone().into_bytes());
+            (
+                Algorithm::HS256,
+                EncodingKey::from_secret(secret.as_bytes()),
+                DecodingKey::from_secret(secret.as_bytes()),
+            )
+        }
+        "RS256" => {
+            let private_pem = read_pem("JWT_PRIVATE_KEY")?;
+            let public_pem = read_pem("JWT_PUBLIC_KEY")?;
+            (
+                Algorithm::RS256,
+                EncodingKey::from_rsa_pem(&private_pem)
+                    .map_err(|e| format!("Invalid JWT_PRIVATE_KEY: {}", e))?,
+                DecodingKey::from_rsa_pem(&public_pem)
+                    .map_err(|e| format!("Invalid JWT_PUBLIC_KEY: {}", e))?,
+            )
+        }
+        other => return Err(format!("Unsupported JWT_ALG '{}' (expected HS256 or RS256)", other)),
+    };
+
+    let current_kid = std::env::var("JWT_KEY_ID")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "1".to_string());
+
+    let mut decoding = HashMap::new();
+    let mut secrets = HashMap::new();
+    if let Ok(previous) = std::env::var("JWT_PREVIOUS_KEYS") {
+        for entry in previous.split(',').filter(|e| !e.trim().is_empty()) {
+            let (kid, value) = entry
+                .split_once('=')
+                .ok_or_else(|| "JWT_PREVIOUS_KEYS entries must look like kid=key".to_string())?;
+            let kid = kid.trim();
+            if kid.is_empty() || kid == current_kid {
+                return Err(format!("JWT_PREVIOUS_KEYS has an invalid key id '{}'", kid));
+            }
+            let name = format!("JWT_PREVIOUS_KEYS[{}]", kid);
+            if algorithm == Algorithm::HS256 {
+                secrets.insert(kid.to_string(), value.trim().as_bytes().to_vec());
+            }
+            decoding.insert(kid.to_string(), decoding_key(algorithm, &name, value.trim())?);
+        }
+    }
+    decoding.insert(current_kid.clone(), current_decoding);
+    if let Some(secret) = current_secret {
+        secrets.insert(current_kid.clone(), secret);
+    }
+
+    let claim = |var: &str| {
+        std::env::var(var)
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    Ok(JwtKeys {
+        algorithm,
+        current_kid,
+        encoding,
+        decoding,
+        secrets,
+        issuer: claim("JWT_ISSUER"),
+        audience: claim("JWT_AUDIENCE"),
+    })
+}
+
+/// Signing key of one group: HMAC-SHA256(secret, "group:<group_id>:v<version>").
+/// A leaked group key only lets someone forge tokens for that group, until the
+/// group moves on to the next version. `None` is the unversioned key of `+g`
+/// tokens issued before key versions.
+fn group_key(secret: &[u8], group_id: Uuid, version: Option<i32>) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    match version {
+        Some(version) => mac.update(format!("group:{}:v{}", group_id, version).as_bytes()),
+        None => mac.update(format!("group:{}", group_id).as_bytes()),
+    }
+    mac.finalize().into_bytes().to_vec()
+}
 
 /// Granular permissions stored in the JWT.
 /// All fields are `Option<bool>` for backward compatibility:
@@ -55,6 +217,15 @@ pub struct Permissions {
         skip_serializing_if = "Option::is_none"
     )]
     pub can_edit_expenses: Option<bool>,
+    /// Recording transfers and settling up. Tokens from before it existed could
+    /// do that with `can_add_expenses`, so a missing value follows that one.
+    #[serde(
+        default,
+        rename = "st",
+        alias = "can_settle",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub can_settle: Option<bool>,
 }
 
 impl Permissions {
@@ -66,6 +237,7 @@ impl Permissions {
             can_update_payment: Some(true),
             can_add_expenses: Some(true),
             can_edit_expenses: Some(true),
+            can_settle: Some(true),
         }
     }
 
@@ -89,6 +261,9 @@ impl Permissions {
     pub fn has_edit_expenses(&self) -> bool {
         Self::resolve(self.can_edit_expenses)
     }
+    pub fn has_settle(&self) -> bool {
+        self.can_settle.unwrap_or_else(|| self.has_add_expenses())
+    }
 
     /// Returns true if every permission is granted.
     pub fn has_all(&self) -> bool {
@@ -97,6 +272,7 @@ impl Permissions {
             && self.has_update_payment()
             && self.has_add_expenses()
             && self.has_edit_expenses()
+            && self.has_settle()
     }
 
     /// Cap each permission by the caller's own permissions (share link can't escalate).
@@ -107,6 +283,7 @@ impl Permissions {
             can_update_payment: Some(self.has_update_payment() && caller.has_update_payment()),
             can_add_expenses: Some(self.has_add_expenses() && caller.has_add_expenses()),
             can_edit_expenses: Some(self.has_edit_expenses() && caller.has_edit_expenses()),
+            can_settle: Some(self.has_settle() && caller.has_settle()),
         }
     }
 
@@ -119,10 +296,73 @@ impl Permissions {
             can_update_payment: Some(self.has_update_payment() || other.has_update_payment()),
             can_add_expenses: Some(self.has_add_expenses() || other.has_add_expenses()),
             can_edit_expenses: Some(self.has_edit_expenses() || other.has_edit_expenses()),
+            can_settle: Some(self.has_settle() || other.has_settle()),
         }
     }
 }
 
+/// Permission fields exactly as they appear in a token, without the defaults
+/// `Permissions` fills in for missing fields.
+#[derive(Deserialize)]
+struct StatedPermissions {
+    #[serde(rename = "dg", alias = "can_delete_group")]
+    can_delete_group: Option<bool>,
+    #[serde(rename = "mm", alias = "can_manage_members")]
+    can_manage_members: Option<bool>,
+    #[serde(rename = "up", alias = "can_update_payment")]
+    can_update_payment: Option<bool>,
+    #[serde(rename = "ae", alias = "can_add_expenses")]
+    can_add_expenses: Option<bool>,
+    #[serde(rename = "ee", alias = "can_edit_expenses")]
+    can_edit_expenses: Option<bool>,
+    #[serde(rename = "st", alias = "can_settle")]
+    can_settle: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct StatedClaims {
+    #[serde(default, rename = "p", alias = "permissions")]
+    permissions: Option<StatedPermissions>,
+}
+
+/// The permissions a token explicitly grants, for merging it into another token.
+/// Fields the token doesn't state (tokens issued before permissions existed) are
+/// not granted here, unlike in `Claims::effective_permissions`, so a merge can only
+/// carry over what the other token actually says. The second value is false when
+/// any field was missing. Call only on tokens that passed `validate_token`.
+pub fn stated_permissions(
+    token: &str,
+) -> Result<(Permissions, bool), jsonwebtoken::errors::Error> {
+    let stated = decode::<StatedClaims>(token, &DecodingKey::from_secret(&[]), &unverified(&JWT_KEYS))?
+        .claims
+        .permissions;
+    let fields = match &stated {
+        Some(p) => [
+            p.can_delete_group,
+            p.can_manage_members,
+            p.can_update_payment,
+            p.can_add_expenses,
+            p.can_edit_expenses,
+        ],
+        None => [None; 5],
+    };
+    let complete = fields.iter().all(Option::is_some);
+    let [dg, mm, up, ae, ee] = fields.map(|f| Some(f.unwrap_or(false)));
+    // Tokens from before `can_settle` settled with `can_add_expenses`
+    let st = stated.as_ref().and_then(|p| p.can_settle).or(ae);
+    Ok((
+        Permissions {
+            can_delete_group: dg,
+            can_manage_members: mm,
+            can_update_payment: up,
+            can_add_expenses: ae,
+            can_edit_expenses: ee,
+            can_settle: st,
+        },
+        complete,
+    ))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     #[serde(rename = "g", alias = "group_id")]
@@ -132,17 +372,69 @@ pub struct Claims {
     #[serde(default, rename = "p", alias = "permissions")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub permissions: Option<Permissions>,
+    /// Member the token acts as, for share links bound to a member.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<Uuid>,
+    /// Unique token id (absent in older tokens).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// Issue time in seconds since the epoch (absent in older tokens).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    /// `JWT_ISSUER` at the time the token was issued, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// `JWT_AUDIENCE` at the time the token was issued, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    /// Version of the group key the token is signed with, carried in the `kid`
+    /// header rather than the claims. `None` for tokens not signed with a group key.
+    #[serde(skip)]
+    pub key_version: Option<i32>,
 }
 
 impl Claims {
     pub fn effective_permissions(&self) -> Permissions {
         self.permissions.clone().unwrap_or_else(Permissions::all)
     }
+
+    /// Whether the token was revoked on its own (`DELETE /groups/current/tokens/<jti>`),
+    /// the group revoked every token issued up to the second this one was (see
+    /// `POST /groups/current/new-owner-token`) or it is signed with an older group
+    /// key. Tokens without `iat` predate any revocation, so they count as revoked
+    /// once the group has one.
+    pub async fn is_revoked(&self) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query_as::<_, (Option<DateTime<Utc>>, i32, bool)>(
+            "SELECT g.tokens_revoked_before, g.key_version,
+                    EXISTS(SELECT 1 FROM issued_tokens t WHERE t.jti = $2 AND t.group_id = g.id AND t.revoked_at IS NOT NULL)
+             FROM groups g WHERE g.id = $1",
+        )
+        .bind(self.group_id)
+        .bind(&self.jti)
+        .fetch_optional(db::get_pool())
+        .await?;
+        let Some((revoked_before, key_version, revoked)) = row else {
+            return Ok(false);
+        };
+        Ok(revoked
+            || self.key_version.is_some_and(|version| version < key_version)
+            || revoked_before.is_some_and(|before| {
+                self.iat.is_none_or(|iat| (iat as i64) <= before.timestamp())
+            }))
+    }
 }
 
 pub struct GroupAuth {
     pub group_id: Uuid,
     pub permissions: Permissions,
+    /// Member the token is bound to (`sub` claim); `None` for anonymous group tokens.
+    pub member_id: Option<Uuid>,
+    /// Expiry of the presented token (seconds since the epoch).
+    pub exp: usize,
+    pub jti: Option<String>,
+    /// Hex SHA-256 of the presented token, to recognize the same token across
+    /// requests without storing it.
+    pub token_hash: String,
 }
 
 #[derive(Debug)]
@@ -157,51 +449,429 @@ impl<'r> FromRequest<'r> for GroupAuth {
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         // Check Authorization header: Bearer <token>
-        let auth_header = request.headers().get_one("Authorization");
-
-        match auth_header {
-            Some(header) => {
-                if let Some(token) = header.strip_prefix("Bearer ") {
-                    match validate_token(token) {
-                        Ok(claims) => Outcome::Success(GroupAuth {
-                            group_id: claims.group_id,
-                            permissions: claims.effective_permissions(),
-                        }),
-                        Err(_) => Outcome::Error((Status::Unauthorized, AuthError::Invalid)),
-                    }
-                } else {
-                    Outcome::Error((Status::Unauthorized, AuthError::Invalid))
-                }
+        let Some(header) = request.headers().get_one("Authorization") else {
+            return Outcome::Error((Status::Unauthorized, AuthError::Missing));
+        };
+        let Some(token) = header.strip_prefix("Bearer ") else {
+            return Outcome::Error((Status::Unauthorized, AuthError::Invalid));
+        };
+        let Ok(claims) = validate_token(token) else {
+            return Outcome::Error((Status::Unauthorized, AuthError::Invalid));
+        };
+        match claims.is_revoked().await {
+            Ok(false) => {}
+            Ok(true) => return Outcome::Error((Status::Unauthorized, AuthError::Invalid)),
+            Err(e) => {
+                eprintln!("Failed to check token revocation: {}", e);
+                return Outcome::Error((db::error_status(&e), AuthError::Invalid));
             }
-            None => Outcome::Error((Status::Unauthorized, AuthError::Missing)),
         }
+
+        if let Some(jti) = claims.jti.clone() {
+            rocket::tokio::spawn(touch_token(jti));
+        }
+
+        Outcome::Success(GroupAuth {
+            group_id: claims.group_id,
+            permissions: claims.effective_permissions(),
+            member_id: claims.sub,
+            exp: claims.exp,
+            jti: claims.jti,
+            token_hash: Sha256::digest(token.as_bytes())
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        })
+    }
+}
+
+/// Record that a registered token was used, at most once a minute so busy
+/// clients don't write on every request. Failures only cost accuracy.
+async fn touch_token(jti: String) {
+    if let Err(e) = sqlx::query(
+        "UPDATE issued_tokens SET last_used_at = NOW()
+         WHERE jti = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')",
+    )
+    .bind(&jti)
+    .execute(db::get_pool())
+    .await
+    {
+        eprintln!("Failed to record token use: {}", e);
     }
 }
 
-pub fn generate_token(
+/// Sign a new token for the group and record it in `issued_tokens`, where the
+/// group can list and revoke it.
+pub async fn issue_token(
+    group_id: Uuid,
+    permissions: Permissions,
+    member_id: Option<Uuid>,
+) -> Result<String, Status> {
+    let mut conn = db::get_pool().acquire().await.map_err(|e| {
+        eprintln!("Failed to acquire connection: {}", e);
+        db::error_status(&e)
+    })?;
+    issue_token_in(&mut conn, group_id, permissions, member_id, Utc::now()).await
+}
+
+/// `issue_token` on a given connection and with a given issue time, so the token
+/// can be issued in the same transaction as a revocation, after it. Tokens are never
+/// issued at or before the group's last revocation (`iat`s are whole seconds, so one
+/// issued in the same second would be revoked from the start).
+pub async fn issue_token_in(
+    conn: &mut PgConnection,
     group_id: Uuid,
-    permissions: Option<Permissions>,
-) -> Result<String, jsonwebtoken::errors::Error> {
+    permissions: Permissions,
+    member_id: Option<Uuid>,
+    now: DateTime<Utc>,
+) -> Result<String, Status> {
+    let (key_version, revoked_before) = sqlx::query_as::<_, (i32, Option<DateTime<Utc>>)>(
+        "SELECT key_version, tokens_revoked_before FROM groups WHERE id = $1",
+    )
+    .bind(group_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to load group key version: {}", e);
+        db::error_status(&e)
+    })?;
+    let now = match revoked_before {
+        Some(before) if now.timestamp() <= before.timestamp() => before + chrono::Duration::seconds(1),
+        _ => now,
+    };
+    // Token expires in 10 years (essentially permanent for share links)
+    let expires_at = now + chrono::Duration::days(3650);
     let claims = Claims {
         group_id,
-        // Token expires in 10 years (essentially permanent for share links)
-        exp: (chrono::Utc::now() + chrono::Duration::days(3650)).timestamp() as usize,
-        permissions,
+        exp: expires_at.timestamp() as usize,
+        permissions: Some(permissions.clone()),
+        sub: member_id,
+        // Unique per issued token, so otherwise identical tokens can be told apart
+        jti: Some(Uuid::new_v4().simple().to_string()),
+        iat: Some(now.timestamp() as usize),
+        iss: JWT_KEYS.issuer.clone(),
+        aud: JWT_KEYS.audience.clone(),
+        key_version: Some(key_version),
     };
+    let token = sign(&JWT_KEYS, &claims).map_err(|_| Status::InternalServerError)?;
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    sqlx::query(
+        "INSERT INTO issued_tokens (jti, group_id, member_id, can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses, can_settle, issued_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, to_timestamp($10), to_timestamp($11))",
     )
+    .bind(&claims.jti)
+    .bind(group_id)
+    .bind(member_id)
+    .bind(permissions.has_delete_group())
+    .bind(permissions.has_manage_members())
+    .bind(permissions.has_update_payment())
+    .bind(permissions.has_add_expenses())
+    .bind(permissions.has_edit_expenses())
+    .bind(permissions.has_settle())
+    .bind(claims.iat.map(|iat| iat as f64))
+    .bind(claims.exp as f64)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to record issued token: {}", e);
+        db::error_status(&e)
+    })?;
+
+    Ok(token)
+}
+
+/// Sign with the current key; with HS256, with the group key of `claims.key_version`.
+fn sign(keys: &JwtKeys, claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
+    let mut header = Header::new(keys.algorithm);
+    match keys.secrets.get(&keys.current_kid) {
+        Some(secret) => {
+            let version = claims.key_version.unwrap_or(1);
+            header.kid = Some(format!("{}{}{}", keys.current_kid, GROUP_KID_SUFFIX, version));
+            let key = EncodingKey::from_secret(&group_key(secret, claims.group_id, Some(version)));
+            encode(&header, claims, &key)
+        }
+        None => {
+            header.kid = Some(keys.current_kid.clone());
+            encode(&header, claims, &keys.encoding)
+        }
+    }
+}
+
+/// Checks for tokens of this deployment: expiry, plus `iss`/`aud` when configured.
+fn validation(keys: &JwtKeys) -> Validation {
+    let mut validation = Validation::new(keys.algorithm);
+    let mut required = vec!["exp"];
+    if let Some(issuer) = &keys.issuer {
+        validation.set_issuer(&[issuer]);
+        required.push("iss");
+    }
+    match &keys.audience {
+        Some(audience) => {
+            validation.set_audience(&[audience]);
+            required.push("aud");
+        }
+        // Otherwise tokens carrying an `aud` (issued while it was set) would be rejected
+        None => validation.validate_aud = false,
+    }
+    validation.set_required_spec_claims(&required);
+    validation
+}
+
+/// Reads claims without checking the signature or any claim, for picking the key
+/// or inspecting a token that is verified separately.
+fn unverified(keys: &JwtKeys) -> Validation {
+    let mut unverified = Validation::new(keys.algorithm);
+    unverified.insecure_disable_signature_validation();
+    unverified.validate_exp = false;
+    unverified.validate_aud = false;
+    unverified
 }
 
 pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )?;
+    validate_with(&JWT_KEYS, token)
+}
+
+fn validate_with(keys: &JwtKeys, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let validation = validation(keys);
+    let header = decode_header(token)?;
+
+    match header.kid {
+        Some(kid) if kid.contains(GROUP_KID_SUFFIX) => {
+            let (kid, version) = kid
+                .rsplit_once(GROUP_KID_SUFFIX)
+                .ok_or(ErrorKind::InvalidSignature)?;
+            let version = match version {
+                "" => None,
+                v => Some(
+                    v.parse::<i32>()
+                        .ok()
+                        .filter(|v| *v >= 1)
+                        .ok_or(ErrorKind::InvalidSignature)?,
+                ),
+            };
+            let secret = keys.secrets.get(kid).ok_or(ErrorKind::InvalidSignature)?;
+            // The key depends on the group the token claims, so read that first;
+            // a token for one group can't verify under another group's key.
+            let group_id =
+                decode::<Claims>(token, &DecodingKey::from_secret(&[]), &unverified(keys))?
+                    .claims
+                    .group_id;
+            let key = DecodingKey::from_secret(&group_key(secret, group_id, version));
+            let mut claims = decode::<Claims>(token, &key, &validation)?.claims;
+            claims.key_version = Some(version.unwrap_or(1));
+            Ok(claims)
+        }
+        Some(kid) => {
+            let key = keys
+                .decoding
+                .get(&kid)
+                .ok_or(ErrorKind::InvalidSignature)?;
+            Ok(decode::<Claims>(token, key, &validation)?.claims)
+        }
+        None => {
+            // Tokens issued before key ids were introduced: try the current key first,
+            // then any previous keys.
+            let current = &keys.decoding[&keys.current_kid];
+            let token_data = decode::<Claims>(token, current, &validation).or_else(|err| {
+                keys.decoding
+                    .iter()
+                    .filter(|(kid, _)| **kid != keys.current_kid)
+                    .find_map(|(_, key)| decode::<Claims>(token, key, &validation).ok())
+                    .ok_or(err)
+            })?;
+            Ok(token_data.claims)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test-secret";
+    const PRIVATE_KEY: &str = include_str!("../tests/fixtures/jwt_private.pem");
+    const PUBLIC_KEY: &str = include_str!("../tests/fixtures/jwt_public.pem");
+
+    fn keys() -> JwtKeys {
+        JwtKeys {
+            algorithm: Algorithm::HS256,
+            current_kid: "1".to_string(),
+            encoding: EncodingKey::from_secret(SECRET),
+            decoding: HashMap::from([("1".to_string(), DecodingKey::from_secret(SECRET))]),
+            secrets: HashMap::from([("1".to_string(), SECRET.to_vec())]),
+            issuer: None,
+            audience: None,
+        }
+    }
+
+    fn claims(group_id: Uuid, key_version: Option<i32>) -> Claims {
+        Claims {
+            group_id,
+            exp: (Utc::now() + chrono::Duration::days(1)).timestamp() as usize,
+            permissions: Some(Permissions::all()),
+            sub: None,
+            jti: None,
+            iat: None,
+            iss: None,
+            aud: None,
+            key_version,
+        }
+    }
+
+    /// Sign with an explicit `kid` and group key, as older or forged tokens would be.
+    fn encode_as(kid: &str, key: &[u8], claims: &Claims) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(kid.to_string());
+        encode(&header, claims, &EncodingKey::from_secret(key)).unwrap()
+    }
+
+    #[test]
+    fn tokens_carry_and_verify_their_group_key_version() {
+        let keys = keys();
+        let group_id = Uuid::new_v4();
+        let token = sign(&keys, &claims(group_id, Some(3))).unwrap();
+
+        assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some("1+g3"));
+        let verified = validate_with(&keys, &token).unwrap();
+        assert_eq!(verified.group_id, group_id);
+        assert_eq!(verified.key_version, Some(3));
+    }
+
+    #[test]
+    fn group_keys_differ_by_group_and_version() {
+        let group_id = Uuid::new_v4();
+        let v1 = group_key(SECRET, group_id, Some(1));
+        assert_ne!(v1, group_key(SECRET, group_id, Some(2)));
+        assert_ne!(v1, group_key(SECRET, Uuid::new_v4(), Some(1)));
+        assert_ne!(v1, group_key(SECRET, group_id, None));
+    }
+
+    #[test]
+    fn a_token_only_verifies_under_the_version_and_group_it_was_signed_for() {
+        let keys = keys();
+        let group_id = Uuid::new_v4();
+        let old_key = group_key(SECRET, group_id, Some(1));
 
-    Ok(token_data.claims)
+        // Claiming a newer version doesn't make an old key's signature valid
+        let relabeled = encode_as("1+g2", &old_key, &claims(group_id, None));
+        assert!(validate_with(&keys, &relabeled).is_err());
+
+        // Nor does one group's key sign tokens for another group
+        let other_group = encode_as("1+g1", &old_key, &claims(Uuid::new_v4(), None));
+        assert!(validate_with(&keys, &other_group).is_err());
+
+        for kid in ["1+g0", "1+gx", "2+g1"] {
+            let token = encode_as(kid, &old_key, &claims(group_id, None));
+            assert!(validate_with(&keys, &token).is_err(), "{kid} verified");
+        }
+    }
+
+    #[test]
+    fn unversioned_group_tokens_count_as_version_one() {
+        let keys = keys();
+        let group_id = Uuid::new_v4();
+        let legacy = encode_as("1+g", &group_key(SECRET, group_id, None), &claims(group_id, None));
+
+        assert_eq!(validate_with(&keys, &legacy).unwrap().key_version, Some(1));
+    }
+
+    #[test]
+    fn tokens_without_a_group_key_have_no_version() {
+        let keys = keys();
+        let plain = encode_as("1", SECRET, &claims(Uuid::new_v4(), None));
+
+        assert_eq!(validate_with(&keys, &plain).unwrap().key_version, None);
+    }
+
+    /// HS256 keys after a rotation from key `old` to the current key `1`.
+    fn rotated_keys() -> JwtKeys {
+        let mut keys = keys();
+        keys.decoding.insert("old".to_string(), DecodingKey::from_secret(b"old-secret"));
+        keys.secrets.insert("old".to_string(), b"old-secret".to_vec());
+        keys
+    }
+
+    #[test]
+    fn tokens_of_previous_keys_keep_verifying() {
+        let keys = rotated_keys();
+        let group_id = Uuid::new_v4();
+        let old_group_key = group_key(b"old-secret", group_id, Some(1));
+
+        let by_kid = encode_as("old+g1", &old_group_key, &claims(group_id, None));
+        assert_eq!(validate_with(&keys, &by_kid).unwrap().group_id, group_id);
+        let plain = encode_as("old", b"old-secret", &claims(group_id, None));
+        assert_eq!(validate_with(&keys, &plain).unwrap().group_id, group_id);
+
+        // Only under the key they name
+        let wrong_kid = encode_as("1+g1", &old_group_key, &claims(group_id, None));
+        assert!(validate_with(&keys, &wrong_kid).is_err());
+        let unknown_kid = encode_as("gone", b"old-secret", &claims(group_id, None));
+        assert!(validate_with(&keys, &unknown_kid).is_err());
+    }
+
+    #[test]
+    fn tokens_without_kid_are_tried_against_every_key() {
+        let keys = rotated_keys();
+        let unnamed = |secret: &[u8]| {
+            let claims = claims(Uuid::new_v4(), None);
+            encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(secret))
+                .unwrap()
+        };
+
+        assert!(validate_with(&keys, &unnamed(SECRET)).is_ok());
+        assert!(validate_with(&keys, &unnamed(b"old-secret")).is_ok());
+        assert!(validate_with(&keys, &unnamed(b"never-configured")).is_err());
+    }
+
+    fn rs256_keys(public_key: &str) -> JwtKeys {
+        let decoding = decoding_key(Algorithm::RS256, "JWT_PUBLIC_KEY", public_key).unwrap();
+        JwtKeys {
+            algorithm: Algorithm::RS256,
+            current_kid: "1".to_string(),
+            encoding: EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap(),
+            decoding: HashMap::from([("1".to_string(), decoding)]),
+            secrets: HashMap::new(),
+            issuer: None,
+            audience: None,
+        }
+    }
+
+    #[test]
+    fn rs256_tokens_are_signed_with_the_private_key_and_verified_with_the_public_one() {
+        let keys = rs256_keys(PUBLIC_KEY);
+        let claims = claims(Uuid::new_v4(), Some(1));
+        let token = sign(&keys, &claims).unwrap();
+
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert_eq!(header.kid.as_deref(), Some("1"));
+        let verified = validate_with(&keys, &token).unwrap();
+        assert_eq!(verified.group_id, claims.group_id);
+        // No per-group keys without a shared secret
+        assert_eq!(verified.key_version, None);
+    }
+
+    #[test]
+    fn rs256_public_keys_can_be_inline_with_escaped_newlines_or_a_path() {
+        let token = sign(&rs256_keys(PUBLIC_KEY), &claims(Uuid::new_v4(), Some(1))).unwrap();
+
+        let escaped = PUBLIC_KEY.replace('\n', "\\n");
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt_public.pem");
+        for public_key in [escaped.as_str(), path] {
+            assert!(validate_with(&rs256_keys(public_key), &token).is_ok());
+        }
+        assert!(decoding_key(Algorithm::RS256, "JWT_PUBLIC_KEY", "/no/such/key.pem").is_err());
+    }
+
+    #[test]
+    fn rs256_rejects_hs256_tokens() {
+        let keys = rs256_keys(PUBLIC_KEY);
+        // Signed with the public key as an HMAC secret, as an attacker could
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("1".to_string());
+        let key = EncodingKey::from_secret(PUBLIC_KEY.as_bytes());
+        let forged = encode(&header, &claims(Uuid::new_v4(), Some(1)), &key).unwrap();
+
+        assert!(validate_with(&keys, &forged).is_err());
+    }
 }
@@ -0,0 +1,163 @@
+use rocket::http::Method;
+use rocket_cors::{AllowedHeaders, AllowedOrigins, Cors, CorsOptions};
+use std::str::FromStr;
+
+/// Methods allowed when `CORS_ALLOWED_METHODS` is not set.
+const DEFAULT_METHODS: [Method; 6] = [
+    Method::Get,
+    Method::Post,
+    Method::Put,
+    Method::Patch,
+    Method::Delete,
+    Method::Options,
+];
+
+/// Read a comma-separated env var, returning `None` if it is unset or blank.
+fn env_list(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// Parse a comma-separated list of origins like `https://a.example, http://localhost:5173`.
+/// Each entry must be a bare `http(s)://host[:port]` origin without path, query or wildcard.
+pub fn parse_origins(value: &str) -> Result<Vec<String>, String> {
+    let mut origins = Vec::new();
+    for raw in value.split(',') {
+        let origin = raw.trim().trim_end_matches('/');
+        if origin.is_empty() {
+            return Err(format!("empty entry in origin list '{}'", value));
+        }
+        let rest = origin
+            .strip_prefix("https://")
+            .or_else(|| origin.strip_prefix("http://"))
+            .ok_or_else(|| format!("origin '{}' must start with http:// or https://", origin))?;
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (rest, None),
+        };
+        if host.is_empty()
+            || !host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        {
+            return Err(format!("origin '{}' has an invalid host", origin));
+        }
+        if let Some(port) = port
+            && port.parse::<u16>().is_err()
+        {
+            return Err(format!("origin '{}' has an invalid port", origin));
+        }
+        origins.push(origin.to_string());
+    }
+    Ok(origins)
+}
+
+fn parse_methods(value: &str) -> Result<Vec<Method>, String> {
+    value
+        .split(',')
+        .map(|m| {
+            let m = m.trim();
+            Method::from_str(&m.to_ascii_uppercase())
+                .map_err(|_| format!("unknown HTTP method '{}'", m))
+        })
+        .collect()
+}
+
+fn parse_headers(value: &str) -> Result<Vec<String>, String> {
+    value
+        .split(',')
+        .map(|h| {
+            let h = h.trim();
+            if h.is_empty() || !h.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                Err(format!("invalid header name '{}'", h))
+            } else {
+                Ok(h.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Build the CORS fairing from the environment.
+///
+/// - `CORS_ALLOWED_ORIGINS`: comma-separated origins. When unset, all origins are
+///   allowed in debug builds and none (same-origin only) in release builds.
+/// - `CORS_ALLOWED_METHODS`: comma-separated methods (default GET, POST, PUT, PATCH, DELETE, OPTIONS).
+/// - `CORS_ALLOWED_HEADERS`: comma-separated header names (default: all).
+pub fn from_env() -> Result<Cors, String> {
+    let origins = match env_list("CORS_ALLOWED_ORIGINS") {
+        Some(value) => {
+            let origins = parse_origins(&value).map_err(|e| format!("CORS_ALLOWED_ORIGINS: {}", e))?;
+            AllowedOrigins::some_exact(&origins)
+        }
+        None if cfg!(debug_assertions) => AllowedOrigins::all(),
+        None => AllowedOrigins::some_exact::<&str>(&[]),
+    };
+
+    let methods = match env_list("CORS_ALLOWED_METHODS") {
+        Some(value) => parse_methods(&value).map_err(|e| format!("CORS_ALLOWED_METHODS: {}", e))?,
+        None => DEFAULT_METHODS.to_vec(),
+    };
+
+    let headers = match env_list("CORS_ALLOWED_HEADERS") {
+        Some(value) => {
+            let headers = parse_headers(&value).map_err(|e| format!("CORS_ALLOWED_HEADERS: {}", e))?;
+            AllowedHeaders::some(&headers.iter().map(String::as_str).collect::<Vec<_>>())
+        }
+        None => AllowedHeaders::all(),
+    };
+
+    CorsOptions::default()
+        .allowed_origins(origins)
+        .allowed_methods(methods.into_iter().map(From::from).collect())
+        .allowed_headers(headers)
+        .to_cors()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins_are_trimmed_and_lose_their_trailing_slash() {
+        assert_eq!(
+            parse_origins(" https://a.example/ , http://localhost:5173").unwrap(),
+            vec!["https://a.example", "http://localhost:5173"]
+        );
+    }
+
+    #[test]
+    fn origins_must_be_bare_http_origins() {
+        for value in [
+            "a.example",
+            "ftp://a.example",
+            "https://*.example",
+            "https://a.example/app",
+            "https://a.example:http",
+            "https://a.example:70000",
+            "https://",
+            "https://a.example,,https://b.example",
+        ] {
+            assert!(parse_origins(value).is_err(), "{value} was accepted");
+        }
+    }
+
+    #[test]
+    fn methods_are_case_insensitive() {
+        assert_eq!(
+            parse_methods("get, Post ,DELETE").unwrap(),
+            vec![Method::Get, Method::Post, Method::Delete]
+        );
+        assert!(parse_methods("GET,FETCH").is_err());
+    }
+
+    #[test]
+    fn headers_must_be_plain_names() {
+        assert_eq!(
+            parse_headers("Authorization, If-None-Match").unwrap(),
+            vec!["Authorization", "If-None-Match"]
+        );
+        for value in ["Authorization,", "X Header", "X-Header:1"] {
+            assert!(parse_headers(value).is_err(), "{value} was accepted");
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+/// ISO 4217 currencies without a minor unit.
+const ZERO_DECIMAL: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "UYI", "VND",
+    "VUV", "XAF", "XOF", "XPF",
+];
+
+/// ISO 4217 currencies with three decimal places.
+const THREE_DECIMAL: &[&str] = &["BHD", "IQD", "JOD", "KWD", "LYD", "OMR", "TND"];
+
+/// Number of decimal places (minor units) of a currency. Unknown codes use 2.
+pub fn minor_units(code: &str) -> u32 {
+    let code = code.to_ascii_uppercase();
+    if ZERO_DECIMAL.contains(&code.as_str()) {
+        0
+    } else if THREE_DECIMAL.contains(&code.as_str()) {
+        3
+    } else {
+        2
+    }
+}
+
+/// Where the currency symbol goes relative to the amount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolPosition {
+    Before,
+    After,
+}
+
+/// Everything a client needs to format amounts of a currency the way the backend rounds them.
+#[derive(Debug, Clone, Serialize)]
+pub struct CurrencyInfo {
+    pub code: String,
+    pub symbol: String,
+    pub symbol_position: SymbolPosition,
+    /// Whether a space separates symbol and amount (e.g. `12,50 €`).
+    pub symbol_spacing: bool,
+    pub decimal_separator: String,
+    pub grouping_separator: String,
+    pub minor_units: u32,
+}
+
+/// Conventional formatting for common currencies:
+/// (code, symbol, position, spacing, decimal separator, grouping separator).
+const FORMATS: &[(&str, &str, SymbolPosition, bool, &str, &str)] = &[
+    ("EUR", "€", SymbolPosition::After, true, ",", "."),
+    ("USD", "$", SymbolPosition::Before, false, ".", ","),
+    ("GBP", "£", SymbolPosition::Before, false, ".", ","),
+    ("CHF", "CHF", SymbolPosition::Before, true, ".", "’"),
+    ("JPY", "¥", SymbolPosition::Before, false, ".", ","),
+    ("CNY", "¥", SymbolPosition::Before, false, ".", ","),
+    ("KRW", "₩", SymbolPosition::Before, false, ".", ","),
+    ("INR", "₹", SymbolPosition::Before, false, ".", ","),
+    ("CAD", "$", SymbolPosition::Before, false, ".", ","),
+    ("AUD", "$", SymbolPosition::Before, false, ".", ","),
+    ("NZD", "$", SymbolPosition::Before, false, ".", ","),
+    ("MXN", "$", SymbolPosition::Before, false, ".", ","),
+    ("BRL", "R$", SymbolPosition::Before, true, ",", "."),
+    ("SEK", "kr", SymbolPosition::After, true, ",", " "),
+    ("NOK", "kr", SymbolPosition::After, true, ",", " "),
+    ("DKK", "kr.", SymbolPosition::After, true, ",", "."),
+    ("ISK", "kr", SymbolPosition::After, true, ",", "."),
+    ("PLN", "zł", SymbolPosition::After, true, ",", " "),
+    ("CZK", "Kč", SymbolPosition::After, true, ",", " "),
+    ("HUF", "Ft", SymbolPosition::After, true, ",", " "),
+    ("RUB", "₽", SymbolPosition::After, true, ",", " "),
+    ("TRY", "₺", SymbolPosition::Before, false, ",", "."),
+    ("ILS", "₪", SymbolPosition::Before, true, ".", ","),
+    ("ZAR", "R", SymbolPosition::Before, true, ",", " "),
+    ("THB", "฿", SymbolPosition::Before, false, ".", ","),
+    ("VND", "₫", SymbolPosition::After, true, ",", "."),
+    ("KWD", "KD", SymbolPosition::Before, true, ".", ","),
+];
+
+/// Formatting metadata of a currency. Unknown codes use the code itself as the
+/// symbol, placed after the amount, with `.` decimals and `,` grouping.
+pub fn info(code: &str) -> CurrencyInfo {
+    let code = code.to_ascii_uppercase();
+    let (symbol, symbol_position, symbol_spacing, decimal_separator, grouping_separator) = FORMATS
+        .iter()
+        .find(|f| f.0 == code)
+        .map(|&(_, symbol, position, spacing, decimal, grouping)| {
+            (symbol.to_string(), position, spacing, decimal, grouping)
+        })
+        .unwrap_or_else(|| (code.clone(), SymbolPosition::After, true, ".", ","));
+    CurrencyInfo {
+        minor_units: minor_units(&code),
+        code,
+        symbol,
+        symbol_position,
+        symbol_spacing,
+        decimal_separator: decimal_separator.to_string(),
+        grouping_separator: grouping_separator.to_string(),
+    }
+}
+
+/// Round to `decimals` places using banker's rounding (round half to even),
+/// so repeated rounding of ties does not drift in one direction. Results that
+/// round to zero are always `0.0`, never `-0.0` (which would serialize as `-0.0`).
+pub fn round_half_even(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    let scaled = value * factor;
+    let floor = scaled.floor();
+    // Treat values within float noise of .5 as exact ties
+    let rounded = if ((scaled - floor) - 0.5).abs() < 1e-9 {
+        if floor % 2.0 == 0.0 { floor } else { floor + 1.0 }
+    } else {
+        scaled.round()
+    };
+    // Float noise such as 0.3 - 0.1 - 0.2 rounds to -0.0; adding 0.0 makes it a plain zero
+    rounded / factor + 0.0
+}
+
+/// An amount as an integer number of minor units (e.g. cents for 2 decimals),
+/// rounded like `round_half_even`.
+pub fn to_minor_units(value: f64, decimals: u32) -> i64 {
+    (round_half_even(value, decimals) * 10f64.powi(decimals as i32)).round() as i64
+}
+
+/// Round an amount to the precision of the given currency.
+pub fn round_amount(value: f64, code: &str) -> f64 {
+    round_half_even(value, minor_units(code))
+}
+
+/// Round each member's share so the shares add up to the rounded total exactly,
+/// by the largest-remainder method: every share is rounded down to whole minor
+/// units, then the units still missing go one each to the shares with the largest
+/// remainders, ties going to the member listed first. E.g. 10.00 split three ways
+/// is 3.34, 3.33, 3.33. Differences larger than rounding can explain (e.g. exact
+/// splits that don't cover the total) are left alone; those shares are just rounded.
+pub fn round_shares(total: f64, shares: Vec<(Uuid, f64)>, decimals: u32) -> Vec<(Uuid, f64)> {
+    let scale = 10f64.powi(decimals as i32);
+    // The epsilon keeps float noise (3.33 as 332.99999...) from losing a unit
+    let floors: Vec<i64> = shares
+        .iter()
+        .map(|(_, share)| (share * scale + 1e-6).floor() as i64)
+        .collect();
+    let missing = to_minor_units(total, decimals) - floors.iter().sum::<i64>();
+    if missing < 0 || missing as usize > shares.len() {
+        return shares
+            .into_iter()
+            .map(|(id, share)| (id, round_half_even(share, decimals)))
+            .collect();
+    }
+
+    // Remainders in millionths of a unit, so noise can't decide a tie
+    let remainder = |i: usize| ((shares[i].1 * scale - floors[i] as f64) * 1e6).round() as i64;
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    by_remainder.sort_by_key(|&i| std::cmp::Reverse(remainder(i)));
+    let mut units = floors;
+    for &i in by_remainder.iter().take(missing as usize) {
+        units[i] += 1;
+    }
+    shares
+        .into_iter()
+        .zip(units)
+        .map(|((id, _), units)| (id, units as f64 / scale))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minor_units_follow_iso_4217() {
+        assert_eq!(minor_units("JPY"), 0);
+        assert_eq!(minor_units("kwd"), 3);
+        assert_eq!(minor_units("EUR"), 2);
+        assert_eq!(minor_units("XYZ"), 2);
+    }
+
+    #[test]
+    fn ties_round_to_even() {
+        assert_eq!(round_half_even(0.125, 2), 0.12);
+        assert_eq!(round_half_even(0.135, 2), 0.14);
+        assert_eq!(round_half_even(2.5, 0), 2.0);
+        assert_eq!(round_half_even(3.5, 0), 4.0);
+        assert_eq!(round_half_even(1.0005, 3), 1.0);
+        assert_eq!(round_half_even(0.126, 2), 0.13);
+        assert_eq!(round_amount(1234.5, "JPY"), 1234.0);
+    }
+
+    #[test]
+    fn rounding_noise_to_zero_gives_a_plain_zero() {
+        let rounded = round_half_even(0.3 - 0.1 - 0.2, 2);
+        assert_eq!(rounded, 0.0);
+        assert!(rounded.is_sign_positive());
+    }
+
+    #[test]
+    fn shares_add_up_to_the_rounded_total() {
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let thirds = |total: f64| ids.iter().map(|id| (*id, total / 3.0)).collect::<Vec<_>>();
+        let amounts =
+            |shares: Vec<(Uuid, f64)>| shares.into_iter().map(|(_, a)| a).collect::<Vec<_>>();
+
+        assert_eq!(amounts(round_shares(10.0, thirds(10.0), 2)), vec![3.34, 3.33, 3.33]);
+        assert_eq!(amounts(round_shares(20.0, thirds(20.0), 2)), vec![6.67, 6.67, 6.66]);
+        assert_eq!(amounts(round_shares(100.0, thirds(100.0), 0)), vec![34.0, 33.0, 33.0]);
+    }
+
+    #[test]
+    fn shares_that_dont_cover_the_total_are_only_rounded() {
+        let shares = vec![(Uuid::new_v4(), 1.004), (Uuid::new_v4(), 2.006)];
+        let rounded = round_shares(10.0, shares, 2);
+        assert_eq!(rounded.iter().map(|(_, a)| *a).collect::<Vec<_>>(), vec![1.0, 2.01]);
+    }
+
+    #[test]
+    fn known_currencies_have_their_conventional_format() {
+        let eur = info("eur");
+        assert_eq!(eur.code, "EUR");
+        assert_eq!(eur.symbol, "€");
+        assert_eq!(eur.symbol_position, SymbolPosition::After);
+        assert!(eur.symbol_spacing);
+        assert_eq!((eur.decimal_separator.as_str(), eur.grouping_separator.as_str()), (",", "."));
+        assert_eq!(eur.minor_units, 2);
+
+        let jpy = info("JPY");
+        assert_eq!((jpy.symbol.as_str(), jpy.symbol_position), ("¥", SymbolPosition::Before));
+        assert_eq!(jpy.minor_units, 0);
+        assert_eq!(info("KWD").minor_units, 3);
+    }
+
+    #[test]
+    fn unknown_currencies_fall_back_to_their_code() {
+        let xyz = info("xyz");
+        assert_eq!((xyz.code.as_str(), xyz.symbol.as_str()), ("XYZ", "XYZ"));
+        assert_eq!(xyz.symbol_position, SymbolPosition::After);
+        assert!(xyz.symbol_spacing);
+        assert_eq!((xyz.decimal_separator.as_str(), xyz.grouping_separator.as_str()), (".", ","));
+        assert_eq!(xyz.minor_units, 2);
+    }
+}
@@ -1,6 +1,10 @@
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
+use rocket::http::Status;
+use serde::Serialize;
 use sqlx::PgPool;
-use sqlx::postgres::PgPoolOptions;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::str::FromStr;
+use std::time::Duration;
 
 mod embedded {
     use refinery::embed_migrations;
@@ -9,11 +13,26 @@ mod embedded {
 
 static POOL: OnceCell<PgPool> = OnceCell::new();
 
+/// How long a single statement may run, and a request may wait for a free
+/// connection, before it fails with `503 Service Unavailable` instead of hanging.
+/// Defaults to 10000 ms; override with `DB_TIMEOUT_MS` (`0` disables it).
+static TIMEOUT: Lazy<Option<Duration>> = Lazy::new(|| {
+    let ms = std::env::var("DB_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(10_000);
+    (ms > 0).then(|| Duration::from_millis(ms))
+});
+
 pub async fn init_pool(database_url: &str) -> Result<(), sqlx::Error> {
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(database_url)
-        .await?;
+    let mut options = PgConnectOptions::from_str(database_url)?;
+    let mut pool_options = PgPoolOptions::new().max_connections(5);
+    if let Some(timeout) = *TIMEOUT {
+        // Set per connection, so Postgres cancels slow statements itself
+        options = options.options([("statement_timeout", timeout.as_millis().to_string())]);
+        pool_options = pool_options.acquire_timeout(timeout);
+    }
+    let pool = pool_options.connect_with(options).await?;
 
     POOL.set(pool).expect("Pool already initialized");
     Ok(())
@@ -23,6 +42,30 @@ pub fn get_pool() -> &'static PgPool {
     POOL.get().expect("Database pool not initialized")
 }
 
+/// Status for a failed database call: `503 Service Unavailable` if it ran into
+/// the timeout (statement cancelled or no free connection), `500` otherwise.
+pub fn error_status(e: &sqlx::Error) -> Status {
+    let timed_out = match e {
+        sqlx::Error::PoolTimedOut => true,
+        // 57014 = query_canceled, raised when statement_timeout fires
+        sqlx::Error::Database(db) => db.code().as_deref() == Some("57014"),
+        _ => false,
+    };
+    if timed_out {
+        Status::ServiceUnavailable
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Close the pool, waiting for checked-out connections to be returned.
+/// Does nothing if the pool was never initialized.
+pub async fn close_pool() {
+    if let Some(pool) = POOL.get() {
+        pool.close().await;
+    }
+}
+
 pub async fn run_migrations(database_url: &str) -> Result<(), Box<dyn std::error::Error>> {
     let (mut client, connection) =
         tokio_postgres::connect(database_url, tokio_postgres::NoTls).await?;
@@ -43,3 +86,73 @@ pub async fn run_migrations(database_url: &str) -> Result<(), Box<dyn std::error
 
     Ok(())
 }
+
+/// How the database's migration history compares with the migrations embedded in
+/// this build. `pending` are not applied yet; `modified` were applied from a
+/// different file than the one embedded now (refinery refuses to start on those).
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub expected_version: i64,
+    pub applied_version: Option<i64>,
+    pub pending: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl MigrationStatus {
+    pub fn is_current(&self) -> bool {
+        self.pending.is_empty() && self.modified.is_empty()
+    }
+}
+
+pub async fn migration_status() -> Result<MigrationStatus, sqlx::Error> {
+    let pool = get_pool();
+    let has_history: bool =
+        sqlx::query_scalar("SELECT to_regclass('refinery_schema_history') IS NOT NULL")
+            .fetch_one(pool)
+            .await?;
+    let applied: Vec<(i64, Option<String>)> = if has_history {
+        sqlx::query_as("SELECT version::bigint, checksum FROM refinery_schema_history")
+            .fetch_all(pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let runner = embedded::migrations::runner();
+    let mut pending = Vec::new();
+    let mut modified = Vec::new();
+    for migration in runner.get_migrations() {
+        let version = i64::from(migration.version());
+        match applied.iter().find(|(v, _)| *v == version) {
+            None => pending.push(migration.to_string()),
+            Some((_, checksum)) if checksum.as_deref() != Some(&migration.checksum().to_string()) => {
+                modified.push(migration.to_string())
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(MigrationStatus {
+        expected_version: runner
+            .get_migrations()
+            .iter()
+            .map(|m| i64::from(m.version()))
+            .max()
+            .unwrap_or(0),
+        applied_version: applied.iter().map(|(v, _)| *v).max(),
+        pending,
+        modified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_timeouts_map_to_503() {
+        assert_eq!(error_status(&sqlx::Error::PoolTimedOut), Status::ServiceUnavailable);
+        assert_eq!(error_status(&sqlx::Error::RowNotFound), Status::InternalServerError);
+        assert_eq!(error_status(&sqlx::Error::PoolClosed), Status::InternalServerError);
+    }
+}
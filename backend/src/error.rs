@@ -0,0 +1,46 @@
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+
+/// Error response for handlers that need to tell the client *why* a request failed.
+/// Converts from a bare `Status`, so `?` on the usual `map_err(.. Status::X)` still works;
+/// errors with a message are sent as `{ "error": "<message>" }`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub message: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(status: Status, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: Some(message.into()),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(Status::BadRequest, message)
+    }
+}
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        ApiError {
+            status,
+            message: None,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self.message {
+            Some(message) => {
+                (self.status, Json(serde_json::json!({ "error": message }))).respond_to(request)
+            }
+            None => Err(self.status),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Json;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// The request's `If-None-Match` header, if any.
+pub struct IfNoneMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(
+            request.headers().get_one("If-None-Match").map(str::to_string),
+        ))
+    }
+}
+
+impl IfNoneMatch {
+    /// Weak comparison against a list of tags (`W/"a", "b"`) or `*`.
+    pub fn matches(&self, etag: &str) -> bool {
+        let Some(header) = &self.0 else {
+            return false;
+        };
+        let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        let wanted = strip(etag);
+        header
+            .split(',')
+            .any(|tag| tag.trim() == "*" || strip(tag) == wanted)
+    }
+}
+
+/// Weak ETag for a view of a group at a given version. The group id is included so
+/// responses for different groups under the same `/groups/current` URL never collide.
+pub fn group_etag(kind: &str, group_id: Uuid, version: i64) -> String {
+    format!("W/\"{}-{}-{}\"", kind, group_id.simple(), version)
+}
+
+/// A JSON response carrying an ETag, or `304 Not Modified` when the client's copy is current.
+pub enum Conditional<T> {
+    Fresh(String, Json<T>),
+    NotModified(String),
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Conditional<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            Conditional::Fresh(etag, body) => Response::build_from(body.respond_to(request)?)
+                .header(Header::new("ETag", etag))
+                .ok(),
+            Conditional::NotModified(etag) => Response::build()
+                .status(Status::NotModified)
+                .header(Header::new("ETag", etag))
+                .ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(value: &str) -> IfNoneMatch {
+        IfNoneMatch(Some(value.to_string()))
+    }
+
+    #[test]
+    fn tags_match_weakly_in_any_list_position() {
+        let etag = group_etag("group", Uuid::nil(), 3);
+        let strong = etag.trim_start_matches("W/");
+
+        assert!(header(&etag).matches(&etag));
+        assert!(header(strong).matches(&etag));
+        assert!(header(&format!("\"other\", {}", strong)).matches(&etag));
+        assert!(header(&format!(" {} ,W/\"other\"", etag)).matches(&etag));
+    }
+
+    #[test]
+    fn star_matches_any_tag() {
+        assert!(header("*").matches("W/\"anything\""));
+        assert!(header("\"other\", *").matches("W/\"anything\""));
+    }
+
+    #[test]
+    fn other_versions_and_missing_headers_do_not_match() {
+        let etag = group_etag("group", Uuid::nil(), 3);
+
+        assert!(!header(&group_etag("group", Uuid::nil(), 4)).matches(&etag));
+        assert!(!header(&group_etag("expenses", Uuid::nil(), 3)).matches(&etag));
+        assert!(!header("").matches(&etag));
+        assert!(!IfNoneMatch(None).matches(&etag));
+    }
+}
@@ -0,0 +1,65 @@
+use rocket::catcher::BoxFuture;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::Responder;
+use rocket::Catcher;
+
+use crate::auth::GroupAuth;
+use crate::db;
+use crate::error::ApiError;
+
+/// Request guard for handlers that change group data. Fails with `403 Forbidden`
+/// when the group is locked and `409 Conflict` when it has been archived; both
+/// stay readable.
+pub struct Writable;
+
+/// Marks a request `Writable` turned away because the group is locked, so the
+/// 403 catcher can tell the client why.
+struct GroupLocked(bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Writable {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth = match request.guard::<GroupAuth>().await {
+            Outcome::Success(auth) => auth,
+            Outcome::Error((status, _)) => return Outcome::Error((status, ())),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        let state: Result<Option<(bool, bool)>, sqlx::Error> =
+            sqlx::query_as("SELECT locked, archived_at IS NOT NULL FROM groups WHERE id = $1")
+                .bind(auth.group_id)
+                .fetch_optional(db::get_pool())
+                .await;
+        match state {
+            Ok(Some((true, _))) => {
+                request.local_cache(|| GroupLocked(true));
+                Outcome::Error((Status::Forbidden, ()))
+            }
+            Ok(Some((_, true))) => Outcome::Error((Status::Conflict, ())),
+            Ok(_) => Outcome::Success(Writable),
+            Err(e) => {
+                eprintln!("Failed to check group lock and archive state: {}", e);
+                Outcome::Error((db::error_status(&e), ()))
+            }
+        }
+    }
+}
+
+/// Explains 403s caused by a locked group; every other 403 gets Rocket's default.
+fn forbidden<'r>(status: Status, request: &'r Request<'_>) -> BoxFuture<'r> {
+    Box::pin(async move {
+        if request.local_cache(|| GroupLocked(false)).0 {
+            ApiError::new(status, "The group is locked; unlock it to make changes")
+                .respond_to(request)
+        } else {
+            Catcher::default().handler.handle(status, request).await
+        }
+    })
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    vec![Catcher::new(403, forbidden)]
+}
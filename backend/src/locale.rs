@@ -0,0 +1,174 @@
+use chrono::NaiveDate;
+
+use crate::currency;
+
+/// Number and date conventions used when rendering exports for people
+/// (the JSON API itself stays locale-neutral).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal_separator: String,
+    pub grouping_separator: String,
+    /// chrono format string, e.g. `%d.%m.%Y`.
+    pub date_format: &'static str,
+}
+
+/// Supported locales: (tag, decimal separator, grouping separator, date format).
+/// A tag without region (e.g. `de`) also matches any regional variant not listed.
+const LOCALES: &[(&str, &str, &str, &str)] = &[
+    ("en", ".", ",", "%m/%d/%Y"),
+    ("en-us", ".", ",", "%m/%d/%Y"),
+    ("en-gb", ".", ",", "%d/%m/%Y"),
+    ("en-au", ".", ",", "%d/%m/%Y"),
+    ("en-in", ".", ",", "%d/%m/%Y"),
+    ("de", ",", ".", "%d.%m.%Y"),
+    ("de-ch", ".", "'", "%d.%m.%Y"),
+    ("fr", ",", " ", "%d/%m/%Y"),
+    ("fr-ch", ".", "'", "%d.%m.%Y"),
+    ("es", ",", ".", "%d/%m/%Y"),
+    ("it", ",", ".", "%d/%m/%Y"),
+    ("nl", ",", ".", "%d-%m-%Y"),
+    ("pt", ",", ".", "%d/%m/%Y"),
+    ("sv", ",", " ", "%Y-%m-%d"),
+    ("nb", ",", " ", "%d.%m.%Y"),
+    ("da", ",", ".", "%d.%m.%Y"),
+    ("pl", ",", " ", "%d.%m.%Y"),
+    ("cs", ",", " ", "%d.%m.%Y"),
+    ("ja", ".", ",", "%Y/%m/%d"),
+    ("zh", ".", ",", "%Y-%m-%d"),
+];
+
+/// Default locale per group currency, for exports requested without `?locale=`.
+const CURRENCY_LOCALES: &[(&str, &str)] = &[
+    ("EUR", "de"),
+    ("USD", "en-us"),
+    ("GBP", "en-gb"),
+    ("AUD", "en-au"),
+    ("INR", "en-in"),
+    ("CHF", "de-ch"),
+    ("BRL", "pt"),
+    ("SEK", "sv"),
+    ("NOK", "nb"),
+    ("DKK", "da"),
+    ("PLN", "pl"),
+    ("CZK", "cs"),
+    ("JPY", "ja"),
+    ("CNY", "zh"),
+];
+
+impl Locale {
+    /// Look up a locale tag like `de-DE`, `en_GB` or `fr`. The exact tag wins,
+    /// otherwise its language is used. Unknown languages are `None`.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+        let language = tag.split('-').next().unwrap_or_default();
+        LOCALES
+            .iter()
+            .find(|l| l.0 == tag)
+            .or_else(|| LOCALES.iter().find(|l| l.0 == language))
+            .map(|&(_, decimal, grouping, date_format)| Locale {
+                decimal_separator: decimal.to_string(),
+                grouping_separator: grouping.to_string(),
+                date_format,
+            })
+    }
+
+    /// The usual locale of a currency's region. Currencies without one keep the
+    /// currency's own separators and use ISO dates.
+    pub fn for_currency(code: &str) -> Self {
+        let code = code.to_ascii_uppercase();
+        CURRENCY_LOCALES
+            .iter()
+            .find(|c| c.0 == code)
+            .and_then(|c| Locale::parse(c.1))
+            .unwrap_or_else(|| {
+                let info = currency::info(&code);
+                Locale {
+                    decimal_separator: info.decimal_separator,
+                    grouping_separator: info.grouping_separator,
+                    date_format: "%Y-%m-%d",
+                }
+            })
+    }
+
+    /// Format a number with `decimals` places, e.g. `1.234,50` or `1,234.50`.
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let fixed = format!("{:.*}", decimals, value.abs());
+        let (int_part, frac_part) = fixed.split_once('.').unwrap_or((&fixed, ""));
+
+        let mut grouped = String::new();
+        for (i, digit) in int_part.chars().enumerate() {
+            if i > 0 && (int_part.len() - i) % 3 == 0 {
+                grouped.push_str(&self.grouping_separator);
+            }
+            grouped.push(digit);
+        }
+        // Don't print "-0,00" for values that round to zero
+        let negative = value < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0');
+
+        let mut out = String::new();
+        if negative {
+            out.push('-');
+        }
+        out.push_str(&grouped);
+        if !frac_part.is_empty() {
+            out.push_str(&self.decimal_separator);
+            out.push_str(frac_part);
+        }
+        out
+    }
+
+    pub fn date(&self, date: NaiveDate) -> String {
+        date.format(self.date_format).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_match_exactly_or_by_language() {
+        assert_eq!(Locale::parse("de_CH").unwrap().grouping_separator, "'");
+        assert_eq!(Locale::parse("de-AT"), Locale::parse("de"));
+        assert_eq!(Locale::parse(" EN-gb ").unwrap().date_format, "%d/%m/%Y");
+        assert_eq!(Locale::parse("xx-YY"), None);
+    }
+
+    #[test]
+    fn numbers_are_grouped_with_the_locales_separators() {
+        let de = Locale::parse("de").unwrap();
+        let en = Locale::parse("en").unwrap();
+
+        assert_eq!(de.number(1234567.5, 2), "1.234.567,50");
+        assert_eq!(en.number(1234567.5, 2), "1,234,567.50");
+        assert_eq!(en.number(-1234.0, 0), "-1,234");
+        assert_eq!(en.number(999.0, 2), "999.00");
+        assert_eq!(Locale::parse("fr").unwrap().number(1000.0, 3), "1 000,000");
+    }
+
+    #[test]
+    fn values_rounding_to_zero_have_no_sign() {
+        let en = Locale::parse("en").unwrap();
+        assert_eq!(en.number(-0.001, 2), "0.00");
+        assert_eq!(en.number(-0.01, 2), "-0.01");
+    }
+
+    #[test]
+    fn dates_use_the_locales_format() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        assert_eq!(Locale::parse("de").unwrap().date(date), "09.03.2024");
+        assert_eq!(Locale::parse("en-us").unwrap().date(date), "03/09/2024");
+        assert_eq!(Locale::parse("ja").unwrap().date(date), "2024/03/09");
+    }
+
+    #[test]
+    fn currencies_pick_their_regions_locale() {
+        assert_eq!(Locale::for_currency("eur"), Locale::parse("de").unwrap());
+        assert_eq!(Locale::for_currency("CHF"), Locale::parse("de-ch").unwrap());
+
+        // Otherwise the currency's own separators with ISO dates
+        let krw = Locale::for_currency("KRW");
+        assert_eq!((krw.decimal_separator.as_str(), krw.grouping_separator.as_str()), (".", ","));
+        assert_eq!(krw.date_format, "%Y-%m-%d");
+    }
+}
@@ -1,23 +1,62 @@
 #[macro_use]
 extern crate rocket;
 
+mod activity;
 mod auth;
+mod cors;
+mod currency;
 mod db;
+mod error;
+mod etag;
+mod guards;
+mod locale;
+mod maintenance;
+mod metrics;
 mod models;
+mod notifications;
+mod openapi;
+mod rates;
+mod report;
 mod routes;
+mod settlement;
+mod shutdown;
+mod storage;
+mod webhooks;
 
+use once_cell::sync::Lazy;
 use rocket::fairing::AdHoc;
 use rocket::fs::NamedFile;
 use rocket::http::ContentType;
-use rocket::http::Method;
-use rocket_cors::{AllowedHeaders, AllowedOrigins, CorsOptions};
 use rocket_governor::rocket_governor_catcher;
 use std::path::{Path, PathBuf};
 
+/// Directory with the built frontend. Defaults to `static`; override with `STATIC_DIR`.
+static STATIC_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    std::env::var("STATIC_DIR")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| PathBuf::from(v.trim()))
+        .unwrap_or_else(|| PathBuf::from("static"))
+});
+
+/// Page served for `/` and unknown non-API routes. Defaults to `index.html`;
+/// override with `INDEX_FILE` (relative paths are inside `STATIC_DIR`).
+static INDEX_FILE: Lazy<PathBuf> =
+    Lazy::new(|| resolve_index(&STATIC_DIR, std::env::var("INDEX_FILE").ok().as_deref()));
+
+fn resolve_index(static_dir: &Path, index_file: Option<&str>) -> PathBuf {
+    let index_file = index_file
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .unwrap_or("index.html");
+    // join keeps absolute paths as they are
+    static_dir.join(index_file)
+}
+
 // Serve the PWA manifest with the correct Content-Type (Rocket doesn't know .webmanifest)
 #[get("/manifest.webmanifest", rank = 5)]
 async fn manifest() -> Option<(ContentType, Vec<u8>)> {
-    let bytes = rocket::tokio::fs::read("static/manifest.webmanifest")
+    let bytes = rocket::tokio::fs::read(STATIC_DIR.join("manifest.webmanifest"))
         .await
         .ok()?;
     Some((ContentType::new("application", "manifest+json"), bytes))
@@ -26,12 +65,24 @@ async fn manifest() -> Option<(ContentType, Vec<u8>)> {
 // SPA fallback: serve index.html for any route not matched by API or static files
 #[get("/<_path..>", rank = 100)]
 async fn spa_fallback(_path: PathBuf) -> Option<NamedFile> {
-    NamedFile::open("static/index.html").await.ok()
+    NamedFile::open(&*INDEX_FILE).await.ok()
 }
 
 #[get("/", rank = 99)]
 async fn index() -> Option<NamedFile> {
-    NamedFile::open("static/index.html").await.ok()
+    NamedFile::open(&*INDEX_FILE).await.ok()
+}
+
+/// Largest accepted JSON request body. Bigger bodies are rejected with
+/// `413 Payload Too Large` instead of being buffered. Defaults to 1 MiB;
+/// override with `MAX_JSON_BYTES`. Receipt scans have their own, larger limit
+/// (`MAX_SCAN_BYTES`).
+fn max_json_bytes() -> u64 {
+    std::env::var("MAX_JSON_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(1024 * 1024)
 }
 
 #[launch]
@@ -39,25 +90,13 @@ fn rocket() -> _ {
     // Load .env file if it exists
     dotenvy::dotenv().ok();
 
-    let cors = CorsOptions::default()
-        .allowed_origins(AllowedOrigins::all())
-        .allowed_methods(
-            vec![
-                Method::Get,
-                Method::Post,
-                Method::Put,
-                Method::Delete,
-                Method::Options,
-            ]
-            .into_iter()
-            .map(From::from)
-            .collect(),
-        )
-        .allowed_headers(AllowedHeaders::all())
-        .to_cors()
-        .expect("CORS configuration failed");
-
-    rocket::build()
+    let cors = cors::from_env().expect("CORS configuration failed");
+    auth::init_keys();
+    storage::init();
+
+    let figment = rocket::Config::figment().merge(("limits.json", max_json_bytes()));
+
+    rocket::custom(figment)
         .attach(cors)
         .attach(AdHoc::try_on_ignite(
             "Initialize Database",
@@ -75,36 +114,83 @@ fn rocket() -> _ {
                 Ok(rocket)
             },
         ))
+        .attach(shutdown::GracefulShutdown)
         .mount("/api", routes::get_routes())
         .register("/api", catchers![rocket_governor_catcher])
-        .attach(AdHoc::on_liftoff("Cleanup Scheduler", |_rocket| Box::pin(async {
+        .register("/api", guards::catchers())
+        .mount("/api", maintenance::get_routes())
+        .mount("/api", openapi::get_routes())
+        .attach(AdHoc::on_liftoff("Maintenance Scheduler", |_rocket| Box::pin(async {
             rocket::tokio::spawn(async {
-                let mut interval = rocket::tokio::time::interval(rocket::tokio::time::Duration::from_secs(24 * 60 * 60));
+                let mut interval = rocket::tokio::time::interval(*maintenance::INTERVAL);
                 loop {
                     interval.tick().await;
-                    let pool = db::get_pool();
-                    match sqlx::query("DELETE FROM groups WHERE last_activity_at < NOW() - INTERVAL '6 months'")
-                        .execute(pool)
-                        .await
-                    {
-                        Ok(result) => {
-                            let count = result.rows_affected();
-                            if count > 0 {
-                                println!("Cleanup: deleted {} inactive group(s)", count);
-                            }
-                        }
-                        Err(e) => eprintln!("Cleanup failed: {}", e),
-                    }
+                    maintenance::run_and_log().await;
+                }
+            });
+        })))
+        .attach(AdHoc::on_liftoff("Exchange Rate Refresh", |_rocket| Box::pin(async {
+            rocket::tokio::spawn(async {
+                let mut interval = rocket::tokio::time::interval(*rates::REFRESH_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    rates::refresh_and_log().await;
                 }
             });
         })))
         .mount("/", routes![manifest, index, spa_fallback])
+        .attach(AdHoc::on_ignite("Metrics", |rocket| async {
+            if metrics::enabled() {
+                rocket
+                    .attach(metrics::Metrics)
+                    .mount("/", metrics::get_routes())
+            } else {
+                println!("METRICS_ENABLED is off — skipping /metrics");
+                rocket
+            }
+        }))
         .attach(AdHoc::on_ignite("Static Files", |rocket| async {
-            if Path::new("static").is_dir() {
-                rocket.mount("/", rocket::fs::FileServer::from("static").rank(10))
+            if !INDEX_FILE.is_file() {
+                eprintln!("Warning: index file {} not found", INDEX_FILE.display());
+            }
+            if STATIC_DIR.is_dir() {
+                println!(
+                    "Serving static files from {} (index {})",
+                    STATIC_DIR.display(),
+                    INDEX_FILE.display()
+                );
+                rocket.mount("/", rocket::fs::FileServer::from(&*STATIC_DIR).rank(10))
             } else {
-                println!("No 'static' directory found — skipping static file serving");
+                println!(
+                    "No '{}' directory found — skipping static file serving",
+                    STATIC_DIR.display()
+                );
                 rocket
             }
         }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_defaults_to_index_html_in_the_static_dir() {
+        let dir = Path::new("/srv/app");
+        assert_eq!(resolve_index(dir, None), Path::new("/srv/app/index.html"));
+        assert_eq!(resolve_index(dir, Some("  ")), Path::new("/srv/app/index.html"));
+    }
+
+    #[test]
+    fn relative_index_files_are_inside_the_static_dir() {
+        let dir = Path::new("static");
+        assert_eq!(resolve_index(dir, Some(" app.html ")), Path::new("static/app.html"));
+        assert_eq!(resolve_index(dir, Some("pages/app.html")), Path::new("static/pages/app.html"));
+    }
+
+    #[test]
+    fn absolute_index_files_are_kept() {
+        let index = resolve_index(Path::new("static"), Some("/opt/app/index.html"));
+        assert_eq!(index, Path::new("/opt/app/index.html"));
+    }
+}
@@ -0,0 +1,177 @@
+use once_cell::sync::Lazy;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::db;
+
+/// Arbitrary key for the Postgres advisory lock that keeps maintenance single-flight
+/// across threads and server instances.
+const ADVISORY_LOCK_KEY: i64 = 0x5348_4152_4543_4c4e;
+
+/// How often the maintenance job runs. Defaults to daily; override with
+/// `MAINTENANCE_INTERVAL_SECS`.
+pub static INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let secs = std::env::var("MAINTENANCE_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n: &u64| n > 0)
+        .unwrap_or(24 * 60 * 60);
+    Duration::from_secs(secs)
+});
+
+/// Webhook delivery log entries older than this many days are purged.
+/// Defaults to 30; override with `WEBHOOK_DELIVERY_RETENTION_DAYS`.
+static WEBHOOK_DELIVERY_RETENTION_DAYS: Lazy<i32> = Lazy::new(|| {
+    std::env::var("WEBHOOK_DELIVERY_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(30)
+});
+
+/// What one maintenance run removed. `ran` is false when another run held the lock.
+#[derive(Debug, Default, Serialize)]
+pub struct MaintenanceReport {
+    pub ran: bool,
+    pub inactive_groups_deleted: u64,
+    pub webhook_deliveries_deleted: u64,
+    pub stale_tokens_deleted: u64,
+}
+
+/// Purge data nobody needs anymore:
+/// - groups inactive for 6 months (archived groups are kept)
+/// - webhook delivery log entries past their retention period
+/// - token registry entries of tokens that expired or were revoked together with
+///   every older token (individually revoked ones are kept, since the entry is
+///   what keeps them revoked)
+///
+/// Safe to call concurrently: only one run at a time holds the advisory lock,
+/// the others return immediately with `ran: false`.
+pub async fn run() -> Result<MaintenanceReport, sqlx::Error> {
+    let mut conn = db::get_pool().acquire().await?;
+
+    let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+        .bind(ADVISORY_LOCK_KEY)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !locked {
+        return Ok(MaintenanceReport::default());
+    }
+
+    let result = async {
+        let inactive_groups_deleted = sqlx::query(
+            "DELETE FROM groups WHERE last_activity_at < NOW() - INTERVAL '6 months' AND archived_at IS NULL",
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        let webhook_deliveries_deleted = sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE created_at < NOW() - make_interval(days => $1)",
+        )
+        .bind(*WEBHOOK_DELIVERY_RETENTION_DAYS)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        let stale_tokens_deleted = sqlx::query(
+            "DELETE FROM issued_tokens t USING groups g
+             WHERE g.id = t.group_id AND (t.expires_at <= NOW() OR t.issued_at <= g.tokens_revoked_before)",
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        Ok(MaintenanceReport {
+            ran: true,
+            inactive_groups_deleted,
+            webhook_deliveries_deleted,
+            stale_tokens_deleted,
+        })
+    }
+    .await;
+
+    // Advisory locks are per session, so unlock on the same connection
+    if let Err(e) = sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(ADVISORY_LOCK_KEY)
+        .execute(&mut *conn)
+        .await
+    {
+        eprintln!("Failed to release maintenance lock: {}", e);
+    }
+
+    result
+}
+
+/// Run maintenance and log what was removed.
+pub async fn run_and_log() {
+    match run().await {
+        Ok(report) if report.ran => {
+            if report.inactive_groups_deleted > 0 {
+                println!(
+                    "Cleanup: deleted {} inactive group(s)",
+                    report.inactive_groups_deleted
+                );
+            }
+            if report.webhook_deliveries_deleted > 0 {
+                println!(
+                    "Cleanup: deleted {} old webhook delivery log entries",
+                    report.webhook_deliveries_deleted
+                );
+            }
+            if report.stale_tokens_deleted > 0 {
+                println!(
+                    "Cleanup: deleted {} stale token registry entries",
+                    report.stale_tokens_deleted
+                );
+            }
+        }
+        Ok(_) => println!("Cleanup: skipped, another run is in progress"),
+        Err(e) => eprintln!("Cleanup failed: {}", e),
+    }
+}
+
+/// Request guard for operator endpoints: `Authorization: Bearer <ADMIN_TOKEN>`.
+/// When `ADMIN_TOKEN` is unset, admin endpoints are disabled (404).
+pub struct AdminAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminAuth {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(expected) = std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()) else {
+            return Outcome::Error((Status::NotFound, ()));
+        };
+        let provided = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "));
+        // Compare without short-circuiting on the first differing byte
+        let matches = provided.is_some_and(|p| {
+            p.len() == expected.len()
+                && p.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+        });
+        if matches {
+            Outcome::Success(AdminAuth)
+        } else {
+            Outcome::Error((Status::Unauthorized, ()))
+        }
+    }
+}
+
+// Trigger a maintenance run now - requires ADMIN_TOKEN
+#[post("/admin/maintenance")]
+async fn trigger_maintenance(_admin: AdminAuth) -> Result<Json<MaintenanceReport>, Status> {
+    run().await.map(Json).map_err(|e| {
+        eprintln!("Maintenance failed: {}", e);
+        Status::InternalServerError
+    })
+}
+
+pub fn get_routes() -> Vec<rocket::Route> {
+    routes![trigger_maintenance]
+}
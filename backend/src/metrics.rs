@@ -0,0 +1,210 @@
+use once_cell::sync::Lazy;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::{Data, Request, Response};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::db;
+
+/// Upper bounds (in seconds) of the request duration histogram buckets.
+const DURATION_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Metrics are enabled unless `METRICS_ENABLED` is set to `false`/`0`.
+pub fn enabled() -> bool {
+    std::env::var("METRICS_ENABLED")
+        .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "false" | "0" | "no" | "off"))
+        .unwrap_or(true)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RouteKey {
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Debug, Default)]
+struct RouteStats {
+    count: u64,
+    duration_sum: f64,
+    /// Cumulative counts per entry of `DURATION_BUCKETS`.
+    buckets: [u64; DURATION_BUCKETS.len()],
+}
+
+static ROUTE_STATS: Lazy<Mutex<BTreeMap<RouteKey, RouteStats>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Request-local start time, stored by `on_request` and read back in `on_response`.
+struct RequestStart(Option<Instant>);
+
+/// Fairing that records per-route request counts and durations.
+pub struct Metrics;
+
+#[rocket::async_trait]
+impl Fairing for Metrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Prometheus Metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let start = request.local_cache(|| RequestStart(None));
+        let Some(start) = start.0 else {
+            return;
+        };
+        // Label by the matched route template (e.g. `/api/groups/current/expenses/<expense_id>`)
+        // rather than the raw path to keep label cardinality bounded.
+        let route = request
+            .route()
+            .map(|r| r.uri.to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let key = RouteKey {
+            method: request.method().as_str().to_string(),
+            route,
+            status: response.status().code,
+        };
+        record(key, start.elapsed().as_secs_f64());
+    }
+}
+
+fn record(key: RouteKey, seconds: f64) {
+    let mut stats = ROUTE_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = stats.entry(key).or_default();
+    entry.count += 1;
+    entry.duration_sum += seconds;
+    for (bucket, bound) in entry.buckets.iter_mut().zip(DURATION_BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render all collected metrics in the Prometheus text exposition format.
+fn render() -> String {
+    let mut out = String::new();
+
+    {
+        let stats = ROUTE_STATS.lock().unwrap_or_else(|e| e.into_inner());
+
+        out.push_str("# HELP http_requests_total Total number of HTTP requests by route.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for (key, s) in stats.iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                key.method,
+                escape_label(&key.route),
+                key.status,
+                s.count
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds HTTP request latency by route.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (key, s) in stats.iter() {
+            let labels = format!(
+                "method=\"{}\",route=\"{}\",status=\"{}\"",
+                key.method,
+                escape_label(&key.route),
+                key.status
+            );
+            for (bound, count) in DURATION_BUCKETS.iter().zip(s.buckets) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, s.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, s.duration_sum
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, s.count
+            );
+        }
+    }
+
+    let pool = db::get_pool();
+    out.push_str("# HELP db_pool_connections Current number of connections in the pool.\n");
+    out.push_str("# TYPE db_pool_connections gauge\n");
+    let _ = writeln!(out, "db_pool_connections {}", pool.size());
+    out.push_str("# HELP db_pool_idle_connections Current number of idle connections in the pool.\n");
+    out.push_str("# TYPE db_pool_idle_connections gauge\n");
+    let _ = writeln!(out, "db_pool_idle_connections {}", pool.num_idle());
+
+    out
+}
+
+// Prometheus scrape endpoint
+#[get("/metrics")]
+fn metrics() -> (ContentType, String) {
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        render(),
+    )
+}
+
+pub fn get_routes() -> Vec<rocket::Route> {
+    routes![metrics]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    #[get("/metrics-test/<_id>")]
+    fn probe(_id: u32) -> &'static str {
+        "ok"
+    }
+
+    fn count(route: &str, status: u16) -> u64 {
+        let key = RouteKey {
+            method: "GET".to_string(),
+            route: route.to_string(),
+            status,
+        };
+        let stats = ROUTE_STATS.lock().unwrap_or_else(|e| e.into_inner());
+        stats.get(&key).map_or(0, |s| s.count)
+    }
+
+    #[test]
+    fn requests_increment_their_routes_counter() {
+        let rocket = rocket::build().mount("/", routes![probe]).attach(Metrics);
+        let client = Client::untracked(rocket).unwrap();
+        let before = count("/metrics-test/<_id>", 200);
+
+        // Different paths of one route share a counter; failed requests count elsewhere
+        for id in [1, 2] {
+            client.get(format!("/metrics-test/{}", id)).dispatch();
+        }
+        client.get("/metrics-test/not-a-number").dispatch();
+
+        assert_eq!(count("/metrics-test/<_id>", 200), before + 2);
+    }
+}
@@ -12,6 +12,10 @@ pub struct GroupRow {
     pub currency: String,
     pub created_at: DateTime<Utc>,
     pub last_activity_at: DateTime<Utc>,
+    pub default_split: Option<sqlx::types::Json<DefaultSplit>>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub locked: bool,
+    pub default_share_permissions: Option<sqlx::types::Json<PermissionsResponse>>,
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -23,6 +27,71 @@ pub struct MemberRow {
     pub paypal_email: Option<String>,
     pub iban: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub email: Option<String>,
+    pub notify_on_expense: bool,
+    pub preferred_payment_method: Option<String>,
+    pub venmo_handle: Option<String>,
+    pub payment_note: Option<String>,
+}
+
+/// What an expense represents. Stored and serialized in lowercase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpenseType {
+    /// Paid by one member, shared by the split members.
+    #[default]
+    Expense,
+    /// Money moved from `paid_by` to `transfer_to`.
+    Transfer,
+    /// Received by one member, owed out to the split members.
+    Income,
+}
+
+impl ExpenseType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExpenseType::Expense => "expense",
+            ExpenseType::Transfer => "transfer",
+            ExpenseType::Income => "income",
+        }
+    }
+
+    /// Parse the canonical lowercase name. Anything else (including other casings) is `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "expense" => Some(ExpenseType::Expense),
+            "transfer" => Some(ExpenseType::Transfer),
+            "income" => Some(ExpenseType::Income),
+            _ => None,
+        }
+    }
+}
+
+// Stored as VARCHAR, so encode/decode through the string form
+impl sqlx::Type<sqlx::Postgres> for ExpenseType {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <String as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for ExpenseType {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+        ExpenseType::parse(s).ok_or_else(|| format!("unknown expense type '{}'", s).into())
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for ExpenseType {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <&str as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(&self.as_str(), buf)
+    }
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -32,28 +101,54 @@ pub struct ExpenseRow {
     pub description: String,
     pub amount: BigDecimal,
     pub paid_by: Uuid,
-    pub expense_type: String,
+    pub expense_type: ExpenseType,
     pub transfer_to: Option<Uuid>,
     pub currency: String,
     pub exchange_rate: BigDecimal,
     pub expense_date: NaiveDate,
     pub created_at: DateTime<Utc>,
     pub split_type: String,
+    pub notes: Option<String>,
+    pub receipt_url: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub updated_by: Option<Uuid>,
+    pub trip_id: Option<Uuid>,
+    pub refund_of: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, FromRow)]
 pub struct ExpenseSplitMemberRow {
     pub member_id: Uuid,
     pub share: Option<BigDecimal>,
+    /// The member settled this share directly with the payer.
+    pub settled: bool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ExpenseReceiptRow {
+    pub storage_key: String,
+    pub content_type: String,
 }
 
 // API response types
+#[derive(Debug, Clone, FromRow)]
+pub struct ExpenseItemRow {
+    pub description: String,
+    pub amount: BigDecimal,
+    pub member_ids: Vec<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Member {
     pub id: Uuid,
     pub name: String,
     pub paypal_email: Option<String>,
     pub iban: Option<String>,
+    pub email: Option<String>,
+    pub notify_on_expense: bool,
+    pub preferred_payment_method: Option<String>,
+    pub venmo_handle: Option<String>,
+    pub payment_note: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +159,14 @@ pub struct Group {
     pub members: Vec<Member>,
     pub created_at: DateTime<Utc>,
     pub last_activity_at: DateTime<Utc>,
+    /// Split applied to new expenses that omit `split_between`.
+    pub default_split: Option<DefaultSplit>,
+    /// Set when the group is archived (read-only).
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Whether the group is locked against edits (still readable).
+    pub locked: bool,
+    /// Permissions new share links get for fields the request leaves out.
+    pub default_share_permissions: Option<PermissionsResponse>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,6 +176,22 @@ pub struct SplitEntry {
     pub share: Option<f64>,
 }
 
+/// An extra amount charged to one member of an `adjustment` split, on top of an
+/// equal part of what is left (e.g. "+4 for Alice's dessert").
+#[derive(Debug, Clone, Deserialize)]
+pub struct Adjustment {
+    pub member_id: Uuid,
+    pub amount: f64,
+}
+
+/// A group's default way of splitting expenses: the split type and the members
+/// (with optional shares/percentages/amounts) taking part.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefaultSplit {
+    pub split_type: String,
+    pub splits: Vec<SplitEntry>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Expense {
     pub id: Uuid,
@@ -81,7 +200,7 @@ pub struct Expense {
     pub amount: f64,
     pub paid_by: Uuid,
     pub split_between: Vec<Uuid>,
-    pub expense_type: String,
+    pub expense_type: ExpenseType,
     pub transfer_to: Option<Uuid>,
     pub currency: String,
     pub exchange_rate: f64,
@@ -91,6 +210,47 @@ pub struct Expense {
     pub split_type: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub splits: Option<Vec<SplitEntry>>,
+    /// Optional longer free-text context (e.g. "split excludes drinks").
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Link to a photo of the receipt (http or https).
+    #[serde(default)]
+    pub receipt_url: Option<String>,
+    /// Member who entered the expense, when it was created with a member-bound token.
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+    /// Member who last edited the expense, when it was edited with a member-bound token.
+    #[serde(default)]
+    pub updated_by: Option<Uuid>,
+    /// Free-form tags, normalized to lowercase without `#`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Line items of an itemized expense.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<ExpenseItem>>,
+    /// Trip or billing period the expense belongs to.
+    #[serde(default)]
+    pub trip_id: Option<Uuid>,
+    /// The expense this one refunds. Refunds are stored as `income` split like
+    /// the original, so they hand part of it back to the same members.
+    #[serde(default)]
+    pub refund_of: Option<Uuid>,
+    /// `amount` as an integer in the minor units of `currency` (e.g. cents), only
+    /// set when requested with `?amounts=minor`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_minor: Option<i64>,
+    /// Split members whose share was settled on its own (see
+    /// `PUT /groups/current/expenses/<id>/splits/<member_id>/settled`).
+    #[serde(default)]
+    pub settled_shares: Vec<Uuid>,
+}
+
+/// One line item of an itemized expense, shared equally by `member_ids`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpenseItem {
+    pub description: String,
+    pub amount: f64,
+    pub member_ids: Vec<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +258,494 @@ pub struct Balance {
     pub user_id: Uuid,
     pub user_name: String,
     pub balance: f64, // positive = owed money, negative = owes money
+    /// `balance` as an integer in minor units, only set with `?amounts=minor`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_minor: Option<i64>,
+}
+
+/// Result of `GET /groups/current/balances/verify`. Balances move money between
+/// members only, so they must add up to zero; anything beyond `tolerance` (half
+/// a minor unit) or any expense referencing a member outside the group points
+/// at broken data.
+#[derive(Debug, Serialize)]
+pub struct BalanceCheck {
+    pub ok: bool,
+    pub sum: f64,
+    pub tolerance: f64,
+    /// Splits, payers and transfer recipients of the group's expenses that are
+    /// not members of the group.
+    pub foreign_references: i64,
+    /// The cached balances differed from the recomputed ones and were dropped.
+    pub cache_stale: bool,
+    /// The recomputed balances.
+    pub balances: Vec<Balance>,
+}
+
+/// How saving an expense would change one member's balance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub delta: f64,
+    pub balance_before: f64,
+    pub balance_after: f64,
+}
+
+/// An exchange rate as served by `GET /rates`: 1 unit of `from` = `rate` units of `to`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRate {
+    pub from: String,
+    pub to: String,
+    pub rate: f64,
+    /// Publication date of the rate (on or shortly before the requested date).
+    pub date: NaiveDate,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Balances converted from the group currency into another currency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConvertedBalances {
+    pub currency: String,
+    pub group_currency: String,
+    /// 1 unit of the group currency in `currency`.
+    pub rate: f64,
+    /// Publication date of the rate.
+    pub rate_date: NaiveDate,
+    pub fetched_at: DateTime<Utc>,
+    pub balances: Vec<Balance>,
+}
+
+/// A member together with their current net balance (positive = owed money).
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberBalance {
+    #[serde(flatten)]
+    pub member: Member,
+    pub balance: f64,
+}
+
+/// A member with a negative balance, with payment info for building reminders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Debtor {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub balance: f64,
+    pub paypal_email: Option<String>,
+    pub iban: Option<String>,
+    pub preferred_payment_method: Option<String>,
+    pub venmo_handle: Option<String>,
+}
+
+/// One payment of the simplified settlement plan, with the payee's payment details,
+/// for feeding into a batch payment tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentRequest {
+    pub payer_id: Uuid,
+    pub payer_name: String,
+    pub payee_id: Uuid,
+    pub payee_name: String,
+    pub amount: f64,
+    /// `amount` in integer minor units of `currency` (e.g. cents).
+    pub amount_minor: i64,
+    pub currency: String,
+    /// How to pay: the payee's preferred method if its details are set, otherwise
+    /// the first of `iban`, `paypal`, `venmo` they have. `null` if none.
+    pub method: Option<String>,
+    pub iban: Option<String>,
+    pub paypal_email: Option<String>,
+    pub venmo_handle: Option<String>,
+    /// The payee's note for payers, e.g. the account holder's name.
+    pub payment_note: Option<String>,
+    /// Text for the payment's reference field.
+    pub reference: String,
+    /// False if the payee has no payment details, so this payment must be arranged by hand.
+    pub payable: bool,
+}
+
+/// All payments that settle the group, from `GET /groups/current/payment-requests`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentRequestBatch {
+    pub group_id: Uuid,
+    pub group_name: String,
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+    pub requests: Vec<PaymentRequest>,
+}
+
+/// One entry of the pairwise debt matrix: `from` owes `to` this amount.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebtEntry {
+    pub from: Uuid,
+    pub to: Uuid,
+    pub amount: f64,
+}
+
+/// One transfer of the simplified settlement plan: `from` pays `to` this amount.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settlement {
+    pub from: Uuid,
+    pub from_name: String,
+    pub to: Uuid,
+    pub to_name: String,
+    pub amount: f64,
+}
+
+/// Why there is no settlement plan: the balances don't add up to zero, so any plan
+/// would leave someone short. `unaccounted` is the sum of all balances (positive
+/// when more is owed to creditors than debtors owe).
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementWarning {
+    pub unaccounted: f64,
+    /// What is left of each balance after settling everything that can be settled.
+    pub members: Vec<UnaccountedBalance>,
+}
+
+/// Part of a member's balance with no counterpart in the other balances.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnaccountedBalance {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub amount: f64,
+}
+
+/// Repayment progress between two members. `owed` comes from regular expenses and
+/// income, `settled` from transfers `from` sent to `to`. A negative `remaining`
+/// means `from` has paid back more than they owed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettlementProgress {
+    pub from: Uuid,
+    pub from_name: String,
+    pub to: Uuid,
+    pub to_name: String,
+    pub owed: f64,
+    pub settled: f64,
+    pub remaining: f64,
+}
+
+/// Who pays whom in a settle-up suggestion between two members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettleUpDirection {
+    FromPaysTo,
+    ToPaysFrom,
+    /// The settlement plan has no payment between the two.
+    None,
+}
+
+/// The single payment that settles `from` and `to` according to the simplified
+/// settlement plan. `amount` is never negative; `direction` says who pays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettleUp {
+    pub from: Uuid,
+    pub from_name: String,
+    pub to: Uuid,
+    pub to_name: String,
+    pub amount: f64,
+    pub direction: SettleUpDirection,
+}
+
+/// Body of `GET /ready`. `error` explains why the database couldn't be checked.
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub database: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrations: Option<crate::db::MigrationStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A trip or billing period that expenses of a long-lived group can be bucketed
+/// into. `end_date` is open-ended when `None`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Trip {
+    pub id: Uuid,
+    pub name: String,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to create or update a trip.
+#[derive(Debug, Deserialize)]
+pub struct TripRequest {
+    pub name: String,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+}
+
+/// A tag used in a group and how many expenses carry it.
+#[derive(Debug, Serialize, FromRow)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+/// Spend and income under one category in `GET /groups/current/stats/by-category`.
+/// Categories are the expense tags; `name` is `null` for the bucket of untagged expenses.
+/// Income is money received, so it is counted in `income`, never in `total`.
+#[derive(Debug, Serialize, FromRow)]
+pub struct CategoryTotal {
+    pub name: Option<String>,
+    /// Sum of the expenses in the group currency, rounded to its minor units.
+    pub total: f64,
+    pub expense_count: i64,
+    /// Sum of the income in the group currency, rounded to its minor units.
+    pub income: f64,
+    pub income_count: i64,
+}
+
+/// Where a member's balance comes from, in `GET /groups/current/stats/members`.
+/// All amounts are in the group currency. `balance` is
+/// `paid - spent - income_collected + income_share + transfers_sent - transfers_received`,
+/// the same as in `GET /groups/current/balances`.
+#[derive(Debug, Serialize)]
+pub struct MemberStats {
+    pub member_id: Uuid,
+    pub name: String,
+    /// Expenses the member paid for.
+    pub paid: f64,
+    /// The member's shares of expenses.
+    pub spent: f64,
+    /// Income the member took in on behalf of the group.
+    pub income_collected: f64,
+    /// The member's shares of income.
+    pub income_share: f64,
+    pub transfers_sent: f64,
+    pub transfers_received: f64,
+    pub balance: f64,
+}
+
+/// An unusually large expense in `GET /groups/current/stats/anomalies`.
+#[derive(Debug, Serialize)]
+pub struct Anomaly {
+    pub expense: Expense,
+    /// The expense amount in the group currency.
+    pub amount: f64,
+    /// How many standard deviations the amount lies above the mean.
+    pub z_score: f64,
+}
+
+/// Expenses more than `k` standard deviations above the mean expense amount,
+/// largest first. Amounts are in the group currency; transfers and income are
+/// left out of the statistics.
+#[derive(Debug, Serialize)]
+pub struct AnomalyReport {
+    pub expense_count: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub k: f64,
+    /// `mean + k * std_dev`: expenses above this are reported.
+    pub threshold: f64,
+    pub anomalies: Vec<Anomaly>,
+}
+
+/// Outcome of `POST /groups/current/settle-all`: the transfers that were recorded
+/// (empty if the group was already settled) and the balances afterwards.
+#[derive(Debug, Serialize)]
+pub struct SettleAllResult {
+    pub transfers: Vec<Expense>,
+    pub balances: Vec<Balance>,
+}
+
+/// Everything the group home screen shows, computed at one group version.
+#[derive(Debug, Serialize)]
+pub struct GroupOverview {
+    pub group: Group,
+    pub balances: Vec<Balance>,
+    /// Empty when the balances can't be settled, see `settlement_warning`.
+    pub settlements: Vec<Settlement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settlement_warning: Option<SettlementWarning>,
+}
+
+/// Body of `POST /groups/overview`: tokens of any number of groups.
+#[derive(Debug, Deserialize)]
+pub struct GroupsOverviewRequest {
+    pub tokens: Vec<String>,
+}
+
+/// One group in `POST /groups/overview`, as seen with the given token.
+#[derive(Debug, Serialize)]
+pub struct GroupSummary {
+    pub group_id: Uuid,
+    pub name: String,
+    pub currency: String,
+    pub member_count: i64,
+    /// Sum of the group's expenses (not transfers or income) in its currency.
+    pub total_spend: f64,
+    pub last_activity_at: DateTime<Utc>,
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Member the token is bound to, if any.
+    pub member_id: Option<Uuid>,
+    /// That member's balance; positive means the group owes them.
+    pub balance: Option<f64>,
+}
+
+/// Entry of `POST /groups/overview` for the token at the same position: the
+/// group's summary, or why the token was skipped.
+#[derive(Debug, Serialize)]
+pub struct GroupsOverviewEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of `POST /groups/current/undo`: which action was reversed and the
+/// expense as it is now (absent when undoing its creation removed it).
+#[derive(Debug, Serialize)]
+pub struct UndoResult {
+    pub action: String,
+    pub expense_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expense: Option<Expense>,
+}
+
+/// What deleting a group would remove (`DELETE /groups/current?dry_run=true`).
+#[derive(Debug, Serialize, FromRow)]
+pub struct GroupDeletionPreview {
+    pub members: i64,
+    pub expenses: i64,
+    pub splits: i64,
+    pub items: i64,
+    pub receipts: i64,
+}
+
+/// Metadata of an uploaded receipt file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptInfo {
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// An expense from one member's point of view.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberExpense {
+    pub expense: Expense,
+    /// The member paid the expense (or sent the transfer / received the income).
+    pub paid: bool,
+    /// The member is the recipient of the transfer.
+    pub received: bool,
+    /// The member's portion of the split, in the group currency (0 if not in the split).
+    pub share: f64,
+    /// How the expense changed the member's balance.
+    pub change: f64,
+}
+
+/// Which of two members owes the other because of an expense.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairDirection {
+    AOwesB,
+    BOwesA,
+    /// Both are involved but the expense created no debt between them
+    /// (e.g. both are only in the split).
+    None,
+}
+
+/// An expense involving both members of a pair.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairExpense {
+    pub expense: Expense,
+    pub direction: PairDirection,
+    /// Debt the expense created between the two, in the group currency (never negative).
+    pub amount: f64,
+}
+
+/// The history between two members, newest first. `net` is what `b` owes `a`
+/// in total across these expenses (negative if `a` owes `b`).
+#[derive(Debug, Clone, Serialize)]
+pub struct PairExpenses {
+    pub a: Uuid,
+    pub a_name: String,
+    pub b: Uuid,
+    pub b_name: String,
+    pub net: f64,
+    pub expenses: Vec<PairExpense>,
+}
+
+/// One page of a group's expenses, newest first. Pass `next_cursor` as `?cursor=`
+/// to get the following page; it is absent on the last page.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpensePage {
+    pub expenses: Vec<Expense>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// One page of the expenses a member is involved in.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberExpensePage {
+    pub member_id: Uuid,
+    /// Number of matching expenses across all pages.
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub expenses: Vec<MemberExpense>,
+}
+
+/// One expense in a member statement, with the member's balance after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementEntry {
+    pub expense: Expense,
+    pub change: f64,
+    pub balance: f64,
+}
+
+/// A member's expenses in a date window with a running balance.
+/// `opening_balance` covers everything before the window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberStatement {
+    pub member_id: Uuid,
+    pub member_name: String,
+    pub opening_balance: f64,
+    pub closing_balance: f64,
+    pub entries: Vec<StatementEntry>,
+}
+
+/// How one expense affects a member's balance: `they_paid - their_share`. For
+/// income both are negative (the payer received the money, split members are owed
+/// their share); the receiver of a transfer carries it as their share.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakdownEntry {
+    pub expense_id: Uuid,
+    pub description: String,
+    pub date: NaiveDate,
+    pub expense_type: ExpenseType,
+    pub their_share: f64,
+    pub they_paid: f64,
+}
+
+/// Every expense behind a member's balance; `net` is what they add up to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberBreakdown {
+    pub member_id: Uuid,
+    pub member_name: String,
+    pub entries: Vec<BreakdownEntry>,
+    pub net: f64,
+}
+
+/// Body of `PUT /groups/current/expenses/<id>/splits/<member_id>/settled`.
+#[derive(Debug, Deserialize)]
+pub struct SettleShareRequest {
+    pub settled: bool,
+}
+
+/// Settlement state of one member's share of an expense.
+#[derive(Debug, Serialize)]
+pub struct ShareSettlement {
+    pub expense_id: Uuid,
+    pub member_id: Uuid,
+    pub settled: bool,
+    pub settled_at: Option<DateTime<Utc>>,
+}
+
+/// A member's balance at the end of a day, for charting its history.
+#[derive(Debug, Clone, Serialize)]
+pub struct BalancePoint {
+    pub date: NaiveDate,
+    pub balance: f64,
 }
 
 // Request DTOs
@@ -106,6 +754,48 @@ pub struct CreateGroupRequest {
     pub name: String,
     pub member_names: Vec<String>,
     pub currency: Option<String>,
+    /// Expenses to record with the group, e.g. when importing from another app.
+    #[serde(default)]
+    pub expenses: Vec<InitialExpense>,
+}
+
+/// An expense created together with its group. Members are referenced by their
+/// position in `member_names`.
+#[derive(Debug, Deserialize)]
+pub struct InitialExpense {
+    pub description: String,
+    pub amount: f64,
+    pub paid_by: usize,
+    /// Defaults to every member (nobody for transfers).
+    pub split_between: Option<Vec<usize>>,
+    #[serde(default = "default_expense_type")]
+    pub expense_type: String,
+    pub transfer_to: Option<usize>,
+    pub currency: Option<String>,
+    pub exchange_rate: Option<f64>,
+    pub expense_date: Option<NaiveDate>,
+    #[serde(default = "default_split_type")]
+    pub split_type: String,
+    pub splits: Option<Vec<InitialSplitEntry>>,
+    pub notes: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// A member's share of an initial expense, like [`SplitEntry`] but by member index.
+#[derive(Debug, Deserialize)]
+pub struct InitialSplitEntry {
+    pub member: usize,
+    pub share: f64,
+}
+
+/// Request to start a new group with the same members as the current one.
+#[derive(Debug, Deserialize)]
+pub struct CloneGroupRequest {
+    /// Name of the new group. Defaults to the current group's name.
+    pub name: Option<String>,
+    /// Also copy each member's payment details (PayPal, IBAN, Venmo, note).
+    #[serde(default)]
+    pub copy_payment_info: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -113,16 +803,48 @@ pub struct AddMemberRequest {
     pub name: String,
 }
 
+/// Request to merge one member (the source) into another (the target).
+#[derive(Debug, Deserialize)]
+pub struct MergeMembersRequest {
+    pub source_id: Uuid,
+    pub target_id: Uuid,
+}
+
+/// New display order of a group's members: every member id, first to last.
+#[derive(Debug, Deserialize)]
+pub struct ReorderMembersRequest {
+    pub member_ids: Vec<Uuid>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateMemberPaymentRequest {
     pub paypal_email: Option<String>,
     pub iban: Option<String>,
+    /// `paypal`, `iban` or `venmo`. The fields below are left unchanged when
+    /// omitted and cleared with `null`.
+    #[serde(default, deserialize_with = "double_option")]
+    pub preferred_payment_method: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub venmo_handle: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub payment_note: Option<Option<String>>,
+}
+
+/// Request to set a member's email notification preferences.
+#[derive(Debug, Deserialize)]
+pub struct UpdateMemberNotificationsRequest {
+    pub email: Option<String>,
+    pub notify_on_expense: bool,
 }
 
 fn default_expense_type() -> String {
     "expense".to_string()
 }
 
+fn default_true() -> bool {
+    true
+}
+
 fn default_split_type() -> String {
     "equal".to_string()
 }
@@ -130,11 +852,18 @@ fn default_split_type() -> String {
 #[derive(Debug, Deserialize)]
 pub struct CreateExpenseRequest {
     pub description: String,
+    /// Ignored (and may be omitted) when `items` are given.
+    #[serde(default)]
     pub amount: f64,
     pub paid_by: Uuid,
-    pub split_between: Vec<Uuid>,
+    /// Members sharing the expense. When omitted, the expense is split among all
+    /// members of the group at creation time. An explicit empty list is rejected
+    /// for everything except transfers; repeated ids are ignored (not an error).
+    pub split_between: Option<Vec<Uuid>>,
+    /// `expense`, `transfer` or `income`; any other value is rejected.
     #[serde(default = "default_expense_type")]
     pub expense_type: String,
+    /// Receiver of a transfer; `paid_by` is the one sending the money.
     pub transfer_to: Option<Uuid>,
     pub currency: Option<String>,
     pub exchange_rate: Option<f64>,
@@ -142,6 +871,26 @@ pub struct CreateExpenseRequest {
     #[serde(default = "default_split_type")]
     pub split_type: String,
     pub splits: Option<Vec<SplitEntry>>,
+    /// Split equally after charging each listed member their extra: shorthand for
+    /// `split_type: "adjustment"` with these amounts as `splits`.
+    pub adjustments: Option<Vec<Adjustment>>,
+    pub notes: Option<String>,
+    /// Link to a photo of the receipt (http or https, at most 2048 characters).
+    pub receipt_url: Option<String>,
+    /// Free-form tags like `vacation` or `#food`.
+    pub tags: Option<Vec<String>>,
+    /// When true, the payer is removed from the split (e.g. they're treating the others).
+    pub exclude_payer: Option<bool>,
+    /// Itemized expense: the amount becomes the item sum and each member pays for
+    /// the items assigned to them (`split_between`/`splits` are then ignored).
+    pub items: Option<Vec<ExpenseItem>>,
+    /// Trip of the group the expense belongs to.
+    pub trip_id: Option<Uuid>,
+    /// Record a refund of this expense of the group: it is stored as `income` in
+    /// the original's currency (unless given), split in proportion to the
+    /// original's shares. `split_between`, `splits`, `adjustments`, `items` and
+    /// `exclude_payer` must be omitted, and refunds can't exceed the original.
+    pub refund_of: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -150,6 +899,7 @@ pub struct UpdateExpenseRequest {
     pub amount: f64,
     pub paid_by: Uuid,
     pub split_between: Vec<Uuid>,
+    /// `expense`, `transfer` or `income`; any other value is rejected.
     #[serde(default = "default_expense_type")]
     pub expense_type: String,
     pub transfer_to: Option<Uuid>,
@@ -159,6 +909,61 @@ pub struct UpdateExpenseRequest {
     #[serde(default = "default_split_type")]
     pub split_type: String,
     pub splits: Option<Vec<SplitEntry>>,
+    /// Same shorthand as `CreateExpenseRequest::adjustments`.
+    pub adjustments: Option<Vec<Adjustment>>,
+    pub notes: Option<String>,
+    /// Link to a photo of the receipt. Omitted keeps the current link; use PATCH to clear it.
+    pub receipt_url: Option<String>,
+    /// Replaces the expense's tags. Omitted keeps the current tags.
+    pub tags: Option<Vec<String>>,
+    /// When true, the payer is removed from the split (e.g. they're treating the others).
+    pub exclude_payer: Option<bool>,
+    /// Moves the expense to another trip. Omitted keeps the current trip; use PATCH to clear it.
+    pub trip_id: Option<Uuid>,
+}
+
+/// Deserialize a field that distinguishes "absent" (`None`) from "explicitly null"
+/// (`Some(None)`). Use together with `#[serde(default)]`.
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Partial expense update: only fields present in the body are changed.
+/// Omitting `split_between` keeps the existing split members; `transfer_to`,
+/// `notes`, `receipt_url` and `trip_id` can be cleared by sending `null`.
+#[derive(Debug, Deserialize)]
+pub struct PatchExpenseRequest {
+    pub description: Option<String>,
+    pub amount: Option<f64>,
+    pub paid_by: Option<Uuid>,
+    pub split_between: Option<Vec<Uuid>>,
+    pub expense_type: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub transfer_to: Option<Option<Uuid>>,
+    pub currency: Option<String>,
+    pub exchange_rate: Option<f64>,
+    pub expense_date: Option<NaiveDate>,
+    pub split_type: Option<String>,
+    pub splits: Option<Vec<SplitEntry>>,
+    /// Same shorthand as `CreateExpenseRequest::adjustments`.
+    pub adjustments: Option<Vec<Adjustment>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub notes: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub receipt_url: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub trip_id: Option<Option<Uuid>>,
+}
+
+/// Optional body for duplicating an expense; the date defaults to today.
+#[derive(Debug, Deserialize)]
+pub struct DuplicateExpenseRequest {
+    pub expense_date: Option<NaiveDate>,
 }
 
 // Response DTOs
@@ -166,6 +971,9 @@ pub struct UpdateExpenseRequest {
 pub struct GroupCreatedResponse {
     pub group: Group,
     pub token: String,
+    /// The initial expenses, in request order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expenses: Vec<Expense>,
 }
 
 /// Request to generate a share link with specific permissions.
@@ -176,6 +984,13 @@ pub struct GenerateShareLinkRequest {
     pub can_update_payment: Option<bool>,
     pub can_add_expenses: Option<bool>,
     pub can_edit_expenses: Option<bool>,
+    pub can_settle: Option<bool>,
+    /// Number of times the link can be redeemed (e.g. 1 for a one-time invite).
+    pub max_uses: Option<i32>,
+    /// The link stops working this many hours after it is created.
+    pub expires_in_hours: Option<i64>,
+    /// Bind the link to a member: actions taken with it are attributed to them.
+    pub member_id: Option<Uuid>,
 }
 
 /// Response containing the generated share token and its effective permissions.
@@ -185,11 +1000,64 @@ pub struct ShareLinkResponse {
     pub permissions: PermissionsResponse,
 }
 
+/// Request to mint a new creator token, optionally revoking every earlier token.
+#[derive(Debug, Default, Deserialize)]
+pub struct NewOwnerTokenRequest {
+    /// Also revoke all previously issued tokens and delete all share links.
+    #[serde(default)]
+    pub revoke_existing: bool,
+}
+
+/// A fresh full-permission token; `revoked_existing` says whether all earlier
+/// tokens (including the one used for the request) stopped working.
+#[derive(Debug, Serialize)]
+pub struct NewOwnerTokenResponse {
+    pub token: String,
+    pub revoked_existing: bool,
+}
+
 /// Response containing the generated short share code and its effective permissions.
 #[derive(Debug, Serialize)]
 pub struct ShareCodeResponse {
     pub code: String,
     pub permissions: PermissionsResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_id: Option<Uuid>,
+}
+
+/// Request to invite a member to the group as themselves. Permissions work like
+/// for share links: missing ones are granted, all are capped by the caller's.
+#[derive(Debug, Default, Deserialize)]
+pub struct InviteMemberRequest {
+    /// Where to send the invite; saved as the member's email. Defaults to the
+    /// email already stored for the member.
+    pub email: Option<String>,
+    pub can_delete_group: Option<bool>,
+    pub can_manage_members: Option<bool>,
+    pub can_update_payment: Option<bool>,
+    pub can_add_expenses: Option<bool>,
+    pub can_edit_expenses: Option<bool>,
+    pub can_settle: Option<bool>,
+    /// How long the link works. Defaults to 168 (one week).
+    pub expires_in_hours: Option<i64>,
+}
+
+/// A sent (or, without SMTP, prepared) member invite. The single-use link signs
+/// the member in with a token bound to them. `link` is only returned when no
+/// email was sent, so it can be passed on by hand in development.
+#[derive(Debug, Serialize)]
+pub struct MemberInviteResponse {
+    pub member_id: Uuid,
+    pub email: Option<String>,
+    pub sent: bool,
+    pub permissions: PermissionsResponse,
+    pub expires_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
 }
 
 /// A share link entry for listing existing links.
@@ -201,7 +1069,12 @@ pub struct ShareLinkItem {
     pub can_update_payment: bool,
     pub can_add_expenses: bool,
     pub can_edit_expenses: bool,
+    pub can_settle: bool,
     pub created_at: String,
+    pub max_uses: Option<i32>,
+    pub use_count: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub member_id: Option<Uuid>,
 }
 
 /// Request to redeem a share code for a JWT token.
@@ -212,18 +1085,79 @@ pub struct RedeemShareCodeRequest {
     pub existing_token: Option<String>,
 }
 
+/// Request to register an outbound webhook.
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Event types to deliver, e.g. `expense.created`.
+    pub events: Vec<String>,
+    /// Signing secret; generated when omitted.
+    pub secret: Option<String>,
+}
+
+/// A registered webhook (the secret is only returned on creation).
+#[derive(Debug, Serialize)]
+pub struct WebhookItem {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_at: String,
+}
+
+/// Response to webhook registration, including the signing secret.
+#[derive(Debug, Serialize)]
+pub struct WebhookCreatedResponse {
+    pub webhook: WebhookItem,
+    pub secret: String,
+}
+
+/// A single logged webhook delivery attempt.
+#[derive(Debug, Serialize)]
+pub struct WebhookDeliveryItem {
+    pub event: String,
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub created_at: String,
+}
+
 /// Request to rename a group.
 #[derive(Debug, Deserialize)]
 pub struct RenameGroupRequest {
     pub name: String,
 }
 
+/// Set (or clear with `null`) the group's default split.
+#[derive(Debug, Deserialize)]
+pub struct SetDefaultSplitRequest {
+    pub default_split: Option<DefaultSplit>,
+}
+
+/// Set (or clear with `null`) the permissions new share links default to.
+#[derive(Debug, Deserialize)]
+pub struct SetDefaultSharePermissionsRequest {
+    pub default_share_permissions: Option<PermissionsResponse>,
+}
+
 /// Request to merge an existing token with the current one.
 #[derive(Debug, Deserialize)]
 pub struct MergeTokenRequest {
     pub other_token: String,
 }
 
+/// Request for a token of the caller's own with fewer permissions. Omitted
+/// permissions keep the caller's; none can exceed them.
+#[derive(Debug, Deserialize)]
+pub struct ScopedTokenRequest {
+    pub can_delete_group: Option<bool>,
+    pub can_manage_members: Option<bool>,
+    pub can_update_payment: Option<bool>,
+    pub can_add_expenses: Option<bool>,
+    pub can_edit_expenses: Option<bool>,
+    pub can_settle: Option<bool>,
+}
+
 /// Permissions in API responses (always resolved to concrete booleans).
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PermissionsResponse {
@@ -232,6 +1166,45 @@ pub struct PermissionsResponse {
     pub can_update_payment: bool,
     pub can_add_expenses: bool,
     pub can_edit_expenses: bool,
+    /// Record transfers and settle up. Granted when omitted, as before it existed.
+    #[serde(default = "default_true")]
+    pub can_settle: bool,
+}
+
+/// Body of `POST /groups/current/inspect-token`.
+#[derive(Debug, Deserialize)]
+pub struct InspectTokenRequest {
+    pub token: String,
+}
+
+/// Non-sensitive claims of the presented token.
+#[derive(Debug, Serialize)]
+pub struct TokenInfo {
+    pub group_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    /// True when the token expires within the warning window.
+    pub expiring_soon: bool,
+    pub permissions: PermissionsResponse,
+    /// Member the token is bound to, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// An active token of the group, for `GET /groups/current/tokens`.
+#[derive(Debug, Serialize)]
+pub struct IssuedTokenItem {
+    pub jti: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Last request made with the token, to the minute; `None` if never used.
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Member the token is bound to, if any.
+    pub member_id: Option<Uuid>,
+    pub permissions: PermissionsResponse,
+    /// True for the token making the request.
+    pub current: bool,
 }
 
 // Conversion helpers
@@ -242,6 +1215,11 @@ impl From<MemberRow> for Member {
             name: row.name,
             paypal_email: row.paypal_email,
             iban: row.iban,
+            email: row.email,
+            notify_on_expense: row.notify_on_expense,
+            preferred_payment_method: row.preferred_payment_method,
+            venmo_handle: row.venmo_handle,
+            payment_note: row.payment_note,
         }
     }
 }
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use once_cell::sync::Lazy;
+
+/// SMTP settings read from the environment:
+/// - `SMTP_HOST` (required to enable notifications), `SMTP_PORT`
+/// - `SMTP_USERNAME` / `SMTP_PASSWORD` (optional credentials)
+/// - `SMTP_FROM` sender address (default `Share Cost <noreply@share-cost.site>`)
+/// - `SMTP_TLS`: `starttls` (default), `tls`, or `none` for local test servers
+///
+/// When `SMTP_HOST` is unset, sending is a no-op so dev and tests never send mail.
+struct SmtpConfig {
+    host: String,
+    port: Option<u16>,
+    credentials: Option<Credentials>,
+    from: String,
+    tls: String,
+}
+
+static SMTP_CONFIG: Lazy<Option<SmtpConfig>> = Lazy::new(|| {
+    let host = std::env::var("SMTP_HOST").ok().filter(|s| !s.trim().is_empty())?;
+    let port = std::env::var("SMTP_PORT").ok().and_then(|p| p.trim().parse().ok());
+    let credentials = match (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+        (Ok(user), Ok(pass)) => Some(Credentials::new(user, pass)),
+        _ => None,
+    };
+    Some(SmtpConfig {
+        host,
+        port,
+        credentials,
+        from: std::env::var("SMTP_FROM")
+            .unwrap_or_else(|_| "Share Cost <noreply@share-cost.site>".to_string()),
+        tls: std::env::var("SMTP_TLS")
+            .unwrap_or_else(|_| "starttls".to_string())
+            .to_ascii_lowercase(),
+    })
+});
+
+/// Whether an SMTP server is configured.
+pub fn enabled() -> bool {
+    SMTP_CONFIG.is_some()
+}
+
+/// Everything needed to tell a member they were added to an expense.
+#[derive(Debug, Clone)]
+pub struct ExpenseNotification {
+    pub recipient_name: String,
+    pub recipient_email: String,
+    pub group_name: String,
+    pub description: String,
+    pub paid_by_name: String,
+    pub amount: f64,
+    pub share: f64,
+    pub currency: String,
+}
+
+/// Build the subject and plain-text body of an expense notification.
+pub fn build_expense_message(n: &ExpenseNotification) -> (String, String) {
+    let subject = format!("{}: new expense \"{}\"", n.group_name, n.description);
+    let body = [
+        format!("Hi {},", n.recipient_name),
+        String::new(),
+        format!(
+            "{} added an expense in \"{}\" that includes you:",
+            n.paid_by_name, n.group_name
+        ),
+        String::new(),
+        format!("  {}", n.description),
+        format!("  Total: {:.2} {}", n.amount, n.currency),
+        format!("  Your share: {:.2} {}", n.share, n.currency),
+        String::new(),
+        "You are receiving this because expense notifications are enabled for you in this group."
+            .to_string(),
+    ]
+    .join("\n");
+    (subject, body)
+}
+
+/// Send an expense notification. Failures are logged, never returned:
+/// notifications are best-effort and must not affect the request that triggered them.
+pub async fn send_expense_notification(n: ExpenseNotification) {
+    let (subject, body) = build_expense_message(&n);
+    send(&n.recipient_name, &n.recipient_email, subject, body).await;
+}
+
+/// Base URL of the web app, used for links in emails and share QR codes.
+/// Defaults to `https://share-cost.site`; override with `APP_BASE_URL` (or the
+/// older `APP_URL`).
+static APP_URL: Lazy<String> = Lazy::new(|| {
+    std::env::var("APP_BASE_URL")
+        .or_else(|_| std::env::var("APP_URL"))
+        .ok()
+        .map(|v| v.trim().trim_end_matches('/').to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "https://share-cost.site".to_string())
+});
+
+/// Link that opens the app and redeems a share code.
+pub fn join_link(code: &str) -> String {
+    format!("{}/#join={}", *APP_URL, code)
+}
+
+/// Everything needed to invite a member to act as themselves in a group.
+#[derive(Debug, Clone)]
+pub struct MemberInvite {
+    pub recipient_name: String,
+    pub recipient_email: String,
+    pub group_name: String,
+    pub link: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Build the subject and plain-text body of a member invite.
+pub fn build_invite_message(n: &MemberInvite) -> (String, String) {
+    let subject = format!("You're invited to \"{}\"", n.group_name);
+    let body = [
+        format!("Hi {},", n.recipient_name),
+        String::new(),
+        format!(
+            "Open this link to see and add expenses in \"{}\" as yourself:",
+            n.group_name
+        ),
+        String::new(),
+        format!("  {}", n.link),
+        String::new(),
+        format!(
+            "The link works once and expires on {}.",
+            n.expires_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+    ]
+    .join("\n");
+    (subject, body)
+}
+
+/// Send a member invite. Returns whether the mail was handed to the SMTP server
+/// (always false when SMTP isn't configured); failures are logged.
+pub async fn send_member_invite(n: &MemberInvite) -> bool {
+    let (subject, body) = build_invite_message(n);
+    send(&n.recipient_name, &n.recipient_email, subject, body).await
+}
+
+async fn send(recipient_name: &str, recipient_email: &str, subject: String, body: String) -> bool {
+    let Some(config) = SMTP_CONFIG.as_ref() else {
+        return false;
+    };
+
+    let from: Mailbox = match config.from.parse() {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Invalid SMTP_FROM address: {}", e);
+            return false;
+        }
+    };
+    let to = match recipient_email.parse() {
+        Ok(address) => Mailbox::new(Some(recipient_name.to_string()), address),
+        Err(e) => {
+            eprintln!("Invalid notification recipient {}: {}", recipient_email, e);
+            return false;
+        }
+    };
+    let message = match Message::builder().from(from).to(to).subject(subject).body(body) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Failed to build notification email: {}", e);
+            return false;
+        }
+    };
+
+    let builder = match config.tls.as_str() {
+        "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)),
+        "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+        _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host),
+    };
+    let mut builder = match builder {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("Failed to configure SMTP transport: {}", e);
+            return false;
+        }
+    };
+    if let Some(port) = config.port {
+        builder = builder.port(port);
+    }
+    if let Some(credentials) = &config.credentials {
+        builder = builder.credentials(credentials.clone());
+    }
+
+    if let Err(e) = builder.build().send(message).await {
+        eprintln!("Failed to send notification to {}: {}", recipient_email, e);
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expense_messages_name_the_expense_and_the_recipients_share() {
+        let (subject, body) = build_expense_message(&ExpenseNotification {
+            recipient_name: "Bob".to_string(),
+            recipient_email: "bob@example.org".to_string(),
+            group_name: "Trip".to_string(),
+            description: "Dinner".to_string(),
+            paid_by_name: "Alice".to_string(),
+            amount: 30.0,
+            share: 12.5,
+            currency: "EUR".to_string(),
+        });
+
+        assert_eq!(subject, "Trip: new expense \"Dinner\"");
+        assert!(body.starts_with("Hi Bob,\n"));
+        assert!(body.contains("Alice added an expense in \"Trip\" that includes you:"));
+        assert!(body.contains("  Total: 30.00 EUR\n"));
+        assert!(body.contains("  Your share: 12.50 EUR\n"));
+    }
+}
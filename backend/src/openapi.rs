@@ -0,0 +1,1194 @@
+use rocket::serde::json::Json;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{Orbit, Rocket};
+use serde_json::{Map, Value, json};
+
+/// Routes under this prefix make up the API described by `GET /api/openapi.json`.
+const API_BASE: &str = "/api";
+
+/// Who may call an operation.
+#[derive(Clone, Copy)]
+enum Access {
+    Public,
+    /// Any valid group token.
+    Token,
+    /// A group token with this permission (`all` = every permission).
+    Permission(&'static str),
+    /// `Authorization: Bearer <ADMIN_TOKEN>`.
+    Admin,
+}
+
+/// Successful response of an operation. JSON bodies are schema names, `[Name]` for
+/// lists and `A | B` when the query decides between two shapes.
+#[derive(Clone, Copy)]
+enum Reply {
+    Json(&'static str),
+    NoContent,
+    /// `204`, or JSON with the given schema (e.g. for dry runs).
+    NoContentOr(&'static str),
+    /// A non-JSON body with this content type.
+    Raw(&'static str),
+}
+
+/// A query parameter: name, type (`string`, `integer`, `boolean`, `date`, `uuid`),
+/// whether it is required, description.
+type QueryParam = (&'static str, &'static str, bool, &'static str);
+
+/// What the route table can't tell about an operation, keyed by handler name.
+struct Operation {
+    handler: &'static str,
+    summary: &'static str,
+    access: Access,
+    body: Option<&'static str>,
+    reply: Reply,
+    query: &'static [QueryParam],
+}
+
+const fn op(
+    handler: &'static str,
+    summary: &'static str,
+    access: Access,
+    body: Option<&'static str>,
+    reply: Reply,
+) -> Operation {
+    Operation {
+        handler,
+        summary,
+        access,
+        body,
+        reply,
+        query: &[],
+    }
+}
+
+const fn with_query(operation: Operation, query: &'static [QueryParam]) -> Operation {
+    Operation { query, ..operation }
+}
+
+use Access::{Admin, Permission, Public, Token};
+use Reply::{Json as Body, NoContent, NoContentOr, Raw};
+
+/// Paths, methods and parameter names come from the mounted routes, so the document
+/// can't list a route that doesn't exist or miss one that does; this table adds
+/// summaries, auth and body schemas. Routes missing here still show up, undocumented.
+const OPERATIONS: &[Operation] = &[
+    op("health", "Liveness check", Public, None, Raw("text/plain")),
+    op("ready", "Database reachable and all migrations applied (503 otherwise)", Public, None, Body("Readiness")),
+    op("openapi_json", "This document", Public, None, Raw("application/json")),
+    op("create_group", "Create a group with members and optional initial expenses, atomically; returns a creator token", Public, Some("CreateGroupRequest"), Body("GroupCreatedResponse")),
+    op("clone_group", "Start a new group with the same members and currency", Token, Some("CloneGroupRequest"), Body("GroupCreatedResponse")),
+    op("get_current_group", "The group of the token (supports If-None-Match)", Token, None, Body("Group")),
+    op("get_overview", "Group, balances and settlement plan at one version (supports If-None-Match)", Token, None, Body("GroupOverview")),
+    op("get_groups_overview", "Summaries of the groups of several tokens; invalid tokens get an error entry", Public, Some("GroupsOverviewRequest"), Body("[GroupsOverviewEntry]")),
+    op("add_member", "Add a member", Permission("manage_members"), Some("AddMemberRequest"), Body("Group")),
+    op("merge_members", "Merge the source member into the target", Permission("manage_members"), Some("MergeMembersRequest"), Body("Group")),
+    op("reorder_members", "Set the order members are listed in", Permission("manage_members"), Some("ReorderMembersRequest"), Body("Group")),
+    with_query(
+        op("reassign_member", "Move a member's expenses to another member", Permission("manage_members"), None, Body("Group")),
+        &[("to", "uuid", true, "Member that takes over the expenses")],
+    ),
+    op("update_member_payment", "Set a member's payment details", Permission("update_payment"), Some("UpdateMemberPaymentRequest"), Body("Member")),
+    op("update_member_notifications", "Set a member's email notification preferences", Permission("update_payment"), Some("UpdateMemberNotificationsRequest"), Body("Member")),
+    with_query(
+        op("get_expenses", "Expenses, newest first; paginated when `limit` or `cursor` is given", Token, None, Body("[Expense] | ExpensePage")),
+        &[
+            ("tag", "string", false, "Only expenses with this tag"),
+            ("limit", "integer", false, "Page size (1-200)"),
+            ("cursor", "string", false, "`next_cursor` of the previous page"),
+            ("amounts", "string", false, "`minor` adds `amount_minor`"),
+        ],
+    ),
+    op("create_expense", "Record an expense, transfer or income (transfers need `settle` instead)", Permission("add_expenses"), Some("CreateExpenseRequest"), Body("Expense")),
+    op("preview_expense", "How an expense would change each balance, without saving it (transfers need `settle` instead)", Permission("add_expenses"), Some("CreateExpenseRequest"), Body("[BalanceChange]")),
+    op("update_expense", "Replace an expense", Permission("edit_expenses"), Some("UpdateExpenseRequest"), Body("Expense")),
+    op("patch_expense", "Change only the given fields of an expense", Permission("edit_expenses"), Some("PatchExpenseRequest"), Body("Expense")),
+    op("duplicate_expense", "Record an expense again (body optional; transfers need `settle` instead)", Permission("add_expenses"), Some("DuplicateExpenseRequest"), Body("Expense")),
+    op("delete_expense", "Delete an expense", Permission("edit_expenses"), None, NoContent),
+    op("set_share_settled", "Mark a member's share of an expense as settled", Permission("settle"), Some("SettleShareRequest"), Body("ShareSettlement")),
+    op("upload_receipt", "Upload a receipt file (multipart field `file`)", Permission("edit_expenses"), None, Body("ReceiptInfo")),
+    op("get_receipt", "Download the receipt file", Token, None, Raw("application/octet-stream")),
+    op("delete_receipt", "Remove the receipt file", Permission("edit_expenses"), None, NoContent),
+    with_query(
+        op("get_activity", "Log of expense changes, newest first", Token, None, Body("ActivityPage")),
+        &[
+            ("action", "string", false, "Only `expense_created`, `expense_updated` or `expense_deleted`"),
+            ("from", "date", false, "First day (inclusive, UTC)"),
+            ("to", "date", false, "Last day (inclusive, UTC)"),
+            ("limit", "integer", false, "Page size (1-200)"),
+            ("offset", "integer", false, "Entries to skip"),
+        ],
+    ),
+    op("undo_last_action", "Undo the latest expense change made with this token", Token, None, Body("UndoResult")),
+    op("get_tags", "Tags in use with their expense counts", Token, None, Body("[TagCount]")),
+    with_query(
+        op("get_stats_by_category", "Spend and income per tag, untagged expenses in a `null` bucket", Token, None, Body("[CategoryTotal]")),
+        &[
+            ("from", "date", false, "First expense date (inclusive)"),
+            ("to", "date", false, "Last expense date (inclusive)"),
+        ],
+    ),
+    op("get_member_stats", "Per-member paid, spent, income and transfers adding up to the balance", Token, None, Body("[MemberStats]")),
+    with_query(
+        op("get_anomalies", "Expenses far above the group's mean expense amount", Token, None, Body("AnomalyReport")),
+        &[("k", "number", false, "Standard deviations above the mean (default 2)")],
+    ),
+    op("get_trips", "Trips of the group, earliest first", Token, None, Body("[Trip]")),
+    op("create_trip", "Create a trip", Permission("add_expenses"), Some("TripRequest"), Body("Trip")),
+    op("update_trip", "Rename a trip or change its dates", Permission("edit_expenses"), Some("TripRequest"), Body("Trip")),
+    op("delete_trip", "Delete a trip, keeping its expenses", Permission("edit_expenses"), None, NoContent),
+    op("get_trip_balances", "Balances from the expenses of one trip", Token, None, Body("[Balance]")),
+    op("get_currency_info", "Symbol and number formatting of the group currency", Token, None, Body("CurrencyInfo")),
+    with_query(
+        op("get_balances", "Net balance per member (positive = owed money)", Token, None, Body("[Balance] | ConvertedBalances")),
+        &[
+            ("currency", "string", false, "Convert into this ISO 4217 currency"),
+            ("fresh", "boolean", false, "Bypass the balance cache"),
+            ("amounts", "string", false, "`minor` adds `balance_minor`"),
+            ("include_settled", "boolean", false, "`false` leaves out shares settled on their own"),
+        ],
+    ),
+    op("verify_balances", "Check that the recomputed balances add up to zero", Token, None, Body("BalanceCheck")),
+    with_query(
+        op("get_member_statement", "A member's expenses with a running balance", Token, None, Body("MemberStatement")),
+        &[
+            ("from", "date", false, "First expense date (inclusive)"),
+            ("to", "date", false, "Last expense date (inclusive)"),
+        ],
+    ),
+    op("get_member_breakdown", "What a member paid and their share of each expense, adding up to their balance", Token, None, Body("MemberBreakdown")),
+    op("get_member_balance_history", "A member's balance at the end of each expense date", Token, None, Body("[BalancePoint]")),
+    with_query(
+        op("get_member_expenses", "Expenses a member is involved in, newest first", Token, None, Body("MemberExpensePage")),
+        &[
+            ("limit", "integer", false, "Page size (1-200)"),
+            ("offset", "integer", false, "Expenses to skip"),
+        ],
+    ),
+    op("get_pair_expenses", "Expenses involving both members, with the debt each created between them", Token, None, Body("PairExpenses")),
+    with_query(
+        op("get_members_by_balance", "Members sorted by balance", Token, None, Body("[MemberBalance]")),
+        &[("order", "string", false, "`desc` (default) or `asc`")],
+    ),
+    with_query(
+        op("get_settle_up", "The payment that settles two members", Token, None, Body("SettleUp")),
+        &[
+            ("from", "uuid", true, "First member"),
+            ("to", "uuid", true, "Second member"),
+        ],
+    ),
+    op("settle_all", "Record every transfer of the settlement plan", Permission("settle"), None, Body("SettleAllResult")),
+    op("get_settlement_progress", "Owed, paid back and remaining per member pair", Token, None, Body("[SettlementProgress]")),
+    with_query(
+        op("get_report_pdf", "Printable settlement report", Token, None, Raw("application/pdf")),
+        &[("locale", "string", false, "Number and date formatting, e.g. `de-DE`")],
+    ),
+    op("get_debt_matrix", "Pairwise debts before simplification", Token, None, Body("[DebtEntry]")),
+    op("get_debtors", "Members who owe money, most indebted first", Token, None, Body("[Debtor]")),
+    op("get_payment_requests", "Settlement plan as payment requests with the payees' payment details", Token, None, Body("PaymentRequestBatch")),
+    op("get_permissions", "Permissions of the token", Token, None, Body("PermissionsResponse")),
+    op("get_token_info", "Non-sensitive claims of the token", Token, None, Body("TokenInfo")),
+    op("inspect_token", "Claims of another token of the same group", Token, Some("InspectTokenRequest"), Body("TokenInfo")),
+    op("generate_share_link", "Create a share code (omitted permissions use the group default; capped by the token's)", Token, Some("GenerateShareLinkRequest"), Body("ShareCodeResponse")),
+    op("invite_member", "Email a member a single-use link that signs them in as themselves (body optional; without SMTP the link is returned)", Permission("manage_members"), Some("InviteMemberRequest"), Body("MemberInviteResponse")),
+    op("redeem_share_code", "Exchange a share code for a token", Public, Some("RedeemShareCodeRequest"), Body("ShareLinkResponse")),
+    op("merge_token", "Combine the permissions of two tokens of the group", Token, Some("MergeTokenRequest"), Body("ShareLinkResponse")),
+    op("scoped_token", "Mint a token for the caller with a subset of their permissions", Token, Some("ScopedTokenRequest"), Body("ShareLinkResponse")),
+    op("new_owner_token", "Mint a creator token, optionally revoking all others (body optional)", Permission("all"), Some("NewOwnerTokenRequest"), Body("NewOwnerTokenResponse")),
+    op("list_share_links", "Share codes of the group", Permission("all"), None, Body("[ShareLinkItem]")),
+    op("delete_share_link", "Delete a share code", Permission("all"), None, NoContent),
+    op("list_tokens", "Active tokens of the group, newest first", Permission("all"), None, Body("[IssuedTokenItem]")),
+    op("revoke_token", "Revoke one token by its jti", Permission("all"), None, NoContent),
+    op("share_link_qr", "QR code (SVG) of a share code's join URL; the token must hold the code's permissions", Token, None, Raw("image/svg+xml")),
+    op("list_webhooks", "Registered webhooks", Permission("all"), None, Body("[WebhookItem]")),
+    op("create_webhook", "Register a webhook", Permission("all"), Some("CreateWebhookRequest"), Body("WebhookCreatedResponse")),
+    op("delete_webhook", "Delete a webhook", Permission("all"), None, NoContent),
+    op("list_webhook_deliveries", "Delivery log of a webhook, newest first", Permission("all"), None, Body("[WebhookDeliveryItem]")),
+    op("rename_group", "Rename the group", Permission("delete_group"), Some("RenameGroupRequest"), Body("Group")),
+    op("set_default_split", "Set or clear the default split", Permission("delete_group"), Some("SetDefaultSplitRequest"), Body("Group")),
+    op("set_default_share_permissions", "Set or clear the permissions new share links default to", Permission("delete_group"), Some("SetDefaultSharePermissionsRequest"), Body("Group")),
+    op("archive_group", "Make the group read-only and keep it past the inactivity cleanup", Permission("delete_group"), None, NoContent),
+    op("unarchive_group", "Make an archived group writable again", Permission("delete_group"), None, NoContent),
+    op("lock_group", "Lock the group against edits; it stays readable", Permission("all"), None, NoContent),
+    op("unlock_group", "Unlock a locked group", Permission("all"), None, NoContent),
+    with_query(
+        op("delete_group", "Delete the group and everything in it", Permission("delete_group"), None, NoContentOr("GroupDeletionPreview")),
+        &[("dry_run", "boolean", false, "Only count what would be deleted")],
+    ),
+    op("extend_lifetime", "Reset the inactivity timer", Token, None, NoContent),
+    op("scan_receipt", "Extract title, total and items from a receipt image", Token, Some("ScanReceiptRequest"), Body("ScanReceiptResponse")),
+    with_query(
+        op("exchange_rate", "Exchange rate as returned by the rate provider", Public, None, Raw("application/json")),
+        &[
+            ("date", "string", true, "`YYYY-MM-DD` or `latest`"),
+            ("from", "string", true, "ISO 4217 code"),
+            ("to", "string", true, "ISO 4217 code"),
+        ],
+    ),
+    with_query(
+        op("get_rate", "Stored exchange rate on a date", Public, None, Body("ExchangeRate")),
+        &[
+            ("from", "string", true, "ISO 4217 code"),
+            ("to", "string", true, "ISO 4217 code"),
+            ("date", "date", false, "Defaults to today"),
+        ],
+    ),
+    op("trigger_maintenance", "Run the cleanup now", Admin, None, Body("MaintenanceReport")),
+];
+
+// OpenAPI 3 description of the API - no auth required
+#[get("/openapi.json")]
+fn openapi_json(rocket: Mounted<'_>) -> Json<Value> {
+    Json(document(rocket.0.routes()))
+}
+
+/// The running instance, to read the mounted routes from.
+struct Mounted<'r>(&'r Rocket<Orbit>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Mounted<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        Outcome::Success(Mounted(request.rocket()))
+    }
+}
+
+pub fn get_routes() -> Vec<rocket::Route> {
+    routes![openapi_json]
+}
+
+fn document<'a>(routes: impl Iterator<Item = &'a rocket::Route>) -> Value {
+    let mut paths = Map::new();
+    for route in routes {
+        let Some(path) = route.uri.path().strip_prefix(API_BASE) else {
+            continue;
+        };
+        let name = route.name.as_deref().unwrap_or_default();
+        let entry = paths
+            .entry(format!("{}{}", API_BASE, openapi_path(path)))
+            .or_insert_with(|| json!({}));
+        entry[route.method.as_str().to_ascii_lowercase()] = operation(
+            name,
+            path,
+            route.uri.query(),
+            OPERATIONS.iter().find(|o| o.handler == name),
+        );
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "share-cost API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "groupToken": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+                "adminToken": { "type": "http", "scheme": "bearer" },
+            },
+            "schemas": schemas(),
+        },
+    })
+}
+
+/// `/groups/current/expenses/<expense_id>` → `/groups/current/expenses/{expense_id}`
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Some(param) => format!("{{{}}}", param.trim_end_matches("..")),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn operation(name: &str, path: &str, query: Option<&str>, meta: Option<&Operation>) -> Value {
+    let mut parameters: Vec<Value> = path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('<')?.strip_suffix('>'))
+        .map(|param| {
+            let schema = if param.ends_with("_id") { "uuid" } else { "string" };
+            json!({ "name": param, "in": "path", "required": true, "schema": param_schema(schema) })
+        })
+        .collect();
+    for param in query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|segment| segment.strip_prefix('<')?.strip_suffix('>'))
+    {
+        let known = meta.and_then(|m| m.query.iter().find(|q| q.0 == param));
+        let (kind, required, description) = known.map_or(("string", false, ""), |q| (q.1, q.2, q.3));
+        parameters.push(json!({
+            "name": param,
+            "in": "query",
+            "required": required,
+            "description": description,
+            "schema": param_schema(kind),
+        }));
+    }
+
+    let mut operation = json!({
+        "operationId": name,
+        "parameters": parameters,
+        "responses": {
+            "default": {
+                "description": "Error. Validation failures carry a message.",
+                "content": { "application/json": { "schema": schema_ref("Error") } },
+            },
+        },
+    });
+    let Some(meta) = meta else {
+        operation["summary"] = json!(name);
+        operation["responses"]["200"] = json!({ "description": "Undocumented" });
+        return operation;
+    };
+
+    operation["summary"] = json!(meta.summary);
+    match meta.access {
+        Public => {}
+        Token => operation["security"] = json!([{ "groupToken": [] }]),
+        Permission(permission) => {
+            operation["security"] = json!([{ "groupToken": [] }]);
+            operation["description"] = if permission == "all" {
+                json!("Requires a token with all permissions.")
+            } else {
+                json!(format!("Requires the `{}` permission.", permission))
+            };
+        }
+        Admin => operation["security"] = json!([{ "adminToken": [] }]),
+    }
+    if let Some(body) = meta.body {
+        operation["requestBody"] = json!({
+            "required": !OPTIONAL_BODIES.contains(&meta.handler),
+            "content": { "application/json": { "schema": schema_ref(body) } },
+        });
+    }
+    let json_reply = |shape: &str| {
+        json!({
+            "description": "OK",
+            "content": { "application/json": { "schema": reply_schema(shape) } },
+        })
+    };
+    match meta.reply {
+        Body(shape) => operation["responses"]["200"] = json_reply(shape),
+        NoContent => operation["responses"]["204"] = json!({ "description": "Done" }),
+        NoContentOr(shape) => {
+            operation["responses"]["204"] = json!({ "description": "Done" });
+            operation["responses"]["200"] = json_reply(shape);
+        }
+        Raw(content_type) => {
+            operation["responses"]["200"] = json!({
+                "description": "OK",
+                "content": { content_type: { "schema": { "type": "string", "format": "binary" } } },
+            })
+        }
+    }
+    operation
+}
+
+/// Handlers whose JSON body may be omitted.
+const OPTIONAL_BODIES: &[&str] = &["duplicate_expense", "new_owner_token", "invite_member"];
+
+fn param_schema(kind: &str) -> Value {
+    match kind {
+        "uuid" => uuid(),
+        "date" => date(),
+        "integer" => integer(),
+        "boolean" => boolean(),
+        _ => string(),
+    }
+}
+
+/// `[A] | B` → `oneOf` of an array of `A` and `B`.
+fn reply_schema(shape: &str) -> Value {
+    let one = |part: &str| match part.strip_prefix('[').and_then(|p| p.strip_suffix(']')) {
+        Some(item) => list(schema_ref(item)),
+        None => schema_ref(part),
+    };
+    let parts: Vec<Value> = shape.split(" | ").map(one).collect();
+    match <[Value; 1]>::try_from(parts) {
+        Ok([single]) => single,
+        Err(parts) => json!({ "oneOf": parts }),
+    }
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn list(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn nullable(schema: Value) -> Value {
+    if schema.get("$ref").is_some() {
+        return json!({ "allOf": [schema], "nullable": true });
+    }
+    let mut schema = schema;
+    schema["nullable"] = json!(true);
+    schema
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn uuid() -> Value {
+    json!({ "type": "string", "format": "uuid" })
+}
+
+fn date() -> Value {
+    json!({ "type": "string", "format": "date" })
+}
+
+fn date_time() -> Value {
+    json!({ "type": "string", "format": "date-time" })
+}
+
+fn number() -> Value {
+    json!({ "type": "number" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer", "format": "int64" })
+}
+
+/// Position of a member in `member_names`.
+fn index() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn one_of(values: &[&str]) -> Value {
+    json!({ "type": "string", "enum": values })
+}
+
+/// An object whose `required` fields are always present and `optional` ones may be
+/// omitted.
+fn object(required: &[(&str, Value)], optional: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = required
+        .iter()
+        .chain(optional)
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    let mut schema = json!({ "type": "object", "properties": properties });
+    if !required.is_empty() {
+        schema["required"] = json!(required.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+    }
+    schema
+}
+
+/// Schemas of the request and response DTOs in `models.rs` (and a few from other modules).
+fn schemas() -> Value {
+    let permissions = || {
+        [
+            "can_delete_group",
+            "can_manage_members",
+            "can_update_payment",
+            "can_add_expenses",
+            "can_edit_expenses",
+            "can_settle",
+        ]
+    };
+    let split_type = || one_of(&["equal", "percentage", "exact", "shares", "adjustment"]);
+    let expense_type = || one_of(&["expense", "transfer", "income"]);
+
+    let schemas: Vec<(&str, Value)> = vec![
+        ("Error", object(&[("error", string())], &[])),
+        ("Readiness", object(
+            &[("ready", boolean()), ("database", boolean())],
+            &[("migrations", schema_ref("MigrationStatus")), ("error", string())],
+        )),
+        ("MigrationStatus", object(
+            &[
+                ("expected_version", integer()),
+                ("applied_version", nullable(integer())),
+                ("pending", list(string())),
+                ("modified", list(string())),
+            ],
+            &[],
+        )),
+        ("Member", object(
+            &[
+                ("id", uuid()),
+                ("name", string()),
+                ("paypal_email", nullable(string())),
+                ("iban", nullable(string())),
+                ("email", nullable(string())),
+                ("notify_on_expense", boolean()),
+                ("preferred_payment_method", nullable(one_of(&["paypal", "iban", "venmo"]))),
+                ("venmo_handle", nullable(string())),
+                ("payment_note", nullable(string())),
+            ],
+            &[],
+        )),
+        ("Group", object(
+            &[
+                ("id", uuid()),
+                ("name", string()),
+                ("currency", string()),
+                ("members", list(schema_ref("Member"))),
+                ("created_at", date_time()),
+                ("last_activity_at", date_time()),
+                ("default_split", nullable(schema_ref("DefaultSplit"))),
+                ("archived_at", nullable(date_time())),
+                ("locked", boolean()),
+                ("default_share_permissions", nullable(schema_ref("PermissionsResponse"))),
+            ],
+            &[],
+        )),
+        ("SplitEntry", object(&[("member_id", uuid())], &[("share", number())])),
+        ("Adjustment", object(&[("member_id", uuid()), ("amount", number())], &[])),
+        ("DefaultSplit", object(
+            &[("split_type", split_type()), ("splits", list(schema_ref("SplitEntry")))],
+            &[],
+        )),
+        ("ExpenseItem", object(
+            &[("description", string()), ("amount", number()), ("member_ids", list(uuid()))],
+            &[],
+        )),
+        ("Expense", object(
+            &[
+                ("id", uuid()),
+                ("group_id", uuid()),
+                ("description", string()),
+                ("amount", number()),
+                ("paid_by", uuid()),
+                ("split_between", list(uuid())),
+                ("expense_type", expense_type()),
+                ("transfer_to", nullable(uuid())),
+                ("currency", string()),
+                ("exchange_rate", number()),
+                ("expense_date", date()),
+                ("created_at", date_time()),
+                ("split_type", split_type()),
+                ("notes", nullable(string())),
+                ("receipt_url", nullable(string())),
+                ("created_by", nullable(uuid())),
+                ("updated_by", nullable(uuid())),
+                ("tags", list(string())),
+                ("trip_id", nullable(uuid())),
+                ("refund_of", nullable(uuid())),
+                ("settled_shares", list(uuid())),
+            ],
+            &[
+                ("splits", list(schema_ref("SplitEntry"))),
+                ("items", list(schema_ref("ExpenseItem"))),
+                ("amount_minor", integer()),
+            ],
+        )),
+        ("ExpensePage", object(
+            &[("expenses", list(schema_ref("Expense")))],
+            &[("next_cursor", string())],
+        )),
+        ("CreateExpenseRequest", object(
+            &[("description", string()), ("paid_by", uuid())],
+            &[
+                ("amount", number()),
+                ("split_between", list(uuid())),
+                ("expense_type", expense_type()),
+                ("transfer_to", uuid()),
+                ("currency", string()),
+                ("exchange_rate", number()),
+                ("expense_date", date()),
+                ("split_type", split_type()),
+                ("splits", list(schema_ref("SplitEntry"))),
+                ("adjustments", list(schema_ref("Adjustment"))),
+                ("notes", string()),
+                ("receipt_url", string()),
+                ("tags", list(string())),
+                ("exclude_payer", boolean()),
+                ("items", list(schema_ref("ExpenseItem"))),
+                ("trip_id", uuid()),
+                ("refund_of", uuid()),
+            ],
+        )),
+        ("UpdateExpenseRequest", object(
+            &[
+                ("description", string()),
+                ("amount", number()),
+                ("paid_by", uuid()),
+                ("split_between", list(uuid())),
+            ],
+            &[
+                ("expense_type", expense_type()),
+                ("transfer_to", uuid()),
+                ("currency", string()),
+                ("exchange_rate", number()),
+                ("expense_date", date()),
+                ("split_type", split_type()),
+                ("splits", list(schema_ref("SplitEntry"))),
+                ("adjustments", list(schema_ref("Adjustment"))),
+                ("notes", string()),
+                ("receipt_url", string()),
+                ("tags", list(string())),
+                ("exclude_payer", boolean()),
+                ("trip_id", uuid()),
+            ],
+        )),
+        ("PatchExpenseRequest", object(
+            &[],
+            &[
+                ("description", string()),
+                ("amount", number()),
+                ("paid_by", uuid()),
+                ("split_between", list(uuid())),
+                ("expense_type", expense_type()),
+                ("transfer_to", nullable(uuid())),
+                ("currency", string()),
+                ("exchange_rate", number()),
+                ("expense_date", date()),
+                ("split_type", split_type()),
+                ("splits", list(schema_ref("SplitEntry"))),
+                ("adjustments", list(schema_ref("Adjustment"))),
+                ("notes", nullable(string())),
+                ("receipt_url", nullable(string())),
+                ("tags", list(string())),
+                ("trip_id", nullable(uuid())),
+            ],
+        )),
+        ("DuplicateExpenseRequest", object(&[], &[("expense_date", date())])),
+        ("SettleShareRequest", object(&[("settled", boolean())], &[])),
+        ("ShareSettlement", object(
+            &[
+                ("expense_id", uuid()),
+                ("member_id", uuid()),
+                ("settled", boolean()),
+                ("settled_at", nullable(date_time())),
+            ],
+            &[],
+        )),
+        ("Balance", object(
+            &[("user_id", uuid()), ("user_name", string()), ("balance", number())],
+            &[("balance_minor", integer())],
+        )),
+        ("BalanceCheck", object(
+            &[
+                ("ok", boolean()),
+                ("sum", number()),
+                ("tolerance", number()),
+                ("foreign_references", integer()),
+                ("cache_stale", boolean()),
+                ("balances", list(schema_ref("Balance"))),
+            ],
+            &[],
+        )),
+        ("ConvertedBalances", object(
+            &[
+                ("currency", string()),
+                ("group_currency", string()),
+                ("rate", number()),
+                ("rate_date", date()),
+                ("fetched_at", date_time()),
+                ("balances", list(schema_ref("Balance"))),
+            ],
+            &[],
+        )),
+        ("BalanceChange", object(
+            &[
+                ("user_id", uuid()),
+                ("user_name", string()),
+                ("delta", number()),
+                ("balance_before", number()),
+                ("balance_after", number()),
+            ],
+            &[],
+        )),
+        ("BalancePoint", object(&[("date", date()), ("balance", number())], &[])),
+        ("MemberBalance", json!({
+            "allOf": [schema_ref("Member"), object(&[("balance", number())], &[])],
+        })),
+        ("Debtor", object(
+            &[
+                ("user_id", uuid()),
+                ("user_name", string()),
+                ("balance", number()),
+                ("paypal_email", nullable(string())),
+                ("iban", nullable(string())),
+                ("preferred_payment_method", nullable(string())),
+                ("venmo_handle", nullable(string())),
+            ],
+            &[],
+        )),
+        ("PaymentRequest", object(
+            &[
+                ("payer_id", uuid()),
+                ("payer_name", string()),
+                ("payee_id", uuid()),
+                ("payee_name", string()),
+                ("amount", number()),
+                ("amount_minor", integer()),
+                ("currency", string()),
+                ("method", nullable(one_of(&["iban", "paypal", "venmo"]))),
+                ("iban", nullable(string())),
+                ("paypal_email", nullable(string())),
+                ("venmo_handle", nullable(string())),
+                ("payment_note", nullable(string())),
+                ("reference", string()),
+                ("payable", boolean()),
+            ],
+            &[],
+        )),
+        ("PaymentRequestBatch", object(
+            &[
+                ("group_id", uuid()),
+                ("group_name", string()),
+                ("currency", string()),
+                ("created_at", date_time()),
+                ("requests", list(schema_ref("PaymentRequest"))),
+            ],
+            &[],
+        )),
+        ("DebtEntry", object(&[("from", uuid()), ("to", uuid()), ("amount", number())], &[])),
+        ("Settlement", object(
+            &[
+                ("from", uuid()),
+                ("from_name", string()),
+                ("to", uuid()),
+                ("to_name", string()),
+                ("amount", number()),
+            ],
+            &[],
+        )),
+        ("SettlementProgress", object(
+            &[
+                ("from", uuid()),
+                ("from_name", string()),
+                ("to", uuid()),
+                ("to_name", string()),
+                ("owed", number()),
+                ("settled", number()),
+                ("remaining", number()),
+            ],
+            &[],
+        )),
+        ("SettleUp", object(
+            &[
+                ("from", uuid()),
+                ("from_name", string()),
+                ("to", uuid()),
+                ("to_name", string()),
+                ("amount", number()),
+                ("direction", one_of(&["from_pays_to", "to_pays_from", "none"])),
+            ],
+            &[],
+        )),
+        ("SettleAllResult", object(
+            &[
+                ("transfers", list(schema_ref("Expense"))),
+                ("balances", list(schema_ref("Balance"))),
+            ],
+            &[],
+        )),
+        ("GroupOverview", object(
+            &[
+                ("group", schema_ref("Group")),
+                ("balances", list(schema_ref("Balance"))),
+                ("settlements", list(schema_ref("Settlement"))),
+            ],
+            &[("settlement_warning", schema_ref("SettlementWarning"))],
+        )),
+        ("SettlementWarning", object(
+            &[
+                ("unaccounted", number()),
+                ("members", list(schema_ref("UnaccountedBalance"))),
+            ],
+            &[],
+        )),
+        ("UnaccountedBalance", object(
+            &[("user_id", uuid()), ("user_name", string()), ("amount", number())],
+            &[],
+        )),
+        ("GroupsOverviewRequest", object(&[("tokens", list(string()))], &[])),
+        ("GroupSummary", object(
+            &[
+                ("group_id", uuid()),
+                ("name", string()),
+                ("currency", string()),
+                ("member_count", integer()),
+                ("total_spend", number()),
+                ("last_activity_at", date_time()),
+                ("archived_at", nullable(date_time())),
+                ("member_id", nullable(uuid())),
+                ("balance", nullable(number())),
+            ],
+            &[],
+        )),
+        ("GroupsOverviewEntry", object(&[], &[("group", schema_ref("GroupSummary")), ("error", string())])),
+        ("ActivityEntry", object(
+            &[
+                ("id", integer()),
+                ("action", string()),
+                ("expense_id", uuid()),
+                ("member_id", nullable(uuid())),
+                ("before", nullable(schema_ref("Expense"))),
+                ("after", nullable(schema_ref("Expense"))),
+                ("created_at", date_time()),
+                ("undone_at", nullable(date_time())),
+            ],
+            &[],
+        )),
+        ("ActivityPage", object(
+            &[
+                ("total", integer()),
+                ("limit", integer()),
+                ("offset", integer()),
+                ("entries", list(schema_ref("ActivityEntry"))),
+            ],
+            &[],
+        )),
+        ("UndoResult", object(
+            &[("action", string()), ("expense_id", uuid())],
+            &[("expense", schema_ref("Expense"))],
+        )),
+        ("GroupDeletionPreview", object(
+            &[
+                ("members", integer()),
+                ("expenses", integer()),
+                ("splits", integer()),
+                ("items", integer()),
+                ("receipts", integer()),
+            ],
+            &[],
+        )),
+        ("ReceiptInfo", object(
+            &[("content_type", string()), ("size_bytes", integer()), ("uploaded_at", date_time())],
+            &[],
+        )),
+        ("MemberExpense", object(
+            &[
+                ("expense", schema_ref("Expense")),
+                ("paid", boolean()),
+                ("received", boolean()),
+                ("share", number()),
+                ("change", number()),
+            ],
+            &[],
+        )),
+        ("MemberExpensePage", object(
+            &[
+                ("member_id", uuid()),
+                ("total", integer()),
+                ("limit", integer()),
+                ("offset", integer()),
+                ("expenses", list(schema_ref("MemberExpense"))),
+            ],
+            &[],
+        )),
+        ("PairExpense", object(
+            &[
+                ("expense", schema_ref("Expense")),
+                ("direction", one_of(&["a_owes_b", "b_owes_a", "none"])),
+                ("amount", number()),
+            ],
+            &[],
+        )),
+        ("PairExpenses", object(
+            &[
+                ("a", uuid()),
+                ("a_name", string()),
+                ("b", uuid()),
+                ("b_name", string()),
+                ("net", number()),
+                ("expenses", list(schema_ref("PairExpense"))),
+            ],
+            &[],
+        )),
+        ("StatementEntry", object(
+            &[("expense", schema_ref("Expense")), ("change", number()), ("balance", number())],
+            &[],
+        )),
+        ("MemberStatement", object(
+            &[
+                ("member_id", uuid()),
+                ("member_name", string()),
+                ("opening_balance", number()),
+                ("closing_balance", number()),
+                ("entries", list(schema_ref("StatementEntry"))),
+            ],
+            &[],
+        )),
+        ("BreakdownEntry", object(
+            &[
+                ("expense_id", uuid()),
+                ("description", string()),
+                ("date", date()),
+                ("expense_type", expense_type()),
+                ("their_share", number()),
+                ("they_paid", number()),
+            ],
+            &[],
+        )),
+        ("MemberBreakdown", object(
+            &[
+                ("member_id", uuid()),
+                ("member_name", string()),
+                ("entries", list(schema_ref("BreakdownEntry"))),
+                ("net", number()),
+            ],
+            &[],
+        )),
+        ("Trip", object(
+            &[
+                ("id", uuid()),
+                ("name", string()),
+                ("start_date", date()),
+                ("end_date", nullable(date())),
+                ("created_at", date_time()),
+            ],
+            &[],
+        )),
+        ("TripRequest", object(
+            &[("name", string()), ("start_date", date())],
+            &[("end_date", nullable(date()))],
+        )),
+        ("TagCount", object(&[("tag", string()), ("count", integer())], &[])),
+        ("CategoryTotal", object(
+            &[
+                ("name", nullable(string())),
+                ("total", number()),
+                ("expense_count", integer()),
+                ("income", number()),
+                ("income_count", integer()),
+            ],
+            &[],
+        )),
+        ("MemberStats", object(
+            &[
+                ("member_id", uuid()),
+                ("name", string()),
+                ("paid", number()),
+                ("spent", number()),
+                ("income_collected", number()),
+                ("income_share", number()),
+                ("transfers_sent", number()),
+                ("transfers_received", number()),
+                ("balance", number()),
+            ],
+            &[],
+        )),
+        ("Anomaly", object(
+            &[("expense", schema_ref("Expense")), ("amount", number()), ("z_score", number())],
+            &[],
+        )),
+        ("AnomalyReport", object(
+            &[
+                ("expense_count", integer()),
+                ("mean", number()),
+                ("std_dev", number()),
+                ("k", number()),
+                ("threshold", number()),
+                ("anomalies", list(schema_ref("Anomaly"))),
+            ],
+            &[],
+        )),
+        ("CurrencyInfo", object(
+            &[
+                ("code", string()),
+                ("symbol", string()),
+                ("symbol_position", one_of(&["before", "after"])),
+                ("symbol_spacing", boolean()),
+                ("decimal_separator", string()),
+                ("grouping_separator", string()),
+                ("minor_units", integer()),
+            ],
+            &[],
+        )),
+        ("ExchangeRate", object(
+            &[
+                ("from", string()),
+                ("to", string()),
+                ("rate", number()),
+                ("date", date()),
+                ("fetched_at", date_time()),
+            ],
+            &[],
+        )),
+        ("CreateGroupRequest", object(
+            &[("name", string()), ("member_names", list(string()))],
+            &[("currency", string()), ("expenses", list(schema_ref("InitialExpense")))],
+        )),
+        ("InitialExpense", object(
+            &[("description", string()), ("amount", number()), ("paid_by", index())],
+            &[
+                ("split_between", list(index())),
+                ("expense_type", expense_type()),
+                ("transfer_to", index()),
+                ("currency", string()),
+                ("exchange_rate", number()),
+                ("expense_date", date()),
+                ("split_type", split_type()),
+                ("splits", list(schema_ref("InitialSplitEntry"))),
+                ("notes", string()),
+                ("tags", list(string())),
+            ],
+        )),
+        ("InitialSplitEntry", object(&[("member", index()), ("share", number())], &[])),
+        ("CloneGroupRequest", object(&[], &[("name", string()), ("copy_payment_info", boolean())])),
+        ("GroupCreatedResponse", object(
+            &[("group", schema_ref("Group")), ("token", string())],
+            &[("expenses", list(schema_ref("Expense")))],
+        )),
+        ("AddMemberRequest", object(&[("name", string())], &[])),
+        ("MergeMembersRequest", object(&[("source_id", uuid()), ("target_id", uuid())], &[])),
+        ("ReorderMembersRequest", object(&[("member_ids", list(uuid()))], &[])),
+        ("UpdateMemberPaymentRequest", object(
+            &[],
+            &[
+                ("paypal_email", nullable(string())),
+                ("iban", nullable(string())),
+                ("preferred_payment_method", nullable(one_of(&["paypal", "iban", "venmo"]))),
+                ("venmo_handle", nullable(string())),
+                ("payment_note", nullable(string())),
+            ],
+        )),
+        ("UpdateMemberNotificationsRequest", object(
+            &[("notify_on_expense", boolean())],
+            &[("email", nullable(string()))],
+        )),
+        ("RenameGroupRequest", object(&[("name", string())], &[])),
+        ("SetDefaultSplitRequest", object(&[("default_split", nullable(schema_ref("DefaultSplit")))], &[])),
+        ("SetDefaultSharePermissionsRequest", object(
+            &[("default_share_permissions", nullable(schema_ref("PermissionsResponse")))],
+            &[],
+        )),
+        ("PermissionsResponse", object(
+            &permissions().map(|p| (p, boolean())),
+            &[],
+        )),
+        ("TokenInfo", object(
+            &[
+                ("group_id", uuid()),
+                ("expires_at", date_time()),
+                ("expiring_soon", boolean()),
+                ("permissions", schema_ref("PermissionsResponse")),
+            ],
+            &[("member_id", uuid()), ("jti", string())],
+        )),
+        ("InspectTokenRequest", object(&[("token", string())], &[])),
+        ("IssuedTokenItem", object(
+            &[
+                ("jti", string()),
+                ("issued_at", date_time()),
+                ("expires_at", date_time()),
+                ("last_used_at", nullable(date_time())),
+                ("member_id", nullable(uuid())),
+                ("permissions", schema_ref("PermissionsResponse")),
+                ("current", boolean()),
+            ],
+            &[],
+        )),
+        ("GenerateShareLinkRequest", object(
+            &[],
+            &permissions()
+                .map(|p| (p, boolean()))
+                .into_iter()
+                .chain([
+                    ("max_uses", integer()),
+                    ("expires_in_hours", integer()),
+                    ("member_id", uuid()),
+                ])
+                .collect::<Vec<_>>(),
+        )),
+        ("ShareCodeResponse", object(
+            &[("code", string()), ("permissions", schema_ref("PermissionsResponse"))],
+            &[("max_uses", integer()), ("expires_at", date_time()), ("member_id", uuid())],
+        )),
+        ("ShareLinkItem", object(
+            &permissions()
+                .map(|p| (p, boolean()))
+                .into_iter()
+                .chain([
+                    ("code", string()),
+                    ("created_at", date_time()),
+                    ("max_uses", nullable(integer())),
+                    ("use_count", integer()),
+                    ("expires_at", nullable(date_time())),
+                    ("member_id", nullable(uuid())),
+                ])
+                .collect::<Vec<_>>(),
+            &[],
+        )),
+        ("InviteMemberRequest", object(
+            &[],
+            &[("email", string())]
+                .into_iter()
+                .chain(permissions().map(|p| (p, boolean())))
+                .chain([("expires_in_hours", integer())])
+                .collect::<Vec<_>>(),
+        )),
+        ("MemberInviteResponse", object(
+            &[
+                ("member_id", uuid()),
+                ("email", nullable(string())),
+                ("sent", boolean()),
+                ("permissions", schema_ref("PermissionsResponse")),
+                ("expires_at", date_time()),
+            ],
+            &[("link", string())],
+        )),
+        ("RedeemShareCodeRequest", object(&[("code", string())], &[("existing_token", string())])),
+        ("ShareLinkResponse", object(
+            &[("token", string()), ("permissions", schema_ref("PermissionsResponse"))],
+            &[],
+        )),
+        ("MergeTokenRequest", object(&[("other_token", string())], &[])),
+        ("ScopedTokenRequest", object(&[], &permissions().map(|p| (p, boolean())))),
+        ("NewOwnerTokenRequest", object(&[], &[("revoke_existing", boolean())])),
+        ("NewOwnerTokenResponse", object(&[("token", string()), ("revoked_existing", boolean())], &[])),
+        ("CreateWebhookRequest", object(
+            &[("url", string()), ("events", list(string()))],
+            &[("secret", string())],
+        )),
+        ("WebhookItem", object(
+            &[
+                ("id", uuid()),
+                ("url", string()),
+                ("events", list(string())),
+                ("created_at", date_time()),
+            ],
+            &[],
+        )),
+        ("WebhookCreatedResponse", object(
+            &[("webhook", schema_ref("WebhookItem")), ("secret", string())],
+            &[],
+        )),
+        ("WebhookDeliveryItem", object(
+            &[
+                ("event", string()),
+                ("attempt", integer()),
+                ("status_code", nullable(integer())),
+                ("success", boolean()),
+                ("error", nullable(string())),
+                ("created_at", date_time()),
+            ],
+            &[],
+        )),
+        ("ScanReceiptRequest", object(&[("image", string()), ("language", string())], &[])),
+        ("ReceiptItem", object(&[("description", string()), ("amount", number())], &[])),
+        ("ScanReceiptResponse", object(
+            &[
+                ("title", string()),
+                ("total", number()),
+                ("date", nullable(string())),
+                ("currency", nullable(string())),
+                ("items", list(schema_ref("ReceiptItem"))),
+            ],
+            &[],
+        )),
+        ("MaintenanceReport", object(
+            &[
+                ("ran", boolean()),
+                ("inactive_groups_deleted", integer()),
+                ("webhook_deliveries_deleted", integer()),
+                ("stale_tokens_deleted", integer()),
+            ],
+            &[],
+        )),
+    ];
+    Value::Object(schemas.into_iter().map(|(name, schema)| (name.to_string(), schema)).collect())
+}
@@ -0,0 +1,252 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::db;
+
+/// Latest rates are reused for this long before asking the provider again.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Base URL of the Frankfurter-compatible rates API. Override with
+/// `EXCHANGE_RATE_API_URL` (e.g. to point tests at a stub server).
+static API_URL: Lazy<String> = Lazy::new(|| {
+    std::env::var("EXCHANGE_RATE_API_URL")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| v.trim().trim_end_matches('/').to_string())
+        .unwrap_or_else(|| "https://api.frankfurter.app".to_string())
+});
+
+/// The background refresh stores all rates against this currency; other pairs
+/// are derived from two of its rates.
+const REFRESH_BASE: &str = "EUR";
+
+/// Stored rates are used for dates up to this many days after their publication
+/// date (rates aren't published on weekends and holidays).
+const MAX_RATE_AGE_DAYS: i64 = 7;
+
+/// How often the stored rates are refreshed. Defaults to daily; override with
+/// `EXCHANGE_RATE_REFRESH_SECS`.
+pub static REFRESH_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let secs = std::env::var("EXCHANGE_RATE_REFRESH_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n: &u64| n > 0)
+        .unwrap_or(24 * 60 * 60);
+    Duration::from_secs(secs)
+});
+
+/// An exchange rate: 1 unit of `from` = `rate` units of `to`, published on `date`.
+#[derive(Debug, Clone)]
+pub struct Rate {
+    pub rate: f64,
+    pub date: NaiveDate,
+    pub fetched_at: DateTime<Utc>,
+}
+
+static LATEST_CACHE: Lazy<Mutex<HashMap<(String, String), Rate>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `code` looks like an ISO 4217 currency code.
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Fetch the raw provider response for a historical (`YYYY-MM-DD`) or `latest` rate.
+pub async fn fetch(date: &str, from: &str, to: &str) -> Result<serde_json::Value, String> {
+    let resp = client()?
+        .get(format!("{}/{}?from={}&to={}", *API_URL, date, from, to))
+        .send()
+        .await
+        .map_err(|e| format!("rates request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("rates provider returned {}", resp.status()));
+    }
+    resp.json()
+        .await
+        .map_err(|e| format!("failed to parse rates response: {}", e))
+}
+
+/// The current rate from one currency to another, cached for an hour.
+pub async fn latest(from: &str, to: &str) -> Result<Rate, String> {
+    let from = from.to_ascii_uppercase();
+    let to = to.to_ascii_uppercase();
+    if from == to {
+        return Ok(Rate {
+            rate: 1.0,
+            date: Utc::now().date_naive(),
+            fetched_at: Utc::now(),
+        });
+    }
+
+    let key = (from.clone(), to.clone());
+    {
+        let cache = LATEST_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(rate) = cache.get(&key)
+            && (Utc::now() - rate.fetched_at).to_std().unwrap_or_default() < CACHE_TTL
+        {
+            return Ok(rate.clone());
+        }
+    }
+
+    let body = fetch("latest", &from, &to).await?;
+    let rate = body["rates"][&to]
+        .as_f64()
+        .ok_or_else(|| format!("no {} rate in response", to))?;
+    let date = parse_date(&body).unwrap_or_else(|| Utc::now().date_naive());
+    let rate = Rate {
+        rate,
+        date,
+        fetched_at: Utc::now(),
+    };
+
+    LATEST_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, rate.clone());
+    Ok(rate)
+}
+
+fn parse_date(body: &serde_json::Value) -> Option<NaiveDate> {
+    body["date"]
+        .as_str()
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+}
+
+async fn store(base: &str, quote: &str, date: NaiveDate, rate: f64) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO exchange_rates (base, quote, date, rate) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (base, quote, date) DO UPDATE SET rate = EXCLUDED.rate, fetched_at = NOW()",
+    )
+    .bind(base)
+    .bind(quote)
+    .bind(date)
+    .bind(rate)
+    .execute(db::get_pool())
+    .await
+    .map(|_| ())
+    .map_err(|e| format!("failed to store rate: {}", e))
+}
+
+/// Fetch the latest rates of every currency against `REFRESH_BASE` and store them.
+/// Returns how many rates were stored.
+pub async fn refresh() -> Result<usize, String> {
+    let resp = client()?
+        .get(format!("{}/latest?from={}", *API_URL, REFRESH_BASE))
+        .send()
+        .await
+        .map_err(|e| format!("rates request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("rates provider returned {}", resp.status()));
+    }
+    let body: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| format!("failed to parse rates response: {}", e))?;
+    let date = parse_date(&body).ok_or("no date in rates response")?;
+    let rates = body["rates"].as_object().ok_or("no rates in rates response")?;
+
+    let mut stored = 0;
+    for (quote, rate) in rates {
+        if let Some(rate) = rate.as_f64().filter(|r| *r > 0.0)
+            && is_currency_code(quote)
+        {
+            store(REFRESH_BASE, &quote.to_ascii_uppercase(), date, rate).await?;
+            stored += 1;
+        }
+    }
+    Ok(stored)
+}
+
+/// Refresh the stored rates and log the outcome.
+pub async fn refresh_and_log() {
+    match refresh().await {
+        Ok(count) => println!("Exchange rates: stored {} rate(s)", count),
+        Err(e) => eprintln!("Exchange rate refresh failed: {}", e),
+    }
+}
+
+/// The most recent stored `base` -> `quote` rate published on or before `date`.
+async fn stored_leg(base: &str, quote: &str, date: NaiveDate) -> Result<Option<Rate>, String> {
+    if base == quote {
+        return Ok(Some(Rate {
+            rate: 1.0,
+            date,
+            fetched_at: Utc::now(),
+        }));
+    }
+    let row: Option<(f64, NaiveDate, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT rate, date, fetched_at FROM exchange_rates
+         WHERE base = $1 AND quote = $2 AND date <= $3 AND date > $3 - $4::int
+         ORDER BY date DESC LIMIT 1",
+    )
+    .bind(base)
+    .bind(quote)
+    .bind(date)
+    .bind(MAX_RATE_AGE_DAYS as i32)
+    .fetch_optional(db::get_pool())
+    .await
+    .map_err(|e| format!("failed to look up rate: {}", e))?;
+    Ok(row.map(|(rate, date, fetched_at)| Rate {
+        rate,
+        date,
+        fetched_at,
+    }))
+}
+
+/// A stored rate for `from` -> `to` on `date`: the pair itself, its inverse,
+/// or the cross rate through `REFRESH_BASE`.
+async fn stored(from: &str, to: &str, date: NaiveDate) -> Result<Option<Rate>, String> {
+    if let Some(rate) = stored_leg(from, to, date).await? {
+        return Ok(Some(rate));
+    }
+    if let Some(inverse) = stored_leg(to, from, date).await? {
+        return Ok(Some(Rate {
+            rate: 1.0 / inverse.rate,
+            ..inverse
+        }));
+    }
+    let (Some(base_from), Some(base_to)) = (
+        stored_leg(REFRESH_BASE, from, date).await?,
+        stored_leg(REFRESH_BASE, to, date).await?,
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some(Rate {
+        rate: base_to.rate / base_from.rate,
+        date: base_from.date.min(base_to.date),
+        fetched_at: base_from.fetched_at.min(base_to.fetched_at),
+    }))
+}
+
+/// The rate from one currency to another on `date`. Stored rates are used when
+/// available; otherwise the provider is asked and its answer stored for next time.
+pub async fn lookup(from: &str, to: &str, date: NaiveDate) -> Result<Rate, String> {
+    let from = from.to_ascii_uppercase();
+    let to = to.to_ascii_uppercase();
+    if let Some(rate) = stored(&from, &to, date).await? {
+        return Ok(rate);
+    }
+
+    let body = fetch(&date.format("%Y-%m-%d").to_string(), &from, &to).await?;
+    let rate = body["rates"][&to]
+        .as_f64()
+        .filter(|r| *r > 0.0)
+        .ok_or_else(|| format!("no {} rate in response", to))?;
+    let rate_date = parse_date(&body).unwrap_or(date);
+    store(&from, &to, rate_date, rate).await?;
+    Ok(Rate {
+        rate,
+        date: rate_date,
+        fetched_at: Utc::now(),
+    })
+}
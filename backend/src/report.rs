@@ -0,0 +1,134 @@
+use chrono::NaiveDate;
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference};
+use rocket::http::Header;
+
+use crate::currency;
+use crate::locale::Locale;
+use crate::models::{Balance, Settlement};
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+
+/// Everything shown on the settlement report.
+pub struct ReportData {
+    pub group_name: String,
+    pub currency: String,
+    pub members: Vec<String>,
+    pub total_spend: f64,
+    pub balances: Vec<Balance>,
+    pub settlements: Vec<Settlement>,
+    /// Number and date formatting of the rendered report.
+    pub locale: Locale,
+    pub generated_on: NaiveDate,
+}
+
+/// A rendered PDF, served inline with a download filename.
+#[derive(Responder)]
+#[response(content_type = "application/pdf")]
+pub struct PdfResponse {
+    pub body: Vec<u8>,
+    pub disposition: Header<'static>,
+}
+
+impl PdfResponse {
+    pub fn new(body: Vec<u8>, filename: &str) -> Self {
+        PdfResponse {
+            body,
+            disposition: Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        }
+    }
+}
+
+/// Turn a group name into a safe ASCII file name stem.
+pub fn file_stem(group_name: &str) -> String {
+    let stem: String = group_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let stem = stem.trim_matches('-');
+    if stem.is_empty() { "group".to_string() } else { stem.to_string() }
+}
+
+/// Writes lines top to bottom, starting a new page when the current one is full.
+struct Writer {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f32,
+}
+
+impl Writer {
+    fn line(&mut self, text: &str, size: f32, bold: bool) {
+        let height = size * 0.5;
+        if self.y - height < MARGIN {
+            let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        self.y -= height;
+        let font = if bold { &self.bold } else { &self.regular };
+        self.layer.use_text(text, size, Mm(MARGIN), Mm(self.y), font);
+    }
+
+    fn gap(&mut self) {
+        self.y -= 4.0;
+    }
+}
+
+/// Render the settlement report as a PDF document.
+pub fn render(data: &ReportData) -> Result<Vec<u8>, String> {
+    let title = format!("{} - settlement report", data.group_name);
+    let (doc, page, layer) = PdfDocument::new(&title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+    let layer = doc.get_page(page).get_layer(layer);
+    let mut w = Writer {
+        doc,
+        layer,
+        regular,
+        bold,
+        y: PAGE_HEIGHT - MARGIN,
+    };
+    let decimals = currency::minor_units(&data.currency) as usize;
+    let money = |amount: f64| format!("{} {}", data.locale.number(amount, decimals), data.currency);
+
+    w.line(&data.group_name, 20.0, true);
+    w.line(
+        &format!("Settlement report, {}", data.locale.date(data.generated_on)),
+        12.0,
+        false,
+    );
+    w.gap();
+
+    w.line(&format!("Total spend: {}", money(data.total_spend)), 12.0, true);
+    w.gap();
+
+    w.line("Members", 14.0, true);
+    w.line(&data.members.join(", "), 11.0, false);
+    w.gap();
+
+    w.line("Balances", 14.0, true);
+    for b in &data.balances {
+        w.line(&format!("{}: {}", b.user_name, money(b.balance)), 11.0, false);
+    }
+    w.gap();
+
+    w.line("Settlement plan", 14.0, true);
+    if data.settlements.is_empty() {
+        w.line("Everyone is settled up.", 11.0, false);
+    }
+    for s in &data.settlements {
+        w.line(
+            &format!("{} pays {} {}", s.from_name, s.to_name, money(s.amount)),
+            11.0,
+            false,
+        );
+    }
+
+    w.doc.save_to_bytes().map_err(|e| e.to_string())
+}
@@ -1,17 +1,40 @@
 use bigdecimal::BigDecimal;
 use bigdecimal::ToPrimitive;
-use chrono::Utc;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, NaiveDate, Utc};
+use once_cell::sync::Lazy;
 use rand::Rng;
-use rocket::Route;
-use rocket::http::Status;
+use rocket::data::{ByteUnit, Data};
+use rocket::{Either, Route};
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::http::{ContentType, Status};
+use rocket::outcome::Outcome;
 use rocket::serde::json::Json;
+use rocket::tokio::io::AsyncReadExt;
 use serde::{Deserialize, Serialize};
-use sqlx;
+use std::collections::HashMap;
+use std::sync::Mutex;
 use uuid::Uuid;
 use rocket_governor::{Method, Quota, RocketGovernable, RocketGovernor};
 
-use crate::auth::{GroupAuth, Permissions, generate_token, validate_token};
+use crate::activity;
+use crate::auth::{
+    GroupAuth, Permissions, issue_token, issue_token_in, stated_permissions, validate_token,
+};
+use crate::currency;
 use crate::db;
+use crate::error::ApiError;
+use crate::etag::{Conditional, IfNoneMatch, group_etag};
+use crate::guards::Writable;
+use crate::locale::Locale;
+use crate::notifications;
+use crate::rates;
+use crate::report::{self, PdfResponse, ReportData};
+use crate::settlement;
+use crate::storage;
+use crate::webhooks;
 use crate::models::*;
 
 /// Rate limit for share code redemption: 10 requests per second per IP.
@@ -32,159 +55,1257 @@ impl<'r> RocketGovernable<'r> for ScanRateLimit {
     }
 }
 
+/// Rate limit for unauthenticated group creation, per client IP.
+/// Defaults to 10 groups per minute; override with `CREATE_GROUP_RATE_LIMIT_PER_MINUTE`.
+/// Behind a proxy the client IP is read from Rocket's `ip_header` (`ROCKET_IP_HEADER`,
+/// `X-Real-IP` in Rocket.toml).
+pub struct CreateGroupRateLimit;
+
+static CREATE_GROUP_RATE_LIMIT: Lazy<u32> = Lazy::new(|| {
+    std::env::var("CREATE_GROUP_RATE_LIMIT_PER_MINUTE")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(10)
+});
+
+impl<'r> RocketGovernable<'r> for CreateGroupRateLimit {
+    fn quota(_method: Method, _route_name: &str) -> Quota {
+        Quota::per_minute(Self::nonzero(*CREATE_GROUP_RATE_LIMIT))
+    }
+}
+
 // Health check
 #[get("/health")]
 fn health() -> &'static str {
     "OK"
 }
 
-// Create group - no auth required
+// Readiness check: the database is reachable and every migration embedded in this
+// build has been applied unchanged. 503 (with the details) otherwise.
+#[get("/ready")]
+async fn ready() -> (Status, Json<Readiness>) {
+    match db::migration_status().await {
+        Ok(migrations) => {
+            let ready = migrations.is_current();
+            let status = if ready { Status::Ok } else { Status::ServiceUnavailable };
+            (
+                status,
+                Json(Readiness {
+                    ready,
+                    database: true,
+                    migrations: Some(migrations),
+                    error: None,
+                }),
+            )
+        }
+        Err(e) => {
+            eprintln!("Readiness check failed: {}", e);
+            (
+                Status::ServiceUnavailable,
+                Json(Readiness {
+                    ready: false,
+                    database: false,
+                    migrations: None,
+                    error: Some("database unavailable".to_string()),
+                }),
+            )
+        }
+    }
+}
+
+/// Maximum length (in characters) of a group name.
+const MAX_GROUP_NAME_LEN: usize = 100;
+/// Maximum length (in characters) of a member name.
+const MAX_MEMBER_NAME_LEN: usize = 60;
+
+/// Trim a group name and check it is 1–100 characters.
+fn validate_group_name(name: &str) -> Result<String, ApiError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("Group name must not be empty"));
+    }
+    if name.chars().count() > MAX_GROUP_NAME_LEN {
+        return Err(ApiError::bad_request(format!(
+            "Group name must be at most {} characters",
+            MAX_GROUP_NAME_LEN
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// Trim a member name and check it is 1–60 characters.
+fn validate_member_name(name: &str) -> Result<String, ApiError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("Member names must not be blank"));
+    }
+    if name.chars().count() > MAX_MEMBER_NAME_LEN {
+        return Err(ApiError::bad_request(format!(
+            "Member name '{}' must be at most {} characters",
+            name, MAX_MEMBER_NAME_LEN
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// Validate the member list of a new group. Duplicate names (case-insensitive) are
+/// rejected: members are told apart by name in the UI and in settlements, so two
+/// identical names would be ambiguous.
+fn validate_member_names(names: &[String]) -> Result<Vec<String>, ApiError> {
+    if names.is_empty() {
+        return Err(ApiError::bad_request("A group needs at least one member"));
+    }
+    let mut validated: Vec<String> = Vec::with_capacity(names.len());
+    for name in names {
+        let name = validate_member_name(name)?;
+        if validated.iter().any(|n| n.to_lowercase() == name.to_lowercase()) {
+            return Err(ApiError::bad_request(format!("Duplicate member name '{}'", name)));
+        }
+        validated.push(name);
+    }
+    Ok(validated)
+}
+
+/// Absolute cap on `split_between` entries, checked before touching the database.
+/// Defaults to 100; override with `MAX_SPLIT_MEMBERS`.
+static MAX_SPLIT_MEMBERS: Lazy<usize> = Lazy::new(|| {
+    std::env::var("MAX_SPLIT_MEMBERS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(100)
+});
+
+/// Most members a group may have. Unlimited by default; set `MAX_GROUP_MEMBERS`
+/// to protect a shared instance.
+static MAX_GROUP_MEMBERS: Lazy<i64> = Lazy::new(|| {
+    std::env::var("MAX_GROUP_MEMBERS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(i64::MAX)
+});
+
+/// Most expenses (transfers included) a group may have. Unlimited by default; set
+/// `MAX_GROUP_EXPENSES` to protect a shared instance. Settling up and undoing a
+/// deletion are not limited, so a full group can always be settled.
+static MAX_GROUP_EXPENSES: Lazy<i64> = Lazy::new(|| {
+    std::env::var("MAX_GROUP_EXPENSES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(i64::MAX)
+});
+
+/// Most active tokens a group may have: issued, not revoked and not expired.
+/// Unlimited by default; set `MAX_GROUP_TOKENS` to keep share links from piling
+/// up tokens nobody audits. Only minting tokens for an existing group (redeeming
+/// a share code, merging, scoping) is limited; a fresh owner token can always be
+/// issued, so the owner can revoke everything and start over.
+static MAX_GROUP_TOKENS: Lazy<i64> = Lazy::new(|| {
+    std::env::var("MAX_GROUP_TOKENS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(i64::MAX)
+});
+
+fn member_limit_error() -> ApiError {
+    ApiError::new(
+        Status::UnprocessableEntity,
+        format!("A group can have at most {} members", *MAX_GROUP_MEMBERS),
+    )
+}
+
+fn expense_limit_error() -> ApiError {
+    ApiError::new(
+        Status::UnprocessableEntity,
+        format!("A group can have at most {} expenses", *MAX_GROUP_EXPENSES),
+    )
+}
+
+/// `UnprocessableEntity` with a message if the group has no room for another member.
+async fn ensure_member_capacity(group_id: Uuid) -> Result<(), ApiError> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM members WHERE group_id = $1")
+        .bind(group_id)
+        .fetch_one(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to count members: {}", e);
+            db::error_status(&e)
+        })?;
+    if count >= *MAX_GROUP_MEMBERS {
+        return Err(member_limit_error());
+    }
+    Ok(())
+}
+
+/// `UnprocessableEntity` with a message if the group has no room for another active token.
+async fn ensure_token_capacity(group_id: Uuid) -> Result<(), ApiError> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM issued_tokens t JOIN groups g ON g.id = t.group_id
+         WHERE t.group_id = $1 AND t.revoked_at IS NULL AND t.expires_at > NOW()
+           AND (g.tokens_revoked_before IS NULL OR t.issued_at > g.tokens_revoked_before)",
+    )
+    .bind(group_id)
+    .fetch_one(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to count tokens: {}", e);
+        db::error_status(&e)
+    })?;
+    if count >= *MAX_GROUP_TOKENS {
+        return Err(ApiError::new(
+            Status::UnprocessableEntity,
+            format!(
+                "A group can have at most {} active tokens; revoke some first",
+                *MAX_GROUP_TOKENS
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// `UnprocessableEntity` with a message if the group has no room for another expense.
+async fn ensure_expense_capacity(group_id: Uuid) -> Result<(), ApiError> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM expenses WHERE group_id = $1")
+        .bind(group_id)
+        .fetch_one(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to count expenses: {}", e);
+            db::error_status(&e)
+        })?;
+    if count >= *MAX_GROUP_EXPENSES {
+        return Err(expense_limit_error());
+    }
+    Ok(())
+}
+
+/// Largest amount the `DECIMAL(12, 2)` amount columns can hold.
+const MAX_AMOUNT: f64 = 9_999_999_999.99;
+
+/// Largest exchange rate the `NUMERIC(12, 6)` column can hold.
+const MAX_EXCHANGE_RATE: f64 = 999_999.999_999;
+
+/// Amounts must be finite and fit the amount columns; they are rounded to cents.
+fn amount_decimal(amount: f64) -> Result<BigDecimal, ApiError> {
+    if !amount.is_finite() {
+        return Err(ApiError::bad_request("amount must be a finite number"));
+    }
+    if amount.abs() > MAX_AMOUNT {
+        return Err(ApiError::bad_request(format!("amount must not exceed {}", MAX_AMOUNT)));
+    }
+    BigDecimal::try_from(amount)
+        .map(|v| v.round(2))
+        .map_err(|_| ApiError::bad_request("amount must be a finite number"))
+}
+
+/// Exchange rates must be positive: a zero or negative rate would wipe out or
+/// invert the expense in every balance. They are stored with 6 decimals, so
+/// anything that rounds to zero or overflows the column is rejected as well.
+fn exchange_rate_decimal(rate: f64) -> Result<BigDecimal, ApiError> {
+    let out_of_range = || {
+        ApiError::bad_request(format!(
+            "exchange_rate must be between 0.000001 and {}",
+            MAX_EXCHANGE_RATE
+        ))
+    };
+    if !(rate.is_finite() && rate > 0.0 && rate <= MAX_EXCHANGE_RATE) {
+        return Err(out_of_range());
+    }
+    let rate = BigDecimal::try_from(rate).map_err(|_| out_of_range())?.round(6);
+    if rate <= 0 {
+        return Err(out_of_range());
+    }
+    Ok(rate)
+}
+
+/// How far past today an expense may be dated (planned bookings, time zones).
+/// Defaults to 7 days; override with `EXPENSE_DATE_MAX_FUTURE_DAYS`.
+static EXPENSE_DATE_MAX_FUTURE_DAYS: Lazy<i64> = Lazy::new(|| {
+    std::env::var("EXPENSE_DATE_MAX_FUTURE_DAYS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n >= 0)
+        .unwrap_or(7)
+});
+
+/// How long before the group was created an expense may be dated, since groups
+/// are often created after the trip they cover. Defaults to 365 days; override
+/// with `EXPENSE_DATE_MAX_PAST_DAYS`.
+static EXPENSE_DATE_MAX_PAST_DAYS: Lazy<i64> = Lazy::new(|| {
+    std::env::var("EXPENSE_DATE_MAX_PAST_DAYS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n >= 0)
+        .unwrap_or(365)
+});
+
+/// Reject expense dates that would skew reports: more than
+/// `EXPENSE_DATE_MAX_FUTURE_DAYS` after today or more than
+/// `EXPENSE_DATE_MAX_PAST_DAYS` before the group was created. Only checked when a
+/// date is set or changed, so existing (e.g. cloned) expenses stay editable.
+async fn validate_expense_date(group_id: Uuid, date: NaiveDate) -> Result<(), Status> {
+    let created_at: DateTime<Utc> = sqlx::query_scalar("SELECT created_at FROM groups WHERE id = $1")
+        .bind(group_id)
+        .fetch_optional(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch group: {}", e);
+            db::error_status(&e)
+        })?
+        .ok_or(Status::NotFound)?;
+    check_expense_date(date, created_at)
+}
+
+/// The date range check of `validate_expense_date` for a group created at `group_created_at`.
+fn check_expense_date(date: NaiveDate, group_created_at: DateTime<Utc>) -> Result<(), Status> {
+    if date > latest_expense_date() || date < earliest_expense_date(group_created_at) {
+        return Err(Status::BadRequest);
+    }
+    Ok(())
+}
+
+fn latest_expense_date() -> NaiveDate {
+    Utc::now().date_naive() + chrono::Duration::days(*EXPENSE_DATE_MAX_FUTURE_DAYS)
+}
+
+fn earliest_expense_date(group_created_at: DateTime<Utc>) -> NaiveDate {
+    group_created_at.date_naive() - chrono::Duration::days(*EXPENSE_DATE_MAX_PAST_DAYS)
+}
+
+/// Drop repeated member ids from a split list so nobody is charged twice, and
+/// reject lists longer than the absolute cap or the group's member count. The
+/// result is in creation order, the order splits are stored and rounded in (ids
+/// of other groups go last, for `ensure_group_members` to reject).
+async fn sanitize_split_between(group_id: Uuid, ids: &[Uuid]) -> Result<Vec<Uuid>, Status> {
+    if ids.len() > *MAX_SPLIT_MEMBERS {
+        return Err(Status::BadRequest);
+    }
+    let mut unique: Vec<Uuid> = Vec::with_capacity(ids.len());
+    for id in ids {
+        if !unique.contains(id) {
+            unique.push(*id);
+        }
+    }
+
+    let member_ids: Vec<Uuid> =
+        sqlx::query_scalar("SELECT id FROM members WHERE group_id = $1 ORDER BY creation_order")
+            .bind(group_id)
+            .fetch_all(db::get_pool())
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch members: {}", e);
+                db::error_status(&e)
+            })?;
+    if unique.len() > member_ids.len() {
+        return Err(Status::BadRequest);
+    }
+    unique.sort_by_key(|id| member_ids.iter().position(|m| m == id).unwrap_or(usize::MAX));
+    Ok(unique)
+}
+
+/// Income is handed out to the split members, so it needs a positive amount and
+/// at least one split member; anything else would skew the balances.
+fn validate_income(expense_type: ExpenseType, amount: f64, split_count: usize) -> Result<(), Status> {
+    if expense_type == ExpenseType::Income && (amount.is_nan() || amount <= 0.0 || split_count == 0) {
+        return Err(Status::BadRequest);
+    }
+    Ok(())
+}
+
+/// Each split member's value in `splits` (percentage, amount or weight), if given.
+fn split_values(split_between: &[Uuid], splits: Option<&[SplitEntry]>) -> Vec<Option<f64>> {
+    split_between
+        .iter()
+        .map(|member_id| {
+            splits
+                .unwrap_or_default()
+                .iter()
+                .find(|s| s.member_id == *member_id)
+                .and_then(|s| s.share)
+        })
+        .collect()
+}
+
+/// An uneven split of income must hand out the whole amount: percentages add up to
+/// 100, exact amounts to the amount (missing ones count as an equal part, like in
+/// `member_shares`) and shares give someone a part. Otherwise the payer would hold
+/// money nobody is credited for. Being off by less than a cent per member is left
+/// to `currency::round_shares`.
+fn validate_income_split(
+    expense_type: ExpenseType,
+    amount: f64,
+    split_type: &str,
+    values: &[Option<f64>],
+) -> Result<(), Status> {
+    if expense_type != ExpenseType::Income || values.is_empty() {
+        return Ok(());
+    }
+    let count = values.len() as f64;
+    let sum = |missing: f64| values.iter().map(|v| v.unwrap_or(missing)).sum::<f64>();
+    let handed_out = match split_type {
+        "percentage" => amount * sum(100.0 / count) / 100.0,
+        "exact" => sum(amount / count),
+        "shares" if sum(0.0) > 0.0 => amount,
+        "shares" => return Err(Status::BadRequest),
+        _ => amount,
+    };
+    if !handed_out.is_finite() || (handed_out - amount).abs() >= 0.01 * count {
+        return Err(Status::BadRequest);
+    }
+    Ok(())
+}
+
+/// The `splits` of an `adjustment` split: each member's extra is stored as their share.
+fn adjustment_splits(adjustments: &[Adjustment]) -> Vec<SplitEntry> {
+    adjustments
+        .iter()
+        .map(|a| SplitEntry {
+            member_id: a.member_id,
+            share: Some(a.amount),
+        })
+        .collect()
+}
+
+/// Each member's extra in the `splits` of an `adjustment` split (none counts as 0).
+fn split_extras(splits: Option<&[SplitEntry]>) -> Vec<(Uuid, f64)> {
+    splits
+        .unwrap_or_default()
+        .iter()
+        .map(|s| (s.member_id, s.share.unwrap_or(0.0)))
+        .collect()
+}
+
+/// Extras of an `adjustment` split must be non-negative, belong to split members
+/// (at most once each) and leave a non-negative remainder to split equally.
+fn validate_adjustments(amount: f64, split_between: &[Uuid], extras: &[(Uuid, f64)]) -> Result<(), Status> {
+    let mut seen = Vec::new();
+    for (member_id, extra) in extras {
+        if !extra.is_finite() || *extra < 0.0 || !split_between.contains(member_id) || seen.contains(member_id) {
+            return Err(Status::BadRequest);
+        }
+        seen.push(*member_id);
+    }
+    let total: f64 = extras.iter().map(|(_, extra)| extra).sum();
+    if total > amount + 1e-9 {
+        return Err(Status::BadRequest);
+    }
+    Ok(())
+}
+
+/// Checks shared by new expenses and the initial expenses of a new group, once the
+/// split members and values are resolved: a known split type, a recipient for
+/// transfers, someone to split with otherwise, and income and adjustment splits
+/// that hand out the amount.
+fn validate_expense_split(
+    expense_type: ExpenseType,
+    transfer_to: Option<Uuid>,
+    amount: f64,
+    split_type: &str,
+    split_between: &[Uuid],
+    splits: Option<&[SplitEntry]>,
+) -> Result<(), ApiError> {
+    if !SPLIT_TYPES.contains(&split_type) {
+        return Err(ApiError::bad_request(format!("Unknown split type '{}'", split_type)));
+    }
+    if expense_type == ExpenseType::Transfer {
+        if transfer_to.is_none() {
+            return Err(ApiError::bad_request("Transfers need transfer_to"));
+        }
+        return Ok(());
+    }
+    if split_between.is_empty() {
+        return Err(ApiError::bad_request("split_between is empty"));
+    }
+    if validate_income(expense_type, amount, split_between.len()).is_err() {
+        return Err(ApiError::bad_request("Income needs a positive amount"));
+    }
+    let values = split_values(split_between, splits);
+    if validate_income_split(expense_type, amount, split_type, &values).is_err() {
+        return Err(ApiError::bad_request("Income splits must add up to its amount"));
+    }
+    if split_type == "adjustment" && validate_adjustments(amount, split_between, &split_extras(splits)).is_err() {
+        return Err(ApiError::bad_request(
+            "Adjustments must be non-negative extras of split members that fit in the amount",
+        ));
+    }
+    Ok(())
+}
+
+/// What a refund needs from the expense it refunds.
+struct RefundBase {
+    currency: String,
+    exchange_rate: f64,
+    /// The original's split members weighted by their share; refunds are split
+    /// in the same proportions.
+    weights: Vec<SplitEntry>,
+    /// Amount (in the group currency) not refunded yet.
+    refundable: f64,
+}
+
+/// Look up the expense a refund refers to: a regular expense of the group.
+/// `exclude` leaves a refund that is being edited out of the refunded total.
+async fn refund_base(group_id: Uuid, expense_id: Uuid, exclude: Option<Uuid>) -> Result<RefundBase, ApiError> {
+    let pool = db::get_pool();
+    let original: ExpenseRow = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE id = $1 AND group_id = $2"
+    )
+    .bind(expense_id)
+    .bind(group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch refunded expense: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or_else(|| ApiError::bad_request("refund_of must be an expense of this group"))?;
+    if original.expense_type != ExpenseType::Expense {
+        return Err(ApiError::bad_request("Only expenses can be refunded, not transfers or income"));
+    }
+
+    let splits = fetch_splits(original.id).await?;
+    // Match the precision of expense_splits.share
+    let weights: Vec<SplitEntry> = member_shares(&original, &splits)
+        .into_iter()
+        .filter(|(_, share)| *share > 0.0)
+        .map(|(member_id, share)| SplitEntry {
+            member_id,
+            share: Some((share * 10_000.0).round() / 10_000.0),
+        })
+        .collect();
+    if weights.is_empty() {
+        return Err(ApiError::bad_request("The expense has no shares to refund"));
+    }
+    let refunded: f64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(amount * exchange_rate), 0)::float8 FROM expenses WHERE refund_of = $1 AND id IS DISTINCT FROM $2",
+    )
+    .bind(original.id)
+    .bind(exclude)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to sum refunds: {}", e);
+        db::error_status(&e)
+    })?;
+
+    Ok(RefundBase {
+        refundable: expense_in_group_currency(&original) - refunded,
+        currency: original.currency,
+        exchange_rate: original.exchange_rate.to_f64().unwrap_or(1.0),
+        weights,
+    })
+}
+
+/// All refunds of an expense together can't hand back more than it cost.
+fn validate_refund_amount(base: &RefundBase, amount_in_group_currency: f64) -> Result<(), ApiError> {
+    if amount_in_group_currency > base.refundable + 1e-6 {
+        return Err(ApiError::bad_request(format!(
+            "Refunds can't exceed the refunded expense ({:.2} left to refund)",
+            base.refundable.max(0.0)
+        )));
+    }
+    Ok(())
+}
+
+/// Edits keep a refund an `income` within what is left of its expense, and a
+/// refunded expense an `expense` that still covers its refunds.
+async fn validate_refund_edit(group_id: Uuid, refund: &ExpenseRow) -> Result<(), ApiError> {
+    let Some(original_id) = refund.refund_of else {
+        return validate_refunded_edit(refund).await;
+    };
+    if refund.expense_type != ExpenseType::Income {
+        return Err(ApiError::bad_request("A refund must stay income"));
+    }
+    let base = refund_base(group_id, original_id, Some(refund.id)).await?;
+    validate_refund_amount(&base, expense_in_group_currency(refund))
+}
+
+async fn validate_refunded_edit(expense: &ExpenseRow) -> Result<(), ApiError> {
+    let refunded: Option<f64> =
+        sqlx::query_scalar("SELECT SUM(amount * exchange_rate)::float8 FROM expenses WHERE refund_of = $1")
+            .bind(expense.id)
+            .fetch_one(db::get_pool())
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to sum refunds: {}", e);
+                db::error_status(&e)
+            })?;
+    let Some(refunded) = refunded else {
+        return Ok(());
+    };
+    if expense.expense_type != ExpenseType::Expense {
+        return Err(ApiError::bad_request("A refunded expense must stay an expense"));
+    }
+    if expense_in_group_currency(expense) + 1e-6 < refunded {
+        return Err(ApiError::bad_request(format!(
+            "The expense can't be less than its refunds ({:.2})",
+            refunded
+        )));
+    }
+    Ok(())
+}
+
+/// Reject trip ids that don't belong to the group.
+async fn ensure_group_trip(group_id: Uuid, trip_id: Option<Uuid>) -> Result<(), Status> {
+    let Some(trip_id) = trip_id else {
+        return Ok(());
+    };
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM trips WHERE id = $1 AND group_id = $2)")
+            .bind(trip_id)
+            .bind(group_id)
+            .fetch_one(db::get_pool())
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to check trip: {}", e);
+                db::error_status(&e)
+            })?;
+    if !exists {
+        return Err(Status::BadRequest);
+    }
+    Ok(())
+}
+
+/// Reject member ids (payer, transfer recipient, split members) that aren't in the group.
+async fn ensure_group_members(group_id: Uuid, ids: &[Uuid]) -> Result<(), Status> {
+    let mut unique = ids.to_vec();
+    unique.sort();
+    unique.dedup();
+    let found: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM members WHERE group_id = $1 AND id = ANY($2)")
+            .bind(group_id)
+            .bind(&unique)
+            .fetch_one(db::get_pool())
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to check members: {}", e);
+                db::error_status(&e)
+            })?;
+    if found as usize != unique.len() {
+        return Err(Status::BadRequest);
+    }
+    Ok(())
+}
+
+/// Validate an initial expense of a new group and resolve its defaults. Members are
+/// referenced by their index in `member_ids`; error messages name the expense.
+/// Returns the payer, the transfer recipient and the prepared expense.
+async fn prepare_initial_expense(
+    index: usize,
+    expense: &InitialExpense,
+    member_ids: &[Uuid],
+    group_currency: &str,
+    group_created_at: DateTime<Utc>,
+) -> Result<(Uuid, Option<Uuid>, PreparedExpense), ApiError> {
+    let invalid = |reason: String| ApiError::bad_request(format!("expenses[{}]: {}", index, reason));
+    let member = |i: usize| {
+        member_ids
+            .get(i)
+            .copied()
+            .ok_or_else(|| invalid(format!("no member at index {}", i)))
+    };
+
+    let expense_type = ExpenseType::parse(&expense.expense_type)
+        .ok_or_else(|| invalid(format!("unknown expense_type '{}'", expense.expense_type)))?;
+    let paid_by = member(expense.paid_by)?;
+    let transfer_to = expense.transfer_to.map(member).transpose()?;
+
+    let expense_date = expense
+        .expense_date
+        .unwrap_or_else(|| Utc::now().date_naive());
+    if check_expense_date(expense_date, group_created_at).is_err() {
+        return Err(invalid("expense_date is out of range".to_string()));
+    }
+    let currency = expense
+        .currency
+        .clone()
+        .unwrap_or_else(|| group_currency.to_string());
+    let exchange_rate = match expense.exchange_rate {
+        Some(rate) => rate,
+        None if !currency.eq_ignore_ascii_case(group_currency) => {
+            rates::lookup(&currency, group_currency, expense_date)
+                .await
+                .map_err(|e| {
+                    eprintln!("Exchange rate lookup failed: {}", e);
+                    Status::ServiceUnavailable
+                })?
+                .rate
+        }
+        None => 1.0,
+    };
+    let exchange_rate_val = exchange_rate_decimal(exchange_rate)
+        .map_err(|e| invalid(e.message.unwrap_or_default()))?;
+    let amount = amount_decimal(expense.amount).map_err(|e| invalid(e.message.unwrap_or_default()))?;
+    let tags = normalize_tags(expense.tags.as_deref().unwrap_or_default())
+        .map_err(|_| invalid("too many or too long tags".to_string()))?;
+
+    let mut split_between: Vec<Uuid> = Vec::new();
+    match &expense.split_between {
+        Some(indices) => {
+            for &i in indices {
+                let member_id = member(i)?;
+                if !split_between.contains(&member_id) {
+                    split_between.push(member_id);
+                }
+            }
+        }
+        None if expense_type == ExpenseType::Transfer => {}
+        None => split_between = member_ids.to_vec(),
+    }
+    let splits = expense
+        .splits
+        .as_ref()
+        .map(|entries| {
+            entries
+                .iter()
+                .map(|entry| {
+                    Ok(SplitEntry {
+                        member_id: member(entry.member)?,
+                        share: Some(entry.share),
+                    })
+                })
+                .collect::<Result<Vec<_>, ApiError>>()
+        })
+        .transpose()?;
+    validate_expense_split(
+        expense_type,
+        transfer_to,
+        expense.amount,
+        &expense.split_type,
+        &split_between,
+        splits.as_deref(),
+    )
+    .map_err(|e| invalid(e.message.unwrap_or_default()))?;
+
+    Ok((
+        paid_by,
+        transfer_to,
+        PreparedExpense {
+            expense_type,
+            amount_value: amount.to_f64().unwrap_or(expense.amount),
+            amount,
+            currency,
+            exchange_rate: exchange_rate_val.to_f64().unwrap_or(exchange_rate),
+            exchange_rate_val,
+            expense_date,
+            split_type: expense.split_type.clone(),
+            split_between,
+            splits,
+            items: None,
+            receipt_url: None,
+            created_by: None,
+            tags,
+        },
+    ))
+}
+
+// Create group - no auth required. Initial expenses (e.g. from an import) are
+// recorded in the same transaction: either everything is created or nothing.
 #[post("/groups", data = "<request>")]
 async fn create_group(
+    _rate_limit: RocketGovernor<'_, CreateGroupRateLimit>,
     request: Json<CreateGroupRequest>,
-) -> Result<Json<GroupCreatedResponse>, Status> {
+) -> Result<Json<GroupCreatedResponse>, ApiError> {
+    let name = validate_group_name(&request.name)?;
+    let member_names = validate_member_names(&request.member_names)?;
+    if member_names.len() as i64 > *MAX_GROUP_MEMBERS {
+        return Err(member_limit_error());
+    }
+    if request.expenses.len() as i64 > *MAX_GROUP_EXPENSES {
+        return Err(expense_limit_error());
+    }
     let pool = db::get_pool();
     let group_id = Uuid::new_v4();
     let created_at = Utc::now();
     let currency = request.currency.as_deref().unwrap_or("EUR");
+    let member_ids: Vec<Uuid> = member_names.iter().map(|_| Uuid::new_v4()).collect();
+
+    // Validate every expense before writing anything
+    let mut initial_expenses = Vec::with_capacity(request.expenses.len());
+    for (index, expense) in request.expenses.iter().enumerate() {
+        initial_expenses
+            .push(prepare_initial_expense(index, expense, &member_ids, currency, created_at).await?);
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
 
     // Insert group
     sqlx::query("INSERT INTO groups (id, name, currency, created_at, last_activity_at) VALUES ($1, $2, $3, $4, $4)")
         .bind(group_id)
-        .bind(&request.name)
+        .bind(&name)
         .bind(currency)
         .bind(created_at)
-        .execute(pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| {
             eprintln!("Failed to create group: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
 
     // Insert members
     let mut members = Vec::new();
-    for name in &request.member_names {
-        let member_id = Uuid::new_v4();
+    for (member_id, member_name) in member_ids.iter().zip(&member_names) {
         sqlx::query("INSERT INTO members (id, group_id, name, created_at) VALUES ($1, $2, $3, $4)")
             .bind(member_id)
             .bind(group_id)
-            .bind(name)
+            .bind(member_name)
             .bind(created_at)
-            .execute(pool)
+            .execute(&mut *tx)
             .await
             .map_err(|e| {
                 eprintln!("Failed to create member: {}", e);
-                Status::InternalServerError
+                db::error_status(&e)
             })?;
 
         members.push(Member {
-            id: member_id,
-            name: name.clone(),
+            id: *member_id,
+            name: member_name.clone(),
             paypal_email: None,
             iban: None,
+            email: None,
+            notify_on_expense: false,
+            preferred_payment_method: None,
+            venmo_handle: None,
+            payment_note: None,
+        });
+    }
+
+    // Insert initial expenses
+    let mut expenses = Vec::with_capacity(initial_expenses.len());
+    for ((paid_by, transfer_to, prepared), expense) in initial_expenses.into_iter().zip(&request.expenses) {
+        let expense_id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO expenses (id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"
+        )
+        .bind(expense_id)
+        .bind(group_id)
+        .bind(&expense.description)
+        .bind(&prepared.amount)
+        .bind(paid_by)
+        .bind(prepared.expense_type)
+        .bind(transfer_to)
+        .bind(&prepared.currency)
+        .bind(&prepared.exchange_rate_val)
+        .bind(prepared.expense_date)
+        .bind(created_at)
+        .bind(&prepared.split_type)
+        .bind(&expense.notes)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to create expense: {}", e);
+            db::error_status(&e)
+        })?;
+
+        for split in prepared.split_rows() {
+            sqlx::query("INSERT INTO expense_splits (expense_id, member_id, share) VALUES ($1, $2, $3)")
+                .bind(expense_id)
+                .bind(split.member_id)
+                .bind(&split.share)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to create expense split: {}", e);
+                    db::error_status(&e)
+                })?;
+        }
+        insert_tags(&mut *tx, expense_id, &prepared.tags).await?;
+
+        expenses.push(Expense {
+            id: expense_id,
+            group_id,
+            description: expense.description.clone(),
+            amount: prepared.amount_value,
+            paid_by,
+            split_between: prepared.split_between,
+            expense_type: prepared.expense_type,
+            transfer_to,
+            currency: prepared.currency,
+            exchange_rate: prepared.exchange_rate,
+            expense_date: prepared.expense_date,
+            created_at,
+            splits: if prepared.split_type != "equal" {
+                prepared.splits
+            } else {
+                None
+            },
+            split_type: prepared.split_type,
+            notes: expense.notes.clone(),
+            receipt_url: None,
+            created_by: None,
+            updated_by: None,
+            tags: prepared.tags,
+            items: None,
+            trip_id: None,
+            refund_of: None,
+            amount_minor: None,
+            settled_shares: Vec::new(),
         });
     }
 
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit group: {}", e);
+        db::error_status(&e)
+    })?;
+
     let group = Group {
         id: group_id,
-        name: request.name.clone(),
+        name,
         currency: currency.to_string(),
         members,
         created_at,
         last_activity_at: created_at,
+        default_split: None,
+        archived_at: None,
+        locked: false,
+        default_share_permissions: None,
     };
 
     // Generate JWT for this group (creator gets all permissions)
-    let token = generate_token(group_id, Some(Permissions::all()))
-        .map_err(|_| Status::InternalServerError)?;
+    let token = issue_token(group_id, Permissions::all(), None).await?;
 
-    Ok(Json(GroupCreatedResponse { group, token }))
+    Ok(Json(GroupCreatedResponse {
+        group,
+        token,
+        expenses,
+    }))
 }
 
-// Get group - requires valid JWT
-#[get("/groups/current")]
-async fn get_current_group(auth: GroupAuth) -> Result<Json<Group>, Status> {
+// Clone group - requires valid JWT. Creates a new group with the same members
+// (fresh ids) and currency but no expenses; the caller gets a creator token for it.
+#[post("/groups/current/clone", data = "<request>")]
+async fn clone_group(
+    auth: GroupAuth,
+    _rate_limit: RocketGovernor<'_, CreateGroupRateLimit>,
+    request: Json<CloneGroupRequest>,
+) -> Result<Json<GroupCreatedResponse>, ApiError> {
     let pool = db::get_pool();
-
-    // Get group
-    let group_row: GroupRow =
-        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at FROM groups WHERE id = $1")
+    let source: GroupRow =
+        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at, default_split, archived_at, locked, default_share_permissions FROM groups WHERE id = $1")
             .bind(auth.group_id)
             .fetch_optional(pool)
             .await
             .map_err(|e| {
                 eprintln!("Failed to fetch group: {}", e);
-                Status::InternalServerError
+                db::error_status(&e)
             })?
             .ok_or(Status::NotFound)?;
+    let name = validate_group_name(request.name.as_deref().unwrap_or(&source.name))?;
 
-    // Get members
-    let member_rows: Vec<MemberRow> = sqlx::query_as(
-        "SELECT id, group_id, name, paypal_email, iban, created_at FROM members WHERE group_id = $1 ORDER BY created_at"
+    let source_members: Vec<MemberRow> = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1 ORDER BY display_order, id"
     )
     .bind(auth.group_id)
     .fetch_all(pool)
     .await
     .map_err(|e| {
         eprintln!("Failed to fetch members: {}", e);
-        Status::InternalServerError
+        db::error_status(&e)
     })?;
+    // The limit may have been lowered since the source group was set up
+    if source_members.len() as i64 > *MAX_GROUP_MEMBERS {
+        return Err(member_limit_error());
+    }
 
-    let group = Group {
-        id: group_row.id,
-        name: group_row.name,
-        currency: group_row.currency.clone(),
-        members: member_rows
-            .into_iter()
-            .map(|r| Member {
-                id: r.id,
-                name: r.name,
-                paypal_email: r.paypal_email,
-                iban: r.iban,
-            })
-            .collect(),
-        created_at: group_row.created_at,
-        last_activity_at: group_row.last_activity_at,
-    };
+    let group_id = Uuid::new_v4();
+    let created_at = Utc::now();
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
 
-    Ok(Json(group))
+    sqlx::query("INSERT INTO groups (id, name, currency, created_at, last_activity_at) VALUES ($1, $2, $3, $4, $4)")
+        .bind(group_id)
+        .bind(&name)
+        .bind(&source.currency)
+        .bind(created_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to create group: {}", e);
+            db::error_status(&e)
+        })?;
+
+    // Emails and notification opt-ins stay with the original group
+    let mut members = Vec::new();
+    for source_member in source_members {
+        let (paypal_email, iban, preferred_payment_method, venmo_handle, payment_note) =
+            if request.copy_payment_info {
+                (
+                    source_member.paypal_email,
+                    source_member.iban,
+                    source_member.preferred_payment_method,
+                    source_member.venmo_handle,
+                    source_member.payment_note,
+                )
+            } else {
+                (None, None, None, None, None)
+            };
+        let member = Member {
+            id: Uuid::new_v4(),
+            name: source_member.name,
+            paypal_email,
+            iban,
+            email: None,
+            notify_on_expense: false,
+            preferred_payment_method,
+            venmo_handle,
+            payment_note,
+        };
+        sqlx::query(
+            "INSERT INTO members (id, group_id, name, created_at, paypal_email, iban, preferred_payment_method, venmo_handle, payment_note) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(member.id)
+        .bind(group_id)
+        .bind(&member.name)
+        .bind(created_at)
+        .bind(&member.paypal_email)
+        .bind(&member.iban)
+        .bind(&member.preferred_payment_method)
+        .bind(&member.venmo_handle)
+        .bind(&member.payment_note)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to create member: {}", e);
+            db::error_status(&e)
+        })?;
+        members.push(member);
+    }
+
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let group = Group {
+        id: group_id,
+        name,
+        currency: source.currency,
+        members,
+        created_at,
+        last_activity_at: created_at,
+        default_split: None,
+        archived_at: None,
+        locked: false,
+        default_share_permissions: None,
+    };
+
+    let token = issue_token(group_id, Permissions::all(), None).await?;
+
+    Ok(Json(GroupCreatedResponse {
+        group,
+        token,
+        expenses: Vec::new(),
+    }))
+}
+
+/// Current change counter of a group (bumped by DB triggers), for ETags.
+async fn group_version(group_id: Uuid) -> Result<i64, Status> {
+    sqlx::query_scalar("SELECT version FROM groups WHERE id = $1")
+        .bind(group_id)
+        .fetch_optional(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch group version: {}", e);
+            db::error_status(&e)
+        })?
+        .ok_or(Status::NotFound)
+}
+
+// Get group - requires valid JWT. Supports If-None-Match.
+#[get("/groups/current")]
+async fn get_current_group(
+    auth: GroupAuth,
+    if_none_match: IfNoneMatch,
+) -> Result<Conditional<Group>, Status> {
+    let etag = group_etag("group", auth.group_id, group_version(auth.group_id).await?);
+    if if_none_match.matches(&etag) {
+        return Ok(Conditional::NotModified(etag));
+    }
+
+    let group = load_group(auth.group_id).await?;
+
+    Ok(Conditional::Fresh(etag, Json(group)))
+}
+
+// Group, balances and settlement plan in one response - requires valid JWT.
+// Supports If-None-Match.
+#[get("/groups/current/overview")]
+async fn get_overview(
+    auth: GroupAuth,
+    if_none_match: IfNoneMatch,
+) -> Result<Conditional<GroupOverview>, Status> {
+    let etag = group_etag("overview", auth.group_id, group_version(auth.group_id).await?);
+    if if_none_match.matches(&etag) {
+        return Ok(Conditional::NotModified(etag));
+    }
+
+    let group = load_group(auth.group_id).await?;
+    let balances = cached_balances(auth.group_id).await?;
+    let decimals = currency::minor_units(&group.currency);
+    let (settlements, settlement_warning) = match settlement::simplify(&balances, decimals) {
+        Ok(settlements) => (settlements, None),
+        Err(warning) => (Vec::new(), Some(warning)),
+    };
+
+    Ok(Conditional::Fresh(
+        etag,
+        Json(GroupOverview {
+            group,
+            balances,
+            settlements,
+            settlement_warning,
+        }),
+    ))
+}
+
+/// Most tokens `POST /groups/overview` takes at once.
+const MAX_OVERVIEW_TOKENS: usize = 50;
+
+// Summaries of several groups at once, e.g. for a dashboard - no auth, each token
+// is checked on its own. Invalid tokens get an error entry instead of failing the request.
+#[post("/groups/overview", data = "<request>")]
+async fn get_groups_overview(
+    request: Json<GroupsOverviewRequest>,
+) -> Result<Json<Vec<GroupsOverviewEntry>>, ApiError> {
+    if request.tokens.len() > MAX_OVERVIEW_TOKENS {
+        return Err(ApiError::bad_request(format!(
+            "At most {} tokens can be looked up at once",
+            MAX_OVERVIEW_TOKENS
+        )));
+    }
+    let mut entries = Vec::with_capacity(request.tokens.len());
+    for token in &request.tokens {
+        entries.push(match group_summary(token.trim()).await? {
+            Ok(summary) => GroupsOverviewEntry {
+                group: Some(summary),
+                error: None,
+            },
+            Err(error) => GroupsOverviewEntry {
+                group: None,
+                error: Some(error.to_string()),
+            },
+        });
+    }
+    Ok(Json(entries))
+}
+
+/// Summary of the group a token belongs to, or why the token can't be used.
+/// Only database failures are returned as `Err`.
+async fn group_summary(token: &str) -> Result<Result<GroupSummary, &'static str>, Status> {
+    let Ok(claims) = validate_token(token) else {
+        return Ok(Err("Invalid or expired token"));
+    };
+    let revoked = claims.is_revoked().await.map_err(|e| {
+        eprintln!("Failed to check token revocation: {}", e);
+        db::error_status(&e)
+    })?;
+    if revoked {
+        return Ok(Err("Token has been revoked"));
+    }
+
+    let pool = db::get_pool();
+    let row = sqlx::query_as::<_, (String, String, DateTime<Utc>, Option<DateTime<Utc>>, i64, f64)>(
+        "SELECT g.name, g.currency, g.last_activity_at, g.archived_at,
+                (SELECT COUNT(*) FROM members WHERE group_id = g.id),
+                (SELECT COALESCE(SUM(amount * exchange_rate), 0)::float8 FROM expenses
+                 WHERE group_id = g.id AND expense_type = 'expense')
+         FROM groups g WHERE g.id = $1",
+    )
+    .bind(claims.group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch group summary: {}", e);
+        db::error_status(&e)
+    })?;
+    let Some((name, currency, last_activity_at, archived_at, member_count, total_spend)) = row else {
+        return Ok(Err("Group not found"));
+    };
+
+    let balance = match claims.sub {
+        Some(member_id) => cached_balances(claims.group_id)
+            .await?
+            .into_iter()
+            .find(|b| b.user_id == member_id)
+            .map(|b| b.balance),
+        None => None,
+    };
+    Ok(Ok(GroupSummary {
+        group_id: claims.group_id,
+        total_spend: currency::round_amount(total_spend, &currency),
+        name,
+        currency,
+        member_count,
+        last_activity_at,
+        archived_at,
+        member_id: claims.sub,
+        balance,
+    }))
 }
 
 // Add member - requires valid JWT + manage_members permission
 #[post("/groups/current/members", data = "<request>")]
 async fn add_member(
     auth: GroupAuth,
+    _writable: Writable,
     request: Json<AddMemberRequest>,
-) -> Result<Json<Group>, Status> {
+) -> Result<Json<Group>, ApiError> {
     if !auth.permissions.has_manage_members() {
-        return Err(Status::Forbidden);
+        return Err(Status::Forbidden.into());
     }
+    let name = validate_member_name(&request.name)?;
     let pool = db::get_pool();
 
     // Check group exists
     let group_row: GroupRow =
-        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at FROM groups WHERE id = $1")
+        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at, default_split, archived_at, locked, default_share_permissions FROM groups WHERE id = $1")
             .bind(auth.group_id)
             .fetch_optional(pool)
             .await
             .map_err(|e| {
                 eprintln!("Failed to fetch group: {}", e);
-                Status::InternalServerError
+                db::error_status(&e)
             })?
             .ok_or(Status::NotFound)?;
 
+    let duplicate: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM members WHERE group_id = $1 AND LOWER(name) = LOWER($2))",
+    )
+    .bind(auth.group_id)
+    .bind(&name)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to check member name: {}", e);
+        db::error_status(&e)
+    })?;
+    if duplicate {
+        return Err(ApiError::bad_request(format!("Duplicate member name '{}'", name)));
+    }
+    ensure_member_capacity(auth.group_id).await?;
+
     // Insert new member
     let member_id = Uuid::new_v4();
     sqlx::query("INSERT INTO members (id, group_id, name, created_at) VALUES ($1, $2, $3, $4)")
         .bind(member_id)
         .bind(auth.group_id)
-        .bind(&request.name)
+        .bind(&name)
         .bind(Utc::now())
         .execute(pool)
         .await
         .map_err(|e| {
             eprintln!("Failed to create member: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
 
     // Update last_activity_at
@@ -194,19 +1315,25 @@ async fn add_member(
         .await
         .map_err(|e| {
             eprintln!("Failed to update last_activity_at: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
 
+    webhooks::dispatch(
+        auth.group_id,
+        webhooks::MEMBER_ADDED,
+        serde_json::json!({ "id": member_id, "name": name }),
+    );
+
     // Get all members
     let member_rows: Vec<MemberRow> = sqlx::query_as(
-        "SELECT id, group_id, name, paypal_email, iban, created_at FROM members WHERE group_id = $1 ORDER BY created_at"
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1 ORDER BY display_order, id"
     )
     .bind(auth.group_id)
     .fetch_all(pool)
     .await
     .map_err(|e| {
         eprintln!("Failed to fetch members: {}", e);
-        Status::InternalServerError
+        db::error_status(&e)
     })?;
 
     let group = Group {
@@ -215,614 +1342,4730 @@ async fn add_member(
         currency: group_row.currency.clone(),
         members: member_rows
             .into_iter()
-            .map(|r| Member {
-                id: r.id,
-                name: r.name,
-                paypal_email: r.paypal_email,
-                iban: r.iban,
-            })
+            .map(Member::from)
             .collect(),
         created_at: group_row.created_at,
         last_activity_at: group_row.last_activity_at,
+        default_split: group_row.default_split.map(|d| d.0),
+        archived_at: group_row.archived_at,
+        locked: group_row.locked,
+        default_share_permissions: group_row.default_share_permissions.map(|d| d.0),
     };
 
     Ok(Json(group))
 }
 
-// Update member payment info - requires valid JWT + update_payment permission
-#[put("/groups/current/members/<member_id>/payment", data = "<request>")]
-async fn update_member_payment(
+// Merge two members - requires valid JWT + manage_members permission.
+// All references to the source member are moved to the target, then the source is deleted.
+#[post("/groups/current/members/merge", data = "<request>")]
+async fn merge_members(
     auth: GroupAuth,
-    member_id: &str,
-    request: Json<UpdateMemberPaymentRequest>,
-) -> Result<Json<Member>, Status> {
-    if !auth.permissions.has_update_payment() {
+    _writable: Writable,
+    request: Json<MergeMembersRequest>,
+) -> Result<Json<Group>, Status> {
+    if !auth.permissions.has_manage_members() {
         return Err(Status::Forbidden);
     }
+    if request.source_id == request.target_id {
+        return Err(Status::BadRequest);
+    }
     let pool = db::get_pool();
-    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+    let source = request.source_id;
+    let target = request.target_id;
 
-    // Verify member belongs to this group
-    let member_row: MemberRow = sqlx::query_as(
-        "SELECT id, group_id, name, paypal_email, iban, created_at FROM members WHERE id = $1 AND group_id = $2"
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Both members must belong to this group
+    let found: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM members WHERE group_id = $1 AND id IN ($2, $3)",
     )
-    .bind(member_uuid)
     .bind(auth.group_id)
-    .fetch_optional(pool)
+    .bind(source)
+    .bind(target)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| {
-        eprintln!("Failed to fetch member: {}", e);
-        Status::InternalServerError
-    })?
-    .ok_or(Status::NotFound)?;
-
-    // Update payment info
-    sqlx::query("UPDATE members SET paypal_email = $1, iban = $2 WHERE id = $3")
-        .bind(&request.paypal_email)
-        .bind(&request.iban)
-        .bind(member_uuid)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to update member payment info: {}", e);
-            Status::InternalServerError
-        })?;
-
-    Ok(Json(Member {
-        id: member_row.id,
-        name: member_row.name,
-        paypal_email: request.paypal_email.clone(),
-        iban: request.iban.clone(),
-    }))
-}
+        eprintln!("Failed to fetch members: {}", e);
+        db::error_status(&e)
+    })?;
+    if found != 2 {
+        return Err(Status::NotFound);
+    }
 
-// Get expenses - requires valid JWT
-#[get("/groups/current/expenses")]
-async fn get_expenses(auth: GroupAuth) -> Result<Json<Vec<Expense>>, Status> {
-    let pool = db::get_pool();
+    move_member_references(&mut tx, auth.group_id, source, target).await?;
 
-    // Get all expenses for this group
-    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
-        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type 
-         FROM expenses WHERE group_id = $1 ORDER BY expense_date DESC, created_at DESC"
+    // Keep who entered/edited expenses attributed to the merged member
+    sqlx::query(
+        "UPDATE expenses SET created_by = CASE WHEN created_by = $2 THEN $1 ELSE created_by END,
+                             updated_by = CASE WHEN updated_by = $2 THEN $1 ELSE updated_by END
+         WHERE group_id = $3 AND (created_by = $2 OR updated_by = $2)",
     )
+    .bind(target)
+    .bind(source)
     .bind(auth.group_id)
-    .fetch_all(pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
-        eprintln!("Failed to fetch expenses: {}", e);
-        Status::InternalServerError
+        eprintln!("Failed to reassign expense attribution: {}", e);
+        db::error_status(&e)
     })?;
 
-    let mut expenses = Vec::new();
-    for row in expense_rows {
-        // Get split members for each expense
-        let splits: Vec<ExpenseSplitMemberRow> =
-            sqlx::query_as("SELECT member_id, share FROM expense_splits WHERE expense_id = $1")
-                .bind(row.id)
-                .fetch_all(pool)
-                .await
-                .map_err(|e| {
-                    eprintln!("Failed to fetch expense splits: {}", e);
-                    Status::InternalServerError
-                })?;
+    sqlx::query("DELETE FROM members WHERE id = $1")
+        .bind(source)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to delete merged member: {}", e);
+            db::error_status(&e)
+        })?;
 
-        let split_type = row.split_type.clone();
-        let split_entries: Option<Vec<SplitEntry>> = if split_type != "equal" {
-            Some(
-                splits
-                    .iter()
-                    .map(|s| SplitEntry {
-                        member_id: s.member_id,
-                        share: s.share.as_ref().and_then(|v| v.to_f64()),
-                    })
-                    .collect(),
-            )
-        } else {
-            None
-        };
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
 
-        expenses.push(Expense {
-            id: row.id,
-            group_id: row.group_id,
-            description: row.description,
-            amount: row.amount.to_f64().unwrap_or(0.0),
-            paid_by: row.paid_by,
-            split_between: splits.into_iter().map(|s| s.member_id).collect(),
-            expense_type: row.expense_type,
-            transfer_to: row.transfer_to,
-            currency: row.currency,
-            exchange_rate: row.exchange_rate.to_f64().unwrap_or(1.0),
-            expense_date: row.expense_date,
-            created_at: row.created_at,
-            split_type,
-            splits: split_entries,
-        });
-    }
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit member merge: {}", e);
+        db::error_status(&e)
+    })?;
 
-    Ok(Json(expenses))
+    Ok(Json(load_group(auth.group_id).await?))
 }
 
-// Create expense - requires valid JWT + add_expenses permission
-#[post("/groups/current/expenses", data = "<request>")]
-async fn create_expense(
+// Set the order members are listed in - requires valid JWT + manage_members permission.
+// The list must contain every current member exactly once. Only affects display:
+// who gets odd cents when splitting still follows the order members were added.
+#[put("/groups/current/members/order", data = "<request>")]
+async fn reorder_members(
     auth: GroupAuth,
-    request: Json<CreateExpenseRequest>,
-) -> Result<Json<Expense>, Status> {
-    if !auth.permissions.has_add_expenses() {
-        return Err(Status::Forbidden);
+    _writable: Writable,
+    request: Json<ReorderMembersRequest>,
+) -> Result<Json<Group>, ApiError> {
+    if !auth.permissions.has_manage_members() {
+        return Err(Status::Forbidden.into());
     }
     let pool = db::get_pool();
-    let expense_id = Uuid::new_v4();
-    let created_at = Utc::now();
-    let expense_date = request
-        .expense_date
-        .unwrap_or_else(|| Utc::now().date_naive());
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
 
-    // Get group for default currency
-    let group_row: GroupRow =
-        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at FROM groups WHERE id = $1")
+    // Lock the members so none is added or removed while the order is checked
+    let member_ids: Vec<Uuid> =
+        sqlx::query_scalar("SELECT id FROM members WHERE group_id = $1 FOR UPDATE")
             .bind(auth.group_id)
-            .fetch_one(pool)
+            .fetch_all(&mut *tx)
             .await
             .map_err(|e| {
-                eprintln!("Failed to fetch group: {}", e);
-                Status::InternalServerError
+                eprintln!("Failed to fetch members: {}", e);
+                db::error_status(&e)
             })?;
-    let currency = request.currency.clone().unwrap_or(group_row.currency);
-    let exchange_rate_val = BigDecimal::try_from(request.exchange_rate.unwrap_or(1.0))
-        .map_err(|_| Status::BadRequest)?;
-
-    // Convert f64 to BigDecimal
-    let amount = BigDecimal::try_from(request.amount).map_err(|_| Status::BadRequest)?;
+    for (i, id) in request.member_ids.iter().enumerate() {
+        if !member_ids.contains(id) {
+            return Err(ApiError::bad_request(format!("{} is not a member of this group", id)));
+        }
+        if request.member_ids[..i].contains(id) {
+            return Err(ApiError::bad_request(format!("Member {} is listed twice", id)));
+        }
+    }
+    if request.member_ids.len() != member_ids.len() {
+        return Err(ApiError::bad_request("Every member of the group must be listed"));
+    }
 
-    // Insert expense
     sqlx::query(
-        "INSERT INTO expenses (id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type) 
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"
+        "UPDATE members m SET display_order = o.position
+         FROM UNNEST($1::uuid[]) WITH ORDINALITY AS o(id, position)
+         WHERE m.id = o.id AND m.group_id = $2 AND m.display_order <> o.position",
     )
-    .bind(expense_id)
+    .bind(&request.member_ids)
     .bind(auth.group_id)
-    .bind(&request.description)
-    .bind(&amount)
-    .bind(request.paid_by)
-    .bind(&request.expense_type)
-    .bind(request.transfer_to)
-    .bind(&currency)
-    .bind(&exchange_rate_val)
-    .bind(expense_date)
-    .bind(created_at)
-    .bind(&request.split_type)
-    .execute(pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
-        eprintln!("Failed to create expense: {}", e);
-        Status::InternalServerError
+        eprintln!("Failed to reorder members: {}", e);
+        db::error_status(&e)
     })?;
 
-    // Insert expense splits (not needed for transfers)
-    if request.expense_type != "transfer" {
-        for member_id in &request.split_between {
-            let share_val: Option<BigDecimal> = request.splits.as_ref().and_then(|splits| {
-                splits
-                    .iter()
-                    .find(|s| &s.member_id == member_id)
-                    .and_then(|s| s.share.and_then(|v| BigDecimal::try_from(v).ok()))
-            });
-            sqlx::query(
-                "INSERT INTO expense_splits (expense_id, member_id, share) VALUES ($1, $2, $3)",
-            )
-            .bind(expense_id)
-            .bind(member_id)
-            .bind(&share_val)
-            .execute(pool)
-            .await
-            .map_err(|e| {
-                eprintln!("Failed to create expense split: {}", e);
-                Status::InternalServerError
-            })?;
-        }
-    }
-
-    let split_entries: Option<Vec<SplitEntry>> = if request.split_type != "equal" {
-        request.splits.clone()
-    } else {
-        None
-    };
-
-    // Update last_activity_at
     sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
         .bind(auth.group_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| {
             eprintln!("Failed to update last_activity_at: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
 
-    let expense = Expense {
-        id: expense_id,
-        group_id: auth.group_id,
-        description: request.description.clone(),
-        amount: request.amount,
-        paid_by: request.paid_by,
-        split_between: request.split_between.clone(),
-        expense_type: request.expense_type.clone(),
-        transfer_to: request.transfer_to,
-        currency,
-        exchange_rate: request.exchange_rate.unwrap_or(1.0),
-        expense_date,
-        created_at,
-        split_type: request.split_type.clone(),
-        splits: split_entries,
-    };
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit member order: {}", e);
+        db::error_status(&e)
+    })?;
 
-    Ok(Json(expense))
+    Ok(Json(load_group(auth.group_id).await?))
 }
 
-// Update expense - requires valid JWT + edit_expenses permission
-#[put("/groups/current/expenses/<expense_id>", data = "<request>")]
-async fn update_expense(
+// Reassign a member's expenses to another member - requires valid JWT + manage_members permission.
+// Like a merge, but the source member is kept (e.g. so it can be removed afterwards).
+#[post("/groups/current/members/<member_id>/reassign?<to>")]
+async fn reassign_member(
     auth: GroupAuth,
-    expense_id: &str,
-    request: Json<UpdateExpenseRequest>,
-) -> Result<Json<Expense>, Status> {
-    if !auth.permissions.has_edit_expenses() {
+    _writable: Writable,
+    member_id: &str,
+    to: &str,
+) -> Result<Json<Group>, Status> {
+    if !auth.permissions.has_manage_members() {
         return Err(Status::Forbidden);
     }
+    let source = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+    let target = Uuid::parse_str(to).map_err(|_| Status::BadRequest)?;
+    if source == target {
+        return Err(Status::BadRequest);
+    }
     let pool = db::get_pool();
-    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
 
-    // Verify expense belongs to this group
-    let _existing: ExpenseRow = sqlx::query_as(
-        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type 
-         FROM expenses WHERE id = $1 AND group_id = $2"
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let found: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM members WHERE group_id = $1 AND id IN ($2, $3)",
     )
-    .bind(expense_uuid)
     .bind(auth.group_id)
-    .fetch_optional(pool)
+    .bind(source)
+    .bind(target)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| {
-        eprintln!("Failed to fetch expense: {}", e);
-        Status::InternalServerError
-    })?
-    .ok_or(Status::NotFound)?;
+        eprintln!("Failed to fetch members: {}", e);
+        db::error_status(&e)
+    })?;
+    if found != 2 {
+        return Err(Status::NotFound);
+    }
 
-    let amount = BigDecimal::try_from(request.amount).map_err(|_| Status::BadRequest)?;
-    let expense_date = request.expense_date.unwrap_or(_existing.expense_date);
-    let currency = request.currency.clone().unwrap_or(_existing.currency);
-    let exchange_rate_val = BigDecimal::try_from(
-        request
-            .exchange_rate
-            .unwrap_or(_existing.exchange_rate.to_f64().unwrap_or(1.0)),
-    )
-    .map_err(|_| Status::BadRequest)?;
+    move_member_references(&mut tx, auth.group_id, source, target).await?;
 
-    // Update expense
-    sqlx::query(
-        "UPDATE expenses SET description = $1, amount = $2, paid_by = $3, expense_type = $4, transfer_to = $5, currency = $6, exchange_rate = $7, expense_date = $8, split_type = $9
-         WHERE id = $10"
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit member reassignment: {}", e);
+        db::error_status(&e)
+    })?;
+
+    Ok(Json(load_group(auth.group_id).await?))
+}
+
+/// Move every expense, split, line item and transfer reference from `source` to
+/// `target` inside `tx`, keeping all balances unchanged.
+async fn move_member_references(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    group_id: Uuid,
+    source: Uuid,
+    target: Uuid,
+) -> Result<(), Status> {
+    // Expenses where both members are in the split would end up with the target twice.
+    // Fold the source's share into the target's so the split (and balances) stay the same.
+    // Equal splits are converted to weighted shares so the target carries both portions,
+    // adjustment splits to exact amounts (the equal part depends on the member count).
+    let overlapping: Vec<(Uuid, String)> = sqlx::query_as(
+        "SELECT e.id, e.split_type FROM expenses e
+         WHERE e.group_id = $1
+           AND EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id AND s.member_id = $2)
+           AND EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id AND s.member_id = $3)",
     )
-    .bind(&request.description)
-    .bind(&amount)
-    .bind(request.paid_by)
-    .bind(&request.expense_type)
-    .bind(request.transfer_to)
-    .bind(&currency)
-    .bind(&exchange_rate_val)
-    .bind(expense_date)
-    .bind(&request.split_type)
-    .bind(expense_uuid)
-    .execute(pool)
+    .bind(group_id)
+    .bind(source)
+    .bind(target)
+    .fetch_all(&mut **tx)
     .await
     .map_err(|e| {
-        eprintln!("Failed to update expense: {}", e);
-        Status::InternalServerError
+        eprintln!("Failed to fetch overlapping splits: {}", e);
+        db::error_status(&e)
     })?;
 
-    // Delete old splits and re-insert
-    sqlx::query("DELETE FROM expense_splits WHERE expense_id = $1")
-        .bind(expense_uuid)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to delete expense splits: {}", e);
-            Status::InternalServerError
-        })?;
-
-    if request.expense_type != "transfer" {
-        for member_id in &request.split_between {
-            let share_val: Option<BigDecimal> = request.splits.as_ref().and_then(|splits| {
-                splits
-                    .iter()
-                    .find(|s| &s.member_id == member_id)
-                    .and_then(|s| s.share.and_then(|v| BigDecimal::try_from(v).ok()))
-            });
+    for (expense_id, split_type) in overlapping {
+        if split_type == "equal" {
+            sqlx::query("UPDATE expense_splits SET share = 1 WHERE expense_id = $1")
+                .bind(expense_id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to convert expense splits: {}", e);
+                    db::error_status(&e)
+                })?;
+            sqlx::query("UPDATE expenses SET split_type = 'shares' WHERE id = $1")
+                .bind(expense_id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to convert expense split type: {}", e);
+                    db::error_status(&e)
+                })?;
+        } else if split_type == "adjustment" {
             sqlx::query(
-                "INSERT INTO expense_splits (expense_id, member_id, share) VALUES ($1, $2, $3)",
+                "UPDATE expense_splits s SET share = COALESCE(s.share, 0) + (e.amount - t.extras) / t.n
+                 FROM expenses e,
+                      (SELECT COUNT(*) AS n, SUM(COALESCE(share, 0)) AS extras
+                       FROM expense_splits WHERE expense_id = $1) t
+                 WHERE s.expense_id = $1 AND e.id = $1",
             )
-            .bind(expense_uuid)
-            .bind(member_id)
-            .bind(&share_val)
-            .execute(pool)
+            .bind(expense_id)
+            .execute(&mut **tx)
             .await
             .map_err(|e| {
-                eprintln!("Failed to create expense split: {}", e);
-                Status::InternalServerError
+                eprintln!("Failed to convert expense splits: {}", e);
+                db::error_status(&e)
             })?;
+            sqlx::query("UPDATE expenses SET split_type = 'exact' WHERE id = $1")
+                .bind(expense_id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to convert expense split type: {}", e);
+                    db::error_status(&e)
+                })?;
         }
+        sqlx::query(
+            "UPDATE expense_splits SET share = COALESCE(share, 0) + COALESCE(
+                (SELECT share FROM expense_splits WHERE expense_id = $1 AND member_id = $2), 0)
+             WHERE expense_id = $1 AND member_id = $3",
+        )
+        .bind(expense_id)
+        .bind(source)
+        .bind(target)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to merge expense split: {}", e);
+            db::error_status(&e)
+        })?;
+        sqlx::query("DELETE FROM expense_splits WHERE expense_id = $1 AND member_id = $2")
+            .bind(expense_id)
+            .bind(source)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to delete merged expense split: {}", e);
+                db::error_status(&e)
+            })?;
     }
 
-    let split_entries: Option<Vec<SplitEntry>> = if request.split_type != "equal" {
-        request.splits.clone()
-    } else {
-        None
-    };
+    sqlx::query("UPDATE expense_splits SET member_id = $1 WHERE member_id = $2")
+        .bind(target)
+        .bind(source)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to reassign expense splits: {}", e);
+            db::error_status(&e)
+        })?;
 
-    // Update last_activity_at
-    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
-        .bind(auth.group_id)
-        .execute(pool)
+    // Same for line item assignments, keeping each member at most once per item
+    sqlx::query(
+        "UPDATE expense_items SET member_ids = ARRAY(
+             SELECT m FROM unnest(array_replace(member_ids, $2, $1)) WITH ORDINALITY AS t(m, i)
+             GROUP BY m ORDER BY MIN(i)
+         )
+         WHERE $2 = ANY(member_ids)",
+    )
+    .bind(target)
+    .bind(source)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to reassign expense items: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query("UPDATE expenses SET paid_by = $1 WHERE paid_by = $2 AND group_id = $3")
+        .bind(target)
+        .bind(source)
+        .bind(group_id)
+        .execute(&mut **tx)
         .await
         .map_err(|e| {
-            eprintln!("Failed to update last_activity_at: {}", e);
-            Status::InternalServerError
+            eprintln!("Failed to reassign payer: {}", e);
+            db::error_status(&e)
         })?;
 
-    let expense = Expense {
-        id: expense_uuid,
-        group_id: auth.group_id,
-        description: request.description.clone(),
-        amount: request.amount,
-        paid_by: request.paid_by,
-        split_between: request.split_between.clone(),
-        expense_type: request.expense_type.clone(),
-        transfer_to: request.transfer_to,
-        currency,
-        exchange_rate: request.exchange_rate.unwrap_or(1.0),
-        expense_date,
-        created_at: _existing.created_at,
-        split_type: request.split_type.clone(),
-        splits: split_entries,
-    };
+    sqlx::query("UPDATE expenses SET transfer_to = $1 WHERE transfer_to = $2 AND group_id = $3")
+        .bind(target)
+        .bind(source)
+        .bind(group_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to reassign transfer recipient: {}", e);
+            db::error_status(&e)
+        })?;
 
-    Ok(Json(expense))
+    // Transfers between the two members are now self-transfers with no effect
+    sqlx::query(
+        "DELETE FROM expenses WHERE group_id = $1 AND expense_type = 'transfer' AND paid_by = $2 AND transfer_to = $2",
+    )
+    .bind(group_id)
+    .bind(target)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to delete self-transfers: {}", e);
+        db::error_status(&e)
+    })?;
+
+    Ok(())
 }
 
-// Delete expense - requires valid JWT + edit_expenses permission
-#[delete("/groups/current/expenses/<expense_id>")]
-async fn delete_expense(auth: GroupAuth, expense_id: &str) -> Result<Status, Status> {
-    if !auth.permissions.has_edit_expenses() {
+/// Load a group with its members, as returned by the group endpoints.
+async fn load_group(group_id: Uuid) -> Result<Group, Status> {
+    let pool = db::get_pool();
+    let group_row: GroupRow =
+        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at, default_split, archived_at, locked, default_share_permissions FROM groups WHERE id = $1")
+            .bind(group_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch group: {}", e);
+                db::error_status(&e)
+            })?
+            .ok_or(Status::NotFound)?;
+
+    let member_rows: Vec<MemberRow> = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1 ORDER BY display_order, id"
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch members: {}", e);
+        db::error_status(&e)
+    })?;
+
+    Ok(Group {
+        id: group_row.id,
+        name: group_row.name,
+        currency: group_row.currency,
+        members: member_rows.into_iter().map(Member::from).collect(),
+        created_at: group_row.created_at,
+        last_activity_at: group_row.last_activity_at,
+        default_split: group_row.default_split.map(|d| d.0),
+        archived_at: group_row.archived_at,
+        locked: group_row.locked,
+        default_share_permissions: group_row.default_share_permissions.map(|d| d.0),
+    })
+}
+
+/// Values accepted for `members.preferred_payment_method`.
+const PAYMENT_METHODS: [&str; 3] = ["paypal", "iban", "venmo"];
+
+// Update member payment info - requires valid JWT + update_payment permission
+#[put("/groups/current/members/<member_id>/payment", data = "<request>")]
+async fn update_member_payment(
+    auth: GroupAuth,
+    _writable: Writable,
+    member_id: &str,
+    request: Json<UpdateMemberPaymentRequest>,
+) -> Result<Json<Member>, Status> {
+    if !auth.permissions.has_update_payment() {
         return Err(Status::Forbidden);
     }
     let pool = db::get_pool();
-    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
 
-    // Verify expense belongs to this group
-    let _existing: ExpenseRow = sqlx::query_as(
-        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type 
-         FROM expenses WHERE id = $1 AND group_id = $2"
+    // Verify member belongs to this group
+    let member_row: MemberRow = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE id = $1 AND group_id = $2"
     )
-    .bind(expense_uuid)
+    .bind(member_uuid)
     .bind(auth.group_id)
     .fetch_optional(pool)
     .await
     .map_err(|e| {
-        eprintln!("Failed to fetch expense: {}", e);
-        Status::InternalServerError
+        eprintln!("Failed to fetch member: {}", e);
+        db::error_status(&e)
     })?
     .ok_or(Status::NotFound)?;
 
-    // Delete splits first
-    sqlx::query("DELETE FROM expense_splits WHERE expense_id = $1")
-        .bind(expense_uuid)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to delete expense splits: {}", e);
-            Status::InternalServerError
-        })?;
-
-    // Delete expense
-    sqlx::query("DELETE FROM expenses WHERE id = $1")
-        .bind(expense_uuid)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to delete expense: {}", e);
-            Status::InternalServerError
-        })?;
+    // Payment preferences are only changed when sent, so older clients that only
+    // know about paypal_email/iban don't wipe them
+    let preferred_payment_method = match &request.preferred_payment_method {
+        Some(Some(method)) if !PAYMENT_METHODS.contains(&method.as_str()) => {
+            return Err(Status::BadRequest);
+        }
+        Some(method) => method.clone(),
+        None => member_row.preferred_payment_method,
+    };
+    let venmo_handle = match &request.venmo_handle {
+        Some(handle) => handle
+            .as_ref()
+            .map(|h| h.trim().trim_start_matches('@').to_string())
+            .filter(|h| !h.is_empty()),
+        None => member_row.venmo_handle,
+    };
+    let payment_note = match &request.payment_note {
+        Some(note) => note
+            .as_ref()
+            .map(|n| n.trim().chars().take(255).collect::<String>())
+            .filter(|n| !n.is_empty()),
+        None => member_row.payment_note,
+    };
 
-    // Update last_activity_at
-    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
-        .bind(auth.group_id)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to update last_activity_at: {}", e);
-            Status::InternalServerError
-        })?;
+    // Update payment info
+    sqlx::query(
+        "UPDATE members SET paypal_email = $1, iban = $2, preferred_payment_method = $3, venmo_handle = $4, payment_note = $5
+         WHERE id = $6 AND group_id = $7",
+    )
+    .bind(&request.paypal_email)
+    .bind(&request.iban)
+    .bind(&preferred_payment_method)
+    .bind(&venmo_handle)
+    .bind(&payment_note)
+    .bind(member_uuid)
+    .bind(auth.group_id)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to update member payment info: {}", e);
+        db::error_status(&e)
+    })?;
 
-    Ok(Status::NoContent)
+    Ok(Json(Member {
+        id: member_row.id,
+        name: member_row.name,
+        paypal_email: request.paypal_email.clone(),
+        iban: request.iban.clone(),
+        email: member_row.email,
+        notify_on_expense: member_row.notify_on_expense,
+        preferred_payment_method,
+        venmo_handle,
+        payment_note,
+    }))
 }
 
-// Get balances - requires valid JWT
-#[get("/groups/current/balances")]
-async fn get_balances(auth: GroupAuth) -> Result<Json<Vec<Balance>>, Status> {
+// Update member notification preferences - requires valid JWT + update_payment permission
+#[put("/groups/current/members/<member_id>/notifications", data = "<request>")]
+async fn update_member_notifications(
+    auth: GroupAuth,
+    _writable: Writable,
+    member_id: &str,
+    request: Json<UpdateMemberNotificationsRequest>,
+) -> Result<Json<Member>, Status> {
+    if !auth.permissions.has_update_payment() {
+        return Err(Status::Forbidden);
+    }
     let pool = db::get_pool();
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
 
-    // Get all members
-    let member_rows: Vec<MemberRow> = sqlx::query_as(
-        "SELECT id, group_id, name, paypal_email, iban, created_at FROM members WHERE group_id = $1"
+    let email = request
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty());
+    if let Some(email) = email
+        && (email.len() > 255 || !email.contains('@'))
+    {
+        return Err(Status::BadRequest);
+    }
+    // Notifications need somewhere to go
+    if request.notify_on_expense && email.is_none() {
+        return Err(Status::BadRequest);
+    }
+
+    let member_row: MemberRow = sqlx::query_as(
+        "UPDATE members SET email = $1, notify_on_expense = $2 WHERE id = $3 AND group_id = $4
+         RETURNING id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note"
     )
+    .bind(email)
+    .bind(request.notify_on_expense)
+    .bind(member_uuid)
     .bind(auth.group_id)
-    .fetch_all(pool)
+    .fetch_optional(pool)
     .await
     .map_err(|e| {
-        eprintln!("Failed to fetch members: {}", e);
-        Status::InternalServerError
-    })?;
+        eprintln!("Failed to update member notifications: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
 
-    // Get all expenses with splits
-    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
-        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type 
-         FROM expenses WHERE group_id = $1"
+    Ok(Json(Member::from(member_row)))
+}
+
+/// Build the API representation of an expense from its row and split rows.
+fn expense_from_row(row: ExpenseRow, splits: Vec<ExpenseSplitMemberRow>) -> Expense {
+    let split_type = row.split_type.clone();
+    let split_entries: Option<Vec<SplitEntry>> = if split_type != "equal" {
+        Some(
+            splits
+                .iter()
+                .map(|s| SplitEntry {
+                    member_id: s.member_id,
+                    share: s.share.as_ref().and_then(|v| v.to_f64()),
+                })
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    Expense {
+        id: row.id,
+        group_id: row.group_id,
+        description: row.description,
+        amount: row.amount.to_f64().unwrap_or(0.0),
+        paid_by: row.paid_by,
+        settled_shares: splits.iter().filter(|s| s.settled).map(|s| s.member_id).collect(),
+        split_between: splits.into_iter().map(|s| s.member_id).collect(),
+        expense_type: row.expense_type,
+        transfer_to: row.transfer_to,
+        currency: row.currency,
+        exchange_rate: row.exchange_rate.to_f64().unwrap_or(1.0),
+        expense_date: row.expense_date,
+        created_at: row.created_at,
+        split_type,
+        splits: split_entries,
+        notes: row.notes,
+        receipt_url: row.receipt_url,
+        created_by: row.created_by,
+        updated_by: row.updated_by,
+        tags: Vec::new(),
+        items: None,
+        trip_id: row.trip_id,
+        refund_of: row.refund_of,
+        amount_minor: None,
+    }
+}
+
+/// An expense with its split members, items and tags.
+async fn full_expense(row: ExpenseRow) -> Result<Expense, Status> {
+    let splits = fetch_splits(row.id).await?;
+    let mut expense = expense_from_row(row, splits);
+    expense.items = fetch_items(expense.id).await?;
+    expense.tags = fetch_tags(expense.id).await?;
+    Ok(expense)
+}
+
+/// Position in the expense list: (expense_date, created_at, id) of the last expense
+/// on the previous page, encoded as an opaque URL-safe string.
+type ExpenseCursor = (NaiveDate, DateTime<Utc>, Uuid);
+
+fn encode_cursor(row: &ExpenseRow) -> String {
+    URL_SAFE_NO_PAD.encode(format!(
+        "{}|{}|{}",
+        row.expense_date,
+        row.created_at.timestamp_micros(),
+        row.id
+    ))
+}
+
+fn decode_cursor(cursor: &str) -> Option<ExpenseCursor> {
+    let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let mut parts = raw.split('|');
+    let date = NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?;
+    let created_at = DateTime::from_timestamp_micros(parts.next()?.parse().ok()?)?;
+    let id = Uuid::parse_str(parts.next()?).ok()?;
+    parts.next().is_none().then_some((date, created_at, id))
+}
+
+/// Parse `?amounts=`: `minor` adds exact integer minor-unit amounts next to the
+/// float ones, `decimal` (the default) leaves them out.
+fn minor_amounts(amounts: Option<&str>) -> Result<bool, Status> {
+    match amounts.unwrap_or("decimal") {
+        "decimal" => Ok(false),
+        "minor" => Ok(true),
+        _ => Err(Status::BadRequest),
+    }
+}
+
+/// Fill in `amount_minor` from the expense's own currency.
+fn set_amount_minor(expense: &mut Expense) {
+    expense.amount_minor = Some(currency::to_minor_units(
+        expense.amount,
+        currency::minor_units(&expense.currency),
+    ));
+}
+
+// Get expenses - requires valid JWT. Supports If-None-Match.
+// `?tag=food` only returns expenses carrying that tag. With `?limit=` or `?cursor=`
+// the expenses come in pages (see `ExpensePage`), which carry no ETag.
+// `?amounts=minor` adds each amount in integer minor units (`amount_minor`).
+#[get("/groups/current/expenses?<tag>&<limit>&<cursor>&<amounts>")]
+async fn get_expenses(
+    auth: GroupAuth,
+    if_none_match: IfNoneMatch,
+    tag: Option<&str>,
+    limit: Option<i64>,
+    cursor: Option<&str>,
+    amounts: Option<&str>,
+) -> Result<Either<Conditional<Vec<Expense>>, Json<ExpensePage>>, Status> {
+    let pool = db::get_pool();
+    let minor = minor_amounts(amounts)?;
+    let tag = tag
+        .map(|t| normalize_tags(&[t.to_string()]))
+        .transpose()?
+        .and_then(|t| t.into_iter().next());
+    let paged = limit.is_some() || cursor.is_some();
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(Status::BadRequest);
+    }
+    let after = cursor
+        .map(|c| decode_cursor(c).ok_or(Status::BadRequest))
+        .transpose()?;
+
+    let mut etag = None;
+    if !paged {
+        // Filtered lists get their own ETag (tag hex-encoded to stay header-safe)
+        let mut kind = match &tag {
+            Some(tag) => format!(
+                "expenses-tag-{}",
+                tag.bytes().map(|b| format!("{:02x}", b)).collect::<String>()
+            ),
+            None => "expenses".to_string(),
+        };
+        if minor {
+            kind.push_str("-minor");
+        }
+        let tag_value = group_etag(&kind, auth.group_id, group_version(auth.group_id).await?);
+        if if_none_match.matches(&tag_value) {
+            return Ok(Either::Left(Conditional::NotModified(tag_value)));
+        }
+        etag = Some(tag_value);
+    }
+
+    // `id` breaks ties between expenses created in the same instant, so pages never
+    // overlap or skip. One extra row tells whether another page follows.
+    let mut expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1
+           AND ($2::text IS NULL OR EXISTS (SELECT 1 FROM expense_tags t WHERE t.expense_id = expenses.id AND t.tag = $2))
+           AND ($3::date IS NULL OR (expense_date, created_at, id) < ($3, $4, $5))
+         ORDER BY expense_date DESC, created_at DESC, id DESC
+         LIMIT $6"
+    )
+    .bind(auth.group_id)
+    .bind(&tag)
+    .bind(after.map(|a| a.0))
+    .bind(after.map(|a| a.1))
+    .bind(after.map(|a| a.2))
+    .bind(if paged { Some(limit + 1) } else { None })
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let next_cursor = if paged && expense_rows.len() as i64 > limit {
+        expense_rows.truncate(limit as usize);
+        expense_rows.last().map(encode_cursor)
+    } else {
+        None
+    };
+
+    let mut expenses = Vec::new();
+    for row in expense_rows {
+        let mut expense = full_expense(row).await?;
+        if minor {
+            set_amount_minor(&mut expense);
+        }
+        expenses.push(expense);
+    }
+
+    match etag {
+        Some(etag) => Ok(Either::Left(Conditional::Fresh(etag, Json(expenses)))),
+        None => Ok(Either::Right(Json(ExpensePage {
+            expenses,
+            next_cursor,
+        }))),
+    }
+}
+
+/// A validated `CreateExpenseRequest` with every default resolved, ready to insert.
+struct PreparedExpense {
+    expense_type: ExpenseType,
+    amount_value: f64,
+    amount: BigDecimal,
+    currency: String,
+    exchange_rate: f64,
+    exchange_rate_val: BigDecimal,
+    expense_date: NaiveDate,
+    split_type: String,
+    split_between: Vec<Uuid>,
+    splits: Option<Vec<SplitEntry>>,
+    items: Option<Vec<ExpenseItem>>,
+    receipt_url: Option<String>,
+    created_by: Option<Uuid>,
+    tags: Vec<String>,
+}
+
+impl PreparedExpense {
+    /// The split rows that would be stored (none for transfers).
+    fn split_rows(&self) -> Vec<ExpenseSplitMemberRow> {
+        if self.expense_type == ExpenseType::Transfer {
+            return Vec::new();
+        }
+        self.split_between
+            .iter()
+            .map(|member_id| ExpenseSplitMemberRow {
+                member_id: *member_id,
+                share: self.splits.as_ref().and_then(|splits| {
+                    splits
+                        .iter()
+                        .find(|s| &s.member_id == member_id)
+                        .and_then(|s| s.share.and_then(|v| BigDecimal::try_from(v).ok()))
+                }),
+                settled: false,
+            })
+            .collect()
+    }
+}
+
+/// Validate a new expense and resolve its defaults (currency, exchange rate, split
+/// members, itemized amounts) without writing anything.
+async fn prepare_expense(
+    auth: &GroupAuth,
+    request: &CreateExpenseRequest,
+) -> Result<PreparedExpense, ApiError> {
+    let pool = db::get_pool();
+    let expense_date = request
+        .expense_date
+        .unwrap_or_else(|| Utc::now().date_naive());
+    if let Some(date) = request.expense_date {
+        validate_expense_date(auth.group_id, date).await?;
+    }
+
+    // Get group for default currency
+    let group_row: GroupRow =
+        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at, default_split, archived_at, locked, default_share_permissions FROM groups WHERE id = $1")
+            .bind(auth.group_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch group: {}", e);
+                db::error_status(&e)
+            })?;
+    let mut expense_type = ExpenseType::parse(&request.expense_type).ok_or(Status::BadRequest)?;
+    // Refunds are income split like the expense they refund
+    let refund = match request.refund_of {
+        Some(original_id) => {
+            if expense_type == ExpenseType::Transfer {
+                return Err(ApiError::bad_request("A refund can't be a transfer"));
+            }
+            if request.split_between.is_some()
+                || request.splits.is_some()
+                || request.adjustments.is_some()
+                || request.items.is_some()
+                || request.exclude_payer.is_some()
+            {
+                return Err(ApiError::bad_request(
+                    "A refund is split like the expense it refunds; leave out split_between, splits, adjustments, items and exclude_payer",
+                ));
+            }
+            expense_type = ExpenseType::Income;
+            Some(refund_base(auth.group_id, original_id, None).await?)
+        }
+        None => None,
+    };
+    let currency = request
+        .currency
+        .clone()
+        .or_else(|| refund.as_ref().map(|r| r.currency.clone()))
+        .unwrap_or(group_row.currency.clone());
+    let mut split_type = request.split_type.clone();
+    let mut splits = request.splits.clone();
+    if let Some(adjustments) = &request.adjustments {
+        split_type = "adjustment".to_string();
+        splits = Some(adjustment_splits(adjustments));
+    }
+    // A refund in the original's currency defaults to the original's rate
+    let refund_rate = refund
+        .as_ref()
+        .filter(|r| r.currency.eq_ignore_ascii_case(&currency))
+        .map(|r| r.exchange_rate);
+    // Without an explicit rate, foreign-currency expenses use the rate of the expense date
+    let exchange_rate = match request.exchange_rate.or(refund_rate) {
+        Some(rate) => rate,
+        None if !currency.eq_ignore_ascii_case(&group_row.currency) => {
+            rates::lookup(&currency, &group_row.currency, expense_date)
+                .await
+                .map_err(|e| {
+                    eprintln!("Exchange rate lookup failed: {}", e);
+                    Status::ServiceUnavailable
+                })?
+                .rate
+        }
+        None => 1.0,
+    };
+    let exchange_rate_val = exchange_rate_decimal(exchange_rate)?;
+    let exchange_rate = exchange_rate_val.to_f64().unwrap_or(exchange_rate);
+    let receipt_url = request.receipt_url.as_deref().map(validate_receipt_url).transpose()?;
+    let created_by = acting_member(auth).await?;
+    let tags = normalize_tags(request.tags.as_deref().unwrap_or_default())?;
+
+    // Itemized expenses derive their total from the items
+    let items = match &request.items {
+        Some(items) if expense_type != ExpenseType::Transfer => {
+            Some(validate_items(auth.group_id, items).await?)
+        }
+        _ => None,
+    };
+    let amount_value = match &items {
+        Some(items) => (items.iter().map(|i| i.amount).sum::<f64>() * 100.0).round() / 100.0,
+        None => request.amount,
+    };
+
+    // Convert f64 to BigDecimal
+    let amount = amount_decimal(amount_value)?;
+    let amount_value = amount.to_f64().unwrap_or(amount_value);
+
+    // Default to the group's default split, or else everyone currently in the group
+    let mut split_between: Vec<Uuid> = match &request.split_between {
+        Some(ids) => sanitize_split_between(auth.group_id, ids).await?,
+        None if expense_type == ExpenseType::Transfer => Vec::new(),
+        None => {
+            let member_ids: Vec<Uuid> =
+                sqlx::query_scalar("SELECT id FROM members WHERE group_id = $1 ORDER BY creation_order")
+                    .bind(auth.group_id)
+                    .fetch_all(pool)
+                    .await
+                    .map_err(|e| {
+                        eprintln!("Failed to fetch members: {}", e);
+                        db::error_status(&e)
+                    })?;
+            // Members removed (e.g. merged) since the default was saved are skipped
+            let default_entries: Vec<SplitEntry> = group_row
+                .default_split
+                .as_ref()
+                .map(|d| {
+                    d.splits
+                        .iter()
+                        .filter(|e| member_ids.contains(&e.member_id))
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default();
+            match &group_row.default_split {
+                Some(default) if !default_entries.is_empty() => {
+                    if splits.is_none() {
+                        split_type = default.split_type.clone();
+                        splits = Some(default_entries.clone());
+                    }
+                    default_entries.iter().map(|e| e.member_id).collect()
+                }
+                _ => member_ids,
+            }
+        }
+    };
+    if request.exclude_payer.unwrap_or(false) {
+        split_between.retain(|id| *id != request.paid_by);
+    }
+    if let Some(items) = &items {
+        let shares = item_shares(items);
+        split_between = shares.iter().map(|s| s.member_id).collect();
+        split_type = "exact".to_string();
+        splits = Some(shares);
+    }
+    if let Some(refund) = &refund {
+        validate_refund_amount(refund, amount_value * exchange_rate)?;
+        split_between = refund.weights.iter().map(|w| w.member_id).collect();
+        split_type = "shares".to_string();
+        splits = Some(refund.weights.clone());
+    }
+    validate_expense_split(
+        expense_type,
+        request.transfer_to,
+        amount_value,
+        &split_type,
+        &split_between,
+        splits.as_deref(),
+    )?;
+    let involved: Vec<Uuid> = split_between
+        .iter()
+        .copied()
+        .chain(std::iter::once(request.paid_by))
+        .chain(request.transfer_to)
+        .collect();
+    ensure_group_members(auth.group_id, &involved).await?;
+    ensure_group_trip(auth.group_id, request.trip_id).await?;
+
+    Ok(PreparedExpense {
+        expense_type,
+        amount_value,
+        amount,
+        currency,
+        exchange_rate,
+        exchange_rate_val,
+        expense_date,
+        split_type,
+        split_between,
+        splits,
+        items,
+        receipt_url,
+        created_by,
+        tags,
+    })
+}
+
+/// Transfers record payments between members, which `can_settle` allows; every
+/// other kind of entry needs `can_add_expenses`.
+fn may_record(permissions: &Permissions, expense_type: &str) -> bool {
+    match ExpenseType::parse(expense_type) {
+        Some(ExpenseType::Transfer) => permissions.has_settle(),
+        _ => permissions.has_add_expenses(),
+    }
+}
+
+// Create expense - requires valid JWT + add_expenses permission (settle for transfers)
+#[post("/groups/current/expenses", data = "<request>")]
+async fn create_expense(
+    auth: GroupAuth,
+    _writable: Writable,
+    request: Json<CreateExpenseRequest>,
+) -> Result<Json<Expense>, ApiError> {
+    if !may_record(&auth.permissions, &request.expense_type) {
+        return Err(Status::Forbidden.into());
+    }
+    let prepared = prepare_expense(&auth, &request).await?;
+    ensure_expense_capacity(auth.group_id).await?;
+    let pool = db::get_pool();
+    let expense_id = Uuid::new_v4();
+    let created_at = Utc::now();
+    let split_rows = prepared.split_rows();
+    let PreparedExpense {
+        expense_type,
+        amount_value,
+        amount,
+        currency,
+        exchange_rate,
+        exchange_rate_val,
+        expense_date,
+        split_type,
+        split_between,
+        splits,
+        items,
+        receipt_url,
+        created_by,
+        tags,
+    } = prepared;
+
+    // The expense, its splits, items and tags are written together, so a failure part-way
+    // can't leave a partial expense behind
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Insert expense
+    sqlx::query(
+        "INSERT INTO expenses (id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, trip_id, refund_of) 
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)"
+    )
+    .bind(expense_id)
+    .bind(auth.group_id)
+    .bind(&request.description)
+    .bind(&amount)
+    .bind(request.paid_by)
+    .bind(expense_type)
+    .bind(request.transfer_to)
+    .bind(&currency)
+    .bind(&exchange_rate_val)
+    .bind(expense_date)
+    .bind(created_at)
+    .bind(&split_type)
+    .bind(&request.notes)
+    .bind(&receipt_url)
+    .bind(created_by)
+    .bind(request.trip_id)
+    .bind(request.refund_of)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to create expense: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Insert expense splits (none for transfers)
+    for split in &split_rows {
+        sqlx::query("INSERT INTO expense_splits (expense_id, member_id, share) VALUES ($1, $2, $3)")
+            .bind(expense_id)
+            .bind(split.member_id)
+            .bind(&split.share)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to create expense split: {}", e);
+                db::error_status(&e)
+            })?;
+    }
+
+    if let Some(items) = &items {
+        insert_items(&mut tx, expense_id, items).await?;
+    }
+    insert_tags(&mut *tx, expense_id, &tags).await?;
+
+    let split_entries: Option<Vec<SplitEntry>> = if split_type != "equal" {
+        splits
+    } else {
+        None
+    };
+
+    // Update last_activity_at
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit expense: {}", e);
+        db::error_status(&e)
+    })?;
+
+    if notifications::enabled() && expense_type != ExpenseType::Transfer {
+        rocket::tokio::spawn(notify_expense_members(auth.group_id, expense_id));
+    }
+
+    let expense = Expense {
+        id: expense_id,
+        group_id: auth.group_id,
+        description: request.description.clone(),
+        amount: amount_value,
+        paid_by: request.paid_by,
+        split_between,
+        expense_type,
+        transfer_to: request.transfer_to,
+        currency,
+        exchange_rate,
+        expense_date,
+        created_at,
+        split_type,
+        splits: split_entries,
+        notes: request.notes.clone(),
+        receipt_url,
+        created_by,
+        updated_by: None,
+        tags,
+        items,
+        trip_id: request.trip_id,
+        refund_of: request.refund_of,
+        amount_minor: None,
+        settled_shares: Vec::new(),
+    };
+
+    webhooks::dispatch(
+        auth.group_id,
+        webhooks::EXPENSE_CREATED,
+        serde_json::to_value(&expense).unwrap_or_default(),
+    );
+
+    activity::record(&auth, activity::EXPENSE_CREATED, expense.id, None, Some(&expense)).await;
+
+    Ok(Json(expense))
+}
+
+// Preview how an expense would change each member's balance, without saving it -
+// requires valid JWT + add_expenses permission
+#[post("/groups/current/expenses/preview", data = "<request>")]
+async fn preview_expense(
+    auth: GroupAuth,
+    request: Json<CreateExpenseRequest>,
+) -> Result<Json<Vec<BalanceChange>>, ApiError> {
+    if !may_record(&auth.permissions, &request.expense_type) {
+        return Err(Status::Forbidden.into());
+    }
+    let prepared = prepare_expense(&auth, &request).await?;
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let split_rows = prepared.split_rows();
+    let row = ExpenseRow {
+        id: Uuid::new_v4(),
+        group_id: auth.group_id,
+        description: request.description.clone(),
+        amount: prepared.amount,
+        paid_by: request.paid_by,
+        expense_type: prepared.expense_type,
+        transfer_to: request.transfer_to,
+        currency: prepared.currency,
+        exchange_rate: prepared.exchange_rate_val,
+        expense_date: prepared.expense_date,
+        created_at: Utc::now(),
+        split_type: prepared.split_type,
+        notes: request.notes.clone(),
+        receipt_url: prepared.receipt_url,
+        created_by: prepared.created_by,
+        updated_by: None,
+        trip_id: request.trip_id,
+        refund_of: request.refund_of,
+    };
+    let deltas = balance_deltas(&row, &split_rows, decimals);
+
+    let changes = cached_balances(auth.group_id)
+        .await?
+        .into_iter()
+        .map(|b| {
+            let delta = currency::round_half_even(
+                deltas
+                    .iter()
+                    .filter(|(member_id, _)| *member_id == b.user_id)
+                    .map(|(_, d)| d)
+                    .sum(),
+                decimals,
+            );
+            BalanceChange {
+                user_id: b.user_id,
+                user_name: b.user_name,
+                delta,
+                balance_before: b.balance,
+                balance_after: currency::round_half_even(b.balance + delta, decimals),
+            }
+        })
+        .collect();
+    Ok(Json(changes))
+}
+
+/// The member a member-bound token acts as, if that member is still in the group.
+async fn acting_member(auth: &GroupAuth) -> Result<Option<Uuid>, Status> {
+    let Some(member_id) = auth.member_id else {
+        return Ok(None);
+    };
+    sqlx::query_scalar("SELECT id FROM members WHERE id = $1 AND group_id = $2")
+        .bind(member_id)
+        .bind(auth.group_id)
+        .fetch_optional(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch acting member: {}", e);
+            db::error_status(&e)
+        })
+}
+
+/// Maximum length (in characters) of a receipt URL.
+const MAX_RECEIPT_URL_LEN: usize = 2048;
+
+/// Trim a receipt link and check it is an absolute http(s) URL of at most 2048 characters.
+fn validate_receipt_url(url: &str) -> Result<String, Status> {
+    let url = url.trim();
+    if url.chars().count() > MAX_RECEIPT_URL_LEN {
+        return Err(Status::BadRequest);
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|_| Status::BadRequest)?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(Status::BadRequest);
+    }
+    Ok(url.to_string())
+}
+
+/// Check line items: at least one, positive amounts, and each assigned to
+/// (deduplicated) members of the group.
+async fn validate_items(group_id: Uuid, items: &[ExpenseItem]) -> Result<Vec<ExpenseItem>, Status> {
+    if items.is_empty() || items.len() > *MAX_SPLIT_MEMBERS {
+        return Err(Status::BadRequest);
+    }
+    let member_ids: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM members WHERE group_id = $1")
+        .bind(group_id)
+        .fetch_all(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch members: {}", e);
+            db::error_status(&e)
+        })?;
+
+    let mut validated = Vec::with_capacity(items.len());
+    for item in items {
+        if !item.amount.is_finite() || item.amount <= 0.0 || item.amount > MAX_AMOUNT {
+            return Err(Status::BadRequest);
+        }
+        let mut assigned: Vec<Uuid> = Vec::new();
+        for id in &item.member_ids {
+            if !member_ids.contains(id) {
+                return Err(Status::BadRequest);
+            }
+            if !assigned.contains(id) {
+                assigned.push(*id);
+            }
+        }
+        if assigned.is_empty() {
+            return Err(Status::BadRequest);
+        }
+        validated.push(ExpenseItem {
+            description: item.description.trim().chars().take(255).collect(),
+            amount: item.amount,
+            member_ids: assigned,
+        });
+    }
+    Ok(validated)
+}
+
+/// Exact per-member shares from line items: each item is split equally among its
+/// assigned members. Members are listed in order of first appearance.
+fn item_shares(items: &[ExpenseItem]) -> Vec<SplitEntry> {
+    let mut shares: Vec<SplitEntry> = Vec::new();
+    for item in items {
+        let per_member = item.amount / item.member_ids.len() as f64;
+        for member_id in &item.member_ids {
+            match shares.iter_mut().find(|s| s.member_id == *member_id) {
+                Some(entry) => entry.share = Some(entry.share.unwrap_or(0.0) + per_member),
+                None => shares.push(SplitEntry {
+                    member_id: *member_id,
+                    share: Some(per_member),
+                }),
+            }
+        }
+    }
+    // Match the precision of expense_splits.share
+    for entry in &mut shares {
+        entry.share = entry.share.map(|v| (v * 10_000.0).round() / 10_000.0);
+    }
+    shares
+}
+
+async fn insert_items(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    expense_id: Uuid,
+    items: &[ExpenseItem],
+) -> Result<(), Status> {
+    for (position, item) in items.iter().enumerate() {
+        let amount = BigDecimal::try_from(item.amount).map_err(|_| Status::BadRequest)?;
+        sqlx::query(
+            "INSERT INTO expense_items (expense_id, position, description, amount, member_ids) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(expense_id)
+        .bind(position as i32)
+        .bind(&item.description)
+        .bind(&amount)
+        .bind(&item.member_ids)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to create expense item: {}", e);
+            db::error_status(&e)
+        })?;
+    }
+    Ok(())
+}
+
+/// Line items of an expense, or `None` if it isn't itemized.
+async fn fetch_items(expense_id: Uuid) -> Result<Option<Vec<ExpenseItem>>, Status> {
+    let rows: Vec<ExpenseItemRow> = sqlx::query_as(
+        "SELECT description, amount, member_ids FROM expense_items WHERE expense_id = $1 ORDER BY position",
+    )
+    .bind(expense_id)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense items: {}", e);
+        db::error_status(&e)
+    })?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        rows.into_iter()
+            .map(|r| ExpenseItem {
+                description: r.description,
+                amount: r.amount.to_f64().unwrap_or(0.0),
+                member_ids: r.member_ids,
+            })
+            .collect(),
+    ))
+}
+
+/// Maximum number of tags per expense and length (in characters) of one tag.
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LEN: usize = 50;
+
+/// Normalize tags: trim, drop a leading `#`, lowercase, skip blanks and duplicates,
+/// and sort them the way they are returned.
+fn normalize_tags(tags: &[String]) -> Result<Vec<String>, Status> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').trim().to_lowercase();
+        if tag.is_empty() || normalized.contains(&tag) {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_LEN {
+            return Err(Status::BadRequest);
+        }
+        normalized.push(tag);
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(Status::BadRequest);
+    }
+    normalized.sort();
+    Ok(normalized)
+}
+
+async fn insert_tags<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    expense_id: Uuid,
+    tags: &[String],
+) -> Result<(), Status> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    sqlx::query("INSERT INTO expense_tags (expense_id, tag) SELECT $1, unnest($2::text[])")
+        .bind(expense_id)
+        .bind(tags)
+        .execute(executor)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to save expense tags: {}", e);
+            db::error_status(&e)
+        })?;
+    Ok(())
+}
+
+/// Replace all tags of an expense.
+async fn replace_tags(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    expense_id: Uuid,
+    tags: &[String],
+) -> Result<(), Status> {
+    sqlx::query("DELETE FROM expense_tags WHERE expense_id = $1")
+        .bind(expense_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to delete expense tags: {}", e);
+            db::error_status(&e)
+        })?;
+    insert_tags(&mut **tx, expense_id, tags).await
+}
+
+/// Tags of an expense, alphabetically.
+async fn fetch_tags(expense_id: Uuid) -> Result<Vec<String>, Status> {
+    sqlx::query_scalar("SELECT tag FROM expense_tags WHERE expense_id = $1 ORDER BY tag")
+        .bind(expense_id)
+        .fetch_all(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch expense tags: {}", e);
+            db::error_status(&e)
+        })
+}
+
+/// Email every member of an expense's split who opted into notifications.
+/// Runs in the background; errors are only logged.
+async fn notify_expense_members(group_id: Uuid, expense_id: Uuid) {
+    let pool = db::get_pool();
+    let loaded = async {
+        let group_name: String = sqlx::query_scalar("SELECT name FROM groups WHERE id = $1")
+            .bind(group_id)
+            .fetch_one(pool)
+            .await?;
+        let expense: ExpenseRow = sqlx::query_as(
+            "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+             FROM expenses WHERE id = $1"
+        )
+        .bind(expense_id)
+        .fetch_one(pool)
+        .await?;
+        let members: Vec<MemberRow> = sqlx::query_as(
+            "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1"
+        )
+        .bind(group_id)
+        .fetch_all(pool)
+        .await?;
+        Ok::<_, sqlx::Error>((group_name, expense, members))
+    };
+    let (group_name, expense, members) = match loaded.await {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Failed to load expense notification data: {}", e);
+            return;
+        }
+    };
+    let Ok(splits) = fetch_splits(expense_id).await else {
+        return;
+    };
+
+    let paid_by_name = members
+        .iter()
+        .find(|m| m.id == expense.paid_by)
+        .map(|m| m.name.clone())
+        .unwrap_or_default();
+    let exchange_rate = expense.exchange_rate.to_f64().unwrap_or(1.0);
+
+    for (member_id, share) in member_shares(&expense, &splits) {
+        let Some(member) = members.iter().find(|m| m.id == member_id) else {
+            continue;
+        };
+        let Some(email) = member.email.clone().filter(|_| member.notify_on_expense) else {
+            continue;
+        };
+        notifications::send_expense_notification(notifications::ExpenseNotification {
+            recipient_name: member.name.clone(),
+            recipient_email: email,
+            group_name: group_name.clone(),
+            description: expense.description.clone(),
+            paid_by_name: paid_by_name.clone(),
+            amount: expense.amount.to_f64().unwrap_or(0.0),
+            // Shares are computed in group currency; report them in the expense currency
+            share: share / exchange_rate,
+            currency: expense.currency.clone(),
+        })
+        .await;
+    }
+}
+
+// Update expense - requires valid JWT + edit_expenses permission
+#[put("/groups/current/expenses/<expense_id>", data = "<request>")]
+async fn update_expense(
+    auth: GroupAuth,
+    _writable: Writable,
+    expense_id: &str,
+    request: Json<UpdateExpenseRequest>,
+) -> Result<Json<Expense>, ApiError> {
+    if !auth.permissions.has_edit_expenses() {
+        return Err(Status::Forbidden.into());
+    }
+    let pool = db::get_pool();
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+
+    // Verify expense belongs to this group
+    let existing: ExpenseRow = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE id = $1 AND group_id = $2"
+    )
+    .bind(expense_uuid)
+    .bind(auth.group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+    let before = full_expense(existing.clone()).await?;
+    let mut request = request.into_inner();
+    if let Some(adjustments) = request.adjustments.take() {
+        request.split_type = "adjustment".to_string();
+        request.splits = Some(adjustment_splits(&adjustments));
+    }
+
+    // created_at is intentionally never updated: it records when the expense was first entered.
+    let expense_type = ExpenseType::parse(&request.expense_type).ok_or(Status::BadRequest)?;
+    let amount = amount_decimal(request.amount)?;
+    let expense_date = request.expense_date.unwrap_or(existing.expense_date);
+    if expense_date != existing.expense_date {
+        validate_expense_date(auth.group_id, expense_date).await?;
+    }
+    let currency = request.currency.clone().unwrap_or(existing.currency.clone());
+    let exchange_rate_val = exchange_rate_decimal(
+        request
+            .exchange_rate
+            .unwrap_or(existing.exchange_rate.to_f64().unwrap_or(1.0)),
+    )?;
+    let receipt_url = match request.receipt_url.as_deref() {
+        Some(url) => Some(validate_receipt_url(url)?),
+        None => existing.receipt_url.clone(),
+    };
+    let updated_by = acting_member(&auth).await?;
+    let tags = request.tags.as_deref().map(normalize_tags).transpose()?;
+
+    let mut split_between = sanitize_split_between(auth.group_id, &request.split_between).await?;
+    if request.exclude_payer.unwrap_or(false) {
+        split_between.retain(|id| *id != request.paid_by);
+    }
+    if expense_type == ExpenseType::Transfer {
+        split_between.clear();
+    } else if split_between.is_empty() {
+        return Err(Status::BadRequest.into());
+    }
+    validate_income(expense_type, request.amount, split_between.len())?;
+    let values = split_values(&split_between, request.splits.as_deref());
+    validate_income_split(expense_type, request.amount, &request.split_type, &values)?;
+    if request.split_type == "adjustment" && expense_type != ExpenseType::Transfer {
+        validate_adjustments(request.amount, &split_between, &split_extras(request.splits.as_deref()))?;
+    }
+    validate_refund_edit(
+        auth.group_id,
+        &ExpenseRow {
+            expense_type,
+            amount: amount.clone(),
+            exchange_rate: exchange_rate_val.clone(),
+            ..existing.clone()
+        },
+    )
+    .await?;
+    let involved: Vec<Uuid> = split_between
+        .iter()
+        .copied()
+        .chain(std::iter::once(request.paid_by))
+        .chain(request.transfer_to)
+        .collect();
+    ensure_group_members(auth.group_id, &involved).await?;
+    let trip_id = request.trip_id.or(existing.trip_id);
+    ensure_group_trip(auth.group_id, request.trip_id).await?;
+
+    // Rewrite the expense and its splits atomically, so a failure part-way can't
+    // leave the expense without splits
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query(
+        "UPDATE expenses SET description = $1, amount = $2, paid_by = $3, expense_type = $4, transfer_to = $5, currency = $6, exchange_rate = $7, expense_date = $8, split_type = $9, notes = $10, receipt_url = $11, updated_by = $12, trip_id = $13
+         WHERE id = $14 AND group_id = $15"
+    )
+    .bind(&request.description)
+    .bind(&amount)
+    .bind(request.paid_by)
+    .bind(expense_type)
+    .bind(request.transfer_to)
+    .bind(&currency)
+    .bind(&exchange_rate_val)
+    .bind(expense_date)
+    .bind(&request.split_type)
+    .bind(&request.notes)
+    .bind(&receipt_url)
+    .bind(updated_by)
+    .bind(trip_id)
+    .bind(expense_uuid)
+    .bind(auth.group_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to update expense: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Delete old splits and re-insert
+    sqlx::query("DELETE FROM expense_splits WHERE expense_id = $1")
+        .bind(expense_uuid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to delete expense splits: {}", e);
+            db::error_status(&e)
+        })?;
+
+    // A full update redefines the split, so any line items no longer apply
+    sqlx::query("DELETE FROM expense_items WHERE expense_id = $1")
+        .bind(expense_uuid)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to delete expense items: {}", e);
+            db::error_status(&e)
+        })?;
+
+    if expense_type != ExpenseType::Transfer {
+        for member_id in &split_between {
+            let share_val: Option<BigDecimal> = request.splits.as_ref().and_then(|splits| {
+                splits
+                    .iter()
+                    .find(|s| &s.member_id == member_id)
+                    .and_then(|s| s.share.and_then(|v| BigDecimal::try_from(v).ok()))
+            });
+            sqlx::query(
+                "INSERT INTO expense_splits (expense_id, member_id, share) VALUES ($1, $2, $3)",
+            )
+            .bind(expense_uuid)
+            .bind(member_id)
+            .bind(&share_val)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to create expense split: {}", e);
+                db::error_status(&e)
+            })?;
+        }
+    }
+
+    let split_entries: Option<Vec<SplitEntry>> = if request.split_type != "equal" {
+        request.splits.clone()
+    } else {
+        None
+    };
+
+    // Update last_activity_at
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    if let Some(tags) = &tags {
+        replace_tags(&mut tx, expense_uuid, tags).await?;
+    }
+
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit expense update: {}", e);
+        db::error_status(&e)
+    })?;
+    let tags = match tags {
+        Some(tags) => tags,
+        None => fetch_tags(expense_uuid).await?,
+    };
+
+    let expense = Expense {
+        id: expense_uuid,
+        group_id: auth.group_id,
+        description: request.description.clone(),
+        amount: amount.to_f64().unwrap_or(request.amount),
+        paid_by: request.paid_by,
+        split_between,
+        expense_type,
+        transfer_to: request.transfer_to,
+        currency,
+        exchange_rate: exchange_rate_val.to_f64().unwrap_or(1.0),
+        expense_date,
+        created_at: existing.created_at,
+        split_type: request.split_type.clone(),
+        splits: split_entries,
+        notes: request.notes.clone(),
+        receipt_url,
+        created_by: existing.created_by,
+        updated_by,
+        tags,
+        items: None,
+        trip_id,
+        refund_of: existing.refund_of,
+        amount_minor: None,
+        settled_shares: Vec::new(),
+    };
+
+    activity::record(
+        &auth,
+        activity::EXPENSE_UPDATED,
+        expense_uuid,
+        Some(&before),
+        Some(&expense),
+    )
+    .await;
+
+    Ok(Json(expense))
+}
+
+// Partially update an expense - requires valid JWT + edit_expenses permission
+#[patch("/groups/current/expenses/<expense_id>", data = "<request>")]
+async fn patch_expense(
+    auth: GroupAuth,
+    _writable: Writable,
+    expense_id: &str,
+    request: Json<PatchExpenseRequest>,
+) -> Result<Json<Expense>, ApiError> {
+    if !auth.permissions.has_edit_expenses() {
+        return Err(Status::Forbidden.into());
+    }
+    let pool = db::get_pool();
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+    let mut request = request.into_inner();
+
+    let existing: ExpenseRow = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE id = $1 AND group_id = $2"
+    )
+    .bind(expense_uuid)
+    .bind(auth.group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+    let before = full_expense(existing.clone()).await?;
+    let existing_splits = fetch_splits(expense_uuid).await?;
+    let tags = request.tags.as_deref().map(normalize_tags).transpose()?;
+    if let Some(adjustments) = request.adjustments.take() {
+        request.split_type = Some("adjustment".to_string());
+        request.splits = Some(adjustment_splits(&adjustments));
+    }
+
+    let amount = match request.amount {
+        Some(a) => amount_decimal(a)?,
+        None => existing.amount.clone(),
+    };
+    let exchange_rate = match request.exchange_rate {
+        Some(r) => exchange_rate_decimal(r)?,
+        None => existing.exchange_rate.clone(),
+    };
+    if let Some(date) = request.expense_date
+        && date != existing.expense_date
+    {
+        validate_expense_date(auth.group_id, date).await?;
+    }
+    if let Some(trip_id) = request.trip_id {
+        ensure_group_trip(auth.group_id, trip_id).await?;
+    }
+    let updated = ExpenseRow {
+        description: request.description.unwrap_or(existing.description),
+        amount,
+        paid_by: request.paid_by.unwrap_or(existing.paid_by),
+        expense_type: match request.expense_type.as_deref() {
+            Some(t) => ExpenseType::parse(t).ok_or(Status::BadRequest)?,
+            None => existing.expense_type,
+        },
+        transfer_to: request.transfer_to.unwrap_or(existing.transfer_to),
+        currency: request.currency.unwrap_or(existing.currency),
+        exchange_rate,
+        expense_date: request.expense_date.unwrap_or(existing.expense_date),
+        split_type: request.split_type.unwrap_or(existing.split_type),
+        notes: request.notes.unwrap_or(existing.notes),
+        receipt_url: match request.receipt_url {
+            Some(url) => url.as_deref().map(validate_receipt_url).transpose()?,
+            None => existing.receipt_url,
+        },
+        updated_by: acting_member(&auth).await?,
+        trip_id: request.trip_id.unwrap_or(existing.trip_id),
+        ..existing
+    };
+    validate_refund_edit(auth.group_id, &updated).await?;
+
+    // Splits are only rewritten when the split members or shares were sent
+    // (or the expense became a transfer, which has no splits).
+    let new_splits: Option<Vec<ExpenseSplitMemberRow>> = if updated.expense_type == ExpenseType::Transfer {
+        Some(Vec::new())
+    } else if request.split_between.is_some() || request.splits.is_some() {
+        let members: Vec<Uuid> = match &request.split_between {
+            Some(ids) => sanitize_split_between(auth.group_id, ids).await?,
+            None => existing_splits.iter().map(|s| s.member_id).collect(),
+        };
+        if members.is_empty() {
+            return Err(Status::BadRequest.into());
+        }
+        Some(
+            members
+                .into_iter()
+                .map(|member_id| {
+                    let share = match &request.splits {
+                        Some(splits) => splits
+                            .iter()
+                            .find(|s| s.member_id == member_id)
+                            .and_then(|s| s.share.and_then(|v| BigDecimal::try_from(v).ok())),
+                        None => existing_splits
+                            .iter()
+                            .find(|s| s.member_id == member_id)
+                            .and_then(|s| s.share.clone()),
+                    };
+                    ExpenseSplitMemberRow {
+                        member_id,
+                        share,
+                        settled: false,
+                    }
+                })
+                .collect(),
+        )
+    } else {
+        None
+    };
+    validate_income(
+        updated.expense_type,
+        updated.amount.to_f64().unwrap_or(0.0),
+        new_splits.as_ref().map_or(existing_splits.len(), Vec::len),
+    )?;
+    let values: Vec<Option<f64>> = new_splits
+        .as_ref()
+        .unwrap_or(&existing_splits)
+        .iter()
+        .map(|s| s.share.as_ref().and_then(|v| v.to_f64()))
+        .collect();
+    validate_income_split(
+        updated.expense_type,
+        updated.amount.to_f64().unwrap_or(0.0),
+        &updated.split_type,
+        &values,
+    )?;
+    // Members named in the patch must be in this group, like on create and update
+    let involved: Vec<Uuid> = new_splits
+        .iter()
+        .flatten()
+        .map(|s| s.member_id)
+        .chain(request.paid_by)
+        .chain(request.transfer_to.flatten())
+        .collect();
+    ensure_group_members(auth.group_id, &involved).await?;
+    if updated.split_type == "adjustment" && updated.expense_type != ExpenseType::Transfer {
+        let rows = new_splits.as_ref().unwrap_or(&existing_splits);
+        let members: Vec<Uuid> = rows.iter().map(|s| s.member_id).collect();
+        let extras = match &request.splits {
+            Some(splits) => split_extras(Some(splits)),
+            None => rows
+                .iter()
+                .map(|s| (s.member_id, s.share.as_ref().and_then(|v| v.to_f64()).unwrap_or(0.0)))
+                .collect(),
+        };
+        validate_adjustments(updated.amount.to_f64().unwrap_or(0.0), &members, &extras)?;
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query(
+        "UPDATE expenses SET description = $1, amount = $2, paid_by = $3, expense_type = $4, transfer_to = $5, currency = $6, exchange_rate = $7, expense_date = $8, split_type = $9, notes = $10, receipt_url = $11, updated_by = $12, trip_id = $13
+         WHERE id = $14 AND group_id = $15"
+    )
+    .bind(&updated.description)
+    .bind(&updated.amount)
+    .bind(updated.paid_by)
+    .bind(updated.expense_type)
+    .bind(updated.transfer_to)
+    .bind(&updated.currency)
+    .bind(&updated.exchange_rate)
+    .bind(updated.expense_date)
+    .bind(&updated.split_type)
+    .bind(&updated.notes)
+    .bind(&updated.receipt_url)
+    .bind(updated.updated_by)
+    .bind(updated.trip_id)
+    .bind(expense_uuid)
+    .bind(auth.group_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to update expense: {}", e);
+        db::error_status(&e)
+    })?;
+
+    if let Some(splits) = &new_splits {
+        sqlx::query("DELETE FROM expense_splits WHERE expense_id = $1")
+            .bind(expense_uuid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to delete expense splits: {}", e);
+                db::error_status(&e)
+            })?;
+        for split in splits {
+            sqlx::query(
+                "INSERT INTO expense_splits (expense_id, member_id, share) VALUES ($1, $2, $3)",
+            )
+            .bind(expense_uuid)
+            .bind(split.member_id)
+            .bind(&split.share)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to create expense split: {}", e);
+                db::error_status(&e)
+            })?;
+        }
+    }
+
+    // Line items no longer describe the expense once its amount or split changes
+    let items_stale = new_splits.is_some() || request.amount.is_some();
+    if items_stale {
+        sqlx::query("DELETE FROM expense_items WHERE expense_id = $1")
+            .bind(expense_uuid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to delete expense items: {}", e);
+                db::error_status(&e)
+            })?;
+        // Nor do shares settled at the old amount (rewritten splits start unsettled)
+        sqlx::query("UPDATE expense_splits SET settled = FALSE, settled_at = NULL WHERE expense_id = $1 AND settled")
+            .bind(expense_uuid)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to reset settled shares: {}", e);
+                db::error_status(&e)
+            })?;
+    }
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    if let Some(tags) = &tags {
+        replace_tags(&mut tx, expense_uuid, tags).await?;
+    }
+
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit expense patch: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let mut expense = expense_from_row(updated, new_splits.unwrap_or(existing_splits));
+    if items_stale {
+        expense.settled_shares.clear();
+    } else {
+        expense.items = fetch_items(expense_uuid).await?;
+    }
+    expense.tags = match tags {
+        Some(tags) => tags,
+        None => fetch_tags(expense_uuid).await?,
+    };
+
+    activity::record(
+        &auth,
+        activity::EXPENSE_UPDATED,
+        expense_uuid,
+        Some(&before),
+        Some(&expense),
+    )
+    .await;
+
+    Ok(Json(expense))
+}
+
+// Duplicate an expense ("same again") - requires valid JWT + add_expenses permission
+// (settle for transfers)
+#[post("/groups/current/expenses/<expense_id>/duplicate", data = "<request>")]
+async fn duplicate_expense(
+    auth: GroupAuth,
+    _writable: Writable,
+    expense_id: &str,
+    request: Option<Json<DuplicateExpenseRequest>>,
+) -> Result<Json<Expense>, ApiError> {
+    if !auth.permissions.has_add_expenses() && !auth.permissions.has_settle() {
+        return Err(Status::Forbidden.into());
+    }
+    let pool = db::get_pool();
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+    ensure_expense_capacity(auth.group_id).await?;
+
+    let source: ExpenseRow = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE id = $1 AND group_id = $2"
+    )
+    .bind(expense_uuid)
+    .bind(auth.group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+    if !may_record(&auth.permissions, source.expense_type.as_str()) {
+        return Err(Status::Forbidden.into());
+    }
+    if source.refund_of.is_some() {
+        return Err(ApiError::bad_request("Refunds can't be duplicated; record a new refund instead"));
+    }
+    let splits = fetch_splits(source.id).await?;
+    let expense_date = request.and_then(|r| r.expense_date);
+    if let Some(date) = expense_date {
+        validate_expense_date(auth.group_id, date).await?;
+    }
+
+    let new_row = ExpenseRow {
+        id: Uuid::new_v4(),
+        expense_date: expense_date.unwrap_or_else(|| Utc::now().date_naive()),
+        created_at: Utc::now(),
+        // The copy is a new purchase, so the original's receipt doesn't apply
+        receipt_url: None,
+        created_by: acting_member(&auth).await?,
+        updated_by: None,
+        ..source
+    };
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query(
+        "INSERT INTO expenses (id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, trip_id) 
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)"
+    )
+    .bind(new_row.id)
+    .bind(new_row.group_id)
+    .bind(&new_row.description)
+    .bind(&new_row.amount)
+    .bind(new_row.paid_by)
+    .bind(new_row.expense_type)
+    .bind(new_row.transfer_to)
+    .bind(&new_row.currency)
+    .bind(&new_row.exchange_rate)
+    .bind(new_row.expense_date)
+    .bind(new_row.created_at)
+    .bind(&new_row.split_type)
+    .bind(&new_row.notes)
+    .bind(&new_row.receipt_url)
+    .bind(new_row.created_by)
+    .bind(new_row.trip_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to duplicate expense: {}", e);
+        db::error_status(&e)
+    })?;
+
+    for split in &splits {
+        sqlx::query("INSERT INTO expense_splits (expense_id, member_id, share) VALUES ($1, $2, $3)")
+            .bind(new_row.id)
+            .bind(split.member_id)
+            .bind(&split.share)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to duplicate expense split: {}", e);
+                db::error_status(&e)
+            })?;
+    }
+
+    sqlx::query(
+        "INSERT INTO expense_items (expense_id, position, description, amount, member_ids)
+         SELECT $1, position, description, amount, member_ids FROM expense_items WHERE expense_id = $2",
+    )
+    .bind(new_row.id)
+    .bind(source.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to duplicate expense items: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query(
+        "INSERT INTO expense_tags (expense_id, tag) SELECT $1, tag FROM expense_tags WHERE expense_id = $2",
+    )
+    .bind(new_row.id)
+    .bind(source.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to duplicate expense tags: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit duplicated expense: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let mut expense = expense_from_row(new_row, splits);
+    expense.items = fetch_items(expense.id).await?;
+    expense.tags = fetch_tags(expense.id).await?;
+
+    activity::record(&auth, activity::EXPENSE_CREATED, expense.id, None, Some(&expense)).await;
+
+    Ok(Json(expense))
+}
+
+// Tags used in the group with the number of expenses carrying each - requires valid JWT
+#[get("/groups/current/tags")]
+async fn get_tags(auth: GroupAuth) -> Result<Json<Vec<TagCount>>, Status> {
+    let tags: Vec<TagCount> = sqlx::query_as(
+        "SELECT t.tag, COUNT(*) AS count FROM expense_tags t
+         JOIN expenses e ON e.id = t.expense_id
+         WHERE e.group_id = $1
+         GROUP BY t.tag ORDER BY count DESC, t.tag",
+    )
+    .bind(auth.group_id)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch tags: {}", e);
+        db::error_status(&e)
+    })?;
+    Ok(Json(tags))
+}
+
+// Spend per category in the group currency - requires valid JWT.
+// Transfers are left out. An expense counts toward each of its tags, so with
+// multi-tagged expenses the category totals add up to more than the overall spend.
+// `from`/`to` (YYYY-MM-DD, inclusive) restrict the expense dates.
+#[get("/groups/current/stats/by-category?<from>&<to>")]
+async fn get_stats_by_category(
+    auth: GroupAuth,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Json<Vec<CategoryTotal>>, Status> {
+    let parse_date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| Status::BadRequest);
+    let from = from.map(parse_date).transpose()?;
+    let to = to.map(parse_date).transpose()?;
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        return Err(Status::BadRequest);
+    }
+
+    let mut totals: Vec<CategoryTotal> = sqlx::query_as(
+        "SELECT t.tag AS name,
+                COALESCE(SUM(e.amount * e.exchange_rate) FILTER (WHERE e.expense_type = 'expense'), 0)::float8 AS total,
+                COUNT(*) FILTER (WHERE e.expense_type = 'expense') AS expense_count,
+                COALESCE(SUM(e.amount * e.exchange_rate) FILTER (WHERE e.expense_type = 'income'), 0)::float8 AS income,
+                COUNT(*) FILTER (WHERE e.expense_type = 'income') AS income_count
+         FROM expenses e LEFT JOIN expense_tags t ON t.expense_id = e.id
+         WHERE e.group_id = $1 AND e.expense_type <> 'transfer'
+           AND ($2::date IS NULL OR e.expense_date >= $2)
+           AND ($3::date IS NULL OR e.expense_date <= $3)
+         GROUP BY t.tag ORDER BY total DESC, income DESC, t.tag NULLS LAST",
+    )
+    .bind(auth.group_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch category totals: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let code = group_currency(auth.group_id).await?;
+    for t in &mut totals {
+        t.total = currency::round_amount(t.total, &code);
+        t.income = currency::round_amount(t.income, &code);
+    }
+    Ok(Json(totals))
+}
+
+// Per-member breakdown of the balance into paid, spent, income and transfers -
+// requires valid JWT. Uses the same rounding as the balances, so they agree.
+#[get("/groups/current/stats/members")]
+async fn get_member_stats(auth: GroupAuth) -> Result<Json<Vec<MemberStats>>, Status> {
+    let pool = db::get_pool();
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let members: Vec<(Uuid, String)> =
+        sqlx::query_as("SELECT id, name FROM members WHERE group_id = $1 ORDER BY display_order, id")
+            .bind(auth.group_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch members: {}", e);
+                db::error_status(&e)
+            })?;
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1"
+    )
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let mut stats: Vec<MemberStats> = members
+        .into_iter()
+        .map(|(member_id, name)| MemberStats {
+            member_id,
+            name,
+            paid: 0.0,
+            spent: 0.0,
+            income_collected: 0.0,
+            income_share: 0.0,
+            transfers_sent: 0.0,
+            transfers_received: 0.0,
+            balance: 0.0,
+        })
+        .collect();
+    for row in expense_rows {
+        let splits = if row.expense_type == ExpenseType::Transfer {
+            Vec::new()
+        } else {
+            fetch_splits(row.id).await?
+        };
+        // Same amounts as balance_deltas: unsplit expenses and income count for nobody
+        let amount = currency::round_half_even(expense_in_group_currency(&row), decimals);
+        let shares = currency::round_shares(amount, member_shares(&row, &splits), decimals);
+        let member = |stats: &[MemberStats], member_id: Uuid| {
+            stats.iter().position(|m| m.member_id == member_id)
+        };
+        match row.expense_type {
+            ExpenseType::Transfer => {
+                if let Some(i) = member(&stats, row.paid_by) {
+                    stats[i].transfers_sent += amount;
+                }
+                if let Some(i) = row.transfer_to.and_then(|to_id| member(&stats, to_id)) {
+                    stats[i].transfers_received += amount;
+                }
+            }
+            _ if splits.is_empty() => {}
+            ExpenseType::Income => {
+                if let Some(i) = member(&stats, row.paid_by) {
+                    stats[i].income_collected += amount;
+                }
+                for (member_id, share) in shares {
+                    if let Some(i) = member(&stats, member_id) {
+                        stats[i].income_share += share;
+                    }
+                }
+            }
+            ExpenseType::Expense => {
+                if let Some(i) = member(&stats, row.paid_by) {
+                    stats[i].paid += amount;
+                }
+                for (member_id, share) in shares {
+                    if let Some(i) = member(&stats, member_id) {
+                        stats[i].spent += share;
+                    }
+                }
+            }
+        }
+    }
+
+    for m in &mut stats {
+        let round = |v: f64| currency::round_half_even(v, decimals);
+        m.balance = round(
+            m.paid - m.spent - m.income_collected + m.income_share + m.transfers_sent
+                - m.transfers_received,
+        );
+        m.paid = round(m.paid);
+        m.spent = round(m.spent);
+        m.income_collected = round(m.income_collected);
+        m.income_share = round(m.income_share);
+        m.transfers_sent = round(m.transfers_sent);
+        m.transfers_received = round(m.transfers_received);
+    }
+    Ok(Json(stats))
+}
+
+/// How many standard deviations above the mean an expense must be to count as
+/// an anomaly, unless the request passes `k`.
+const DEFAULT_ANOMALY_K: f64 = 2.0;
+
+// Unusually large expenses, e.g. an amount typed with an extra zero - requires valid JWT
+#[get("/groups/current/stats/anomalies?<k>")]
+async fn get_anomalies(auth: GroupAuth, k: Option<f64>) -> Result<Json<AnomalyReport>, Status> {
+    let k = k.unwrap_or(DEFAULT_ANOMALY_K);
+    if !(k.is_finite() && k >= 0.0) {
+        return Err(Status::BadRequest);
+    }
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1 AND expense_type = 'expense'"
+    )
+    .bind(auth.group_id)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let amounts: Vec<f64> = rows.iter().map(expense_in_group_currency).collect();
+    let count = amounts.len() as f64;
+    let (mean, std_dev) = if amounts.is_empty() {
+        (0.0, 0.0)
+    } else {
+        let mean = amounts.iter().sum::<f64>() / count;
+        let variance = amounts.iter().map(|a| (a - mean).powi(2)).sum::<f64>() / count;
+        (mean, variance.sqrt())
+    };
+    let threshold = mean + k * std_dev;
+
+    let mut anomalies = Vec::new();
+    // With no spread at all nothing stands out
+    if std_dev > 0.0 {
+        for (row, amount) in rows.into_iter().zip(&amounts) {
+            if *amount > threshold {
+                anomalies.push(Anomaly {
+                    expense: full_expense(row).await?,
+                    amount: currency::round_half_even(*amount, decimals),
+                    z_score: ((amount - mean) / std_dev * 100.0).round() / 100.0,
+                });
+            }
+        }
+    }
+    anomalies.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+
+    Ok(Json(AnomalyReport {
+        expense_count: amounts.len(),
+        mean: currency::round_half_even(mean, decimals),
+        std_dev: currency::round_half_even(std_dev, decimals),
+        k,
+        threshold: currency::round_half_even(threshold, decimals),
+        anomalies,
+    }))
+}
+
+/// Maximum length (in characters) of a trip name.
+const MAX_TRIP_NAME_LEN: usize = 100;
+
+/// Trim a trip's name and check its dates are in order.
+fn validate_trip(request: &TripRequest) -> Result<String, ApiError> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("Trip name must not be empty"));
+    }
+    if name.chars().count() > MAX_TRIP_NAME_LEN {
+        return Err(ApiError::bad_request(format!(
+            "Trip name must be at most {} characters",
+            MAX_TRIP_NAME_LEN
+        )));
+    }
+    if request.end_date.is_some_and(|end| end < request.start_date) {
+        return Err(ApiError::bad_request("Trip must not end before it starts"));
+    }
+    Ok(name.to_string())
+}
+
+/// Fetch a trip of the group, `NotFound` if it belongs to another group.
+async fn fetch_trip(group_id: Uuid, trip_id: &str) -> Result<Trip, Status> {
+    let trip_uuid = Uuid::parse_str(trip_id).map_err(|_| Status::BadRequest)?;
+    sqlx::query_as(
+        "SELECT id, name, start_date, end_date, created_at FROM trips WHERE id = $1 AND group_id = $2",
+    )
+    .bind(trip_uuid)
+    .bind(group_id)
+    .fetch_optional(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch trip: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)
+}
+
+// List the group's trips, earliest first - requires valid JWT
+#[get("/groups/current/trips")]
+async fn get_trips(auth: GroupAuth) -> Result<Json<Vec<Trip>>, Status> {
+    let trips: Vec<Trip> = sqlx::query_as(
+        "SELECT id, name, start_date, end_date, created_at FROM trips
+         WHERE group_id = $1 ORDER BY start_date, created_at",
+    )
+    .bind(auth.group_id)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch trips: {}", e);
+        db::error_status(&e)
+    })?;
+    Ok(Json(trips))
+}
+
+// Create a trip - requires valid JWT + add_expenses permission
+#[post("/groups/current/trips", data = "<request>")]
+async fn create_trip(
+    auth: GroupAuth,
+    _writable: Writable,
+    request: Json<TripRequest>,
+) -> Result<Json<Trip>, ApiError> {
+    if !auth.permissions.has_add_expenses() {
+        return Err(Status::Forbidden.into());
+    }
+    let name = validate_trip(&request)?;
+    let pool = db::get_pool();
+
+    let trip: Trip = sqlx::query_as(
+        "INSERT INTO trips (id, group_id, name, start_date, end_date) VALUES ($1, $2, $3, $4, $5)
+         RETURNING id, name, start_date, end_date, created_at",
+    )
+    .bind(Uuid::new_v4())
+    .bind(auth.group_id)
+    .bind(&name)
+    .bind(request.start_date)
+    .bind(request.end_date)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to create trip: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    Ok(Json(trip))
+}
+
+// Rename a trip or change its dates - requires valid JWT + edit_expenses permission
+#[put("/groups/current/trips/<trip_id>", data = "<request>")]
+async fn update_trip(
+    auth: GroupAuth,
+    _writable: Writable,
+    trip_id: &str,
+    request: Json<TripRequest>,
+) -> Result<Json<Trip>, ApiError> {
+    if !auth.permissions.has_edit_expenses() {
+        return Err(Status::Forbidden.into());
+    }
+    let existing = fetch_trip(auth.group_id, trip_id).await?;
+    let name = validate_trip(&request)?;
+    let pool = db::get_pool();
+
+    let trip: Trip = sqlx::query_as(
+        "UPDATE trips SET name = $1, start_date = $2, end_date = $3 WHERE id = $4 AND group_id = $5
+         RETURNING id, name, start_date, end_date, created_at",
+    )
+    .bind(&name)
+    .bind(request.start_date)
+    .bind(request.end_date)
+    .bind(existing.id)
+    .bind(auth.group_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to update trip: {}", e);
+        db::error_status(&e)
+    })?;
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    Ok(Json(trip))
+}
+
+// Delete a trip; its expenses stay in the group without a trip - requires valid
+// JWT + edit_expenses permission
+#[delete("/groups/current/trips/<trip_id>")]
+async fn delete_trip(auth: GroupAuth, _writable: Writable, trip_id: &str) -> Result<Status, Status> {
+    if !auth.permissions.has_edit_expenses() {
+        return Err(Status::Forbidden);
+    }
+    let trip = fetch_trip(auth.group_id, trip_id).await?;
+    let pool = db::get_pool();
+
+    sqlx::query("DELETE FROM trips WHERE id = $1 AND group_id = $2")
+        .bind(trip.id)
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to delete trip: {}", e);
+            db::error_status(&e)
+        })?;
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    Ok(Status::NoContent)
+}
+
+// Mark one member's share of an expense as settled (or not) - requires valid JWT +
+// settle permission. Settled shares still count in the default balances;
+// `GET /groups/current/balances?include_settled=false` leaves them out.
+#[put("/groups/current/expenses/<expense_id>/splits/<member_id>/settled", data = "<request>")]
+async fn set_share_settled(
+    auth: GroupAuth,
+    _writable: Writable,
+    expense_id: &str,
+    member_id: &str,
+    request: Json<SettleShareRequest>,
+) -> Result<Json<ShareSettlement>, Status> {
+    if !auth.permissions.has_settle() {
+        return Err(Status::Forbidden);
+    }
+    let pool = db::get_pool();
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+
+    let existing: ExpenseRow = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE id = $1 AND group_id = $2"
+    )
+    .bind(expense_uuid)
+    .bind(auth.group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+    let before = full_expense(existing).await?;
+
+    // Keep the first settled_at when the share is marked settled again
+    let (settled, settled_at): (bool, Option<DateTime<Utc>>) = sqlx::query_as(
+        "UPDATE expense_splits
+         SET settled = $3, settled_at = CASE WHEN $3 THEN COALESCE(settled_at, NOW()) END
+         WHERE expense_id = $1 AND member_id = $2
+         RETURNING settled, settled_at",
+    )
+    .bind(expense_uuid)
+    .bind(member_uuid)
+    .bind(request.settled)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to update expense split: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    if settled != before.settled_shares.contains(&member_uuid) {
+        let mut after = before.clone();
+        if settled {
+            after.settled_shares.push(member_uuid);
+        } else {
+            after.settled_shares.retain(|id| *id != member_uuid);
+        }
+        activity::record(&auth, activity::EXPENSE_UPDATED, expense_uuid, Some(&before), Some(&after)).await;
+    }
+
+    Ok(Json(ShareSettlement {
+        expense_id: expense_uuid,
+        member_id: member_uuid,
+        settled,
+        settled_at,
+    }))
+}
+
+// Balances from only the expenses of one trip - requires valid JWT
+#[get("/groups/current/trips/<trip_id>/balances")]
+async fn get_trip_balances(auth: GroupAuth, trip_id: &str) -> Result<Json<Vec<Balance>>, Status> {
+    let trip = fetch_trip(auth.group_id, trip_id).await?;
+    Ok(Json(compute_balances(auth.group_id, Some(trip.id), true).await?))
+}
+
+// Delete expense - requires valid JWT + edit_expenses permission
+#[delete("/groups/current/expenses/<expense_id>")]
+async fn delete_expense(
+    auth: GroupAuth,
+    _writable: Writable,
+    expense_id: &str,
+) -> Result<Status, Status> {
+    if !auth.permissions.has_edit_expenses() {
+        return Err(Status::Forbidden);
+    }
+    let pool = db::get_pool();
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+
+    // Verify expense belongs to this group
+    let existing: ExpenseRow = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE id = $1 AND group_id = $2"
+    )
+    .bind(expense_uuid)
+    .bind(auth.group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+    let before = full_expense(existing).await?;
+    let receipt = fetch_receipt(auth.group_id, expense_uuid).await?;
+
+    // Splits, items, tags and the receipt row go with it (ON DELETE CASCADE)
+    sqlx::query("DELETE FROM expenses WHERE id = $1 AND group_id = $2")
+        .bind(expense_uuid)
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to delete expense: {}", e);
+            db::error_status(&e)
+        })?;
+    if let Some(receipt) = receipt
+        && let Err(e) = storage::delete(&receipt.storage_key).await
+    {
+        eprintln!("Failed to delete receipt file: {}", e);
+    }
+
+    // Update last_activity_at
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    webhooks::dispatch(
+        auth.group_id,
+        webhooks::EXPENSE_DELETED,
+        serde_json::json!({ "id": expense_uuid }),
+    );
+
+    activity::record(&auth, activity::EXPENSE_DELETED, expense_uuid, Some(&before), None).await;
+
+    Ok(Status::NoContent)
+}
+
+/// Write an expense snapshot's split members, items and tags (the expense row must exist).
+async fn insert_expense_details(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    expense: &Expense,
+) -> Result<(), Status> {
+    if expense.expense_type != ExpenseType::Transfer {
+        for member_id in &expense.split_between {
+            let share: Option<BigDecimal> = expense.splits.as_ref().and_then(|splits| {
+                splits
+                    .iter()
+                    .find(|s| &s.member_id == member_id)
+                    .and_then(|s| s.share.and_then(|v| BigDecimal::try_from(v).ok()))
+            });
+            sqlx::query(
+                "INSERT INTO expense_splits (expense_id, member_id, share, settled, settled_at)
+                 VALUES ($1, $2, $3, $4, CASE WHEN $4 THEN NOW() END)",
+            )
+                .bind(expense.id)
+                .bind(member_id)
+                .bind(&share)
+                .bind(expense.settled_shares.contains(member_id))
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to restore expense split: {}", e);
+                    db::error_status(&e)
+                })?;
+        }
+    }
+    for (position, item) in expense.items.iter().flatten().enumerate() {
+        let amount = BigDecimal::try_from(item.amount).map_err(|_| Status::InternalServerError)?;
+        sqlx::query(
+            "INSERT INTO expense_items (expense_id, position, description, amount, member_ids) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(expense.id)
+        .bind(position as i32)
+        .bind(&item.description)
+        .bind(&amount)
+        .bind(&item.member_ids)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to restore expense item: {}", e);
+            db::error_status(&e)
+        })?;
+    }
+    insert_tags(&mut **tx, expense.id, &expense.tags).await
+}
+
+/// Put an expense back the way a snapshot recorded it. With `recreate`, the row is
+/// inserted again (it was deleted); otherwise the existing row is overwritten.
+/// `Conflict` when the expense's members are gone or the row isn't in the expected state.
+async fn restore_expense(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    group_id: Uuid,
+    expense: &Expense,
+    recreate: bool,
+) -> Result<(), Status> {
+    let involved: Vec<Uuid> = expense
+        .split_between
+        .iter()
+        .copied()
+        .chain(std::iter::once(expense.paid_by))
+        .chain(expense.transfer_to)
+        .collect();
+    ensure_group_members(group_id, &involved)
+        .await
+        .map_err(|s| if s == Status::BadRequest { Status::Conflict } else { s })?;
+    let amount = BigDecimal::try_from(expense.amount).map_err(|_| Status::InternalServerError)?;
+    let exchange_rate =
+        BigDecimal::try_from(expense.exchange_rate).map_err(|_| Status::InternalServerError)?;
+
+    // A trip deleted in the meantime just leaves the expense without one; a refund
+    // whose expense is gone is skipped, as deleting the expense would have removed it
+    let query = if recreate {
+        "INSERT INTO expenses (id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of)
+         SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, (SELECT id FROM trips WHERE id = $17 AND group_id = $2), $18
+         WHERE $18::uuid IS NULL OR EXISTS (SELECT 1 FROM expenses WHERE id = $18 AND group_id = $2)
+         ON CONFLICT (id) DO NOTHING"
+    } else {
+        "UPDATE expenses SET description = $3, amount = $4, paid_by = $5, expense_type = $6, transfer_to = $7, currency = $8, exchange_rate = $9, expense_date = $10,
+             created_at = $11, split_type = $12, notes = $13, receipt_url = $14, created_by = $15, updated_by = $16,
+             trip_id = (SELECT id FROM trips WHERE id = $17 AND group_id = $2)
+         WHERE id = $1 AND group_id = $2 AND refund_of IS NOT DISTINCT FROM $18"
+    };
+    let affected = sqlx::query(query)
+        .bind(expense.id)
+        .bind(group_id)
+        .bind(&expense.description)
+        .bind(&amount)
+        .bind(expense.paid_by)
+        .bind(expense.expense_type)
+        .bind(expense.transfer_to)
+        .bind(&expense.currency)
+        .bind(&exchange_rate)
+        .bind(expense.expense_date)
+        .bind(expense.created_at)
+        .bind(&expense.split_type)
+        .bind(&expense.notes)
+        .bind(&expense.receipt_url)
+        .bind(expense.created_by)
+        .bind(expense.updated_by)
+        .bind(expense.trip_id)
+        .bind(expense.refund_of)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to restore expense: {}", e);
+            db::error_status(&e)
+        })?
+        .rows_affected();
+    if affected == 0 {
+        return Err(Status::Conflict);
+    }
+
+    if !recreate {
+        for table in ["expense_splits", "expense_items", "expense_tags"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE expense_id = $1", table))
+                .bind(expense.id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to clear {}: {}", table, e);
+                    db::error_status(&e)
+                })?;
+        }
+    }
+    insert_expense_details(tx, expense).await
+}
+
+// Log of expense changes, newest first - requires valid JWT
+#[get("/groups/current/activity?<action>&<from>&<to>&<limit>&<offset>")]
+async fn get_activity(
+    auth: GroupAuth,
+    action: Option<&str>,
+    from: Option<&str>,
+    to: Option<&str>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Json<activity::ActivityPage>, Status> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+        return Err(Status::BadRequest);
+    }
+    if action.is_some_and(|a| !activity::ACTIONS.contains(&a)) {
+        return Err(Status::BadRequest);
+    }
+    let parse_date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| Status::BadRequest);
+    let from = from.map(parse_date).transpose()?;
+    let to = to.map(parse_date).transpose()?;
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        return Err(Status::BadRequest);
+    }
+
+    let page = activity::list(auth.group_id, action, from, to, limit, offset)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch activity: {}", e);
+            db::error_status(&e)
+        })?;
+    Ok(Json(page))
+}
+
+// Undo the most recent expense change made with this token, if it is inside the
+// undo window and nobody changed the expense since - requires valid JWT
+#[post("/groups/current/undo")]
+async fn undo_last_action(auth: GroupAuth, _writable: Writable) -> Result<Json<UndoResult>, Status> {
+    let pool = db::get_pool();
+    let entry = activity::last_undoable(&auth)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch activity: {}", e);
+            db::error_status(&e)
+        })?
+        .ok_or(Status::NotFound)?;
+
+    // Undoing would silently overwrite a later change to the same expense
+    let changed_since: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM activity_log WHERE expense_id = $1 AND id > $2 AND undone_at IS NULL)",
+    )
+    .bind(entry.expense_id)
+    .bind(entry.id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch activity: {}", e);
+        db::error_status(&e)
+    })?;
+    if changed_since {
+        return Err(Status::Conflict);
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+    let mut receipt = None;
+    let restored = match (entry.action.as_str(), &entry.before) {
+        (activity::EXPENSE_CREATED, _) => {
+            receipt = fetch_receipt(auth.group_id, entry.expense_id).await?;
+            let deleted = sqlx::query("DELETE FROM expenses WHERE id = $1 AND group_id = $2")
+                .bind(entry.expense_id)
+                .bind(auth.group_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to delete expense: {}", e);
+                    db::error_status(&e)
+                })?
+                .rows_affected();
+            if deleted == 0 {
+                return Err(Status::Conflict);
+            }
+            None
+        }
+        (activity::EXPENSE_UPDATED, Some(before)) => {
+            restore_expense(&mut tx, auth.group_id, before, false).await?;
+            Some(before.0.clone())
+        }
+        (activity::EXPENSE_DELETED, Some(before)) => {
+            restore_expense(&mut tx, auth.group_id, before, true).await?;
+            Some(before.0.clone())
+        }
+        _ => {
+            eprintln!("Activity {} can't be undone", entry.id);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    sqlx::query("UPDATE activity_log SET undone_at = NOW() WHERE id = $1")
+        .bind(entry.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to mark activity as undone: {}", e);
+            db::error_status(&e)
+        })?;
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit undo: {}", e);
+        db::error_status(&e)
+    })?;
+
+    if let Some(receipt) = receipt
+        && let Err(e) = storage::delete(&receipt.storage_key).await
+    {
+        eprintln!("Failed to delete receipt file: {}", e);
+    }
+    match entry.action.as_str() {
+        activity::EXPENSE_CREATED => webhooks::dispatch(
+            auth.group_id,
+            webhooks::EXPENSE_DELETED,
+            serde_json::json!({ "id": entry.expense_id }),
+        ),
+        activity::EXPENSE_DELETED => webhooks::dispatch(
+            auth.group_id,
+            webhooks::EXPENSE_CREATED,
+            serde_json::to_value(&restored).unwrap_or_default(),
+        ),
+        _ => {}
+    }
+
+    Ok(Json(UndoResult {
+        action: entry.action,
+        expense_id: entry.expense_id,
+        expense: restored,
+    }))
+}
+
+/// Maximum size of an uploaded receipt. Defaults to 5 MiB; override with
+/// `MAX_RECEIPT_BYTES` (Rocket's `file` limit in Rocket.toml caps it too).
+static MAX_RECEIPT_BYTES: Lazy<u64> = Lazy::new(|| {
+    std::env::var("MAX_RECEIPT_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(5 * 1024 * 1024)
+});
+
+/// Multipart body of a receipt upload.
+#[derive(FromForm)]
+struct ReceiptUpload<'r> {
+    file: TempFile<'r>,
+}
+
+/// Content type and file extension of a supported receipt, detected from its
+/// leading bytes rather than trusting the client.
+fn receipt_type(bytes: &[u8]) -> Option<(&'static str, &'static str)> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(("image/jpeg", "jpg"))
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(("image/png", "png"))
+    } else if bytes.starts_with(b"%PDF-") {
+        Some(("application/pdf", "pdf"))
+    } else {
+        None
+    }
+}
+
+/// The stored receipt of an expense in this group, if any.
+/// `Err(NotFound)` when the expense itself doesn't belong to the group.
+async fn fetch_receipt(group_id: Uuid, expense_id: Uuid) -> Result<Option<ExpenseReceiptRow>, Status> {
+    let pool = db::get_pool();
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM expenses WHERE id = $1 AND group_id = $2)",
+    )
+    .bind(expense_id)
+    .bind(group_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense: {}", e);
+        db::error_status(&e)
+    })?;
+    if !exists {
+        return Err(Status::NotFound);
+    }
+    sqlx::query_as(
+        "SELECT storage_key, content_type FROM expense_receipts WHERE expense_id = $1",
+    )
+    .bind(expense_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch receipt: {}", e);
+        db::error_status(&e)
+    })
+}
+
+// Upload a receipt file (JPEG, PNG or PDF) for an expense, replacing any previous one
+// - requires valid JWT + edit_expenses permission
+#[post("/groups/current/expenses/<expense_id>/receipt", data = "<upload>")]
+async fn upload_receipt(
+    auth: GroupAuth,
+    _writable: Writable,
+    expense_id: &str,
+    upload: Form<ReceiptUpload<'_>>,
+) -> Result<Json<ReceiptInfo>, Status> {
+    if !auth.permissions.has_edit_expenses() {
+        return Err(Status::Forbidden);
+    }
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+    let previous = fetch_receipt(auth.group_id, expense_uuid).await?;
+
+    let file = &upload.file;
+    if file.len() > *MAX_RECEIPT_BYTES {
+        return Err(Status::PayloadTooLarge);
+    }
+    let mut bytes = Vec::new();
+    let read = async {
+        let mut reader = file.open().await?;
+        reader.read_to_end(&mut bytes).await
+    };
+    read.await.map_err(|e| {
+        eprintln!("Failed to read uploaded receipt: {}", e);
+        Status::InternalServerError
+    })?;
+    let (content_type, extension) = receipt_type(&bytes).ok_or(Status::UnsupportedMediaType)?;
+
+    let key = format!("{}/{}.{}", auth.group_id, expense_uuid, extension);
+    let size_bytes = bytes.len() as i64;
+    storage::put(&key, bytes, content_type).await.map_err(|e| {
+        eprintln!("Failed to store receipt: {}", e);
+        Status::InternalServerError
+    })?;
+
+    let pool = db::get_pool();
+    let uploaded_at = Utc::now();
+    sqlx::query(
+        "INSERT INTO expense_receipts (expense_id, storage_key, content_type, size_bytes, uploaded_at)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (expense_id) DO UPDATE SET storage_key = $2, content_type = $3, size_bytes = $4, uploaded_at = $5",
+    )
+    .bind(expense_uuid)
+    .bind(&key)
+    .bind(content_type)
+    .bind(size_bytes)
+    .bind(uploaded_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to save receipt: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // A replaced receipt of another type lives under a different key
+    if let Some(previous) = previous
+        && previous.storage_key != key
+        && let Err(e) = storage::delete(&previous.storage_key).await
+    {
+        eprintln!("Failed to delete replaced receipt: {}", e);
+    }
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    Ok(Json(ReceiptInfo {
+        content_type: content_type.to_string(),
+        size_bytes,
+        uploaded_at,
+    }))
+}
+
+// Download the receipt file of an expense - requires valid JWT
+#[get("/groups/current/expenses/<expense_id>/receipt")]
+async fn get_receipt(auth: GroupAuth, expense_id: &str) -> Result<(ContentType, Vec<u8>), Status> {
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+    let receipt = fetch_receipt(auth.group_id, expense_uuid)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let bytes = storage::get(&receipt.storage_key)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to read receipt: {}", e);
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    let content_type =
+        ContentType::parse_flexible(&receipt.content_type).unwrap_or(ContentType::Binary);
+    Ok((content_type, bytes))
+}
+
+// Remove the receipt file of an expense - requires valid JWT + edit_expenses permission
+#[delete("/groups/current/expenses/<expense_id>/receipt")]
+async fn delete_receipt(
+    auth: GroupAuth,
+    _writable: Writable,
+    expense_id: &str,
+) -> Result<Status, Status> {
+    if !auth.permissions.has_edit_expenses() {
+        return Err(Status::Forbidden);
+    }
+    let expense_uuid = Uuid::parse_str(expense_id).map_err(|_| Status::BadRequest)?;
+    let receipt = fetch_receipt(auth.group_id, expense_uuid)
+        .await?
+        .ok_or(Status::NotFound)?;
+
+    let pool = db::get_pool();
+    sqlx::query("DELETE FROM expense_receipts WHERE expense_id = $1")
+        .bind(expense_uuid)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to delete receipt: {}", e);
+            db::error_status(&e)
+        })?;
+    if let Err(e) = storage::delete(&receipt.storage_key).await {
+        eprintln!("Failed to delete receipt file: {}", e);
+    }
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    Ok(Status::NoContent)
+}
+
+/// Balances within this distance of zero are treated as settled.
+pub(crate) const BALANCE_EPSILON: f64 = 0.005;
+
+// Symbol and number formatting of the group currency - requires valid JWT
+#[get("/groups/current/currency-info")]
+async fn get_currency_info(auth: GroupAuth) -> Result<Json<currency::CurrencyInfo>, Status> {
+    Ok(Json(currency::info(&group_currency(auth.group_id).await?)))
+}
+
+/// Upper bound on cached groups; the cache is simply emptied when it is full.
+const BALANCES_CACHE_CAPACITY: usize = 10_000;
+
+/// Last computed balances per group, tagged with the group version they were computed at.
+/// The version is bumped by database triggers on every change to members, expenses and
+/// splits, so a stale entry is never served and no handler has to invalidate it explicitly.
+static BALANCES_CACHE: Lazy<Mutex<HashMap<Uuid, VersionedBalances>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+type VersionedBalances = (i64, Vec<Balance>);
+
+/// Balances of a group, from the cache when it is current.
+async fn cached_balances(group_id: Uuid) -> Result<Vec<Balance>, Status> {
+    // Read the version first: anything computed afterwards is at least this new
+    let version = group_version(group_id).await?;
+    {
+        let cache = BALANCES_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached_version, balances)) = cache.get(&group_id)
+            && *cached_version == version
+        {
+            return Ok(balances.clone());
+        }
+    }
+
+    let balances = compute_balances(group_id, None, true).await?;
+    let mut cache = BALANCES_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if cache.len() >= BALANCES_CACHE_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(group_id, (version, balances.clone()));
+    Ok(balances)
+}
+
+// Recompute the balances and check they add up to zero, dropping a cached copy
+// that disagrees - requires valid JWT
+#[get("/groups/current/balances/verify")]
+async fn verify_balances(auth: GroupAuth) -> Result<Json<BalanceCheck>, Status> {
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let balances = compute_balances(auth.group_id, None, true).await?;
+    let sum: f64 = balances.iter().map(|b| b.balance).sum();
+    let tolerance = 0.5 / 10f64.powi(decimals as i32);
+
+    let foreign_references: i64 = sqlx::query_scalar(
+        "SELECT (SELECT COUNT(*) FROM expense_splits s JOIN expenses e ON e.id = s.expense_id
+                 WHERE e.group_id = $1 AND s.member_id NOT IN (SELECT id FROM members WHERE group_id = $1))
+              + (SELECT COUNT(*) FROM expenses
+                 WHERE group_id = $1 AND paid_by NOT IN (SELECT id FROM members WHERE group_id = $1))
+              + (SELECT COUNT(*) FROM expenses
+                 WHERE group_id = $1 AND transfer_to NOT IN (SELECT id FROM members WHERE group_id = $1))",
+    )
+    .bind(auth.group_id)
+    .fetch_one(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to check expense members: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let cache_stale = {
+        let mut cache = BALANCES_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        let stale = cache.get(&auth.group_id).is_some_and(|(_, cached)| {
+            cached.len() != balances.len()
+                || cached.iter().zip(&balances).any(|(c, b)| {
+                    c.user_id != b.user_id || (c.balance - b.balance).abs() > f64::EPSILON
+                })
+        });
+        if stale {
+            cache.remove(&auth.group_id);
+        }
+        stale
+    };
+    if cache_stale || sum.abs() > tolerance || foreign_references > 0 {
+        eprintln!(
+            "Balance check failed for group {}: sum {}, {} foreign references, cache stale: {}",
+            auth.group_id, sum, foreign_references, cache_stale
+        );
+    }
+
+    Ok(Json(BalanceCheck {
+        ok: sum.abs() <= tolerance && foreign_references == 0,
+        sum: currency::round_half_even(sum, decimals + 2),
+        tolerance,
+        foreign_references,
+        cache_stale,
+        balances,
+    }))
+}
+
+// Get balances - requires valid JWT.
+// With `?currency=XYZ` the balances are converted at the current rate and returned
+// together with the rate that was used. Balances are cached per group version;
+// `?fresh=true` recomputes them regardless. `?amounts=minor` adds each balance in
+// integer minor units of its currency (`balance_minor`). `?include_settled=false`
+// leaves out expense shares that were settled on their own.
+#[get("/groups/current/balances?<currency>&<fresh>&<amounts>&<include_settled>")]
+async fn get_balances(
+    auth: GroupAuth,
+    currency: Option<&str>,
+    fresh: Option<bool>,
+    amounts: Option<&str>,
+    include_settled: Option<bool>,
+) -> Result<Either<Json<Vec<Balance>>, Json<ConvertedBalances>>, Status> {
+    let minor = minor_amounts(amounts)?;
+    let include_settled = include_settled.unwrap_or(true);
+    let mut balances = if fresh.unwrap_or(false) || !include_settled {
+        compute_balances(auth.group_id, None, include_settled).await?
+    } else {
+        cached_balances(auth.group_id).await?
+    };
+    let Some(target) = currency else {
+        if minor {
+            let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+            for b in &mut balances {
+                b.balance_minor = Some(currency::to_minor_units(b.balance, decimals));
+            }
+        }
+        return Ok(Either::Left(Json(balances)));
+    };
+    if !rates::is_currency_code(target) {
+        return Err(Status::BadRequest);
+    }
+    let target = target.to_ascii_uppercase();
+
+    let group_currency = group_currency(auth.group_id).await?;
+    let rate = rates::latest(&group_currency, &target).await.map_err(|e| {
+        eprintln!("Failed to fetch exchange rate: {}", e);
+        Status::ServiceUnavailable
+    })?;
+    let decimals = currency::minor_units(&target);
+
+    Ok(Either::Right(Json(ConvertedBalances {
+        currency: target,
+        group_currency,
+        rate: rate.rate,
+        rate_date: rate.date,
+        fetched_at: rate.fetched_at,
+        balances: balances
+            .into_iter()
+            .map(|b| {
+                let balance = currency::round_half_even(b.balance * rate.rate, decimals);
+                Balance {
+                    balance,
+                    balance_minor: minor.then(|| currency::to_minor_units(balance, decimals)),
+                    ..b
+                }
+            })
+            .collect(),
+    })))
+}
+
+/// Groups with at least this many expenses get their balances computed in SQL
+/// instead of loading every expense and split. Defaults to 200; override with
+/// `SQL_BALANCES_THRESHOLD` (0 = always use SQL).
+static SQL_BALANCES_THRESHOLD: Lazy<i64> = Lazy::new(|| {
+    std::env::var("SQL_BALANCES_THRESHOLD")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n >= 0)
+        .unwrap_or(200)
+});
+
+/// Compute each member's net balance in the group currency, from all expenses or
+/// only those of one trip. Without `include_settled`, shares settled on their own
+/// are left out (see `settled_share_deltas`). Balances are rounded to the
+/// currency's minor units (see `currency::round_half_even`).
+async fn compute_balances(
+    group_id: Uuid,
+    trip_id: Option<Uuid>,
+    include_settled: bool,
+) -> Result<Vec<Balance>, Status> {
+    let expense_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM expenses WHERE group_id = $1 AND ($2::uuid IS NULL OR trip_id = $2)",
+    )
+    .bind(group_id)
+    .bind(trip_id)
+    .fetch_one(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to count expenses: {}", e);
+        db::error_status(&e)
+    })?;
+    if expense_count >= *SQL_BALANCES_THRESHOLD {
+        compute_balances_in_sql(group_id, trip_id, include_settled).await
+    } else {
+        compute_balances_in_rust(group_id, trip_id, include_settled).await
+    }
+}
+
+/// Net balances from a single aggregate query. Follows the same sign conventions,
+/// split rules and rounding as `balance_deltas`/`member_shares`: each amount is
+/// rounded to the group currency (`round_half_even`, see V37) and its shares are
+/// rounded by the largest-remainder method of `currency::round_shares`, with the
+/// odd minor units going to the members created first. So both paths give the
+/// same balances for the same data.
+async fn compute_balances_in_sql(
+    group_id: Uuid,
+    trip_id: Option<Uuid>,
+    include_settled: bool,
+) -> Result<Vec<Balance>, Status> {
+    let decimals = currency::minor_units(&group_currency(group_id).await?);
+    let rows: Vec<(Uuid, String, f64)> = sqlx::query_as(
+        "WITH group_expenses AS (
+             SELECT e.id, e.paid_by, e.expense_type, e.transfer_to, e.split_type,
+                    e.amount::float8 AS raw_amount, e.exchange_rate::float8 AS rate,
+                    round_half_even(e.amount::float8 * e.exchange_rate::float8, $4) AS total
+             FROM expenses e WHERE e.group_id = $1 AND ($2::uuid IS NULL OR e.trip_id = $2)
+         ),
+         split_info AS (
+             SELECT s.expense_id, s.member_id, s.share::float8 AS share, s.settled,
+                    m.creation_order AS member_order,
+                    COUNT(*) OVER w AS n,
+                    SUM(COALESCE(s.share::float8, 0)) OVER w AS total_shares
+             FROM expense_splits s
+             JOIN group_expenses e ON e.id = s.expense_id
+             JOIN members m ON m.id = s.member_id
+             WHERE e.expense_type <> 'transfer'
+             WINDOW w AS (PARTITION BY s.expense_id)
+         ),
+         raw_shares AS (
+             -- Unrounded share, computed in the same order of operations as member_shares
+             SELECT si.expense_id, si.member_id, si.member_order, si.settled, si.n,
+                    e.paid_by, e.expense_type, e.total,
+                    CASE e.split_type
+                        WHEN 'percentage' THEN e.raw_amount * e.rate * COALESCE(si.share, 100::float8 / si.n) / 100
+                        WHEN 'exact' THEN COALESCE(si.share, e.raw_amount / si.n) * e.rate
+                        WHEN 'shares' THEN CASE WHEN si.total_shares > 0
+                            THEN e.raw_amount * e.rate * COALESCE(si.share, 0) / si.total_shares
+                            ELSE 0 END
+                        WHEN 'adjustment' THEN ((e.raw_amount - si.total_shares) / si.n + COALESCE(si.share, 0)) * e.rate
+                        ELSE e.raw_amount * e.rate / si.n
+                    END AS raw
+             FROM split_info si JOIN group_expenses e ON e.id = si.expense_id
+         ),
+         floored AS (
+             SELECT r.*, floor(r.raw * power(10::float8, $4) + 1e-6) AS units
+             FROM raw_shares r
+         ),
+         ranked AS (
+             -- Minor units the floored shares fall short of the total, and who gets them
+             SELECT f.*,
+                    round(f.total * power(10::float8, $4)) - SUM(f.units) OVER (PARTITION BY f.expense_id) AS missing,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY f.expense_id
+                        ORDER BY round((f.raw * power(10::float8, $4) - f.units) * 1e6) DESC, f.member_order
+                    ) AS position
+             FROM floored f
+         ),
+         split_amounts AS (
+             SELECT member_id, settled, paid_by, expense_type,
+                    CASE WHEN missing < 0 OR missing > n THEN round_half_even(raw, $4)
+                         ELSE (units + CASE WHEN position <= missing THEN 1 ELSE 0 END) / power(10::float8, $4)
+                    END AS amount
+             FROM ranked
+         ),
+         deltas AS (
+             -- Payer: credited for expenses and transfers, holds the money for income.
+             -- Expenses without splits are ignored, as in the Rust computation.
+             SELECT e.paid_by AS member_id,
+                    CASE WHEN e.expense_type = 'income' THEN -e.total ELSE e.total END AS delta
+             FROM group_expenses e
+             WHERE e.expense_type = 'transfer'
+                OR EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id)
+             UNION ALL
+             -- Transfer receiver
+             SELECT e.transfer_to, -e.total
+             FROM group_expenses e
+             WHERE e.expense_type = 'transfer' AND e.transfer_to IS NOT NULL
+             UNION ALL
+             -- Split members: owe their share of expenses, are owed their share of income
+             SELECT member_id, CASE WHEN expense_type = 'income' THEN amount ELSE -amount END
+             FROM split_amounts
+             UNION ALL
+             -- Shares settled on their own, when excluded: undo them for member and payer
+             SELECT member_id, CASE WHEN expense_type = 'income' THEN -amount ELSE amount END
+             FROM split_amounts WHERE settled AND NOT $3
+             UNION ALL
+             SELECT paid_by, CASE WHEN expense_type = 'income' THEN amount ELSE -amount END
+             FROM split_amounts WHERE settled AND NOT $3
+         )
+         SELECT m.id, m.name, COALESCE(SUM(d.delta), 0)::float8
+         FROM members m LEFT JOIN deltas d ON d.member_id = m.id
+         WHERE m.group_id = $1
+         GROUP BY m.id, m.name, m.display_order
+         ORDER BY m.display_order, m.id",
+    )
+    .bind(group_id)
+    .bind(trip_id)
+    .bind(include_settled)
+    .bind(decimals as i32)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to compute balances: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Summing rounded deltas can still leave float noise (e.g. 0.1 + 0.2)
+    Ok(rows
+        .into_iter()
+        .map(|(user_id, user_name, balance)| Balance {
+            user_id,
+            user_name,
+            balance: currency::round_half_even(balance, decimals),
+            balance_minor: None,
+        })
+        .collect())
+}
+
+/// Balances computed expense by expense with `balance_deltas`.
+async fn compute_balances_in_rust(
+    group_id: Uuid,
+    trip_id: Option<Uuid>,
+    include_settled: bool,
+) -> Result<Vec<Balance>, Status> {
+    let pool = db::get_pool();
+    let decimals = currency::minor_units(&group_currency(group_id).await?);
+
+    // Get all members
+    let member_rows: Vec<MemberRow> = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1 ORDER BY display_order, id"
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch members: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Get all expenses with splits
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1 AND ($2::uuid IS NULL OR trip_id = $2)"
+    )
+    .bind(group_id)
+    .bind(trip_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Initialize balances
+    let mut balances: Vec<Balance> = member_rows
+        .iter()
+        .map(|m| Balance {
+            user_id: m.id,
+            user_name: m.name.clone(),
+            balance: 0.0,
+            balance_minor: None,
+        })
+        .collect();
+
+    // Calculate balances for each expense
+    for expense_row in expense_rows {
+        let splits = if expense_row.expense_type == ExpenseType::Transfer {
+            Vec::new()
+        } else {
+            fetch_splits(expense_row.id).await?
+        };
+        let mut deltas = balance_deltas(&expense_row, &splits, decimals);
+        if !include_settled {
+            deltas.extend(settled_share_deltas(&expense_row, &splits, decimals));
+        }
+        for (member_id, delta) in deltas {
+            if let Some(member) = balances.iter_mut().find(|b| b.user_id == member_id) {
+                member.balance += delta;
+            }
+        }
+    }
+
+    // Summing rounded deltas can still leave float noise (e.g. 0.1 + 0.2)
+    for balance in &mut balances {
+        balance.balance = currency::round_half_even(balance.balance, decimals);
+    }
+
+    Ok(balances)
+}
+
+/// The currency code of a group.
+async fn group_currency(group_id: Uuid) -> Result<String, Status> {
+    sqlx::query_scalar("SELECT currency FROM groups WHERE id = $1")
+        .bind(group_id)
+        .fetch_one(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch group currency: {}", e);
+            db::error_status(&e)
+        })
+}
+
+/// Fetch the split rows of a single expense, in the order members were created
+/// (which decides who gets the odd minor units in `currency::round_shares`). Not
+/// the display order, so reordering members never moves a cent.
+async fn fetch_splits(expense_id: Uuid) -> Result<Vec<ExpenseSplitMemberRow>, Status> {
+    sqlx::query_as(
+        "SELECT s.member_id, s.share, s.settled FROM expense_splits s JOIN members m ON m.id = s.member_id
+         WHERE s.expense_id = $1 ORDER BY m.creation_order",
+    )
+    .bind(expense_id)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expense splits: {}", e);
+        db::error_status(&e)
+    })
+}
+
+/// The expense amount converted to the group currency.
+fn expense_in_group_currency(expense: &ExpenseRow) -> f64 {
+    let raw_amount = expense.amount.to_f64().unwrap_or(0.0);
+    let exchange_rate = expense.exchange_rate.to_f64().unwrap_or(1.0);
+    raw_amount * exchange_rate
+}
+
+/// How one expense changes each member's balance, in the group currency.
+/// Amounts are rounded to `decimals` places of the group currency (banker's rounding),
+/// with split shares adjusted so they still add up to the rounded amount.
+///
+/// A positive balance means the group owes the member money. A transfer is money
+/// handed from `paid_by` to `transfer_to`, so it credits the sender and debits the
+/// receiver, just like an expense paid by the sender and split only to the receiver.
+/// Example: Bob paid a 20 dinner split with Alice, so Alice is at -10 and Bob at
+/// +10. Alice settles by recording a transfer of 10 from herself to Bob: Alice
+/// `+= 10` and Bob `-= 10`, and both are back to 0. This is also the direction
+/// settlement plans use: `Settlement::from` (the debtor) is the transfer's payer.
+fn balance_deltas(
+    expense: &ExpenseRow,
+    splits: &[ExpenseSplitMemberRow],
+    decimals: u32,
+) -> Vec<(Uuid, f64)> {
+    let amount = currency::round_half_even(expense_in_group_currency(expense), decimals);
+    let shares = || currency::round_shares(amount, member_shares(expense, splits), decimals);
+    let mut deltas = Vec::new();
+
+    match expense.expense_type {
+        ExpenseType::Transfer => {
+            // Direct transfer: sender's debt shrinks (or credit grows), the
+            // receiver's credit shrinks by the same amount
+            deltas.push((expense.paid_by, amount));
+            if let Some(to_id) = expense.transfer_to {
+                deltas.push((to_id, -amount));
+            }
+        }
+        ExpenseType::Income => {
+            // External income: receiver holds money, split members are owed their share
+            if splits.is_empty() {
+                return deltas;
+            }
+            deltas.push((expense.paid_by, -amount));
+            deltas.extend(shares());
+        }
+        ExpenseType::Expense => {
+            // Regular expense: payer gets credit, split members owe
+            if splits.is_empty() {
+                return deltas;
+            }
+            deltas.push((expense.paid_by, amount));
+            deltas.extend(
+                shares()
+                    .into_iter()
+                    .map(|(member_id, share)| (member_id, -share)),
+            );
+        }
+    }
+
+    deltas
+}
+
+/// Balance changes that take the settled shares of an expense back out of
+/// `balance_deltas`: a settled share was paid to (or, for income, received from)
+/// the payer directly, so it no longer counts for the member or the payer.
+fn settled_share_deltas(
+    expense: &ExpenseRow,
+    splits: &[ExpenseSplitMemberRow],
+    decimals: u32,
+) -> Vec<(Uuid, f64)> {
+    if expense.expense_type == ExpenseType::Transfer || !splits.iter().any(|s| s.settled) {
+        return Vec::new();
+    }
+    let amount = currency::round_half_even(expense_in_group_currency(expense), decimals);
+    let sign = if expense.expense_type == ExpenseType::Income { -1.0 } else { 1.0 };
+    currency::round_shares(amount, member_shares(expense, splits), decimals)
+        .into_iter()
+        .filter(|(member_id, _)| splits.iter().any(|s| s.settled && s.member_id == *member_id))
+        .flat_map(|(member_id, share)| {
+            [(member_id, sign * share), (expense.paid_by, -sign * share)]
+        })
+        .collect()
+}
+
+/// Each split member's portion of an expense, in the group currency.
+fn member_shares(expense: &ExpenseRow, splits: &[ExpenseSplitMemberRow]) -> Vec<(Uuid, f64)> {
+    let raw_amount = expense.amount.to_f64().unwrap_or(0.0);
+    let exchange_rate = expense.exchange_rate.to_f64().unwrap_or(1.0);
+    let amount = raw_amount * exchange_rate;
+    let split_count = splits.len() as f64;
+    let share_of = |s: &ExpenseSplitMemberRow| s.share.as_ref().and_then(|v| v.to_f64());
+
+    splits
+        .iter()
+        .map(|split| {
+            let member_amount = match expense.split_type.as_str() {
+                "percentage" => {
+                    let pct = share_of(split).unwrap_or(100.0 / split_count);
+                    amount * pct / 100.0
+                }
+                "exact" => {
+                    let exact = share_of(split).unwrap_or(raw_amount / split_count);
+                    exact * exchange_rate
+                }
+                "shares" => {
+                    let total_shares: f64 = splits.iter().map(|s| share_of(s).unwrap_or(0.0)).sum();
+                    let my_shares = share_of(split).unwrap_or(0.0);
+                    if total_shares > 0.0 { amount * my_shares / total_shares } else { 0.0 }
+                }
+                "adjustment" => {
+                    let extras: f64 = splits.iter().map(|s| share_of(s).unwrap_or(0.0)).sum();
+                    let extra = share_of(split).unwrap_or(0.0);
+                    ((raw_amount - extras) / split_count + extra) * exchange_rate
+                }
+                _ => amount / split_count, // equal
+            };
+            (split.member_id, member_amount)
+        })
+        .collect()
+}
+
+// Statement of a member's expenses with running balance - requires valid JWT
+#[get("/groups/current/members/<member_id>/statement?<from>&<to>")]
+async fn get_member_statement(
+    auth: GroupAuth,
+    member_id: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Json<MemberStatement>, Status> {
+    let pool = db::get_pool();
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+    let parse_date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| Status::BadRequest);
+    let from = from.map(parse_date).transpose()?;
+    let to = to.map(parse_date).transpose()?;
+
+    let member: MemberRow = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE id = $1 AND group_id = $2"
+    )
+    .bind(member_uuid)
+    .bind(auth.group_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch member: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+
+    // Only expenses up to `to` matter; earlier ones feed the opening balance
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1 AND ($2::date IS NULL OR expense_date <= $2)
+         ORDER BY expense_date, created_at, id"
+    )
+    .bind(auth.group_id)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let mut opening_balance = 0.0;
+    let mut balance = 0.0;
+    let mut entries = Vec::new();
+    for expense_row in expense_rows {
+        let splits = fetch_splits(expense_row.id).await?;
+        let change: f64 = balance_deltas(&expense_row, &splits, decimals)
+            .into_iter()
+            .filter(|(id, _)| *id == member_uuid)
+            .map(|(_, delta)| delta)
+            .sum();
+        let involved = expense_row.paid_by == member_uuid
+            || expense_row.transfer_to == Some(member_uuid)
+            || splits.iter().any(|s| s.member_id == member_uuid);
+        if !involved {
+            continue;
+        }
+        balance = currency::round_half_even(balance + change, decimals);
+        if from.is_some_and(|from| expense_row.expense_date < from) {
+            opening_balance = balance;
+            continue;
+        }
+        entries.push(StatementEntry {
+            expense: expense_from_row(expense_row, splits),
+            change,
+            balance,
+        });
+    }
+
+    Ok(Json(MemberStatement {
+        member_id: member.id,
+        member_name: member.name,
+        opening_balance,
+        closing_balance: balance,
+        entries,
+    }))
+}
+
+// Itemized balance of one member for checking it: what they paid and their share
+// of every expense they are involved in, oldest first - requires valid JWT. Settled
+// shares count like in the default balances, so `net` matches `GET /balances`.
+#[get("/groups/current/members/<member_id>/breakdown")]
+async fn get_member_breakdown(auth: GroupAuth, member_id: &str) -> Result<Json<MemberBreakdown>, Status> {
+    let pool = db::get_pool();
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+
+    let member_name: String =
+        sqlx::query_scalar("SELECT name FROM members WHERE id = $1 AND group_id = $2")
+            .bind(member_uuid)
+            .bind(auth.group_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch member: {}", e);
+                db::error_status(&e)
+            })?
+            .ok_or(Status::NotFound)?;
+
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses e WHERE e.group_id = $1 AND (e.paid_by = $2 OR e.transfer_to = $2
+           OR EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id AND s.member_id = $2))
+         ORDER BY expense_date, created_at, id"
+    )
+    .bind(auth.group_id)
+    .bind(member_uuid)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let mut entries = Vec::with_capacity(expense_rows.len());
+    let mut net = 0.0;
+    for row in expense_rows {
+        let splits = if row.expense_type == ExpenseType::Transfer {
+            Vec::new()
+        } else {
+            fetch_splits(row.id).await?
+        };
+        // Same amounts as balance_deltas: unsplit expenses and income count for nobody
+        let amount = currency::round_half_even(expense_in_group_currency(&row), decimals);
+        let (they_paid, their_share) = match row.expense_type {
+            ExpenseType::Transfer => (
+                if row.paid_by == member_uuid { amount } else { 0.0 },
+                if row.transfer_to == Some(member_uuid) { amount } else { 0.0 },
+            ),
+            _ if splits.is_empty() => (0.0, 0.0),
+            expense_type => {
+                let sign = if expense_type == ExpenseType::Income { -1.0 } else { 1.0 };
+                let share = currency::round_shares(amount, member_shares(&row, &splits), decimals)
+                    .into_iter()
+                    .find(|(id, _)| *id == member_uuid)
+                    .map_or(0.0, |(_, share)| share);
+                (
+                    if row.paid_by == member_uuid { sign * amount } else { 0.0 },
+                    sign * share,
+                )
+            }
+        };
+        net += they_paid - their_share;
+        entries.push(BreakdownEntry {
+            expense_id: row.id,
+            description: row.description,
+            date: row.expense_date,
+            expense_type: row.expense_type,
+            // `+ 0.0` turns a negated zero into a plain zero
+            their_share: their_share + 0.0,
+            they_paid: they_paid + 0.0,
+        });
+    }
+
+    Ok(Json(MemberBreakdown {
+        member_id: member_uuid,
+        member_name,
+        entries,
+        net: currency::round_half_even(net, decimals),
+    }))
+}
+
+// How a member's balance evolved: one point per expense date with the balance at the
+// end of that day, oldest first - requires valid JWT. The last point is the current
+// balance (no points if the member was never involved in an expense).
+#[get("/groups/current/members/<member_id>/balance-history")]
+async fn get_member_balance_history(
+    auth: GroupAuth,
+    member_id: &str,
+) -> Result<Json<Vec<BalancePoint>>, Status> {
+    let pool = db::get_pool();
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM members WHERE id = $1 AND group_id = $2)")
+            .bind(member_uuid)
+            .bind(auth.group_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch member: {}", e);
+                db::error_status(&e)
+            })?;
+    if !exists {
+        return Err(Status::NotFound);
+    }
+
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses e WHERE e.group_id = $1 AND (e.paid_by = $2 OR e.transfer_to = $2
+           OR EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id AND s.member_id = $2))
+         ORDER BY expense_date, created_at, id"
+    )
+    .bind(auth.group_id)
+    .bind(member_uuid)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let mut balance = 0.0;
+    let mut points: Vec<BalancePoint> = Vec::new();
+    for expense_row in expense_rows {
+        let splits = fetch_splits(expense_row.id).await?;
+        let change: f64 = balance_deltas(&expense_row, &splits, decimals)
+            .into_iter()
+            .filter(|(id, _)| *id == member_uuid)
+            .map(|(_, delta)| delta)
+            .sum();
+        balance = currency::round_half_even(balance + change, decimals);
+        match points.last_mut() {
+            Some(last) if last.date == expense_row.expense_date => last.balance = balance,
+            _ => points.push(BalancePoint {
+                date: expense_row.expense_date,
+                balance,
+            }),
+        }
+    }
+
+    Ok(Json(points))
+}
+
+/// Default and maximum page size of paginated lists.
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+// Expenses a member is involved in (payer, transfer recipient or split member),
+// newest first, paginated with `limit`/`offset` - requires valid JWT
+#[get("/groups/current/members/<member_id>/expenses?<limit>&<offset>")]
+async fn get_member_expenses(
+    auth: GroupAuth,
+    member_id: &str,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Json<MemberExpensePage>, Status> {
+    let pool = db::get_pool();
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+        return Err(Status::BadRequest);
+    }
+
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM members WHERE id = $1 AND group_id = $2)")
+            .bind(member_uuid)
+            .bind(auth.group_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch member: {}", e);
+                db::error_status(&e)
+            })?;
+    if !exists {
+        return Err(Status::NotFound);
+    }
+
+    const INVOLVED: &str = "e.group_id = $1 AND (e.paid_by = $2 OR e.transfer_to = $2
+         OR EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id AND s.member_id = $2))";
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM expenses e WHERE {}", INVOLVED))
+        .bind(auth.group_id)
+        .bind(member_uuid)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to count member expenses: {}", e);
+            db::error_status(&e)
+        })?;
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(&format!(
+        "SELECT e.id, e.group_id, e.description, e.amount, e.paid_by, e.expense_type, e.transfer_to, e.currency, e.exchange_rate, e.expense_date, e.created_at, e.split_type, e.notes, e.receipt_url, e.created_by, e.updated_by, e.trip_id, e.refund_of
+         FROM expenses e WHERE {}
+         ORDER BY e.expense_date DESC, e.created_at DESC, e.id DESC LIMIT $3 OFFSET $4",
+        INVOLVED
+    ))
+    .bind(auth.group_id)
+    .bind(member_uuid)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch member expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let mut expenses = Vec::with_capacity(expense_rows.len());
+    for row in expense_rows {
+        let splits = if row.expense_type == ExpenseType::Transfer {
+            Vec::new()
+        } else {
+            fetch_splits(row.id).await?
+        };
+        let amount = currency::round_half_even(expense_in_group_currency(&row), decimals);
+        let share = currency::round_shares(amount, member_shares(&row, &splits), decimals)
+            .into_iter()
+            .find(|(id, _)| *id == member_uuid)
+            .map(|(_, share)| share)
+            .unwrap_or(0.0);
+        let change = currency::round_half_even(
+            balance_deltas(&row, &splits, decimals)
+                .into_iter()
+                .filter(|(id, _)| *id == member_uuid)
+                .map(|(_, delta)| delta)
+                .sum(),
+            decimals,
+        );
+        let paid = row.paid_by == member_uuid;
+        let received = row.transfer_to == Some(member_uuid);
+        expenses.push(MemberExpense {
+            expense: expense_from_row(row, splits),
+            paid,
+            received,
+            share,
+            change,
+        });
+    }
+
+    Ok(Json(MemberExpensePage {
+        member_id: member_uuid,
+        total,
+        limit,
+        offset,
+        expenses,
+    }))
+}
+
+// Expenses involving both members (both in the split, one paid and the other is
+// in the split, or a transfer between them), newest first, with the debt each
+// created between them - requires valid JWT
+#[get("/groups/current/pairs/<a>/<b>/expenses")]
+async fn get_pair_expenses(auth: GroupAuth, a: &str, b: &str) -> Result<Json<PairExpenses>, Status> {
+    let pool = db::get_pool();
+    let a = Uuid::parse_str(a).map_err(|_| Status::BadRequest)?;
+    let b = Uuid::parse_str(b).map_err(|_| Status::BadRequest)?;
+    if a == b {
+        return Err(Status::BadRequest);
+    }
+
+    let names: Vec<(Uuid, String)> =
+        sqlx::query_as("SELECT id, name FROM members WHERE group_id = $1 AND id = ANY($2)")
+            .bind(auth.group_id)
+            .bind([a, b])
+            .fetch_all(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch members: {}", e);
+                db::error_status(&e)
+            })?;
+    let name_of = |id: Uuid| {
+        names
+            .iter()
+            .find(|(member_id, _)| *member_id == id)
+            .map(|(_, name)| name.clone())
+            .ok_or(Status::NotFound)
+    };
+    let (a_name, b_name) = (name_of(a)?, name_of(b)?);
+
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT e.id, e.group_id, e.description, e.amount, e.paid_by, e.expense_type, e.transfer_to, e.currency, e.exchange_rate, e.expense_date, e.created_at, e.split_type, e.notes, e.receipt_url, e.created_by, e.updated_by, e.trip_id, e.refund_of
+         FROM expenses e
+         WHERE e.group_id = $1
+           AND (e.paid_by = $2 OR e.transfer_to = $2
+                OR EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id AND s.member_id = $2))
+           AND (e.paid_by = $3 OR e.transfer_to = $3
+                OR EXISTS (SELECT 1 FROM expense_splits s WHERE s.expense_id = e.id AND s.member_id = $3))
+         ORDER BY e.expense_date DESC, e.created_at DESC, e.id DESC"
+    )
+    .bind(auth.group_id)
+    .bind(a)
+    .bind(b)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch pair expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let mut net = 0.0;
+    let mut expenses = Vec::with_capacity(expense_rows.len());
+    for row in expense_rows {
+        let splits = if row.expense_type == ExpenseType::Transfer {
+            Vec::new()
+        } else {
+            fetch_splits(row.id).await?
+        };
+        // Positive: b owes a (for transfers: a paid b)
+        let owed_to_a: f64 = pair_flows(&row, &splits, decimals)
+            .into_iter()
+            .map(|(from, to, amount)| {
+                if (from, to) == (b, a) {
+                    amount
+                } else if (from, to) == (a, b) {
+                    -amount
+                } else {
+                    0.0
+                }
+            })
+            .sum();
+        // A transfer from a to b is a payment: it leaves b owing a
+        let owed_to_a = if row.expense_type == ExpenseType::Transfer {
+            -owed_to_a
+        } else {
+            owed_to_a
+        };
+        let owed_to_a = currency::round_half_even(owed_to_a, decimals);
+        net += owed_to_a;
+        let direction = if owed_to_a > 0.0 {
+            PairDirection::BOwesA
+        } else if owed_to_a < 0.0 {
+            PairDirection::AOwesB
+        } else {
+            PairDirection::None
+        };
+        expenses.push(PairExpense {
+            expense: expense_from_row(row, splits),
+            direction,
+            amount: owed_to_a.abs(),
+        });
+    }
+
+    Ok(Json(PairExpenses {
+        a,
+        a_name,
+        b,
+        b_name,
+        net: currency::round_half_even(net, decimals),
+        expenses,
+    }))
+}
+
+/// Who owes whom because of one expense, as `(debtor, creditor, amount)`.
+/// For a transfer the sender is recorded as paying the receiver.
+fn pair_flows(
+    expense: &ExpenseRow,
+    splits: &[ExpenseSplitMemberRow],
+    decimals: u32,
+) -> Vec<(Uuid, Uuid, f64)> {
+    let amount = currency::round_half_even(expense_in_group_currency(expense), decimals);
+    let shares = || currency::round_shares(amount, member_shares(expense, splits), decimals);
+    let flows: Vec<(Uuid, Uuid, f64)> = match expense.expense_type {
+        ExpenseType::Transfer => expense
+            .transfer_to
+            .map(|to_id| (expense.paid_by, to_id, amount))
+            .into_iter()
+            .collect(),
+        // The receiver of income owes each split member their share
+        ExpenseType::Income => shares()
+            .into_iter()
+            .map(|(member_id, share)| (expense.paid_by, member_id, share))
+            .collect(),
+        ExpenseType::Expense => shares()
+            .into_iter()
+            .map(|(member_id, share)| (member_id, expense.paid_by, share))
+            .collect(),
+    };
+    flows.into_iter().filter(|(from, to, _)| from != to).collect()
+}
+
+/// The simplified settlement plan, or `Conflict` naming what doesn't add up when
+/// the balances can't be settled (`GET /groups/current/overview` has the details).
+fn settlement_plan(balances: &[Balance], decimals: u32) -> Result<Vec<Settlement>, ApiError> {
+    let places = decimals as usize;
+    settlement::simplify(balances, decimals).map_err(|warning| {
+        let members: Vec<String> = warning
+            .members
+            .iter()
+            .map(|m| format!("{} {:.*}", m.user_name, places, m.amount))
+            .collect();
+        ApiError::new(
+            Status::Conflict,
+            format!(
+                "Balances don't add up to zero ({:.*} unaccounted: {}), so they can't be settled",
+                places,
+                warning.unaccounted,
+                members.join(", ")
+            ),
+        )
+    })
+}
+
+// How much `from` should pay `to` (or the other way round) according to the
+// simplified settlement plan - requires valid JWT
+#[get("/groups/current/settle-up?<from>&<to>")]
+async fn get_settle_up(auth: GroupAuth, from: &str, to: &str) -> Result<Json<SettleUp>, ApiError> {
+    let from = Uuid::parse_str(from).map_err(|_| Status::BadRequest)?;
+    let to = Uuid::parse_str(to).map_err(|_| Status::BadRequest)?;
+    if from == to {
+        return Err(Status::BadRequest.into());
+    }
+
+    let balances = cached_balances(auth.group_id).await?;
+    let name_of = |id: Uuid| {
+        balances
+            .iter()
+            .find(|b| b.user_id == id)
+            .map(|b| b.user_name.clone())
+            .ok_or(Status::NotFound)
+    };
+    let (from_name, to_name) = (name_of(from)?, name_of(to)?);
+
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let plan = settlement_plan(&balances, decimals)?;
+    let (amount, direction) = match plan.iter().find(|s| {
+        (s.from == from && s.to == to) || (s.from == to && s.to == from)
+    }) {
+        Some(s) if s.from == from => (s.amount, SettleUpDirection::FromPaysTo),
+        Some(s) => (s.amount, SettleUpDirection::ToPaysFrom),
+        None => (0.0, SettleUpDirection::None),
+    };
+
+    Ok(Json(SettleUp {
+        from,
+        from_name,
+        to,
+        to_name,
+        amount,
+        direction,
+    }))
+}
+
+// Record every transfer of the simplified settlement plan, dated today, in one
+// transaction - requires valid JWT + settle permission. Does nothing if the
+// group is already settled.
+#[post("/groups/current/settle-all")]
+async fn settle_all(
+    auth: GroupAuth,
+    _writable: Writable,
+) -> Result<Json<SettleAllResult>, ApiError> {
+    if !auth.permissions.has_settle() {
+        return Err(Status::Forbidden.into());
+    }
+    let pool = db::get_pool();
+    let version = group_version(auth.group_id).await?;
+    let balances = cached_balances(auth.group_id).await?;
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+    let plan = settlement_plan(&balances, decimals)?;
+    if plan.is_empty() {
+        return Ok(Json(SettleAllResult {
+            transfers: Vec::new(),
+            balances,
+        }));
+    }
+
+    let currency = group_currency(auth.group_id).await?;
+    let created_by = acting_member(&auth).await?;
+    let expense_date = Utc::now().date_naive();
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        eprintln!("Failed to start transaction: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // The plan is only valid for the balances it was computed from: if anything
+    // changed in the meantime (e.g. a concurrent settle-all), let the client retry
+    let current_version: i64 =
+        sqlx::query_scalar("SELECT version FROM groups WHERE id = $1 FOR UPDATE")
+            .bind(auth.group_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to lock group: {}", e);
+                db::error_status(&e)
+            })?;
+    if current_version != version {
+        return Err(Status::Conflict.into());
+    }
+
+    let mut transfers = Vec::with_capacity(plan.len());
+    for s in &plan {
+        let row = ExpenseRow {
+            id: Uuid::new_v4(),
+            group_id: auth.group_id,
+            description: "Settlement".to_string(),
+            amount: BigDecimal::try_from(s.amount).map_err(|_| Status::InternalServerError)?,
+            paid_by: s.from,
+            expense_type: ExpenseType::Transfer,
+            transfer_to: Some(s.to),
+            currency: currency.clone(),
+            exchange_rate: BigDecimal::from(1),
+            expense_date,
+            created_at: Utc::now(),
+            split_type: "equal".to_string(),
+            notes: None,
+            receipt_url: None,
+            created_by,
+            updated_by: None,
+            trip_id: None,
+            refund_of: None,
+        };
+
+        sqlx::query(
+            "INSERT INTO expenses (id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by) 
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"
+        )
+        .bind(row.id)
+        .bind(row.group_id)
+        .bind(&row.description)
+        .bind(&row.amount)
+        .bind(row.paid_by)
+        .bind(row.expense_type)
+        .bind(row.transfer_to)
+        .bind(&row.currency)
+        .bind(&row.exchange_rate)
+        .bind(row.expense_date)
+        .bind(row.created_at)
+        .bind(&row.split_type)
+        .bind(&row.notes)
+        .bind(&row.receipt_url)
+        .bind(row.created_by)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to record settlement transfer: {}", e);
+            db::error_status(&e)
+        })?;
+
+        transfers.push(expense_from_row(row, Vec::new()));
+    }
+
+    sqlx::query("UPDATE groups SET last_activity_at = NOW() WHERE id = $1")
+        .bind(auth.group_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update last_activity_at: {}", e);
+            db::error_status(&e)
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        eprintln!("Failed to commit settlement transfers: {}", e);
+        db::error_status(&e)
+    })?;
+
+    for expense in &transfers {
+        webhooks::dispatch(
+            auth.group_id,
+            webhooks::EXPENSE_CREATED,
+            serde_json::to_value(expense).unwrap_or_default(),
+        );
+        activity::record(&auth, activity::EXPENSE_CREATED, expense.id, None, Some(expense)).await;
+    }
+
+    Ok(Json(SettleAllResult {
+        transfers,
+        balances: cached_balances(auth.group_id).await?,
+    }))
+}
+
+// Per debtor/creditor pair: what was owed from expenses, what has been paid back
+// via transfers, and what remains - requires valid JWT
+#[get("/groups/current/settlement-progress")]
+async fn get_settlement_progress(auth: GroupAuth) -> Result<Json<Vec<SettlementProgress>>, Status> {
+    let pool = db::get_pool();
+    let decimals = currency::minor_units(&group_currency(auth.group_id).await?);
+
+    let member_rows: Vec<MemberRow> = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1 ORDER BY display_order, id"
+    )
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch members: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1"
+    )
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // Net amounts per unordered pair (a, b) with a < b; positive means a owes b
+    let mut owed: HashMap<(Uuid, Uuid), f64> = HashMap::new();
+    let mut settled: HashMap<(Uuid, Uuid), f64> = HashMap::new();
+    for expense_row in expense_rows {
+        let is_transfer = expense_row.expense_type == ExpenseType::Transfer;
+        let splits = if is_transfer {
+            Vec::new()
+        } else {
+            fetch_splits(expense_row.id).await?
+        };
+        let ledger = if is_transfer { &mut settled } else { &mut owed };
+        for (from, to, amount) in pair_flows(&expense_row, &splits, decimals) {
+            let (key, signed) = if from < to {
+                ((from, to), amount)
+            } else {
+                ((to, from), -amount)
+            };
+            *ledger.entry(key).or_insert(0.0) += signed;
+        }
+    }
+
+    let name_of = |id: Uuid| {
+        member_rows
+            .iter()
+            .find(|m| m.id == id)
+            .map(|m| m.name.clone())
+            .unwrap_or_default()
+    };
+    let mut pairs: Vec<(Uuid, Uuid)> = owed.keys().chain(settled.keys()).copied().collect();
+    pairs.sort();
+    pairs.dedup();
+
+    let mut progress = Vec::new();
+    for (a, b) in pairs {
+        let owed_ab = currency::round_half_even(owed.get(&(a, b)).copied().unwrap_or(0.0), decimals);
+        let settled_ab =
+            currency::round_half_even(settled.get(&(a, b)).copied().unwrap_or(0.0), decimals);
+        if owed_ab.abs() < BALANCE_EPSILON && settled_ab.abs() < BALANCE_EPSILON {
+            continue;
+        }
+        // Orient the pair by the original debt, or by the transfers if nothing was owed
+        let a_is_debtor = if owed_ab.abs() >= BALANCE_EPSILON {
+            owed_ab > 0.0
+        } else {
+            settled_ab > 0.0
+        };
+        let (from, to, original, paid) = if a_is_debtor {
+            (a, b, owed_ab, settled_ab)
+        } else {
+            // `+ 0.0` turns a negated zero into a plain zero
+            (b, a, -owed_ab + 0.0, -settled_ab + 0.0)
+        };
+        progress.push(SettlementProgress {
+            from,
+            from_name: name_of(from),
+            to,
+            to_name: name_of(to),
+            owed: original,
+            settled: paid,
+            remaining: currency::round_half_even(original - paid, decimals),
+        });
+    }
+    progress.sort_by(|x, y| y.remaining.total_cmp(&x.remaining));
+
+    Ok(Json(progress))
+}
+
+// Printable PDF settlement report - requires valid JWT. `?locale=de-DE` picks
+// number and date formatting; the default follows the group currency.
+#[get("/groups/current/report.pdf?<locale>")]
+async fn get_report_pdf(auth: GroupAuth, locale: Option<&str>) -> Result<PdfResponse, ApiError> {
+    let pool = db::get_pool();
+
+    let group_row: GroupRow =
+        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at, default_split, archived_at, locked, default_share_permissions FROM groups WHERE id = $1")
+            .bind(auth.group_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch group: {}", e);
+                db::error_status(&e)
+            })?;
+
+    let locale = match locale {
+        Some(tag) => Locale::parse(tag).ok_or(Status::BadRequest)?,
+        None => Locale::for_currency(&group_row.currency),
+    };
+
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1 AND expense_type = 'expense'"
+    )
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+    let total_spend = currency::round_amount(
+        expense_rows.iter().map(expense_in_group_currency).sum(),
+        &group_row.currency,
+    );
+
+    let balances = compute_balances(auth.group_id, None, true).await?;
+    let decimals = currency::minor_units(&group_row.currency);
+    let data = ReportData {
+        group_name: group_row.name.clone(),
+        currency: group_row.currency,
+        members: balances.iter().map(|b| b.user_name.clone()).collect(),
+        total_spend,
+        settlements: settlement_plan(&balances, decimals)?,
+        balances,
+        locale,
+        generated_on: Utc::now().date_naive(),
+    };
+
+    let pdf = report::render(&data).map_err(|e| {
+        eprintln!("Failed to render report: {}", e);
+        Status::InternalServerError
+    })?;
+    Ok(PdfResponse::new(
+        pdf,
+        &format!("{}-report.pdf", report::file_stem(&group_row.name)),
+    ))
+}
+
+// Get the pairwise debt matrix (before simplification) - requires valid JWT
+#[get("/groups/current/debt-matrix")]
+async fn get_debt_matrix(auth: GroupAuth) -> Result<Json<Vec<DebtEntry>>, Status> {
+    let pool = db::get_pool();
+
+    let expense_rows: Vec<ExpenseRow> = sqlx::query_as(
+        "SELECT id, group_id, description, amount, paid_by, expense_type, transfer_to, currency, exchange_rate, expense_date, created_at, split_type, notes, receipt_url, created_by, updated_by, trip_id, refund_of
+         FROM expenses WHERE group_id = $1 ORDER BY expense_date, created_at, id"
+    )
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch expenses: {}", e);
+        db::error_status(&e)
+    })?;
+
+    // (debtor, creditor) -> amount, in first-seen order
+    let mut matrix: Vec<DebtEntry> = Vec::new();
+    let mut add = |from: Uuid, to: Uuid, amount: f64| {
+        if from == to || amount == 0.0 {
+            return;
+        }
+        match matrix.iter_mut().find(|d| d.from == from && d.to == to) {
+            Some(entry) => entry.amount += amount,
+            None => matrix.push(DebtEntry { from, to, amount }),
+        }
+    };
+
+    for expense_row in &expense_rows {
+        match expense_row.expense_type {
+            ExpenseType::Transfer => {
+                // The receiver now owes the sender the transferred amount
+                if let Some(to_id) = expense_row.transfer_to {
+                    add(to_id, expense_row.paid_by, expense_in_group_currency(expense_row));
+                }
+            }
+            ExpenseType::Income => {
+                // The receiver owes each split member their share
+                let splits = fetch_splits(expense_row.id).await?;
+                for (member_id, share) in member_shares(expense_row, &splits) {
+                    add(expense_row.paid_by, member_id, share);
+                }
+            }
+            ExpenseType::Expense => {
+                // Each split member owes the payer their share
+                let splits = fetch_splits(expense_row.id).await?;
+                for (member_id, share) in member_shares(expense_row, &splits) {
+                    add(member_id, expense_row.paid_by, share);
+                }
+            }
+        }
+    }
+
+    Ok(Json(matrix))
+}
+
+// Members with their current balance, sorted by balance (`?order=desc`, the
+// default, puts whoever is owed the most first) - requires valid JWT
+#[get("/groups/current/members/by-balance?<order>")]
+async fn get_members_by_balance(
+    auth: GroupAuth,
+    order: Option<&str>,
+) -> Result<Json<Vec<MemberBalance>>, Status> {
+    let descending = match order.unwrap_or("desc") {
+        "desc" => true,
+        "asc" => false,
+        _ => return Err(Status::BadRequest),
+    };
+    let balances = cached_balances(auth.group_id).await?;
+    let group = load_group(auth.group_id).await?;
+
+    let mut members: Vec<MemberBalance> = group
+        .members
+        .into_iter()
+        .map(|member| {
+            // Members without any expenses simply have a zero balance; adding
+            // 0.0 turns -0.0 into 0.0 so total_cmp treats all zeros as equal
+            let balance = balances
+                .iter()
+                .find(|b| b.user_id == member.id)
+                .map(|b| b.balance)
+                .unwrap_or(0.0)
+                + 0.0;
+            MemberBalance { member, balance }
+        })
+        .collect();
+    members.sort_by(|a, b| {
+        let by_balance = if descending {
+            b.balance.total_cmp(&a.balance)
+        } else {
+            a.balance.total_cmp(&b.balance)
+        };
+        by_balance.then_with(|| a.member.name.cmp(&b.member.name))
+    });
+
+    Ok(Json(members))
+}
+
+// Get members who owe money, most indebted first - requires valid JWT
+#[get("/groups/current/debtors")]
+async fn get_debtors(auth: GroupAuth) -> Result<Json<Vec<Debtor>>, Status> {
+    let pool = db::get_pool();
+    let balances = compute_balances(auth.group_id, None, true).await?;
+
+    let member_rows: Vec<MemberRow> = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1"
+    )
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        eprintln!("Failed to fetch members: {}", e);
+        db::error_status(&e)
+    })?;
+
+    let mut debtors: Vec<Debtor> = balances
+        .into_iter()
+        .filter(|b| b.balance < -BALANCE_EPSILON)
+        .filter_map(|b| {
+            let member = member_rows.iter().find(|m| m.id == b.user_id)?;
+            Some(Debtor {
+                user_id: b.user_id,
+                user_name: b.user_name,
+                balance: b.balance,
+                paypal_email: member.paypal_email.clone(),
+                iban: member.iban.clone(),
+                preferred_payment_method: member.preferred_payment_method.clone(),
+                venmo_handle: member.venmo_handle.clone(),
+            })
+        })
+        .collect();
+    debtors.sort_by(|a, b| a.balance.total_cmp(&b.balance));
+
+    Ok(Json(debtors))
+}
+
+/// The payment method to use for a payee: their preferred one if its details are
+/// set, otherwise the first one with details (IBAN, then PayPal, then Venmo).
+fn payment_method(member: &MemberRow) -> Option<&'static str> {
+    let available = [
+        ("iban", member.iban.is_some()),
+        ("paypal", member.paypal_email.is_some()),
+        ("venmo", member.venmo_handle.is_some()),
+    ];
+    let preferred = member.preferred_payment_method.as_deref();
+    available
+        .iter()
+        .find(|(method, set)| *set && Some(*method) == preferred)
+        .or_else(|| available.iter().find(|(_, set)| *set))
+        .map(|(method, _)| *method)
+}
+
+// The settlement plan as a batch of payment requests with each payee's payment
+// details, for a payment tool - requires valid JWT. Payments to members without
+// payment details are included with `payable: false`.
+#[get("/groups/current/payment-requests")]
+async fn get_payment_requests(auth: GroupAuth) -> Result<Json<PaymentRequestBatch>, ApiError> {
+    let pool = db::get_pool();
+    let (group_name, code): (String, String) =
+        sqlx::query_as("SELECT name, currency FROM groups WHERE id = $1")
+            .bind(auth.group_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch group: {}", e);
+                db::error_status(&e)
+            })?;
+    let member_rows: Vec<MemberRow> = sqlx::query_as(
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1"
     )
     .bind(auth.group_id)
     .fetch_all(pool)
     .await
     .map_err(|e| {
-        eprintln!("Failed to fetch expenses: {}", e);
-        Status::InternalServerError
+        eprintln!("Failed to fetch members: {}", e);
+        db::error_status(&e)
     })?;
 
-    // Initialize balances
-    let mut balances: Vec<Balance> = member_rows
-        .iter()
-        .map(|m| Balance {
-            user_id: m.id,
-            user_name: m.name.clone(),
-            balance: 0.0,
+    let decimals = currency::minor_units(&code);
+    let plan = settlement_plan(&cached_balances(auth.group_id).await?, decimals)?;
+    let requests = plan
+        .into_iter()
+        .filter_map(|s| {
+            let payee = member_rows.iter().find(|m| m.id == s.to)?;
+            let method = payment_method(payee);
+            Some(PaymentRequest {
+                payer_id: s.from,
+                payer_name: s.from_name.clone(),
+                payee_id: s.to,
+                payee_name: s.to_name,
+                amount: s.amount,
+                amount_minor: currency::to_minor_units(s.amount, decimals),
+                currency: code.clone(),
+                method: method.map(str::to_string),
+                iban: payee.iban.clone(),
+                paypal_email: payee.paypal_email.clone(),
+                venmo_handle: payee.venmo_handle.clone(),
+                payment_note: payee.payment_note.clone(),
+                reference: format!("{}: {}", group_name, s.from_name),
+                payable: method.is_some(),
+            })
         })
         .collect();
 
-    // Calculate balances for each expense
-    for expense_row in expense_rows {
-        let raw_amount = expense_row.amount.to_f64().unwrap_or(0.0);
-        let exchange_rate = expense_row.exchange_rate.to_f64().unwrap_or(1.0);
-        let amount = raw_amount * exchange_rate; // Convert to group currency
-        let paid_by = expense_row.paid_by;
-
-        match expense_row.expense_type.as_str() {
-            "transfer" => {
-                // Direct transfer: sender is owed money back, receiver owes
-                if let Some(sender) = balances.iter_mut().find(|b| b.user_id == paid_by) {
-                    sender.balance += amount;
-                }
-                if let Some(to_id) = expense_row.transfer_to {
-                    if let Some(receiver) = balances.iter_mut().find(|b| b.user_id == to_id) {
-                        receiver.balance -= amount;
-                    }
-                }
-            }
-            "income" => {
-                // External income: receiver holds money, split members are owed their share
-                let splits: Vec<ExpenseSplitMemberRow> = sqlx::query_as(
-                    "SELECT member_id, share FROM expense_splits WHERE expense_id = $1",
-                )
-                .bind(expense_row.id)
-                .fetch_all(pool)
-                .await
-                .map_err(|e| {
-                    eprintln!("Failed to fetch expense splits: {}", e);
-                    Status::InternalServerError
-                })?;
-
-                let split_count = splits.len() as f64;
-                if split_count == 0.0 {
-                    continue;
-                }
-
-                // The receiver holds the money (owes distribution)
-                if let Some(receiver) = balances.iter_mut().find(|b| b.user_id == paid_by) {
-                    receiver.balance -= amount;
-                }
-
-                // Each split member is owed their share
-                for split in &splits {
-                    let member_amount = match expense_row.split_type.as_str() {
-                        "percentage" => {
-                            let pct = split
-                                .share
-                                .as_ref()
-                                .and_then(|v| v.to_f64())
-                                .unwrap_or(100.0 / split_count);
-                            amount * pct / 100.0
-                        }
-                        "exact" => {
-                            let exact = split
-                                .share
-                                .as_ref()
-                                .and_then(|v| v.to_f64())
-                                .unwrap_or(raw_amount / split_count);
-                            exact * exchange_rate
-                        }
-                        "shares" => {
-                            let total_shares: f64 = splits.iter()
-                                .map(|s| s.share.as_ref().and_then(|v| v.to_f64()).unwrap_or(0.0))
-                                .sum();
-                            let my_shares = split.share.as_ref().and_then(|v| v.to_f64()).unwrap_or(0.0);
-                            if total_shares > 0.0 { amount * my_shares / total_shares } else { 0.0 }
-                        }
-                        _ => amount / split_count, // equal
-                    };
-                    if let Some(member) = balances.iter_mut().find(|b| b.user_id == split.member_id)
-                    {
-                        member.balance += member_amount;
-                    }
-                }
-            }
-            _ => {
-                // Regular expense: payer gets credit, split members owe
-                let splits: Vec<ExpenseSplitMemberRow> = sqlx::query_as(
-                    "SELECT member_id, share FROM expense_splits WHERE expense_id = $1",
-                )
-                .bind(expense_row.id)
-                .fetch_all(pool)
-                .await
-                .map_err(|e| {
-                    eprintln!("Failed to fetch expense splits: {}", e);
-                    Status::InternalServerError
-                })?;
-
-                let split_count = splits.len() as f64;
-                if split_count == 0.0 {
-                    continue;
-                }
-
-                // The payer gets credit
-                if let Some(payer) = balances.iter_mut().find(|b| b.user_id == paid_by) {
-                    payer.balance += amount;
-                }
-
-                // Each person in the split owes
-                for split in &splits {
-                    let member_amount = match expense_row.split_type.as_str() {
-                        "percentage" => {
-                            let pct = split
-                                .share
-                                .as_ref()
-                                .and_then(|v| v.to_f64())
-                                .unwrap_or(100.0 / split_count);
-                            amount * pct / 100.0
-                        }
-                        "exact" => {
-                            let exact = split
-                                .share
-                                .as_ref()
-                                .and_then(|v| v.to_f64())
-                                .unwrap_or(raw_amount / split_count);
-                            exact * exchange_rate
-                        }
-                        "shares" => {
-                            let total_shares: f64 = splits.iter()
-                                .map(|s| s.share.as_ref().and_then(|v| v.to_f64()).unwrap_or(0.0))
-                                .sum();
-                            let my_shares = split.share.as_ref().and_then(|v| v.to_f64()).unwrap_or(0.0);
-                            if total_shares > 0.0 { amount * my_shares / total_shares } else { 0.0 }
-                        }
-                        _ => amount / split_count, // equal
-                    };
-                    if let Some(member) = balances.iter_mut().find(|b| b.user_id == split.member_id)
-                    {
-                        member.balance -= member_amount;
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(Json(balances))
+    Ok(Json(PaymentRequestBatch {
+        group_id: auth.group_id,
+        group_name,
+        currency: code,
+        created_at: Utc::now(),
+        requests,
+    }))
 }
 
 // Get current token's permissions
@@ -835,9 +6078,78 @@ fn get_permissions(auth: GroupAuth) -> Json<PermissionsResponse> {
         can_update_payment: p.has_update_payment(),
         can_add_expenses: p.has_add_expenses(),
         can_edit_expenses: p.has_edit_expenses(),
+        can_settle: p.has_settle(),
+    })
+}
+
+/// Tokens expiring within this many days are reported as expiring soon.
+/// Defaults to 30; override with `TOKEN_EXPIRY_WARNING_DAYS`.
+static TOKEN_EXPIRY_WARNING_DAYS: Lazy<i64> = Lazy::new(|| {
+    std::env::var("TOKEN_EXPIRY_WARNING_DAYS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n >= 0)
+        .unwrap_or(30)
+});
+
+/// Describe a token's non-sensitive claims.
+fn token_info(
+    group_id: Uuid,
+    exp: usize,
+    p: &Permissions,
+    member_id: Option<Uuid>,
+    jti: Option<String>,
+) -> Result<TokenInfo, Status> {
+    let expires_at =
+        chrono::DateTime::from_timestamp(exp as i64, 0).ok_or(Status::InternalServerError)?;
+    let expiring_soon =
+        expires_at - Utc::now() <= chrono::Duration::days(*TOKEN_EXPIRY_WARNING_DAYS);
+    Ok(TokenInfo {
+        group_id,
+        expires_at,
+        expiring_soon,
+        permissions: PermissionsResponse {
+            can_delete_group: p.has_delete_group(),
+            can_manage_members: p.has_manage_members(),
+            can_update_payment: p.has_update_payment(),
+            can_add_expenses: p.has_add_expenses(),
+            can_edit_expenses: p.has_edit_expenses(),
+            can_settle: p.has_settle(),
+        },
+        member_id,
+        jti,
     })
 }
 
+// Describe the current token (group, expiry, permissions) without echoing it back
+#[get("/groups/current/token-info")]
+fn get_token_info(auth: GroupAuth) -> Result<Json<TokenInfo>, Status> {
+    token_info(auth.group_id, auth.exp, &auth.permissions, auth.member_id, auth.jti).map(Json)
+}
+
+// Describe another token of the current group, e.g. a share link's token before it is
+// activated - requires valid JWT. `BadRequest` if the inspected token is invalid,
+// expired, revoked or belongs to a different group.
+#[post("/groups/current/inspect-token", data = "<request>")]
+async fn inspect_token(
+    auth: GroupAuth,
+    request: Json<InspectTokenRequest>,
+) -> Result<Json<TokenInfo>, Status> {
+    let claims = validate_token(request.token.trim()).map_err(|_| Status::BadRequest)?;
+    if claims.group_id != auth.group_id {
+        return Err(Status::BadRequest);
+    }
+    let revoked = claims.is_revoked().await.map_err(|e| {
+        eprintln!("Failed to check token revocation: {}", e);
+        db::error_status(&e)
+    })?;
+    if revoked {
+        return Err(Status::BadRequest);
+    }
+    let permissions = claims.effective_permissions();
+    token_info(claims.group_id, claims.exp, &permissions, claims.sub, claims.jti).map(Json)
+}
+
 /// Generate a random alphanumeric code of the given length.
 /// Uses `rand::rng()` which returns `ThreadRng` — a CSPRNG (ChaCha12 seeded
 /// from the OS). Safe for generating unguessable share codes.
@@ -852,8 +6164,86 @@ fn random_code(len: usize) -> String {
         .collect()
 }
 
+/// Permissions of a new share link: fields the request leaves out come from the
+/// group's `default_share_permissions` (granted if it has none), and the result
+/// is capped by the caller's own permissions.
+async fn share_link_permissions(auth: &GroupAuth, requested: Permissions) -> Result<Permissions, Status> {
+    let defaults: Option<sqlx::types::Json<PermissionsResponse>> =
+        sqlx::query_scalar("SELECT default_share_permissions FROM groups WHERE id = $1")
+            .bind(auth.group_id)
+            .fetch_optional(db::get_pool())
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch default share permissions: {}", e);
+                db::error_status(&e)
+            })?
+            .flatten();
+    let requested = match defaults {
+        Some(sqlx::types::Json(d)) => Permissions {
+            can_delete_group: requested.can_delete_group.or(Some(d.can_delete_group)),
+            can_manage_members: requested.can_manage_members.or(Some(d.can_manage_members)),
+            can_update_payment: requested.can_update_payment.or(Some(d.can_update_payment)),
+            can_add_expenses: requested.can_add_expenses.or(Some(d.can_add_expenses)),
+            can_edit_expenses: requested.can_edit_expenses.or(Some(d.can_edit_expenses)),
+            can_settle: requested.can_settle.or(Some(d.can_settle)),
+        },
+        None => requested,
+    };
+    Ok(requested.cap_by(&auth.permissions))
+}
+
+/// Store a share code with the given (already capped) permissions and limits.
+async fn insert_share_link(
+    group_id: Uuid,
+    permissions: &PermissionsResponse,
+    max_uses: Option<i32>,
+    expires_at: Option<DateTime<Utc>>,
+    member_id: Option<Uuid>,
+) -> Result<String, Status> {
+    let pool = db::get_pool();
+    // Generate a unique 20-char code (retry on collision)
+    let code = loop {
+        let candidate = random_code(20);
+        let exists: bool =
+            sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM share_links WHERE code = $1)")
+                .bind(&candidate)
+                .fetch_one(pool)
+                .await
+                .map_err(|e| {
+                    eprintln!("DB error checking share code: {}", e);
+                    db::error_status(&e)
+                })?;
+        if !exists {
+            break candidate;
+        }
+    };
+
+    sqlx::query(
+        "INSERT INTO share_links (code, group_id, can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses, can_settle, max_uses, expires_at, member_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"
+    )
+    .bind(&code)
+    .bind(group_id)
+    .bind(permissions.can_delete_group)
+    .bind(permissions.can_manage_members)
+    .bind(permissions.can_update_payment)
+    .bind(permissions.can_add_expenses)
+    .bind(permissions.can_edit_expenses)
+    .bind(permissions.can_settle)
+    .bind(max_uses)
+    .bind(expires_at)
+    .bind(member_id)
+    .execute(pool)
+    .await
+    .map_err(|e| { eprintln!("Failed to insert share link: {}", e); db::error_status(&e) })?;
+    Ok(code)
+}
+
+/// Longest allowed share link lifetime (10 years, like the tokens themselves).
+const MAX_SHARE_LINK_HOURS: i64 = 24 * 3650;
+
 // Generate share link with selected permissions (capped by caller's own)
-// Now stores a short code in the DB instead of returning a raw JWT
+// Now stores a short code in the DB instead of returning a raw JWT.
+// `max_uses` / `expires_in_hours` limit how often and how long the code can be redeemed.
 #[post("/groups/current/share", data = "<request>")]
 async fn generate_share_link(
     auth: GroupAuth,
@@ -865,104 +6255,271 @@ async fn generate_share_link(
         can_update_payment: request.can_update_payment,
         can_add_expenses: request.can_add_expenses,
         can_edit_expenses: request.can_edit_expenses,
+        can_settle: request.can_settle,
     };
-    let effective = requested.cap_by(&auth.permissions);
+    let effective = share_link_permissions(&auth, requested).await?;
     let pool = db::get_pool();
 
+    if request.max_uses.is_some_and(|n| n < 1)
+        || request
+            .expires_in_hours
+            .is_some_and(|h| !(1..=MAX_SHARE_LINK_HOURS).contains(&h))
+    {
+        return Err(Status::BadRequest);
+    }
+    let expires_at = request
+        .expires_in_hours
+        .map(|h| Utc::now() + chrono::Duration::hours(h));
+
+    // A member-bound token can only hand out links bound to the same member
+    if let Some(member_id) = request.member_id {
+        if auth.member_id.is_some_and(|own| own != member_id) {
+            return Err(Status::Forbidden);
+        }
+        let in_group: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM members WHERE id = $1 AND group_id = $2)",
+        )
+        .bind(member_id)
+        .bind(auth.group_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch member: {}", e);
+            db::error_status(&e)
+        })?;
+        if !in_group {
+            return Err(Status::BadRequest);
+        }
+    }
+
     let dg = effective.has_delete_group();
     let mm = effective.has_manage_members();
     let up = effective.has_update_payment();
     let ae = effective.has_add_expenses();
     let ee = effective.has_edit_expenses();
+    let st = effective.has_settle();
+    let permissions = PermissionsResponse {
+        can_delete_group: dg,
+        can_manage_members: mm,
+        can_update_payment: up,
+        can_add_expenses: ae,
+        can_edit_expenses: ee,
+        can_settle: st,
+    };
 
-    // Return an existing share link if one already exists with the same group + permissions
+    // Return an existing share link if one already exists with the same group + permissions.
+    // Only unlimited links are reused; limited ones are always new.
     // Exclude old 16-char codes so a new 20-char code is generated instead
-    let existing: Option<String> = sqlx::query_scalar(
-        "SELECT code FROM share_links WHERE group_id = $1 AND can_delete_group = $2 AND can_manage_members = $3 AND can_update_payment = $4 AND can_add_expenses = $5 AND can_edit_expenses = $6 AND LENGTH(code) >= 20 LIMIT 1"
+    if request.max_uses.is_none() && expires_at.is_none() {
+        let existing: Option<String> = sqlx::query_scalar(
+            "SELECT code FROM share_links WHERE group_id = $1 AND can_delete_group = $2 AND can_manage_members = $3 AND can_update_payment = $4 AND can_add_expenses = $5 AND can_edit_expenses = $6 AND can_settle = $7 AND LENGTH(code) >= 20 AND max_uses IS NULL AND expires_at IS NULL AND member_id IS NOT DISTINCT FROM $8 LIMIT 1"
+        )
+        .bind(auth.group_id)
+        .bind(dg)
+        .bind(mm)
+        .bind(up)
+        .bind(ae)
+        .bind(ee)
+        .bind(st)
+        .bind(request.member_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| { eprintln!("DB error checking existing share link: {}", e); db::error_status(&e) })?;
+
+        if let Some(code) = existing {
+            return Ok(Json(ShareCodeResponse {
+                code,
+                permissions,
+                max_uses: None,
+                expires_at: None,
+                member_id: request.member_id,
+            }));
+        }
+    }
+
+    let code = insert_share_link(
+        auth.group_id,
+        &permissions,
+        request.max_uses,
+        expires_at,
+        request.member_id,
+    )
+    .await?;
+
+    Ok(Json(ShareCodeResponse {
+        code,
+        permissions,
+        max_uses: request.max_uses,
+        expires_at,
+        member_id: request.member_id,
+    }))
+}
+
+/// Default lifetime of a member invite link (one week).
+const INVITE_EXPIRY_HOURS: i64 = 24 * 7;
+
+// Invite a member: email them a single-use link that signs them in with a token
+// bound to them - requires valid JWT + manage_members permission. Without SMTP
+// nothing is sent and the link is returned instead.
+#[post("/groups/current/members/<member_id>/invite", data = "<request>")]
+async fn invite_member(
+    auth: GroupAuth,
+    _writable: Writable,
+    member_id: &str,
+    request: Option<Json<InviteMemberRequest>>,
+) -> Result<Json<MemberInviteResponse>, ApiError> {
+    if !auth.permissions.has_manage_members() {
+        return Err(Status::Forbidden.into());
+    }
+    let pool = db::get_pool();
+    let member_uuid = Uuid::parse_str(member_id).map_err(|_| Status::BadRequest)?;
+    let request = request.map(Json::into_inner).unwrap_or_default();
+    // Like share links, a member-bound token can only invite its own member
+    if auth.member_id.is_some_and(|own| own != member_uuid) {
+        return Err(Status::Forbidden.into());
+    }
+    let expires_in_hours = request.expires_in_hours.unwrap_or(INVITE_EXPIRY_HOURS);
+    if !(1..=MAX_SHARE_LINK_HOURS).contains(&expires_in_hours) {
+        return Err(ApiError::bad_request(format!(
+            "expires_in_hours must be between 1 and {}",
+            MAX_SHARE_LINK_HOURS
+        )));
+    }
+
+    let email = request
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty());
+    if let Some(email) = email
+        && (email.len() > 255 || !email.contains('@'))
+    {
+        return Err(ApiError::bad_request("Invalid email address"));
+    }
+    let (member_name, email, group_name): (String, Option<String>, String) = sqlx::query_as(
+        "UPDATE members m SET email = COALESCE($1, m.email) FROM groups g
+         WHERE m.id = $2 AND m.group_id = $3 AND g.id = m.group_id
+         RETURNING m.name, m.email, g.name",
     )
+    .bind(email)
+    .bind(member_uuid)
     .bind(auth.group_id)
-    .bind(dg)
-    .bind(mm)
-    .bind(up)
-    .bind(ae)
-    .bind(ee)
     .fetch_optional(pool)
     .await
-    .map_err(|e| { eprintln!("DB error checking existing share link: {}", e); Status::InternalServerError })?;
-
-    if let Some(code) = existing {
-        return Ok(Json(ShareCodeResponse {
-            code,
-            permissions: PermissionsResponse {
-                can_delete_group: dg,
-                can_manage_members: mm,
-                can_update_payment: up,
-                can_add_expenses: ae,
-                can_edit_expenses: ee,
-            },
-        }));
+    .map_err(|e| {
+        eprintln!("Failed to update member email: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+    if notifications::enabled() && email.is_none() {
+        return Err(ApiError::bad_request("The member has no email address"));
     }
 
-    // Generate a unique 20-char code (retry on collision)
-    let code = loop {
-        let candidate = random_code(20);
-        let exists: bool =
-            sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM share_links WHERE code = $1)")
-                .bind(&candidate)
-                .fetch_one(pool)
-                .await
-                .map_err(|e| {
-                    eprintln!("DB error checking share code: {}", e);
-                    Status::InternalServerError
-                })?;
-        if !exists {
-            break candidate;
+    let requested = Permissions {
+        can_delete_group: request.can_delete_group,
+        can_manage_members: request.can_manage_members,
+        can_update_payment: request.can_update_payment,
+        can_add_expenses: request.can_add_expenses,
+        can_edit_expenses: request.can_edit_expenses,
+        can_settle: request.can_settle,
+    };
+    let effective = share_link_permissions(&auth, requested).await?;
+    let permissions = PermissionsResponse {
+        can_delete_group: effective.has_delete_group(),
+        can_manage_members: effective.has_manage_members(),
+        can_update_payment: effective.has_update_payment(),
+        can_add_expenses: effective.has_add_expenses(),
+        can_edit_expenses: effective.has_edit_expenses(),
+        can_settle: effective.has_settle(),
+    };
+    let expires_at = Utc::now() + chrono::Duration::hours(expires_in_hours);
+    let code =
+        insert_share_link(auth.group_id, &permissions, Some(1), Some(expires_at), Some(member_uuid))
+            .await?;
+    let link = notifications::join_link(&code);
+
+    let sent = match &email {
+        Some(recipient_email) if notifications::enabled() => {
+            let invite = notifications::MemberInvite {
+                recipient_name: member_name,
+                recipient_email: recipient_email.clone(),
+                group_name,
+                link: link.clone(),
+                expires_at,
+            };
+            if !notifications::send_member_invite(&invite).await {
+                // Don't leave a working link behind that nobody received
+                sqlx::query("DELETE FROM share_links WHERE code = $1")
+                    .bind(&code)
+                    .execute(pool)
+                    .await
+                    .map_err(|e| {
+                        eprintln!("Failed to delete share link: {}", e);
+                        db::error_status(&e)
+                    })?;
+                return Err(ApiError::new(Status::BadGateway, "Failed to send the invite email"));
+            }
+            true
         }
+        _ => false,
     };
 
-    sqlx::query(
-        "INSERT INTO share_links (code, group_id, can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses) VALUES ($1, $2, $3, $4, $5, $6, $7)"
-    )
-    .bind(&code)
-    .bind(auth.group_id)
-    .bind(dg)
-    .bind(mm)
-    .bind(up)
-    .bind(ae)
-    .bind(ee)
-    .execute(pool)
-    .await
-    .map_err(|e| { eprintln!("Failed to insert share link: {}", e); Status::InternalServerError })?;
-
-    Ok(Json(ShareCodeResponse {
-        code,
-        permissions: PermissionsResponse {
-            can_delete_group: dg,
-            can_manage_members: mm,
-            can_update_payment: up,
-            can_add_expenses: ae,
-            can_edit_expenses: ee,
-        },
+    Ok(Json(MemberInviteResponse {
+        member_id: member_uuid,
+        email,
+        sent,
+        permissions,
+        expires_at,
+        link: (!sent).then_some(link),
     }))
 }
 
-// Redeem a short share code → returns a JWT token (no auth required)
+// Redeem a short share code → returns a JWT token (no auth required).
+// Unknown codes are 404; expired or used-up codes are 401.
 #[post("/share/redeem", data = "<request>")]
 async fn redeem_share_code(
     _rate_limit: RocketGovernor<'_, RedeemRateLimit>,
     request: Json<RedeemShareCodeRequest>,
-) -> Result<Json<ShareLinkResponse>, Status> {
+) -> Result<Json<ShareLinkResponse>, ApiError> {
     let pool = db::get_pool();
 
-    let row = sqlx::query_as::<_, (Uuid, bool, bool, bool, bool, bool)>(
-        "SELECT group_id, can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses FROM share_links WHERE code = $1"
+    // Check for room before counting the use, so a full group doesn't use up the link
+    let link_group: Option<Uuid> =
+        sqlx::query_scalar("SELECT group_id FROM share_links WHERE code = $1")
+            .bind(&request.code)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("DB error looking up share code: {}", e);
+                db::error_status(&e)
+            })?;
+    if let Some(group_id) = link_group {
+        ensure_token_capacity(group_id).await?;
+    }
+
+    // Count the use atomically so concurrent redemptions can't exceed max_uses
+    let row = sqlx::query_as::<_, (Uuid, bool, bool, bool, bool, bool, bool, Option<Uuid>)>(
+        "UPDATE share_links SET use_count = use_count + 1
+         WHERE code = $1 AND (max_uses IS NULL OR use_count < max_uses) AND (expires_at IS NULL OR expires_at > NOW())
+         RETURNING group_id, can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses, can_settle, member_id"
     )
     .bind(&request.code)
     .fetch_optional(pool)
     .await
-    .map_err(|e| { eprintln!("DB error redeeming share code: {}", e); Status::InternalServerError })?;
+    .map_err(|e| { eprintln!("DB error redeeming share code: {}", e); db::error_status(&e) })?;
 
-    let (group_id, dg, mm, up, ae, ee) = row.ok_or(Status::NotFound)?;
+    let Some((group_id, dg, mm, up, ae, ee, st, link_member)) = row else {
+        let exists: bool =
+            sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM share_links WHERE code = $1)")
+                .bind(&request.code)
+                .fetch_one(pool)
+                .await
+                .map_err(|e| {
+                    eprintln!("DB error checking share code: {}", e);
+                    db::error_status(&e)
+                })?;
+        return Err(if exists { Status::Unauthorized } else { Status::NotFound }.into());
+    };
 
     let link_perms = Permissions {
         can_delete_group: Some(dg),
@@ -970,25 +6527,38 @@ async fn redeem_share_code(
         can_update_payment: Some(up),
         can_add_expenses: Some(ae),
         can_edit_expenses: Some(ee),
+        can_settle: Some(st),
     };
 
-    // If user sent an existing token for the same group, merge permissions
-    let final_perms = if let Some(ref existing) = request.existing_token {
-        if let Ok(claims) = validate_token(existing) {
-            if claims.group_id == group_id {
-                claims.effective_permissions().union_with(&link_perms)
-            } else {
-                link_perms
-            }
-        } else {
-            link_perms
+    // If user sent an existing (unrevoked) token for the same group, merge
+    // permissions (and keep its member binding unless the link brings its own)
+    let mut existing_claims = request
+        .existing_token
+        .as_deref()
+        .and_then(|t| validate_token(t).ok())
+        .filter(|claims| claims.group_id == group_id);
+    if let Some(claims) = &existing_claims
+        && claims.is_revoked().await.map_err(|e| {
+            eprintln!("Failed to check token revocation: {}", e);
+            db::error_status(&e)
+        })?
+    {
+        existing_claims = None;
+    }
+    let (final_perms, member_id) = match (existing_claims, request.existing_token.as_deref()) {
+        (Some(claims), Some(existing_token)) => {
+            // Like merge-token: only permissions the token states are carried over
+            let (existing_perms, _) =
+                stated_permissions(existing_token).map_err(|_| Status::BadRequest)?;
+            (
+                existing_perms.union_with(&link_perms),
+                link_member.or(claims.sub),
+            )
         }
-    } else {
-        link_perms
+        _ => (link_perms, link_member),
     };
 
-    let token = generate_token(group_id, Some(final_perms.clone()))
-        .map_err(|_| Status::InternalServerError)?;
+    let token = issue_token(group_id, final_perms.clone(), member_id).await?;
 
     Ok(Json(ShareLinkResponse {
         token,
@@ -998,38 +6568,170 @@ async fn redeem_share_code(
             can_update_payment: final_perms.has_update_payment(),
             can_add_expenses: final_perms.has_add_expenses(),
             can_edit_expenses: final_perms.has_edit_expenses(),
+            can_settle: final_perms.has_settle(),
         },
     }))
 }
 
 // Merge two tokens for the same group → new token with the union of permissions
 #[post("/groups/current/merge-token", data = "<request>")]
-fn merge_token(
+async fn merge_token(
     auth: GroupAuth,
     request: Json<MergeTokenRequest>,
-) -> Result<Json<ShareLinkResponse>, Status> {
+) -> Result<Json<ShareLinkResponse>, ApiError> {
     let other_claims = validate_token(&request.other_token).map_err(|_| Status::BadRequest)?;
 
     // Both tokens must be for the same group
     if other_claims.group_id != auth.group_id {
-        return Err(Status::BadRequest);
+        return Err(Status::BadRequest.into());
+    }
+    let revoked = other_claims.is_revoked().await.map_err(|e| {
+        eprintln!("Failed to check token revocation: {}", e);
+        db::error_status(&e)
+    })?;
+    if revoked {
+        return Err(Status::BadRequest.into());
+    }
+    // Tokens of two different members can't be combined into one identity
+    if let (Some(mine), Some(theirs)) = (auth.member_id, other_claims.sub)
+        && mine != theirs
+    {
+        eprintln!(
+            "Rejected merge of tokens bound to different members in group {}",
+            auth.group_id
+        );
+        return Err(Status::BadRequest.into());
     }
 
-    let merged = auth
-        .permissions
-        .union_with(&other_claims.effective_permissions());
-    let token = generate_token(auth.group_id, Some(merged.clone()))
-        .map_err(|_| Status::InternalServerError)?;
+    // Only carry over what the other token explicitly grants, so the merge can't
+    // turn a missing permission claim into full access
+    let (other_permissions, complete) =
+        stated_permissions(&request.other_token).map_err(|_| Status::BadRequest)?;
+    if !complete {
+        eprintln!(
+            "Merging a token without explicit permissions in group {}; only stated permissions are kept",
+            auth.group_id
+        );
+    }
+    let merged = auth.permissions.union_with(&other_permissions);
+    ensure_token_capacity(auth.group_id).await?;
+    let token = issue_token(
+        auth.group_id,
+        merged.clone(),
+        auth.member_id.or(other_claims.sub),
+    )
+    .await?;
+
+    Ok(Json(ShareLinkResponse {
+        token,
+        permissions: PermissionsResponse {
+            can_delete_group: merged.has_delete_group(),
+            can_manage_members: merged.has_manage_members(),
+            can_update_payment: merged.has_update_payment(),
+            can_add_expenses: merged.has_add_expenses(),
+            can_edit_expenses: merged.has_edit_expenses(),
+            can_settle: merged.has_settle(),
+        },
+    }))
+}
+
+// Mint a token for the caller's own use with a subset of their permissions,
+// e.g. to browse read-only - requires valid JWT
+#[post("/groups/current/scoped-token", data = "<request>")]
+async fn scoped_token(
+    auth: GroupAuth,
+    request: Json<ScopedTokenRequest>,
+) -> Result<Json<ShareLinkResponse>, ApiError> {
+    let requested = Permissions {
+        can_delete_group: Some(request.can_delete_group.unwrap_or(true)),
+        can_manage_members: Some(request.can_manage_members.unwrap_or(true)),
+        can_update_payment: Some(request.can_update_payment.unwrap_or(true)),
+        can_add_expenses: Some(request.can_add_expenses.unwrap_or(true)),
+        can_edit_expenses: Some(request.can_edit_expenses.unwrap_or(true)),
+        can_settle: Some(request.can_settle.unwrap_or(true)),
+    };
+    let scoped = requested.cap_by(&auth.permissions);
+    // Still acts as the same member, if the caller is bound to one
+    ensure_token_capacity(auth.group_id).await?;
+    let token = issue_token(auth.group_id, scoped.clone(), auth.member_id).await?;
+
+    Ok(Json(ShareLinkResponse {
+        token,
+        permissions: PermissionsResponse {
+            can_delete_group: scoped.has_delete_group(),
+            can_manage_members: scoped.has_manage_members(),
+            can_update_payment: scoped.has_update_payment(),
+            can_add_expenses: scoped.has_add_expenses(),
+            can_edit_expenses: scoped.has_edit_expenses(),
+            can_settle: scoped.has_settle(),
+        },
+    }))
+}
+
+// Mint a fresh creator token ("reset sharing"). With `revoke_existing`, every
+// token issued before it stops working, the group moves on to a new signing key
+// and all share links are deleted (requires all permissions)
+#[post("/groups/current/new-owner-token", data = "<request>")]
+async fn new_owner_token(
+    auth: GroupAuth,
+    request: Option<Json<NewOwnerTokenRequest>>,
+) -> Result<Json<NewOwnerTokenResponse>, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    let revoke_existing = request.is_some_and(|r| r.revoke_existing);
+
+    let token = if revoke_existing {
+        let pool = db::get_pool();
+        let mut tx = pool.begin().await.map_err(|e| {
+            eprintln!("Failed to start transaction: {}", e);
+            db::error_status(&e)
+        })?;
+        // Token `iat`s are whole seconds: revoke everything issued up to and including
+        // this second, and past the last revocation in case that was this second too
+        let revoked_until = DateTime::from_timestamp(Utc::now().timestamp(), 0)
+            .ok_or(Status::InternalServerError)?;
+        let revoked_until: DateTime<Utc> = sqlx::query_scalar(
+            "UPDATE groups
+             SET tokens_revoked_before = GREATEST($1, tokens_revoked_before + INTERVAL '1 second'),
+                 key_version = key_version + 1
+             WHERE id = $2
+             RETURNING tokens_revoked_before",
+        )
+        .bind(revoked_until)
+        .bind(auth.group_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to revoke tokens: {}", e);
+            db::error_status(&e)
+        })?;
+        // Share links would hand out new tokens to whoever still has them
+        sqlx::query("DELETE FROM share_links WHERE group_id = $1")
+            .bind(auth.group_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to delete share links: {}", e);
+                db::error_status(&e)
+            })?;
+        // Issued the second after the revocation and signed with the new key version,
+        // in the same transaction so the group isn't left without a working owner token
+        let issued_at = revoked_until + chrono::Duration::seconds(1);
+        let token =
+            issue_token_in(&mut tx, auth.group_id, Permissions::all(), None, issued_at).await?;
+        tx.commit().await.map_err(|e| {
+            eprintln!("Failed to commit token revocation: {}", e);
+            db::error_status(&e)
+        })?;
+        token
+    } else {
+        issue_token(auth.group_id, Permissions::all(), None).await?
+    };
 
-    Ok(Json(ShareLinkResponse {
+    Ok(Json(NewOwnerTokenResponse {
         token,
-        permissions: PermissionsResponse {
-            can_delete_group: merged.has_delete_group(),
-            can_manage_members: merged.has_manage_members(),
-            can_update_payment: merged.has_update_payment(),
-            can_add_expenses: merged.has_add_expenses(),
-            can_edit_expenses: merged.has_edit_expenses(),
-        },
+        revoked_existing: revoke_existing,
     }))
 }
 
@@ -1040,25 +6742,32 @@ async fn list_share_links(auth: GroupAuth) -> Result<Json<Vec<ShareLinkItem>>, S
         return Err(Status::Forbidden);
     }
     let pool = db::get_pool();
-    let rows = sqlx::query_as::<_, (String, bool, bool, bool, bool, bool, chrono::DateTime<chrono::Utc>)>(
-        "SELECT code, can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses, created_at FROM share_links WHERE group_id = $1 ORDER BY created_at DESC"
+    let rows = sqlx::query_as::<_, (String, bool, bool, bool, bool, bool, bool, chrono::DateTime<chrono::Utc>, Option<i32>, i32, Option<chrono::DateTime<chrono::Utc>>, Option<Uuid>)>(
+        "SELECT code, can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses, can_settle, created_at, max_uses, use_count, expires_at, member_id FROM share_links WHERE group_id = $1 ORDER BY created_at DESC"
     )
     .bind(auth.group_id)
     .fetch_all(pool)
     .await
-    .map_err(|e| { eprintln!("DB error listing share links: {}", e); Status::InternalServerError })?;
+    .map_err(|e| { eprintln!("DB error listing share links: {}", e); db::error_status(&e) })?;
 
     let items: Vec<ShareLinkItem> = rows
         .into_iter()
-        .map(|(code, dg, mm, up, ae, ee, created_at)| ShareLinkItem {
-            code,
-            can_delete_group: dg,
-            can_manage_members: mm,
-            can_update_payment: up,
-            can_add_expenses: ae,
-            can_edit_expenses: ee,
-            created_at: created_at.to_rfc3339(),
-        })
+        .map(
+            |(code, dg, mm, up, ae, ee, st, created_at, max_uses, use_count, expires_at, member_id)| ShareLinkItem {
+                code,
+                can_delete_group: dg,
+                can_manage_members: mm,
+                can_update_payment: up,
+                can_add_expenses: ae,
+                can_edit_expenses: ee,
+                can_settle: st,
+                created_at: created_at.to_rfc3339(),
+                max_uses,
+                use_count,
+                expires_at,
+                member_id,
+            },
+        )
         .collect();
 
     Ok(Json(items))
@@ -1078,7 +6787,233 @@ async fn delete_share_link(auth: GroupAuth, code: &str) -> Result<Status, Status
         .await
         .map_err(|e| {
             eprintln!("DB error deleting share link: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
+        })?;
+
+    if result.rows_affected() == 0 {
+        return Err(Status::NotFound);
+    }
+    Ok(Status::NoContent)
+}
+
+// List the group's active tokens, newest first (requires all permissions).
+// Tokens issued before the registry existed are not listed.
+#[get("/groups/current/tokens")]
+async fn list_tokens(auth: GroupAuth) -> Result<Json<Vec<IssuedTokenItem>>, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    let rows = sqlx::query_as::<_, (String, chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>, Option<chrono::DateTime<chrono::Utc>>, Option<Uuid>, bool, bool, bool, bool, bool, bool)>(
+        "SELECT t.jti, t.issued_at, t.expires_at, t.last_used_at, t.member_id, t.can_delete_group, t.can_manage_members, t.can_update_payment, t.can_add_expenses, t.can_edit_expenses, t.can_settle
+         FROM issued_tokens t JOIN groups g ON g.id = t.group_id
+         WHERE t.group_id = $1 AND t.revoked_at IS NULL AND t.expires_at > NOW()
+           AND (g.tokens_revoked_before IS NULL OR t.issued_at > g.tokens_revoked_before)
+         ORDER BY t.issued_at DESC, t.jti"
+    )
+    .bind(auth.group_id)
+    .fetch_all(db::get_pool())
+    .await
+    .map_err(|e| { eprintln!("DB error listing tokens: {}", e); db::error_status(&e) })?;
+
+    let items = rows
+        .into_iter()
+        .map(
+            |(jti, issued_at, expires_at, last_used_at, member_id, dg, mm, up, ae, ee, st)| IssuedTokenItem {
+                current: auth.jti.as_deref() == Some(jti.as_str()),
+                jti,
+                issued_at,
+                expires_at,
+                last_used_at,
+                member_id,
+                permissions: PermissionsResponse {
+                    can_delete_group: dg,
+                    can_manage_members: mm,
+                    can_update_payment: up,
+                    can_add_expenses: ae,
+                    can_edit_expenses: ee,
+                    can_settle: st,
+                },
+            },
+        )
+        .collect();
+
+    Ok(Json(items))
+}
+
+// Revoke one token by its id (requires all permissions). It stops working
+// immediately and no longer counts towards MAX_GROUP_TOKENS.
+#[delete("/groups/current/tokens/<jti>")]
+async fn revoke_token(auth: GroupAuth, jti: &str) -> Result<Status, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    let result = sqlx::query(
+        "UPDATE issued_tokens SET revoked_at = NOW() WHERE jti = $1 AND group_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(jti)
+    .bind(auth.group_id)
+    .execute(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("DB error revoking token: {}", e);
+        db::error_status(&e)
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(Status::NotFound);
+    }
+    Ok(Status::NoContent)
+}
+
+// Render a share link as a QR code (SVG) of its join URL - requires valid JWT
+// and at least the permissions the link grants, like generating it did.
+#[get("/groups/current/share-links/<code>/qr")]
+async fn share_link_qr(auth: GroupAuth, code: &str) -> Result<(ContentType, String), Status> {
+    let link = sqlx::query_as::<_, (bool, bool, bool, bool, bool, bool)>(
+        "SELECT can_delete_group, can_manage_members, can_update_payment, can_add_expenses, can_edit_expenses, can_settle FROM share_links
+         WHERE code = $1 AND group_id = $2
+           AND (max_uses IS NULL OR use_count < max_uses)
+           AND (expires_at IS NULL OR expires_at > NOW())",
+    )
+    .bind(code)
+    .bind(auth.group_id)
+    .fetch_optional(db::get_pool())
+    .await
+    .map_err(|e| {
+        eprintln!("DB error fetching share link: {}", e);
+        db::error_status(&e)
+    })?
+    .ok_or(Status::NotFound)?;
+
+    let (dg, mm, up, ae, ee, st) = link;
+    let own = &auth.permissions;
+    if (dg && !own.has_delete_group())
+        || (mm && !own.has_manage_members())
+        || (up && !own.has_update_payment())
+        || (ae && !own.has_add_expenses())
+        || (ee && !own.has_edit_expenses())
+        || (st && !own.has_settle())
+    {
+        return Err(Status::Forbidden);
+    }
+
+    let qr = qrcode::QrCode::new(notifications::join_link(code)).map_err(|e| {
+        eprintln!("Failed to encode share link QR: {}", e);
+        Status::InternalServerError
+    })?;
+    let svg = qr
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(256, 256)
+        .build();
+    Ok((ContentType::SVG, svg))
+}
+
+// List webhooks for the current group (requires all permissions)
+#[get("/groups/current/webhooks")]
+async fn list_webhooks(auth: GroupAuth) -> Result<Json<Vec<WebhookItem>>, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    let pool = db::get_pool();
+    let rows = sqlx::query_as::<_, (Uuid, String, Vec<String>, chrono::DateTime<chrono::Utc>)>(
+        "SELECT id, url, events, created_at FROM webhooks WHERE group_id = $1 ORDER BY created_at"
+    )
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| { eprintln!("DB error listing webhooks: {}", e); db::error_status(&e) })?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|(id, url, events, created_at)| WebhookItem {
+                id,
+                url,
+                events,
+                created_at: created_at.to_rfc3339(),
+            })
+            .collect(),
+    ))
+}
+
+// Register a webhook (requires all permissions)
+#[post("/groups/current/webhooks", data = "<request>")]
+async fn create_webhook(
+    auth: GroupAuth,
+    _writable: Writable,
+    request: Json<CreateWebhookRequest>,
+) -> Result<Json<WebhookCreatedResponse>, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    let url = request.url.trim();
+    if url.len() > 2048 || !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(Status::BadRequest);
+    }
+    let mut events: Vec<String> = Vec::new();
+    for event in &request.events {
+        if !webhooks::EVENTS.contains(&event.as_str()) {
+            return Err(Status::BadRequest);
+        }
+        if !events.contains(event) {
+            events.push(event.clone());
+        }
+    }
+    if events.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let secret = match &request.secret {
+        Some(secret) if secret.is_empty() || secret.len() > 64 => return Err(Status::BadRequest),
+        Some(secret) => secret.clone(),
+        None => random_code(32),
+    };
+
+    let pool = db::get_pool();
+    let id = Uuid::new_v4();
+    let created_at = Utc::now();
+    sqlx::query(
+        "INSERT INTO webhooks (id, group_id, url, secret, events, created_at) VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(id)
+    .bind(auth.group_id)
+    .bind(url)
+    .bind(&secret)
+    .bind(&events)
+    .bind(created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| { eprintln!("Failed to insert webhook: {}", e); db::error_status(&e) })?;
+
+    Ok(Json(WebhookCreatedResponse {
+        webhook: WebhookItem {
+            id,
+            url: url.to_string(),
+            events,
+            created_at: created_at.to_rfc3339(),
+        },
+        secret,
+    }))
+}
+
+// Delete a webhook (requires all permissions)
+#[delete("/groups/current/webhooks/<webhook_id>")]
+async fn delete_webhook(
+    auth: GroupAuth,
+    _writable: Writable,
+    webhook_id: &str,
+) -> Result<Status, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    let webhook_uuid = Uuid::parse_str(webhook_id).map_err(|_| Status::BadRequest)?;
+    let pool = db::get_pool();
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND group_id = $2")
+        .bind(webhook_uuid)
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("DB error deleting webhook: {}", e);
+            db::error_status(&e)
         })?;
 
     if result.rows_affected() == 0 {
@@ -1087,25 +7022,73 @@ async fn delete_share_link(auth: GroupAuth, code: &str) -> Result<Status, Status
     Ok(Status::NoContent)
 }
 
+// Delivery log for a webhook, newest first (requires all permissions)
+#[get("/groups/current/webhooks/<webhook_id>/deliveries")]
+async fn list_webhook_deliveries(
+    auth: GroupAuth,
+    webhook_id: &str,
+) -> Result<Json<Vec<WebhookDeliveryItem>>, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    let webhook_uuid = Uuid::parse_str(webhook_id).map_err(|_| Status::BadRequest)?;
+    let pool = db::get_pool();
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM webhooks WHERE id = $1 AND group_id = $2)")
+        .bind(webhook_uuid)
+        .bind(auth.group_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| { eprintln!("DB error loading webhook: {}", e); db::error_status(&e) })?;
+    if !exists {
+        return Err(Status::NotFound);
+    }
+    let rows = sqlx::query_as::<_, (String, i32, Option<i32>, bool, Option<String>, chrono::DateTime<chrono::Utc>)>(
+        "SELECT d.event, d.attempt, d.status_code, d.success, d.error, d.created_at
+         FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+         WHERE d.webhook_id = $1 AND w.group_id = $2
+         ORDER BY d.created_at DESC LIMIT 100"
+    )
+    .bind(webhook_uuid)
+    .bind(auth.group_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| { eprintln!("DB error listing webhook deliveries: {}", e); db::error_status(&e) })?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|(event, attempt, status_code, success, error, created_at)| WebhookDeliveryItem {
+                event,
+                attempt,
+                status_code,
+                success,
+                error,
+                created_at: created_at.to_rfc3339(),
+            })
+            .collect(),
+    ))
+}
+
 // Rename group - requires valid JWT + delete_group permission
 #[put("/groups/current/name", data = "<request>")]
 async fn rename_group(
     auth: GroupAuth,
+    _writable: Writable,
     request: Json<RenameGroupRequest>,
-) -> Result<Json<Group>, Status> {
+) -> Result<Json<Group>, ApiError> {
     if !auth.permissions.has_delete_group() {
-        return Err(Status::Forbidden);
+        return Err(Status::Forbidden.into());
     }
+    let name = validate_group_name(&request.name)?;
     let pool = db::get_pool();
 
     sqlx::query("UPDATE groups SET name = $1 WHERE id = $2")
-        .bind(&request.name)
+        .bind(&name)
         .bind(auth.group_id)
         .execute(pool)
         .await
         .map_err(|e| {
             eprintln!("Failed to rename group: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
 
     // Update last_activity_at
@@ -1115,27 +7098,27 @@ async fn rename_group(
         .await
         .map_err(|e| {
             eprintln!("Failed to update last_activity_at: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
 
     // Return updated group
     let group_row: GroupRow =
-        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at FROM groups WHERE id = $1")
+        sqlx::query_as("SELECT id, name, currency, created_at, last_activity_at, default_split, archived_at, locked, default_share_permissions FROM groups WHERE id = $1")
             .bind(auth.group_id)
             .fetch_one(pool)
             .await
             .map_err(|e| {
                 eprintln!("DB error: {}", e);
-                Status::InternalServerError
+                db::error_status(&e)
             })?;
 
     let member_rows: Vec<MemberRow> = sqlx::query_as(
-        "SELECT id, group_id, name, paypal_email, iban, created_at FROM members WHERE group_id = $1 ORDER BY created_at"
+        "SELECT id, group_id, name, paypal_email, iban, created_at, email, notify_on_expense, preferred_payment_method, venmo_handle, payment_note FROM members WHERE group_id = $1 ORDER BY display_order, id"
     )
     .bind(auth.group_id)
     .fetch_all(pool)
     .await
-    .map_err(|e| { eprintln!("DB error: {}", e); Status::InternalServerError })?;
+    .map_err(|e| { eprintln!("DB error: {}", e); db::error_status(&e) })?;
 
     let group = Group {
         id: group_row.id,
@@ -1144,56 +7127,216 @@ async fn rename_group(
         members: member_rows.into_iter().map(Member::from).collect(),
         created_at: group_row.created_at,
         last_activity_at: group_row.last_activity_at,
+        default_split: group_row.default_split.map(|d| d.0),
+        archived_at: group_row.archived_at,
+        locked: group_row.locked,
+        default_share_permissions: group_row.default_share_permissions.map(|d| d.0),
     };
 
     Ok(Json(group))
 }
 
-// Delete group - requires valid JWT + delete_group permission
-#[delete("/groups/current")]
-async fn delete_group(auth: GroupAuth) -> Result<Status, Status> {
+/// Split types understood by `member_shares`.
+const SPLIT_TYPES: [&str; 5] = ["equal", "percentage", "exact", "shares", "adjustment"];
+
+// Set the group's default split - requires valid JWT + delete_group permission
+#[put("/groups/current/default-split", data = "<request>")]
+async fn set_default_split(
+    auth: GroupAuth,
+    _writable: Writable,
+    request: Json<SetDefaultSplitRequest>,
+) -> Result<Json<Group>, ApiError> {
+    if !auth.permissions.has_delete_group() {
+        return Err(Status::Forbidden.into());
+    }
+    let pool = db::get_pool();
+
+    if let Some(default) = &request.default_split {
+        if !SPLIT_TYPES.contains(&default.split_type.as_str()) {
+            return Err(ApiError::bad_request(format!(
+                "Unknown split type '{}'",
+                default.split_type
+            )));
+        }
+        if default.splits.is_empty() {
+            return Err(ApiError::bad_request("Default split needs at least one member"));
+        }
+        let member_ids: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM members WHERE group_id = $1")
+            .bind(auth.group_id)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to fetch members: {}", e);
+                db::error_status(&e)
+            })?;
+        for (i, entry) in default.splits.iter().enumerate() {
+            if !member_ids.contains(&entry.member_id) {
+                return Err(ApiError::bad_request(format!(
+                    "Member {} is not part of this group",
+                    entry.member_id
+                )));
+            }
+            if default.splits[..i].iter().any(|e| e.member_id == entry.member_id) {
+                return Err(ApiError::bad_request(format!(
+                    "Member {} appears more than once",
+                    entry.member_id
+                )));
+            }
+            if entry.share.is_some_and(|share| !share.is_finite() || share < 0.0) {
+                return Err(ApiError::bad_request("Shares must be non-negative numbers"));
+            }
+        }
+    }
+
+    sqlx::query("UPDATE groups SET default_split = $1, last_activity_at = NOW() WHERE id = $2")
+        .bind(request.default_split.clone().map(sqlx::types::Json))
+        .bind(auth.group_id)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update default split: {}", e);
+            db::error_status(&e)
+        })?;
+
+    Ok(Json(load_group(auth.group_id).await?))
+}
+
+// Set the permissions new share links default to - requires valid JWT + delete_group permission
+#[put("/groups/current/default-share-permissions", data = "<request>")]
+async fn set_default_share_permissions(
+    auth: GroupAuth,
+    _writable: Writable,
+    request: Json<SetDefaultSharePermissionsRequest>,
+) -> Result<Json<Group>, Status> {
+    if !auth.permissions.has_delete_group() {
+        return Err(Status::Forbidden);
+    }
+    sqlx::query("UPDATE groups SET default_share_permissions = $1, last_activity_at = NOW() WHERE id = $2")
+        .bind(request.default_share_permissions.clone().map(sqlx::types::Json))
+        .bind(auth.group_id)
+        .execute(db::get_pool())
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to update default share permissions: {}", e);
+            db::error_status(&e)
+        })?;
+
+    Ok(Json(load_group(auth.group_id).await?))
+}
+
+// Archive group (read-only, kept past the inactivity cleanup) - requires valid JWT + delete_group permission
+#[post("/groups/current/archive")]
+async fn archive_group(auth: GroupAuth) -> Result<Status, Status> {
+    set_archived(auth, true).await
+}
+
+// Restore an archived group - requires valid JWT + delete_group permission
+#[post("/groups/current/unarchive")]
+async fn unarchive_group(auth: GroupAuth) -> Result<Status, Status> {
+    set_archived(auth, false).await
+}
+
+async fn set_archived(auth: GroupAuth, archived: bool) -> Result<Status, Status> {
     if !auth.permissions.has_delete_group() {
         return Err(Status::Forbidden);
     }
     let pool = db::get_pool();
 
-    // Delete expense splits, then expenses, then members, then group
+    // Keep the original timestamp when archiving twice
     sqlx::query(
-        "DELETE FROM expense_splits WHERE expense_id IN (SELECT id FROM expenses WHERE group_id = $1)"
+        "UPDATE groups SET archived_at = CASE WHEN $1 THEN COALESCE(archived_at, NOW()) END, last_activity_at = NOW()
+         WHERE id = $2",
     )
+    .bind(archived)
     .bind(auth.group_id)
     .execute(pool)
     .await
-    .map_err(|e| { eprintln!("Failed to delete expense splits: {}", e); Status::InternalServerError })?;
+    .map_err(|e| {
+        eprintln!("Failed to update archive state: {}", e);
+        db::error_status(&e)
+    })?;
+
+    Ok(Status::NoContent)
+}
 
-    sqlx::query("DELETE FROM expenses WHERE group_id = $1")
+// Lock group against edits (expenses, members, settings, webhooks, deletion)
+// while keeping it readable - requires valid JWT + all permissions
+#[post("/groups/current/lock")]
+async fn lock_group(auth: GroupAuth) -> Result<Status, Status> {
+    set_locked(auth, true).await
+}
+
+// Lift the lock - requires valid JWT + all permissions
+#[post("/groups/current/unlock")]
+async fn unlock_group(auth: GroupAuth) -> Result<Status, Status> {
+    set_locked(auth, false).await
+}
+
+async fn set_locked(auth: GroupAuth, locked: bool) -> Result<Status, Status> {
+    if !auth.permissions.has_all() {
+        return Err(Status::Forbidden);
+    }
+    sqlx::query("UPDATE groups SET locked = $1, last_activity_at = NOW() WHERE id = $2")
+        .bind(locked)
         .bind(auth.group_id)
-        .execute(pool)
+        .execute(db::get_pool())
         .await
         .map_err(|e| {
-            eprintln!("Failed to delete expenses: {}", e);
-            Status::InternalServerError
+            eprintln!("Failed to update lock state: {}", e);
+            db::error_status(&e)
         })?;
 
-    sqlx::query("DELETE FROM members WHERE group_id = $1")
+    Ok(Status::NoContent)
+}
+
+// Delete group - requires valid JWT + delete_group permission; locked and
+// archived groups can't be deleted until unlocked or restored.
+// With `?dry_run=true` nothing is deleted; the response counts what would be,
+// for locked and archived groups too.
+#[delete("/groups/current?<dry_run>")]
+async fn delete_group(
+    auth: GroupAuth,
+    writable: rocket::request::Outcome<Writable, ()>,
+    dry_run: Option<bool>,
+) -> Result<Either<Json<GroupDeletionPreview>, Status>, Status> {
+    if !auth.permissions.has_delete_group() {
+        return Err(Status::Forbidden);
+    }
+    let pool = db::get_pool();
+
+    if dry_run.unwrap_or(false) {
+        let preview: GroupDeletionPreview = sqlx::query_as(
+            "SELECT
+                (SELECT COUNT(*) FROM members WHERE group_id = $1) AS members,
+                (SELECT COUNT(*) FROM expenses WHERE group_id = $1) AS expenses,
+                (SELECT COUNT(*) FROM expense_splits s JOIN expenses e ON e.id = s.expense_id WHERE e.group_id = $1) AS splits,
+                (SELECT COUNT(*) FROM expense_items i JOIN expenses e ON e.id = i.expense_id WHERE e.group_id = $1) AS items,
+                (SELECT COUNT(*) FROM expense_receipts r JOIN expenses e ON e.id = r.expense_id WHERE e.group_id = $1) AS receipts",
+        )
         .bind(auth.group_id)
-        .execute(pool)
+        .fetch_one(pool)
         .await
         .map_err(|e| {
-            eprintln!("Failed to delete members: {}", e);
-            Status::InternalServerError
+            eprintln!("Failed to count group data: {}", e);
+            db::error_status(&e)
         })?;
+        return Ok(Either::Left(Json(preview)));
+    }
+    if let Outcome::Error((status, _)) | Outcome::Forward(status) = writable {
+        return Err(status);
+    }
 
+    // Members, expenses and everything hanging off them go with the group (ON DELETE CASCADE)
     sqlx::query("DELETE FROM groups WHERE id = $1")
         .bind(auth.group_id)
         .execute(pool)
         .await
         .map_err(|e| {
             eprintln!("Failed to delete group: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
 
-    Ok(Status::NoContent)
+    Ok(Either::Right(Status::NoContent))
 }
 
 // Extend group lifetime - resets the inactivity timer
@@ -1206,7 +7349,7 @@ async fn extend_lifetime(auth: GroupAuth) -> Result<Status, Status> {
         .await
         .map_err(|e| {
             eprintln!("Failed to extend lifetime: {}", e);
-            Status::InternalServerError
+            db::error_status(&e)
         })?;
     Ok(Status::NoContent)
 }
@@ -1293,12 +7436,39 @@ async fn ollama_chat(
     Ok(chat_resp.message.content)
 }
 
-#[post("/receipt/scan", data = "<request>")]
+/// Largest accepted receipt scan body. The image travels base64-encoded inside
+/// the JSON, so a phone photo easily exceeds `MAX_JSON_BYTES`; scans get their
+/// own limit instead. Defaults to 20 MiB; override with `MAX_SCAN_BYTES`.
+static MAX_SCAN_BYTES: Lazy<u64> = Lazy::new(|| {
+    std::env::var("MAX_SCAN_BYTES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(20 * 1024 * 1024)
+});
+
+// Extract title, total, date and items from a receipt photo - requires valid JWT.
+// Bodies over MAX_SCAN_BYTES are rejected with 413.
+#[post("/receipt/scan", data = "<body>")]
 async fn scan_receipt(
     _auth: GroupAuth,
     _rate_limit: RocketGovernor<'_, ScanRateLimit>,
-    request: Json<ScanReceiptRequest>,
+    body: Data<'_>,
 ) -> Result<Json<ScanReceiptResponse>, Status> {
+    let body = body
+        .open(ByteUnit::from(*MAX_SCAN_BYTES))
+        .into_string()
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to read receipt scan body: {}", e);
+            Status::BadRequest
+        })?;
+    if !body.is_complete() {
+        return Err(Status::PayloadTooLarge);
+    }
+    let request: ScanReceiptRequest =
+        serde_json::from_str(&body).map_err(|_| Status::UnprocessableEntity)?;
+
     let url = ollama_url();
     let token = ollama_api_token();
     let model = scan_model();
@@ -1368,10 +7538,10 @@ fn extract_json_block(s: &str) -> String {
         }
     }
     // Try to find first { ... last }
-    if let Some(start) = s.find('{') {
-        if let Some(end) = s.rfind('}') {
-            return s[start..=end].to_string();
-        }
+    if let Some(start) = s.find('{')
+        && let Some(end) = s.rfind('}')
+    {
+        return s[start..=end].to_string();
     }
     s.trim().to_string()
 }
@@ -1387,50 +7557,221 @@ async fn exchange_rate(
     if date.len() != 10 || from.len() != 3 || to.len() != 3 {
         return Err(Status::BadRequest);
     }
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|_| Status::InternalServerError)?;
-    let resp = client
-        .get(format!("https://api.frankfurter.app/{}?from={}&to={}", date, from, to))
-        .send()
-        .await
-        .map_err(|e| {
-            eprintln!("Frankfurter request failed: {}", e);
-            Status::ServiceUnavailable
-        })?;
-    if !resp.status().is_success() {
-        return Err(Status::ServiceUnavailable);
-    }
-    let body: serde_json::Value = resp.json().await.map_err(|e| {
-        eprintln!("Failed to parse Frankfurter response: {}", e);
-        Status::InternalServerError
+    let body = rates::fetch(date, from, to).await.map_err(|e| {
+        eprintln!("Exchange rate lookup failed: {}", e);
+        Status::ServiceUnavailable
     })?;
     Ok(Json(body))
 }
 
+// Exchange rate between two currencies on a date (default today), from the stored
+// rates with the provider as fallback - no auth required
+#[get("/rates?<from>&<to>&<date>")]
+async fn get_rate(from: &str, to: &str, date: Option<&str>) -> Result<Json<ExchangeRate>, Status> {
+    if !rates::is_currency_code(from) || !rates::is_currency_code(to) {
+        return Err(Status::BadRequest);
+    }
+    let date = match date {
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| Status::BadRequest)?,
+        None => Utc::now().date_naive(),
+    };
+    let rate = rates::lookup(from, to, date).await.map_err(|e| {
+        eprintln!("Exchange rate lookup failed: {}", e);
+        Status::ServiceUnavailable
+    })?;
+    Ok(Json(ExchangeRate {
+        from: from.to_ascii_uppercase(),
+        to: to.to_ascii_uppercase(),
+        rate: rate.rate,
+        date: rate.date,
+        fetched_at: rate.fetched_at,
+    }))
+}
+
 pub fn get_routes() -> Vec<Route> {
     routes![
         health,
+        ready,
         create_group,
+        clone_group,
         get_current_group,
+        get_overview,
+        get_groups_overview,
         get_permissions,
+        get_token_info,
+        inspect_token,
         add_member,
+        merge_members,
+        reorder_members,
+        reassign_member,
         update_member_payment,
+        update_member_notifications,
         get_expenses,
         create_expense,
+        preview_expense,
         update_expense,
+        patch_expense,
         delete_expense,
+        get_activity,
+        undo_last_action,
+        get_tags,
+        get_stats_by_category,
+        get_member_stats,
+        get_anomalies,
+        get_trips,
+        create_trip,
+        update_trip,
+        delete_trip,
+        get_trip_balances,
+        set_share_settled,
+        upload_receipt,
+        get_receipt,
+        delete_receipt,
+        duplicate_expense,
         get_balances,
+        verify_balances,
+        get_currency_info,
+        get_debtors,
+        get_payment_requests,
+        get_members_by_balance,
+        get_member_statement,
+        get_member_breakdown,
+        get_member_balance_history,
+        get_member_expenses,
+        get_pair_expenses,
+        get_settlement_progress,
+        get_settle_up,
+        settle_all,
+        get_report_pdf,
+        get_debt_matrix,
         generate_share_link,
+        invite_member,
         list_share_links,
         delete_share_link,
+        list_tokens,
+        revoke_token,
+        share_link_qr,
+        list_webhooks,
+        create_webhook,
+        delete_webhook,
+        list_webhook_deliveries,
         redeem_share_code,
         merge_token,
+        scoped_token,
+        new_owner_token,
         rename_group,
+        set_default_split,
+        set_default_share_permissions,
+        archive_group,
+        unarchive_group,
+        lock_group,
+        unlock_group,
         delete_group,
         extend_lifetime,
         scan_receipt,
-        exchange_rate
+        exchange_rate,
+        get_rate,
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expense(
+        expense_type: ExpenseType,
+        amount: i64,
+        paid_by: Uuid,
+        transfer_to: Option<Uuid>,
+    ) -> ExpenseRow {
+        ExpenseRow {
+            id: Uuid::new_v4(),
+            group_id: Uuid::new_v4(),
+            description: "Test".to_string(),
+            amount: BigDecimal::from(amount),
+            paid_by,
+            expense_type,
+            transfer_to,
+            currency: "EUR".to_string(),
+            exchange_rate: BigDecimal::from(1),
+            expense_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            created_at: Utc::now(),
+            split_type: "equal".to_string(),
+            notes: None,
+            receipt_url: None,
+            created_by: None,
+            updated_by: None,
+            trip_id: None,
+            refund_of: None,
+        }
+    }
+
+    fn split(member_id: Uuid) -> ExpenseSplitMemberRow {
+        ExpenseSplitMemberRow {
+            member_id,
+            share: None,
+            settled: false,
+        }
+    }
+
+    fn apply(balances: &mut HashMap<Uuid, f64>, deltas: Vec<(Uuid, f64)>) {
+        for (member_id, delta) in deltas {
+            *balances.entry(member_id).or_default() += delta;
+        }
+    }
+
+    #[test]
+    fn transfers_credit_the_sender_and_debit_the_receiver() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let transfer = expense(ExpenseType::Transfer, 10, alice, Some(bob));
+
+        assert_eq!(balance_deltas(&transfer, &[], 2), vec![(alice, 10.0), (bob, -10.0)]);
+    }
+
+    #[test]
+    fn a_transfer_along_the_settlement_plan_settles_the_debt() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut balances = HashMap::new();
+        let dinner = expense(ExpenseType::Expense, 20, bob, None);
+        apply(&mut balances, balance_deltas(&dinner, &[split(alice), split(bob)], 2));
+        assert_eq!((balances[&alice], balances[&bob]), (-10.0, 10.0));
+
+        let rows = [(alice, "Alice"), (bob, "Bob")].map(|(user_id, name)| Balance {
+            user_id,
+            user_name: name.to_string(),
+            balance: balances[&user_id],
+            balance_minor: None,
+        });
+        let plan = settlement::simplify(&rows, 2).unwrap();
+        assert_eq!(plan.len(), 1);
+        let step = &plan[0];
+        assert_eq!((step.from, step.to, step.amount), (alice, bob, 10.0));
+
+        let payment = expense(ExpenseType::Transfer, 10, step.from, Some(step.to));
+        apply(&mut balances, balance_deltas(&payment, &[], 2));
+        assert_eq!((balances[&alice], balances[&bob]), (0.0, 0.0));
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn group_names_are_trimmed_and_limited() {
+        assert_eq!(validate_group_name("  Trip ").unwrap(), "Trip");
+        assert!(validate_group_name("   ").is_err());
+        assert!(validate_group_name(&"é".repeat(MAX_GROUP_NAME_LEN)).is_ok());
+        assert!(validate_group_name(&"é".repeat(MAX_GROUP_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn member_lists_reject_empty_blank_long_and_duplicate_names() {
+        assert_eq!(validate_member_names(&names(&[" Alice", "Bob "])).unwrap(), names(&["Alice", "Bob"]));
+        let message = |list: &[&str]| validate_member_names(&names(list)).unwrap_err().message.unwrap();
+        assert_eq!(message(&[]), "A group needs at least one member");
+        assert_eq!(message(&["Alice", " "]), "Member names must not be blank");
+        assert!(message(&[&"x".repeat(MAX_MEMBER_NAME_LEN + 1)]).contains("at most 60"));
+        assert_eq!(message(&["Alice", " alice"]), "Duplicate member name 'alice'");
+        assert!(validate_member_names(&names(&[&"x".repeat(MAX_MEMBER_NAME_LEN)])).is_ok());
+    }
+}
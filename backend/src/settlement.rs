@@ -0,0 +1,287 @@
+use crate::currency;
+use crate::models::{Balance, Settlement, SettlementWarning, UnaccountedBalance};
+
+/// Above this many non-zero balances the zero-sum partition search is skipped
+/// (it is exponential in the number of people) and plain greedy matching is used.
+const MAX_PARTITION_PEOPLE: usize = 16;
+
+/// Rounding of the group's currency: amounts are whole minor units, and anything
+/// below half a unit counts as zero.
+#[derive(Debug, Clone, Copy)]
+struct Precision {
+    decimals: u32,
+    epsilon: f64,
+}
+
+impl Precision {
+    fn new(decimals: u32) -> Self {
+        Precision {
+            decimals,
+            epsilon: 0.5 / 10f64.powi(decimals as i32),
+        }
+    }
+
+    fn round(self, amount: f64) -> f64 {
+        currency::round_half_even(amount, self.decimals)
+    }
+
+    fn units(self, amount: f64) -> i64 {
+        currency::to_minor_units(amount, self.decimals)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Person<'a> {
+    balance: &'a Balance,
+    amount: f64,
+}
+
+/// Compute the minimum set of transfers that settles all balances.
+///
+/// Mirrors the frontend: balances are rounded to the minor unit of the group's
+/// currency (`decimals` places), split into as many independent zero-sum groups as
+/// possible (a group of k people needs k-1 transfers), and each group is settled
+/// greedily largest debtor to largest creditor.
+///
+/// Balances that don't add up to zero within half a minor unit (only possible with
+/// inconsistent data, e.g. splits of members from another group) can't be settled;
+/// instead of a plan that leaves someone short, the warning says how much is
+/// unaccounted and for whom.
+pub fn simplify(balances: &[Balance], decimals: u32) -> Result<Vec<Settlement>, SettlementWarning> {
+    let precision = Precision::new(decimals);
+    let people = people(balances, precision);
+    let total: f64 = balances.iter().map(|b| b.balance).sum();
+    if total.abs() > precision.epsilon {
+        return Err(unbalanced(&people, total, precision));
+    }
+    Ok(settle(people, precision))
+}
+
+/// Everyone with a non-zero balance, rounded to minor units.
+fn people(balances: &[Balance], precision: Precision) -> Vec<Person<'_>> {
+    balances
+        .iter()
+        .filter(|b| b.balance.abs() > precision.epsilon)
+        .map(|b| Person {
+            balance: b,
+            amount: precision.round(b.balance),
+        })
+        .collect()
+}
+
+/// Settle as much as the balances allow; what is left of each balance afterwards
+/// has no counterpart on the other side.
+fn unbalanced(people: &[Person], total: f64, precision: Precision) -> SettlementWarning {
+    let plan = greedy_settle(people, precision);
+    let members = people
+        .iter()
+        .filter_map(|p| {
+            let id = p.balance.user_id;
+            let received: f64 = plan.iter().filter(|s| s.to == id).map(|s| s.amount).sum();
+            let paid: f64 = plan.iter().filter(|s| s.from == id).map(|s| s.amount).sum();
+            let amount = precision.round(p.amount - received + paid);
+            (amount.abs() > precision.epsilon).then(|| UnaccountedBalance {
+                user_id: id,
+                user_name: p.balance.user_name.clone(),
+                amount,
+            })
+        })
+        .collect();
+    SettlementWarning {
+        unaccounted: precision.round(total),
+        members,
+    }
+}
+
+/// The plan for balances that add up to zero.
+fn settle(people: Vec<Person>, precision: Precision) -> Vec<Settlement> {
+    if people.is_empty() {
+        return Vec::new();
+    }
+
+    let n = people.len();
+    if n > MAX_PARTITION_PEOPLE {
+        return greedy_settle(&people, precision);
+    }
+
+    // Subset sums in integer minor units to avoid float comparison issues
+    let units: Vec<i64> = people.iter().map(|p| precision.units(p.amount)).collect();
+    let total_subsets = 1usize << n;
+    let mut subset_sum = vec![0i64; total_subsets];
+    for mask in 1..total_subsets {
+        let bit = mask.trailing_zeros() as usize;
+        subset_sum[mask] = subset_sum[mask & (mask - 1)] + units[bit];
+    }
+    let zero_subsets: Vec<usize> = (1..total_subsets).filter(|&m| subset_sum[m] == 0).collect();
+
+    // dp[mask] = max number of disjoint zero-sum subsets covering exactly `mask`, -1 = impossible
+    let mut dp = vec![-1i32; total_subsets];
+    dp[0] = 0;
+    for mask in 1..total_subsets {
+        if subset_sum[mask] == 0 && dp[mask] < 1 {
+            dp[mask] = 1;
+        }
+        if dp[mask] < 0 {
+            continue;
+        }
+        for &zs in &zero_subsets {
+            if mask & zs == 0 && dp[mask | zs] < dp[mask] + 1 {
+                dp[mask | zs] = dp[mask] + 1;
+            }
+        }
+    }
+
+    let full_mask = total_subsets - 1;
+    if dp[full_mask] <= 0 {
+        return greedy_settle(&people, precision);
+    }
+
+    // Backtrack the partition
+    let mut groups: Vec<Vec<Person>> = Vec::new();
+    let mut mask = full_mask;
+    while mask != 0 {
+        let Some(&zs) = zero_subsets
+            .iter()
+            .find(|&&zs| mask & zs == zs && dp[mask ^ zs] == dp[mask] - 1)
+        else {
+            return greedy_settle(&people, precision);
+        };
+        groups.push(
+            (0..n)
+                .filter(|i| zs & (1 << i) != 0)
+                .map(|i| people[i].clone())
+                .collect(),
+        );
+        mask ^= zs;
+    }
+
+    groups.iter().flat_map(|g| greedy_settle(g, precision)).collect()
+}
+
+/// Greedily settle a group of people whose balances sum to ~0.
+fn greedy_settle(group: &[Person], precision: Precision) -> Vec<Settlement> {
+    let mut debtors: Vec<Person> = group
+        .iter()
+        .filter(|p| p.amount < -precision.epsilon)
+        .map(|p| Person {
+            balance: p.balance,
+            amount: p.amount.abs(),
+        })
+        .collect();
+    let mut creditors: Vec<Person> = group
+        .iter()
+        .filter(|p| p.amount > precision.epsilon)
+        .cloned()
+        .collect();
+    debtors.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+    creditors.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+
+    let mut result = Vec::new();
+    let (mut di, mut ci) = (0, 0);
+    while di < debtors.len() && ci < creditors.len() {
+        let transfer = debtors[di].amount.min(creditors[ci].amount);
+        if transfer > precision.epsilon {
+            result.push(Settlement {
+                from: debtors[di].balance.user_id,
+                from_name: debtors[di].balance.user_name.clone(),
+                to: creditors[ci].balance.user_id,
+                to_name: creditors[ci].balance.user_name.clone(),
+                amount: precision.round(transfer),
+            });
+        }
+        debtors[di].amount -= transfer;
+        creditors[ci].amount -= transfer;
+        if debtors[di].amount < precision.epsilon {
+            di += 1;
+        }
+        if creditors[ci].amount < precision.epsilon {
+            ci += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn balance(name: &str, amount: f64) -> Balance {
+        Balance {
+            user_id: Uuid::new_v4(),
+            user_name: name.to_string(),
+            balance: amount,
+            balance_minor: None,
+        }
+    }
+
+    #[test]
+    fn transfers_keep_the_currencys_minor_units() {
+        let balances = [balance("Alice", 1.234), balance("Bob", -1.234)];
+        let plan = simplify(&balances, 3).unwrap();
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].from_name, "Bob");
+        assert_eq!(plan[0].amount, 1.234);
+
+        let balances = [balance("Alice", 100.4), balance("Bob", -100.4)];
+        assert_eq!(simplify(&balances, 0).unwrap()[0].amount, 100.0);
+    }
+
+    #[test]
+    fn unbalanced_is_judged_by_the_currencys_minor_unit() {
+        // Off by 0.3 yen: less than half a unit, so it still settles
+        let balances = [balance("Alice", 1000.3), balance("Bob", -1000.0)];
+        assert_eq!(simplify(&balances, 0).unwrap()[0].amount, 1000.0);
+
+        // Off by 0.004 dinar: eight times the tolerance of a three-decimal currency
+        let balances = [balance("Alice", 5.004), balance("Bob", -5.0)];
+        let warning = simplify(&balances, 3).unwrap_err();
+        assert_eq!(warning.unaccounted, 0.004);
+        assert_eq!(warning.members.len(), 1);
+        assert_eq!(warning.members[0].user_name, "Alice");
+        assert_eq!(warning.members[0].amount, 0.004);
+
+        // The same data in a two-decimal currency is within rounding
+        assert!(simplify(&balances, 2).is_ok());
+    }
+
+    #[test]
+    fn missing_money_is_blamed_on_whoever_is_left_unsettled() {
+        // More owed than is due: the debtor can only pay what Alice is owed
+        let balances = [balance("Alice", 10.0), balance("Bob", -15.0)];
+        let warning = simplify(&balances, 2).unwrap_err();
+        assert_eq!(warning.unaccounted, -5.0);
+        assert_eq!(warning.members.len(), 1);
+        assert_eq!(warning.members[0].user_name, "Bob");
+        assert_eq!(warning.members[0].amount, -5.0);
+
+        // Nobody owes anything: every creditor is left short by their whole balance
+        let balances = [balance("Alice", 5.0), balance("Bob", 2.5), balance("Carol", 0.0)];
+        let warning = simplify(&balances, 2).unwrap_err();
+        assert_eq!(warning.unaccounted, 7.5);
+        let short: Vec<(&str, f64)> = warning.members.iter().map(|m| (m.user_name.as_str(), m.amount)).collect();
+        assert_eq!(short, [("Alice", 5.0), ("Bob", 2.5)]);
+    }
+
+    #[test]
+    fn unbalanced_groups_too_large_to_partition_are_reported_too() {
+        let mut balances: Vec<Balance> = (0..MAX_PARTITION_PEOPLE).map(|i| balance(&format!("M{}", i), 1.0)).collect();
+        balances.push(balance("Payer", -(MAX_PARTITION_PEOPLE as f64) + 0.5));
+        balances.push(balance("Other", -0.25));
+        let warning = simplify(&balances, 2).unwrap_err();
+        assert_eq!(warning.unaccounted, 0.25);
+        let short: f64 = warning.members.iter().map(|m| m.amount).sum();
+        assert_eq!(currency::round_half_even(short, 2), 0.25);
+        assert!(warning.members.iter().all(|m| m.amount > 0.0));
+    }
+
+    #[test]
+    fn float_noise_across_many_members_still_settles() {
+        // Ten times 0.1 doesn't add up to exactly 1.0 in floating point
+        let mut balances: Vec<Balance> = (0..10).map(|i| balance(&format!("M{}", i), 0.1)).collect();
+        balances.push(balance("Payer", -1.0));
+        let plan = simplify(&balances, 2).unwrap();
+        assert_eq!(plan.len(), 10);
+        assert!(plan.iter().all(|s| s.from_name == "Payer" && s.amount == 0.1));
+    }
+}
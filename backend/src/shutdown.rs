@@ -0,0 +1,65 @@
+use once_cell::sync::Lazy;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Orbit, Request, Response, Rocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::db;
+
+/// How long shutdown waits for in-flight requests and pooled connections before
+/// giving up. Defaults to 10 seconds; override with `DB_DRAIN_TIMEOUT_SECS`.
+static DRAIN_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+    let secs = std::env::var("DB_DRAIN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(10);
+    Duration::from_secs(secs)
+});
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Fairing that counts in-flight requests and, on shutdown (e.g. SIGTERM), waits
+/// for them to finish before closing the database pool so that no transaction is
+/// cut off halfway.
+///
+/// Rocket stops accepting new connections during shutdown but aborts open ones
+/// after its own `shutdown.grace` + `shutdown.mercy` periods (2s + 3s by default),
+/// so those should be at least as long as `DB_DRAIN_TIMEOUT_SECS`.
+pub struct GracefulShutdown;
+
+#[rocket::async_trait]
+impl Fairing for GracefulShutdown {
+    fn info(&self) -> Info {
+        Info {
+            name: "Graceful Shutdown",
+            kind: Kind::Request | Kind::Response | Kind::Shutdown,
+        }
+    }
+
+    async fn on_request(&self, _: &mut Request<'_>, _: &mut Data<'_>) {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    }
+
+    async fn on_response<'r>(&self, _: &'r Request<'_>, _: &mut Response<'r>) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        let deadline = Instant::now() + *DRAIN_TIMEOUT;
+
+        while IN_FLIGHT.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            rocket::tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let pending = IN_FLIGHT.load(Ordering::SeqCst);
+        if pending > 0 {
+            eprintln!("Shutdown: {} request(s) still in flight after drain timeout", pending);
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if rocket::tokio::time::timeout(remaining, db::close_pool()).await.is_err() {
+            eprintln!("Shutdown: timed out waiting for database connections to close");
+        } else {
+            println!("Shutdown: database pool closed");
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::db;
+
+pub const EXPENSE_CREATED: &str = "expense.created";
+pub const EXPENSE_DELETED: &str = "expense.deleted";
+pub const MEMBER_ADDED: &str = "member.added";
+
+/// Every event a webhook can subscribe to.
+pub const EVENTS: [&str; 3] = [EXPENSE_CREATED, EXPENSE_DELETED, MEMBER_ADDED];
+
+/// Header carrying the hex HMAC-SHA256 of the request body, keyed with the webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-ShareCost-Signature";
+
+/// Number of delivery attempts before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    id: Uuid,
+    event: &'a str,
+    group_id: Uuid,
+    timestamp: String,
+    data: &'a serde_json::Value,
+}
+
+/// Serialize the JSON body sent for an event.
+pub fn build_payload(event: &str, group_id: Uuid, data: &serde_json::Value) -> String {
+    let payload = Payload {
+        id: Uuid::new_v4(),
+        event,
+        group_id,
+        timestamp: Utc::now().to_rfc3339(),
+        data,
+    };
+    serde_json::to_string(&payload).unwrap_or_default()
+}
+
+/// Signature header value for a body: `sha256=<hex hmac>`.
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body.as_bytes());
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// Fire an event to all of the group's webhooks subscribed to it.
+/// Deliveries run in the background and never affect the triggering request.
+pub fn dispatch(group_id: Uuid, event: &'static str, data: serde_json::Value) {
+    rocket::tokio::spawn(async move {
+        let pool = db::get_pool();
+        let hooks: Vec<(Uuid, String, String)> = match sqlx::query_as(
+            "SELECT id, url, secret FROM webhooks WHERE group_id = $1 AND $2 = ANY(events)",
+        )
+        .bind(group_id)
+        .bind(event)
+        .fetch_all(pool)
+        .await
+        {
+            Ok(hooks) => hooks,
+            Err(e) => {
+                eprintln!("Failed to load webhooks: {}", e);
+                return;
+            }
+        };
+        if hooks.is_empty() {
+            return;
+        }
+
+        let body = build_payload(event, group_id, &data);
+        let client = match reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+        {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Failed to build webhook client: {}", e);
+                return;
+            }
+        };
+
+        for (webhook_id, url, secret) in hooks {
+            deliver(&client, webhook_id, &url, &secret, event, &body).await;
+        }
+    });
+}
+
+/// POST the body to one webhook, retrying with backoff, and log every attempt.
+async fn deliver(
+    client: &reqwest::Client,
+    webhook_id: Uuid,
+    url: &str,
+    secret: &str,
+    event: &str,
+    body: &str,
+) {
+    let signature = sign(secret, body);
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.to_string())
+            .send()
+            .await;
+
+        let (status_code, success, error) = match result {
+            Ok(resp) => {
+                let status = resp.status();
+                (
+                    Some(status.as_u16() as i32),
+                    status.is_success(),
+                    (!status.is_success()).then(|| format!("HTTP {}", status)),
+                )
+            }
+            Err(e) => (None, false, Some(e.to_string())),
+        };
+
+        if let Err(e) = sqlx::query(
+            "INSERT INTO webhook_deliveries (webhook_id, event, attempt, status_code, success, error) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(webhook_id)
+        .bind(event)
+        .bind(attempt as i32)
+        .bind(status_code)
+        .bind(success)
+        .bind(&error)
+        .execute(db::get_pool())
+        .await
+        {
+            eprintln!("Failed to log webhook delivery: {}", e);
+        }
+
+        if success {
+            return;
+        }
+        if attempt < MAX_ATTEMPTS {
+            rocket::tokio::time::sleep(std::time::Duration::from_secs(2u64.pow(attempt))).await;
+        }
+    }
+    eprintln!("Webhook {} failed after {} attempts", webhook_id, MAX_ATTEMPTS);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_are_prefixed_hex_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let body = "what do ya want for nothing?";
+        assert_ne!(sign("other", body), sign("Jefe", body));
+    }
+
+    #[test]
+    fn payloads_wrap_the_event_data() {
+        let group_id = Uuid::new_v4();
+        let data = serde_json::json!({ "description": "Dinner" });
+        let payload: serde_json::Value =
+            serde_json::from_str(&build_payload(EXPENSE_CREATED, group_id, &data)).unwrap();
+
+        assert_eq!(payload["event"], EXPENSE_CREATED);
+        assert_eq!(payload["group_id"], group_id.to_string());
+        assert_eq!(payload["data"], data);
+        assert!(payload["id"].as_str().is_some_and(|id| id.parse::<Uuid>().is_ok()));
+        let timestamp = payload["timestamp"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+    }
+}
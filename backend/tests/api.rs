@@ -0,0 +1,5738 @@
+mod common;
+
+use common::TestApp;
+use reqwest::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn expense_shows_up_in_balances() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+
+    let (status, expense) = app
+        .post(
+            "/groups/current/expenses",
+            json!({
+                "description": "Dinner",
+                "amount": 30.0,
+                "paid_by": members["Alice"],
+                "split_between": [members["Alice"], members["Bob"], members["Carol"]],
+            }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "create expense failed: {}", expense);
+    assert_eq!(expense["amount"], 30.0);
+
+    let balances = app.balances(&token).await;
+    assert_eq!(balances["Alice"], 20.0);
+    assert_eq!(balances["Bob"], -10.0);
+    assert_eq!(balances["Carol"], -10.0);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn group_requires_token() {
+    let app = TestApp::spawn().await;
+    let (status, _) = app.request(Method::GET, "/groups/current", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn non_finite_amounts_are_rejected() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+
+    // JSON has no NaN or infinity; out-of-range literals must not get through either
+    for amount in ["NaN", "Infinity", "-Infinity", "1e400"] {
+        let body = format!(
+            r#"{{"description": "Dinner", "amount": {}, "paid_by": "{}"}}"#,
+            amount, members["Alice"]
+        );
+        let status = app.post_raw("/groups/current/expenses", &body, &token).await;
+        assert!(status.is_client_error(), "amount {} gave {}", amount, status);
+    }
+    assert!(app.balances(&token).await.values().all(|b| *b == 0.0));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn huge_amounts_are_rejected() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+
+    let (status, error) = app
+        .post(
+            "/groups/current/expenses",
+            json!({ "description": "Yacht", "amount": 1e15, "paid_by": members["Alice"] }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(error["error"].as_str().unwrap().contains("amount"));
+
+    let (_, expense) = app
+        .post(
+            "/groups/current/expenses",
+            json!({ "description": "Dinner", "amount": 20.0, "paid_by": members["Alice"] }),
+            &token,
+        )
+        .await;
+    let (status, _) = app
+        .request(
+            Method::PATCH,
+            &format!("/groups/current/expenses/{}", expense["id"].as_str().unwrap()),
+            Some(json!({ "amount": -1e300 })),
+            Some(&token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.balances(&token).await["Alice"], 10.0);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn exchange_rates_are_range_checked_and_rounded() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let expense = |rate: f64| {
+        json!({
+            "description": "Souvenirs",
+            "amount": 10.0,
+            "currency": "USD",
+            "exchange_rate": rate,
+            "paid_by": members["Alice"],
+        })
+    };
+
+    for rate in [1e9, 1e-9, 0.0, -1.5] {
+        let (status, _) = app.post("/groups/current/expenses", expense(rate), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "rate {}", rate);
+    }
+
+    let (status, created) = app.post("/groups/current/expenses", expense(1.23456789), &token).await;
+    assert_eq!(status, StatusCode::OK, "create expense failed: {}", created);
+    assert_eq!(created["exchange_rate"], 1.234568);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn scoped_token_has_requested_permissions() {
+    let app = TestApp::spawn().await;
+    let (token, _) = app.create_group(&["Alice", "Bob"]).await;
+
+    let (status, scoped) = app
+        .post(
+            "/groups/current/scoped-token",
+            json!({ "can_delete_group": false, "can_manage_members": false, "can_edit_expenses": false }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "scoped token failed: {}", scoped);
+    let expected = json!({
+        "can_delete_group": false,
+        "can_manage_members": false,
+        "can_update_payment": true,
+        "can_add_expenses": true,
+        "can_edit_expenses": false,
+        "can_settle": true,
+    });
+    assert_eq!(scoped["permissions"], expected);
+    let scoped_token = scoped["token"].as_str().unwrap();
+    let (_, permissions) = app.get("/groups/current/permissions", scoped_token).await;
+    assert_eq!(permissions, expected);
+
+    // A scoped token can't grant itself back what it lacks
+    let (status, rescoped) = app
+        .post(
+            "/groups/current/scoped-token",
+            json!({ "can_delete_group": true, "can_add_expenses": false }),
+            scoped_token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        rescoped["permissions"],
+        json!({
+            "can_delete_group": false,
+            "can_manage_members": false,
+            "can_update_payment": true,
+            "can_add_expenses": false,
+            "can_edit_expenses": false,
+            "can_settle": true,
+        })
+    );
+    let (status, _) = app.request(Method::DELETE, "/groups/current", None, Some(scoped_token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn anomalies_flag_the_outlier() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let amounts = [12.0, 9.5, 14.0, 11.0, 10.0, 13.5, 8.0, 12.5, 10.5, 11.5, 450.0];
+    for (i, amount) in amounts.iter().enumerate() {
+        let (status, _) = app
+            .post(
+                "/groups/current/expenses",
+                json!({ "description": format!("Lunch {}", i), "amount": amount, "paid_by": members["Alice"] }),
+                &token,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    // Transfers don't count towards the statistics
+    let (status, _) = app
+        .post(
+            "/groups/current/expenses",
+            json!({
+                "description": "Payback",
+                "amount": 5000.0,
+                "paid_by": members["Bob"],
+                "expense_type": "transfer",
+                "transfer_to": members["Alice"],
+            }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, report) = app.get("/groups/current/stats/anomalies", &token).await;
+    assert_eq!(status, StatusCode::OK, "anomalies failed: {}", report);
+    assert_eq!(report["expense_count"], 11);
+    let anomalies = report["anomalies"].as_array().unwrap();
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!(anomalies[0]["amount"], 450.0);
+    assert_eq!(anomalies[0]["expense"]["description"], "Lunch 10");
+    assert!(anomalies[0]["z_score"].as_f64().unwrap() > 3.0);
+
+    let (_, strict) = app.get("/groups/current/stats/anomalies?k=5", &token).await;
+    assert!(strict["anomalies"].as_array().unwrap().is_empty());
+    let (status, _) = app.get("/groups/current/stats/anomalies?k=-1", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn activity_log_filters_and_pages() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let mut ids = Vec::new();
+    for description in ["Taxi", "Hotel", "Museum"] {
+        let (_, expense) = app
+            .post(
+                "/groups/current/expenses",
+                json!({ "description": description, "amount": 30.0, "paid_by": members["Alice"] }),
+                &token,
+            )
+            .await;
+        ids.push(expense["id"].as_str().unwrap().to_string());
+    }
+    let (status, _) = app
+        .request(
+            Method::PATCH,
+            &format!("/groups/current/expenses/{}", ids[0]),
+            Some(json!({ "amount": 35.0 })),
+            Some(&token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .request(Method::DELETE, &format!("/groups/current/expenses/{}", ids[1]), None, Some(&token))
+        .await;
+    assert!(status.is_success());
+
+    let (status, created) = app.get("/groups/current/activity?action=expense_created", &token).await;
+    assert_eq!(status, StatusCode::OK, "activity failed: {}", created);
+    assert_eq!(created["total"], 3);
+    let entries = created["entries"].as_array().unwrap();
+    assert!(entries.iter().all(|e| e["action"] == "expense_created"));
+    // Newest first
+    assert_eq!(entries[0]["expense_id"], ids[2].as_str());
+    assert_eq!(entries[2]["expense_id"], ids[0].as_str());
+
+    let mut seen = Vec::new();
+    for offset in [0, 2, 4] {
+        let (_, page) = app
+            .get(&format!("/groups/current/activity?limit=2&offset={}", offset), &token)
+            .await;
+        assert_eq!(page["total"], 5);
+        seen.extend(page["entries"].as_array().unwrap().iter().map(|e| e["id"].as_i64().unwrap()));
+    }
+    assert_eq!(seen.len(), 5);
+    assert!(seen.windows(2).all(|w| w[0] > w[1]));
+    assert_eq!(app.get("/groups/current/activity?action=expense_deleted", &token).await.1["total"], 1);
+
+    let (_, future) = app.get("/groups/current/activity?from=2999-01-01", &token).await;
+    assert_eq!(future["total"], 0);
+    let (status, _) = app.get("/groups/current/activity?action=exploded", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn other_groups_rows_are_not_found() {
+    let app = TestApp::spawn().await;
+    let (token_a, members_a) = app.create_group(&["Alice", "Bob"]).await;
+    let (token_b, members_b) = app.create_group(&["Carol", "Dave"]).await;
+    let new_expense = |paid_by: &str| json!({ "description": "Groceries", "amount": 40.0, "paid_by": paid_by });
+    let (_, own) = app.post("/groups/current/expenses", new_expense(&members_a["Alice"]), &token_a).await;
+    let (_, theirs) = app.post("/groups/current/expenses", new_expense(&members_b["Carol"]), &token_b).await;
+    let own = format!("/groups/current/expenses/{}", own["id"].as_str().unwrap());
+    let theirs = format!("/groups/current/expenses/{}", theirs["id"].as_str().unwrap());
+    let carol = &members_b["Carol"];
+
+    let update = json!({
+        "description": "Hijacked",
+        "amount": 1.0,
+        "paid_by": members_a["Alice"],
+        "split_between": [members_a["Alice"]],
+    });
+    let attempts = [
+        (Method::PUT, theirs.clone(), Some(update)),
+        (Method::PATCH, theirs.clone(), Some(json!({ "description": "Hijacked" }))),
+        (Method::DELETE, theirs.clone(), None),
+        (Method::GET, format!("{}/receipt", theirs), None),
+        (Method::POST, format!("{}/duplicate", theirs), None),
+        (Method::PUT, format!("/groups/current/members/{}/payment", carol), Some(json!({ "iban": "DE89370400440532013000" }))),
+        (Method::PUT, format!("/groups/current/members/{}/notifications", carol), Some(json!({ "notify_on_expense": false }))),
+        (Method::GET, format!("/groups/current/members/{}/statement", carol), None),
+        (Method::GET, format!("/groups/current/members/{}/expenses", carol), None),
+    ];
+    for (method, path, body) in attempts {
+        let (status, _) = app.request(method.clone(), &path, body, Some(&token_a)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, path);
+    }
+
+    // Another group's member can't be pulled into an expense either
+    for patch in [json!({ "paid_by": carol }), json!({ "split_between": [members_a["Alice"], carol] })] {
+        let (status, _) = app.request(Method::PATCH, &own, Some(patch), Some(&token_a)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (_, listed) = app.get("/groups/current/expenses", &token_a).await;
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    let (_, theirs_listed) = app.get("/groups/current/expenses", &token_b).await;
+    assert_eq!(theirs_listed[0]["description"], "Groceries");
+    assert_eq!(theirs_listed[0]["amount"], 40.0);
+    let (_, carol_member) = app.get("/groups/current", &token_b).await;
+    let carol_member = carol_member["members"].as_array().unwrap().iter().find(|m| &m["id"] == carol).unwrap().clone();
+    assert!(carol_member["iban"].is_null());
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn every_id_route_refuses_another_groups_rows() {
+    let app = TestApp::spawn().await;
+    let (token_a, members_a) = app.create_group(&["Alice", "Bob"]).await;
+    let (token_b, members_b) = app.create_group(&["Carol", "Dave"]).await;
+    let (alice, carol, dave) = (&members_a["Alice"], &members_b["Carol"], &members_b["Dave"]);
+    let today = chrono::Utc::now().date_naive().to_string();
+    let (_, trip) = app.post("/groups/current/trips", json!({ "name": "Paris", "start_date": today }), &token_b).await;
+    let trip = trip["id"].as_str().unwrap().to_string();
+    let expense = app
+        .create_expense(&token_b, json!({ "description": "Groceries", "amount": 40.0, "paid_by": carol, "trip_id": trip }))
+        .await;
+    let expense = expense["id"].as_str().unwrap().to_string();
+    assert_eq!(app.upload_receipt(&expense, b"%PDF-1.4 receipt", &token_b).await.0, StatusCode::OK);
+    let (_, link) = app.post("/groups/current/share", json!({}), &token_b).await;
+    let code = link["code"].as_str().unwrap().to_string();
+    let (_, hook) = app
+        .post("/groups/current/webhooks", json!({ "url": "https://example.org/hook", "events": ["expense.created"] }), &token_b)
+        .await;
+    let hook = hook["webhook"]["id"].as_str().unwrap().to_string();
+    let (_, tokens) = app.get("/groups/current/tokens", &token_b).await;
+    let jti = tokens[0]["jti"].as_str().unwrap().to_string();
+
+    let snapshot = || {
+        let (app, token_b) = (&app, &token_b);
+        async move {
+            let mut state = Vec::new();
+            for path in ["/groups/current", "/groups/current/expenses", "/groups/current/trips", "/groups/current/tokens"] {
+                let (status, body) = app.get(path, token_b).await;
+                assert_eq!(status, StatusCode::OK, "{} {}", path, body);
+                state.push(body);
+            }
+            state
+        }
+    };
+    let before = snapshot().await;
+
+    let theirs = format!("/groups/current/expenses/{}", expense);
+    let trip_path = format!("/groups/current/trips/{}", trip);
+    let attempts = [
+        (Method::POST, format!("/groups/current/members/{}/reassign?to={}", carol, alice), None),
+        (Method::POST, format!("/groups/current/members/{}/reassign?to={}", alice, carol), None),
+        (Method::PUT, format!("{}/splits/{}/settled", theirs, carol), Some(json!({ "settled": true }))),
+        (Method::PUT, trip_path.clone(), Some(json!({ "name": "Hijacked", "start_date": today }))),
+        (Method::DELETE, trip_path.clone(), None),
+        (Method::GET, format!("{}/balances", trip_path), None),
+        (Method::DELETE, format!("{}/receipt", theirs), None),
+        (Method::GET, format!("/groups/current/members/{}/breakdown", carol), None),
+        (Method::GET, format!("/groups/current/members/{}/balance-history", carol), None),
+        (Method::GET, format!("/groups/current/pairs/{}/{}/expenses", carol, dave), None),
+        (Method::GET, format!("/groups/current/pairs/{}/{}/expenses", alice, carol), None),
+        (Method::GET, format!("/groups/current/settle-up?from={}&to={}", carol, alice), None),
+        (Method::POST, format!("/groups/current/members/{}/invite", carol), Some(json!({}))),
+        (Method::DELETE, format!("/groups/current/share-links/{}", code), None),
+        (Method::GET, format!("/groups/current/share-links/{}/qr", code), None),
+        (Method::DELETE, format!("/groups/current/tokens/{}", jti), None),
+        (Method::DELETE, format!("/groups/current/webhooks/{}", hook), None),
+        (Method::GET, format!("/groups/current/webhooks/{}/deliveries", hook), None),
+    ];
+    for (method, path, body) in attempts {
+        let (status, _) = app.request(method.clone(), &path, body, Some(&token_a)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, path);
+    }
+    let (status, _) = app.upload_receipt(&expense, b"%PDF-1.4 forged", &token_a).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Ids in a body are checked too: members to merge aren't found, ids the request refers to make it invalid
+    let body_attempts = [
+        ("/groups/current/members/merge", Method::POST, json!({ "source_id": carol, "target_id": alice }), StatusCode::NOT_FOUND),
+        (
+            "/groups/current/members/merge",
+            Method::POST,
+            json!({ "source_id": members_a["Bob"], "target_id": carol }),
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            "/groups/current/members/order",
+            Method::PUT,
+            json!({ "member_ids": [alice, carol, members_a["Bob"]] }),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/groups/current/expenses",
+            Method::POST,
+            json!({ "description": "Refund", "amount": 10.0, "paid_by": alice, "refund_of": expense }),
+            StatusCode::BAD_REQUEST,
+        ),
+    ];
+    for (path, method, body, expected) in body_attempts {
+        let (status, error) = app.request(method.clone(), path, Some(body.clone()), Some(&token_a)).await;
+        assert_eq!(status, expected, "{} {} {}: {}", method, path, body, error);
+    }
+
+    // Nothing of the other group moved, and its token and links still work
+    assert_eq!(snapshot().await, before);
+    let (status, _, receipt) = app.get_text(&format!("{}/receipt", theirs), &token_b).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(receipt, "%PDF-1.4 receipt");
+    let (status, _) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": code })), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, deliveries) = app.get(&format!("/groups/current/webhooks/{}/deliveries", hook), &token_b).await;
+    assert!(deliveries.is_array(), "{}", deliveries);
+    let (_, own) = app.get("/groups/current/expenses", &token_a).await;
+    assert_eq!(own, json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn balance_check_flags_foreign_splits() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (_, other_members) = app.create_group(&["Mallory"]).await;
+    let (_, expense) = app
+        .post(
+            "/groups/current/expenses",
+            json!({ "description": "Rent", "amount": 900.0, "paid_by": members["Alice"] }),
+            &token,
+        )
+        .await;
+
+    let (status, check) = app.get("/groups/current/balances/verify", &token).await;
+    assert_eq!(status, StatusCode::OK, "verify failed: {}", check);
+    assert_eq!(check["ok"], true);
+    assert_eq!(check["sum"], 0.0);
+    assert_eq!(check["foreign_references"], 0);
+
+    // A split for someone outside the group takes a share nobody in the group owes
+    app.execute(&format!(
+        "INSERT INTO expense_splits (expense_id, member_id) VALUES ('{}', '{}')",
+        expense["id"].as_str().unwrap(),
+        other_members["Mallory"]
+    ))
+    .await;
+    let (_, check) = app.get("/groups/current/balances/verify", &token).await;
+    assert_eq!(check["ok"], false);
+    assert_eq!(check["sum"], 300.0);
+    assert_eq!(check["foreign_references"], 1);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn balance_check_tolerates_rounding_and_catches_foreign_payers_and_stale_caches() {
+    let app = TestApp::spawn().await;
+    let (status, created) = app
+        .request(Method::POST, "/groups", Some(json!({ "name": "Tokyo", "member_names": ["Alice", "Bob", "Carol"], "currency": "JPY" })), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let token = created["token"].as_str().unwrap().to_string();
+    let members: std::collections::HashMap<&str, &str> = created["group"]["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["name"].as_str().unwrap(), m["id"].as_str().unwrap()))
+        .collect();
+    let (_, outsiders) = app.create_group(&["Mallory"]).await;
+    let verify = || {
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, check) = app.get("/groups/current/balances/verify", token).await;
+            assert_eq!(status, StatusCode::OK, "{}", check);
+            check
+        }
+    };
+
+    // Nothing to check yet is a pass; whole yen get half a yen of slack
+    let check = verify().await;
+    assert_eq!(check["ok"], true);
+    assert_eq!(check["sum"], 0.0);
+    assert_eq!(check["tolerance"], 0.5);
+    assert_eq!(check["balances"].as_array().unwrap().len(), 3);
+
+    // 1000 yen can't be split three ways evenly, but the shares still add up
+    let dinner = app
+        .create_expense(&token, json!({ "description": "Dinner", "amount": 1000.0, "paid_by": members["Alice"] }))
+        .await;
+    let dinner = dinner["id"].as_str().unwrap().to_string();
+    let payback = app
+        .create_expense(
+            &token,
+            json!({ "description": "Payback", "amount": 300.0, "paid_by": members["Bob"], "expense_type": "transfer", "transfer_to": members["Alice"], "split_between": [] }),
+        )
+        .await;
+    let check = verify().await;
+    assert_eq!(check["ok"], true, "{}", check);
+    assert_eq!(check["foreign_references"], 0);
+    assert_eq!(check["cache_stale"], false);
+
+    // A transfer to someone outside the group is flagged even though the sum looks fine
+    app.execute(&format!(
+        "UPDATE expenses SET transfer_to = '{}' WHERE id = '{}'",
+        outsiders["Mallory"],
+        payback["id"].as_str().unwrap()
+    ))
+    .await;
+    let check = verify().await;
+    assert_eq!(check["ok"], false);
+    assert_eq!(check["foreign_references"], 1);
+    app.execute(&format!(
+        "UPDATE expenses SET transfer_to = '{}' WHERE id = '{}'",
+        members["Alice"],
+        payback["id"].as_str().unwrap()
+    ))
+    .await;
+
+    // So is a payer from another group, whose credit nobody in the group holds
+    app.execute(&format!("UPDATE expenses SET paid_by = '{}' WHERE id = '{}'", outsiders["Mallory"], dinner)).await;
+    let check = verify().await;
+    assert_eq!(check["ok"], false);
+    assert_eq!(check["sum"], -1000.0);
+    assert_eq!(check["foreign_references"], 1);
+    app.execute(&format!("UPDATE expenses SET paid_by = '{}' WHERE id = '{}'", members["Alice"], dinner)).await;
+    assert_eq!(verify().await["ok"], true);
+
+    // A change that slipped past the version bump leaves the cache behind; the check drops it
+    let before = app.balances(&token).await;
+    app.execute("ALTER TABLE expenses DISABLE TRIGGER expenses_bump_version").await;
+    app.execute(&format!("UPDATE expenses SET amount = 1200 WHERE id = '{}'", dinner)).await;
+    app.execute("ALTER TABLE expenses ENABLE TRIGGER expenses_bump_version").await;
+    assert_eq!(app.balances(&token).await, before);
+    let check = verify().await;
+    assert_eq!(check["cache_stale"], true);
+    assert_eq!(check["ok"], true);
+    assert_eq!(verify().await["cache_stale"], false);
+    let after = app.balances(&token).await;
+    assert_eq!((after["Alice"], after["Bob"], after["Carol"]), (500.0, -100.0, -400.0), "{:?}", after);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn tokens_are_bound_to_the_configured_audience() {
+    let plain = TestApp::spawn().await;
+    let ours = TestApp::spawn_with(&[("JWT_ISSUER", "share-cost"), ("JWT_AUDIENCE", "ours")]).await;
+    let theirs = TestApp::spawn_with(&[("JWT_ISSUER", "share-cost"), ("JWT_AUDIENCE", "theirs")]).await;
+    let (our_token, _) = ours.create_group(&["Alice"]).await;
+    let (plain_token, _) = plain.create_group(&["Bob"]).await;
+    let path = "/groups/current/permissions";
+    assert_eq!(ours.get(path, &our_token).await.0, StatusCode::OK);
+    // Same signing key, but meant for another deployment
+    assert_eq!(theirs.get(path, &our_token).await.0, StatusCode::UNAUTHORIZED);
+    // Tokens without the claims only work where they aren't required
+    assert_eq!(ours.get(path, &plain_token).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(plain.get(path, &our_token).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn issuer_and_audience_are_checked_separately_and_carried_by_derived_tokens() {
+    let ours = TestApp::spawn_with(&[("JWT_ISSUER", "share-cost"), ("JWT_AUDIENCE", "ours")]).await;
+    let path = "/groups/current/permissions";
+    let (our_token, _) = ours.create_group(&["Alice", "Bob"]).await;
+
+    // Either claim alone ties a token to the deployment
+    let other_issuer = TestApp::spawn_with(&[("JWT_ISSUER", "elsewhere"), ("JWT_AUDIENCE", "ours")]).await;
+    assert_eq!(other_issuer.get(path, &our_token).await.0, StatusCode::UNAUTHORIZED);
+    let issuer_only = TestApp::spawn_with(&[("JWT_ISSUER", "share-cost")]).await;
+    assert_eq!(issuer_only.get(path, &our_token).await.0, StatusCode::OK);
+    let (issuer_only_token, _) = issuer_only.create_group(&["Carol"]).await;
+    assert_eq!(ours.get(path, &issuer_only_token).await.0, StatusCode::UNAUTHORIZED);
+
+    // Surrounding whitespace is ignored, and empty values count as unset
+    let padded = TestApp::spawn_with(&[("JWT_ISSUER", " share-cost "), ("JWT_AUDIENCE", "ours\n")]).await;
+    assert_eq!(padded.get(path, &our_token).await.0, StatusCode::OK);
+    let empty = TestApp::spawn_with(&[("JWT_ISSUER", ""), ("JWT_AUDIENCE", "  ")]).await;
+    let (empty_token, _) = empty.create_group(&["Dave"]).await;
+    assert_eq!(empty.get(path, &our_token).await.0, StatusCode::OK);
+    assert_eq!(ours.get(path, &empty_token).await.0, StatusCode::UNAUTHORIZED);
+
+    // Tokens handed out by a group carry the claims as well
+    let (_, scoped) = ours.post("/groups/current/scoped-token", json!({ "can_settle": false }), &our_token).await;
+    let scoped = scoped["token"].as_str().unwrap();
+    let (_, link) = ours.post("/groups/current/share", json!({}), &our_token).await;
+    let (status, redeemed) = ours
+        .request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", redeemed);
+    let redeemed = redeemed["token"].as_str().unwrap();
+    for token in [scoped, redeemed] {
+        assert_eq!(ours.get(path, token).await.0, StatusCode::OK);
+        assert_eq!(other_issuer.get(path, token).await.0, StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn share_link_renders_as_qr_svg() {
+    let app = TestApp::spawn_with(&[("APP_BASE_URL", "https://costs.example.org")]).await;
+    let (token, _) = app.create_group(&["Alice"]).await;
+    let (other_token, _) = app.create_group(&["Bob"]).await;
+    let (status, link) = app.post("/groups/current/share", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", link);
+    let code = link["code"].as_str().unwrap();
+
+    let (status, content_type, svg) = app.get_text(&format!("/groups/current/share-links/{}/qr", code), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "image/svg+xml");
+    assert!(svg.starts_with("<?xml"), "{}", svg);
+    assert!(svg.contains("<svg") && svg.trim_end().ends_with("</svg>"));
+
+    // Codes of other groups are not found
+    let (status, _, _) = app.get_text(&format!("/groups/current/share-links/{}/qr", code), &other_token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn share_link_qr_needs_a_usable_link_the_token_could_have_made() {
+    let app = TestApp::spawn_with(&[("APP_BASE_URL", " https://costs.example.org/ ")]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let qr = |code: &str, token: &str| {
+        let (app, path, token) = (&app, format!("/groups/current/share-links/{}/qr", code), token.to_string());
+        async move {
+            let (status, _, svg) = app.get_text(&path, &token).await;
+            (status, svg)
+        }
+    };
+    let share = |body: serde_json::Value| {
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, link) = app.post("/groups/current/share", body, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", link);
+            link["code"].as_str().unwrap().to_string()
+        }
+    };
+
+    // The encoded link uses the base URL without its padding or trailing slash
+    let (status, invite) = app.post(&format!("/groups/current/members/{}/invite", members["Bob"]), json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", invite);
+    let invite_code = invite["link"].as_str().unwrap().strip_prefix("https://costs.example.org/#join=").unwrap();
+    let plain = TestApp::spawn_with(&[("APP_BASE_URL", "https://costs.example.org")]).await;
+    let (plain_token, _) = plain.create_group(&["Carol"]).await;
+    let (_, plain_link) = plain.post("/groups/current/share", json!({}), &plain_token).await;
+    plain
+        .execute(&format!("UPDATE share_links SET code = '{}' WHERE code = '{}'", invite_code, plain_link["code"].as_str().unwrap()))
+        .await;
+    let (status, _, plain_svg) = plain.get_text(&format!("/groups/current/share-links/{}/qr", invite_code), &plain_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(qr(invite_code, &token).await, (StatusCode::OK, plain_svg));
+
+    // Links that can no longer be redeemed have no QR
+    let used = share(json!({ "max_uses": 1 })).await;
+    assert_eq!(qr(&used, &token).await.0, StatusCode::OK);
+    let (status, _) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": used })), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(qr(&used, &token).await.0, StatusCode::NOT_FOUND);
+    let expired = share(json!({})).await;
+    app.execute(&format!("UPDATE share_links SET expires_at = NOW() - INTERVAL '1 minute' WHERE code = '{}'", expired)).await;
+    assert_eq!(qr(&expired, &token).await.0, StatusCode::NOT_FOUND);
+    let revoked = share(json!({})).await;
+    let (status, _) = app.request(Method::DELETE, &format!("/groups/current/share-links/{}", revoked), None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(qr(&revoked, &token).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(qr("no-such-code", &token).await.0, StatusCode::NOT_FOUND);
+
+    // A token may only show links granting no more than it has itself
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_settle": false }), &token).await;
+    let scoped = scoped["token"].as_str().unwrap();
+    let full = share(json!({})).await;
+    assert_eq!(qr(&full, scoped).await.0, StatusCode::FORBIDDEN);
+    let no_settle = share(json!({ "can_settle": false })).await;
+    let (status, svg) = qr(&no_settle, scoped).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(svg.contains("<svg"), "{}", svg);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn refund_reverses_part_of_the_split() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (status, expense) = app
+        .post(
+            "/groups/current/expenses",
+            json!({
+                "description": "Tickets",
+                "amount": 90.0,
+                "paid_by": members["Alice"],
+                "split_type": "shares",
+                "splits": [
+                    { "member_id": members["Alice"], "share": 1 },
+                    { "member_id": members["Bob"], "share": 1 },
+                    { "member_id": members["Carol"], "share": 4 },
+                ],
+                "split_between": [members["Alice"], members["Bob"], members["Carol"]],
+            }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", expense);
+
+    // Alice gets 30 back; it is handed back in the same 1:1:4 proportions
+    let (status, refund) = app
+        .post(
+            "/groups/current/expenses",
+            json!({ "description": "Cancelled show", "amount": 30.0, "paid_by": members["Alice"], "refund_of": expense["id"] }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", refund);
+    assert_eq!(refund["refund_of"], expense["id"]);
+    assert_eq!(refund["expense_type"], "income");
+    let balances = app.balances(&token).await;
+    assert_eq!(balances["Alice"], 50.0);
+    assert_eq!(balances["Bob"], -10.0);
+    assert_eq!(balances["Carol"], -40.0);
+
+    // Refunds can't add up to more than the expense, or refund other groups' expenses
+    let over = json!({ "description": "Too much", "amount": 60.01, "paid_by": members["Alice"], "refund_of": expense["id"] });
+    assert_eq!(app.post("/groups/current/expenses", over, &token).await.0, StatusCode::BAD_REQUEST);
+    let (other_token, other_members) = app.create_group(&["Dave"]).await;
+    let foreign = json!({ "description": "Refund", "amount": 1.0, "paid_by": other_members["Dave"], "refund_of": expense["id"] });
+    assert_eq!(app.post("/groups/current/expenses", foreign, &other_token).await.0, StatusCode::BAD_REQUEST);
+    let patch = json!({ "amount": 90.01 });
+    let path = format!("/groups/current/expenses/{}", refund["id"].as_str().unwrap());
+    assert_eq!(app.request(Method::PATCH, &path, Some(patch), Some(&token)).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn refunds_stay_within_the_expense_through_edits_and_deletes() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let tickets = app
+        .create_expense(&token, json!({ "description": "Tickets", "amount": 90.0, "paid_by": members["Alice"] }))
+        .await;
+    let tickets_path = format!("/groups/current/expenses/{}", tickets["id"].as_str().unwrap());
+    let refund = |amount: f64| json!({ "description": "Refund", "amount": amount, "paid_by": members["Alice"], "refund_of": tickets["id"] });
+
+    // Several refunds may add up to the whole expense, but not a cent more
+    let first = app.create_expense(&token, refund(30.0)).await;
+    let first_path = format!("/groups/current/expenses/{}", first["id"].as_str().unwrap());
+    let second = app.create_expense(&token, refund(59.98)).await;
+    let (status, error) = app.post("/groups/current/expenses", refund(0.03), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(error["error"].as_str().unwrap().contains("0.02 left"), "{}", error);
+    app.create_expense(&token, refund(0.02)).await;
+    let balances = app.balances(&token).await;
+    assert!(balances.values().all(|b| *b == 0.0), "{:?}", balances);
+
+    // Only plain expenses can be refunded, and a refund takes the expense's split
+    let payback = app
+        .create_expense(
+            &token,
+            json!({ "description": "Payback", "amount": 5.0, "paid_by": members["Bob"], "expense_type": "transfer", "transfer_to": members["Alice"], "split_between": [] }),
+        )
+        .await;
+    let invalid = [
+        json!({ "description": "Refund", "amount": 1.0, "paid_by": members["Alice"], "refund_of": first["id"] }),
+        json!({ "description": "Refund", "amount": 1.0, "paid_by": members["Alice"], "refund_of": payback["id"] }),
+        json!({ "description": "Refund", "amount": 1.0, "paid_by": members["Alice"], "refund_of": tickets["id"], "split_between": [members["Bob"]] }),
+        json!({ "description": "Refund", "amount": 1.0, "paid_by": members["Alice"], "refund_of": tickets["id"], "expense_type": "transfer", "transfer_to": members["Bob"] }),
+        json!({ "description": "Refund", "amount": 1.0, "paid_by": members["Alice"], "refund_of": uuid::Uuid::new_v4() }),
+    ];
+    for body in invalid {
+        let (status, _) = app.post("/groups/current/expenses", body.clone(), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    // A refund being edited doesn't count against itself, and stays income
+    let (status, _) = app.request(Method::PATCH, &first_path, Some(json!({ "amount": 29.99 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.request(Method::PATCH, &first_path, Some(json!({ "amount": 30.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.request(Method::PATCH, &first_path, Some(json!({ "amount": 30.01 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.request(Method::PATCH, &first_path, Some(json!({ "expense_type": "expense" })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.request(Method::POST, &format!("{}/duplicate", first_path), None, Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The refunded expense can't shrink below its refunds or stop being an expense
+    let (status, _) = app.request(Method::PATCH, &tickets_path, Some(json!({ "amount": 89.99 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.request(Method::PATCH, &tickets_path, Some(json!({ "expense_type": "income" })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let put = json!({ "description": "Tickets", "amount": 50.0, "paid_by": members["Alice"], "split_between": [members["Alice"], members["Bob"]] });
+    let (status, _) = app.request(Method::PUT, &tickets_path, Some(put), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.request(Method::PATCH, &tickets_path, Some(json!({ "amount": 100.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Deleting a refund frees its amount; deleting the expense takes its refunds along
+    let second_path = format!("/groups/current/expenses/{}", second["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::DELETE, &second_path, None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    app.create_expense(&token, refund(69.98)).await;
+    let (status, _) = app.request(Method::DELETE, &tickets_path, None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, listed) = app.get("/groups/current/expenses", &token).await;
+    assert_eq!(listed.as_array().unwrap().len(), 1, "{}", listed);
+    assert_eq!(listed[0]["description"], "Payback");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn settle_permission_covers_transfers_only() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (status, scoped) = app
+        .post(
+            "/groups/current/scoped-token",
+            json!({
+                "can_delete_group": false,
+                "can_manage_members": false,
+                "can_update_payment": false,
+                "can_add_expenses": false,
+                "can_edit_expenses": false,
+            }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", scoped);
+    assert_eq!(scoped["permissions"]["can_settle"], true);
+    let settler = scoped["token"].as_str().unwrap();
+
+    let transfer = json!({
+        "description": "Paid back",
+        "amount": 15.0,
+        "paid_by": members["Bob"],
+        "expense_type": "transfer",
+        "transfer_to": members["Alice"],
+    });
+    let (status, created) = app.post("/groups/current/expenses", transfer, settler).await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let expense = json!({ "description": "Dinner", "amount": 30.0, "paid_by": members["Alice"] });
+    assert_eq!(app.post("/groups/current/expenses", expense.clone(), settler).await.0, StatusCode::FORBIDDEN);
+
+    // And the other way round: adding expenses no longer implies recording transfers
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_settle": false }), &token).await;
+    let adder = scoped["token"].as_str().unwrap();
+    assert_eq!(app.post("/groups/current/expenses", expense, adder).await.0, StatusCode::OK);
+    assert_eq!(app.post("/groups/current/settle-all", json!({}), adder).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(app.post("/groups/current/settle-all", json!({}), settler).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn settle_permission_is_capped_and_follows_adding_expenses_on_old_tokens() {
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, Validation};
+
+    let fixture = |name: &str| format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let private_key = fixture("jwt_private.pem");
+    let app = TestApp::spawn_with(&[
+        ("JWT_ALG", "RS256"),
+        ("JWT_PRIVATE_KEY", &private_key),
+        ("JWT_PUBLIC_KEY", &fixture("jwt_public.pem")),
+    ])
+    .await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let scoped = |body: serde_json::Value, token: &str| {
+        let (app, token) = (&app, token.to_string());
+        async move {
+            let (status, scoped) = app.post("/groups/current/scoped-token", body, &token).await;
+            assert_eq!(status, StatusCode::OK, "{}", scoped);
+            scoped["token"].as_str().unwrap().to_string()
+        }
+    };
+    let settler = scoped(
+        json!({ "can_delete_group": false, "can_manage_members": false, "can_update_payment": false, "can_add_expenses": false, "can_edit_expenses": false }),
+        &token,
+    )
+    .await;
+    let adder = scoped(json!({ "can_settle": false }), &token).await;
+    let dinner = app
+        .create_expense(&token, json!({ "description": "Dinner", "amount": 30.0, "paid_by": members["Alice"] }))
+        .await;
+    let dinner_path = format!("/groups/current/expenses/{}", dinner["id"].as_str().unwrap());
+    let payback = app
+        .create_expense(
+            &settler,
+            json!({ "description": "Paid back", "amount": 15.0, "paid_by": members["Bob"], "expense_type": "transfer", "transfer_to": members["Alice"] }),
+        )
+        .await;
+    let payback_path = format!("/groups/current/expenses/{}", payback["id"].as_str().unwrap());
+
+    // Duplicating follows the kind of the copy, and settling one share is a settlement
+    let duplicate = |path: &str| format!("{}/duplicate", path);
+    assert_eq!(app.request(Method::POST, &duplicate(&payback_path), None, Some(&settler)).await.0, StatusCode::OK);
+    assert_eq!(app.request(Method::POST, &duplicate(&dinner_path), None, Some(&settler)).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(app.request(Method::POST, &duplicate(&payback_path), None, Some(&adder)).await.0, StatusCode::FORBIDDEN);
+    let share = format!("{}/splits/{}/settled", dinner_path, members["Bob"]);
+    assert_eq!(app.request(Method::PUT, &share, Some(json!({ "settled": true })), Some(&adder)).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(app.request(Method::PUT, &share, Some(json!({ "settled": true })), Some(&settler)).await.0, StatusCode::OK);
+
+    // Recording a transfer doesn't extend to changing or removing it
+    let (status, _) = app.request(Method::PATCH, &payback_path, Some(json!({ "amount": 1.0 })), Some(&settler)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(app.request(Method::DELETE, &payback_path, None, Some(&settler)).await.0, StatusCode::FORBIDDEN);
+
+    // A token without it can't hand it out, directly or through a share link
+    let (_, capped) = app.post("/groups/current/scoped-token", json!({ "can_settle": true }), &adder).await;
+    assert_eq!(capped["permissions"]["can_settle"], false);
+    let (_, link) = app.post("/groups/current/share", json!({}), &adder).await;
+    let (_, redeemed) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None).await;
+    let (_, permissions) = app.get("/groups/current/permissions", redeemed["token"].as_str().unwrap()).await;
+    assert_eq!(permissions["can_settle"], false);
+    assert_eq!(permissions["can_add_expenses"], true);
+
+    // Tokens from before the permission existed settle if they could add expenses, as they did then
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.insecure_disable_signature_validation();
+    let header = jsonwebtoken::decode_header(&adder).unwrap();
+    let mut claims =
+        jsonwebtoken::decode::<serde_json::Value>(&adder, &jsonwebtoken::DecodingKey::from_secret(b""), &validation)
+            .unwrap()
+            .claims;
+    let private_pem = std::fs::read(&private_key).unwrap();
+    let key = EncodingKey::from_rsa_pem(&private_pem).unwrap();
+    for (old_permissions, may_settle) in [
+        (json!({ "dg": false, "mm": false, "up": false, "ae": true, "ee": false }), true),
+        (json!({ "dg": true, "mm": true, "up": true, "ae": false, "ee": true }), false),
+        (json!({ "can_add_expenses": false }), false),
+        (json!({}), true),
+    ] {
+        claims["p"] = old_permissions.clone();
+        let old = jsonwebtoken::encode(&Header { kid: header.kid.clone(), ..Header::new(Algorithm::RS256) }, &claims, &key).unwrap();
+        let (status, permissions) = app.get("/groups/current/permissions", &old).await;
+        assert_eq!(status, StatusCode::OK, "{}", permissions);
+        assert_eq!(permissions["can_settle"], may_settle, "{}", old_permissions);
+        let expected = if may_settle { StatusCode::OK } else { StatusCode::FORBIDDEN };
+        assert_eq!(app.post("/groups/current/settle-all", json!({}), &old).await.0, expected, "{}", old_permissions);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn groups_overview_reports_each_token() {
+    let app = TestApp::spawn().await;
+    let (trip_token, trip_members) = app.create_group(&["Alice", "Bob"]).await;
+    let (flat_token, _) = app.create_group(&["Carol", "Dave", "Erin"]).await;
+    let dinner = json!({ "description": "Dinner", "amount": 40.0, "paid_by": trip_members["Alice"] });
+    assert_eq!(app.post("/groups/current/expenses", dinner, &trip_token).await.0, StatusCode::OK);
+
+    // A token bound to Bob also reports his position
+    let (_, link) = app.post("/groups/current/share", json!({ "member_id": trip_members["Bob"] }), &trip_token).await;
+    let (_, redeemed) = app
+        .request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None)
+        .await;
+    let bob_token = redeemed["token"].as_str().unwrap();
+
+    let (status, overview) = app
+        .request(
+            Method::POST,
+            "/groups/overview",
+            Some(json!({ "tokens": [trip_token, "not-a-token", flat_token, bob_token] })),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", overview);
+    let entries = overview.as_array().unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0]["group"]["member_count"], 2);
+    assert_eq!(entries[0]["group"]["total_spend"], 40.0);
+    assert_eq!(entries[0]["group"]["balance"], serde_json::Value::Null);
+    assert!(entries[1]["error"].is_string());
+    assert!(entries[1].get("group").is_none());
+    assert_eq!(entries[2]["group"]["member_count"], 3);
+    assert_eq!(entries[2]["group"]["total_spend"], 0.0);
+    assert_eq!(entries[3]["group"]["member_id"], trip_members["Bob"].as_str());
+    assert_eq!(entries[3]["group"]["balance"], -20.0);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn groups_overview_explains_unusable_tokens_and_counts_only_spending() {
+    let app = TestApp::spawn().await;
+    let overview = |tokens: serde_json::Value| {
+        let app = &app;
+        async move { app.request(Method::POST, "/groups/overview", Some(json!({ "tokens": tokens })), None).await }
+    };
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    app.create_expense(
+        &token,
+        json!({ "description": "Museum", "amount": 20.0, "paid_by": members["Alice"], "currency": "USD", "exchange_rate": 0.9 }),
+    )
+    .await;
+    app.create_expense(
+        &token,
+        json!({ "description": "Paid back", "amount": 9.0, "paid_by": members["Bob"], "expense_type": "transfer", "transfer_to": members["Alice"] }),
+    )
+    .await;
+    app.create_expense(&token, json!({ "description": "Deposit back", "amount": 50.0, "paid_by": members["Bob"], "expense_type": "income" }))
+        .await;
+
+    // Only expenses count as spending, in the group currency
+    let (status, entries) = overview(json!([format!("  {}\n", token), token])).await;
+    assert_eq!(status, StatusCode::OK, "{}", entries);
+    assert_eq!(entries[0]["group"]["total_spend"], 18.0);
+    assert_eq!(entries[0]["group"]["member_count"], 2);
+    assert_eq!(entries[0], entries[1]);
+    assert!(entries[0]["group"]["archived_at"].is_null());
+
+    // Each way a token can stop working has its own error, and doesn't affect the others
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({}), &token).await;
+    let revoked = scoped["token"].as_str().unwrap();
+    let (_, tokens) = app.get("/groups/current/tokens", &token).await;
+    let jti = tokens.as_array().unwrap().iter().find(|t| t["current"] == false).unwrap()["jti"].as_str().unwrap().to_string();
+    let (status, _) = app.request(Method::DELETE, &format!("/groups/current/tokens/{}", jti), None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (deleted, _) = app.create_group(&["Carol"]).await;
+    assert_eq!(app.request(Method::DELETE, "/groups/current", None, Some(&deleted)).await.0, StatusCode::NO_CONTENT);
+    let truncated = &token[..token.len() - 4];
+    let (archived, _) = app.create_group(&["Dave"]).await;
+    app.request(Method::POST, "/groups/current/archive", None, Some(&archived)).await;
+
+    let (status, entries) = overview(json!([revoked, deleted, truncated, "", archived, token])).await;
+    assert_eq!(status, StatusCode::OK, "{}", entries);
+    let errors: Vec<_> = entries.as_array().unwrap().iter().map(|e| e["error"].as_str()).collect();
+    assert_eq!(
+        errors,
+        [
+            Some("Token has been revoked"),
+            Some("Group not found"),
+            Some("Invalid or expired token"),
+            Some("Invalid or expired token"),
+            None,
+            None,
+        ]
+    );
+    assert!(entries[4]["group"]["archived_at"].is_string());
+    assert_eq!(entries[5]["group"]["total_spend"], 18.0);
+
+    // Nothing to look up is fine; too much at once is not
+    let (status, entries) = overview(json!([])).await;
+    assert_eq!((status, entries), (StatusCode::OK, json!([])));
+    let (status, entries) = overview(json!(vec![token.clone(); 50])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(entries.as_array().unwrap().len(), 50);
+    let (status, error) = overview(json!(vec![token.clone(); 51])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(error["error"].as_str().unwrap().contains("At most 50"), "{}", error);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn indivisible_amounts_give_odd_cents_to_the_first_members() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Payer"]).await;
+    // Added one by one, so they are created (and get odd cents) in this order
+    let mut order = Vec::new();
+    for name in ["A", "B", "C", "D", "E", "F", "G"] {
+        let (status, group) = app.post("/groups/current/members", json!({ "name": name }), &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", group);
+        order.push(group["members"].as_array().unwrap().last().unwrap()["id"].as_str().unwrap().to_string());
+    }
+    // The display order doesn't matter
+    let mut reversed: Vec<&String> = order.iter().rev().collect();
+    reversed.push(&members["Payer"]);
+    let (status, group) = app.request(Method::PUT, "/groups/current/members/order", Some(json!({ "member_ids": reversed })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", group);
+
+    for (cents, count) in [(1000, 3), (10000, 7), (5, 4), (100, 6), (1001, 7), (299, 3)] {
+        let split: Vec<&String> = order.iter().take(count).collect();
+        // The order members are listed in the request doesn't matter
+        let listed: Vec<&String> = split.iter().rev().copied().collect();
+        let body = json!({
+            "description": "Odd",
+            "amount": cents as f64 / 100.0,
+            "paid_by": members["Payer"],
+            "split_between": listed,
+        });
+        // Twice, to see the same members get the extra cents every time
+        let mut previous = None;
+        for _ in 0..2 {
+            let (status, changes) = app.post("/groups/current/expenses/preview", body.clone(), &token).await;
+            assert_eq!(status, StatusCode::OK, "{}", changes);
+            let owed: Vec<i64> = split
+                .iter()
+                .map(|id| {
+                    let change = changes.as_array().unwrap().iter().find(|c| c["user_id"] == id.as_str()).unwrap();
+                    (-change["delta"].as_f64().unwrap() * 100.0).round() as i64
+                })
+                .collect();
+            assert_eq!(owed.iter().sum::<i64>(), cents, "{} cents / {}: {:?}", cents, count, owed);
+            let base = cents / count as i64;
+            let extra = (cents % count as i64) as usize;
+            let expected: Vec<i64> = (0..count).map(|i| base + i64::from(i < extra)).collect();
+            assert_eq!(owed, expected, "{} cents / {}", cents, count);
+            assert!(previous.is_none_or(|p| p == owed));
+            previous = Some(owed);
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn odd_units_go_to_the_largest_remainders_in_both_balance_engines() {
+    // (currency, amount, rate, split type, weights of A, B, C, D, expected minor units owed by each)
+    let cases = [
+        ("EUR", 0.10, 1.0, "shares", [1.0, 2.0, 0.0, 0.0], [3, 7, 0, 0]),
+        ("EUR", 1.00, 1.0, "shares", [1.0, 1.0, 1.0, 3.0], [17, 17, 16, 50]),
+        // Every remainder is half a cent: ties go to the members created first
+        ("EUR", 0.05, 1.0, "percentage", [10.0, 30.0, 30.0, 30.0], [1, 2, 1, 1]),
+        ("EUR", -0.10, 1.0, "equal", [1.0, 1.0, 1.0, 0.0], [-3, -3, -4, 0]),
+        ("JPY", 100.0, 1.0, "equal", [1.0, 1.0, 1.0, 0.0], [34, 33, 33, 0]),
+        ("EUR", 10.0, 0.333333, "equal", [1.0, 1.0, 0.0, 0.0], [167, 166, 0, 0]),
+    ];
+    for sql_threshold in ["0", "1000"] {
+        let app = TestApp::spawn_with(&[("SQL_BALANCES_THRESHOLD", sql_threshold)]).await;
+        for (currency, amount, rate, split_type, weights, expected) in cases {
+            let (status, created) = app
+                .request(
+                    Method::POST,
+                    "/groups",
+                    Some(json!({ "name": "Odd", "member_names": ["Payer", "A", "B", "C", "D"], "currency": currency })),
+                    None,
+                )
+                .await;
+            assert_eq!(status, StatusCode::OK, "{}", created);
+            let token = created["token"].as_str().unwrap();
+            let ids: Vec<&str> = created["group"]["members"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap()).collect();
+            let (payer, split) = (ids[0], &ids[1..]);
+            let in_split: Vec<(&str, f64)> = split.iter().copied().zip(weights).filter(|(_, w)| *w > 0.0).collect();
+            let mut body = json!({
+                "description": "Odd",
+                "amount": amount,
+                "paid_by": payer,
+                // Listed backwards: only remainders and the members' creation order decide
+                "split_between": in_split.iter().rev().map(|(id, _)| id).collect::<Vec<_>>(),
+                "split_type": split_type,
+            });
+            if rate != 1.0 {
+                body["currency"] = json!("USD");
+                body["exchange_rate"] = json!(rate);
+            }
+            if split_type != "equal" {
+                body["splits"] = in_split.iter().map(|(id, w)| json!({ "member_id": id, "share": w })).collect();
+            }
+            app.create_expense(token, body).await;
+
+            let balances = app.balances(token).await;
+            let scale = if currency == "JPY" { 1.0 } else { 100.0 };
+            let owed: Vec<i64> = ["A", "B", "C", "D"].iter().map(|name| (-balances[*name] * scale).round() as i64).collect();
+            let what = format!("{} {} at {} by {} with SQL_BALANCES_THRESHOLD={}", amount, currency, rate, split_type, sql_threshold);
+            assert_eq!(owed, expected, "{}", what);
+            assert_eq!((balances["Payer"] * scale).round() as i64, expected.iter().sum::<i64>(), "{}", what);
+        }
+    }
+}
+
+/// A zero as the API should report it: a plain number, not `null` (NaN) or `-0.0`.
+fn assert_zero(value: &serde_json::Value, what: &str) {
+    let number = value.as_f64().unwrap_or_else(|| panic!("{} is not a number: {}", what, value));
+    assert!(number == 0.0 && number.is_sign_positive(), "{} is not a clean zero: {}", what, value);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn stats_report_clean_zeros_without_expenses() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, carol) = (&members["Alice"], &members["Carol"]);
+
+    let check_carol = |stage: &'static str| {
+        let app = &app;
+        let token = &token;
+        async move {
+            let (status, stats) = app.get("/groups/current/stats/members", token).await;
+            assert_eq!(status, StatusCode::OK, "{}", stats);
+            let stats = stats.as_array().unwrap().iter().find(|m| m["member_id"] == carol.as_str()).unwrap().clone();
+            for field in ["paid", "spent", "income_collected", "income_share", "transfers_sent", "transfers_received", "balance"] {
+                assert_zero(&stats[field], &format!("{}: stats {}", stage, field));
+            }
+            let (_, balances) = app.get("/groups/current/balances", token).await;
+            let balance = balances.as_array().unwrap().iter().find(|b| b["user_id"] == carol.as_str()).unwrap().clone();
+            assert_zero(&balance["balance"], &format!("{}: balance", stage));
+            let (_, ranked) = app.get("/groups/current/members/by-balance", token).await;
+            let ranked = ranked.as_array().unwrap().iter().find(|m| m["id"] == carol.as_str()).unwrap().clone();
+            assert_zero(&ranked["balance"], &format!("{}: by-balance", stage));
+            let (status, statement) = app.get(&format!("/groups/current/members/{}/statement", carol), token).await;
+            assert_eq!(status, StatusCode::OK, "{}", statement);
+            assert_zero(&statement["opening_balance"], &format!("{}: opening balance", stage));
+            assert_zero(&statement["closing_balance"], &format!("{}: closing balance", stage));
+            assert_eq!(statement["entries"], json!([]));
+            let (_, history) = app.get(&format!("/groups/current/members/{}/balance-history", carol), token).await;
+            assert_eq!(history, json!([]));
+            let path = format!("/groups/current/settle-up?from={}&to={}", carol, alice);
+            let (status, settle_up) = app.get(&path, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", settle_up);
+            assert_zero(&settle_up["amount"], &format!("{}: settle-up", stage));
+        }
+    };
+
+    // A brand-new group
+    check_carol("empty group").await;
+    let (status, anomalies) = app.get("/groups/current/stats/anomalies", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", anomalies);
+    assert_eq!(anomalies["expense_count"], 0);
+    for field in ["mean", "std_dev", "threshold"] {
+        assert_zero(&anomalies[field], &format!("anomalies {}", field));
+    }
+    assert_eq!(anomalies["anomalies"], json!([]));
+    let (_, categories) = app.get("/groups/current/stats/by-category", &token).await;
+    assert_eq!(categories, json!([]));
+    for path in ["/groups/current/settlement-progress", "/groups/current/debt-matrix", "/groups/current/debtors"] {
+        let (status, list) = app.get(path, &token).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", path, list);
+        assert_eq!(list, json!([]), "{}", path);
+    }
+    let (_, overview) = app.request(Method::POST, "/groups/overview", Some(json!({ "tokens": [token] })), None).await;
+    assert_zero(&overview[0]["group"]["total_spend"], "overview total spend");
+
+    // Expenses, income and a transfer that never involve Carol
+    for body in [
+        json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"], "split_between": [members["Alice"], members["Bob"]] }),
+        json!({ "description": "Taxi", "amount": 7.0, "paid_by": members["Bob"], "split_between": [members["Alice"], members["Bob"]], "split_type": "shares",
+                "splits": [{ "member_id": members["Alice"], "share": 1.0 }, { "member_id": members["Bob"], "share": 2.0 }] }),
+        json!({ "description": "Deposit back", "amount": 3.0, "paid_by": members["Alice"], "expense_type": "income", "split_between": [members["Alice"], members["Bob"]] }),
+        json!({ "description": "Payback", "amount": 1.0, "paid_by": members["Bob"], "expense_type": "transfer", "transfer_to": members["Alice"], "split_between": [] }),
+    ] {
+        let (status, created) = app.post("/groups/current/expenses", body, &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", created);
+    }
+    check_carol("member in no splits").await;
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn settled_balances_are_clean_zeros() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (alice, bob) = (&members["Alice"], &members["Bob"]);
+    // 0.1 + 0.2 - 0.3 is not exactly zero in floating point
+    for body in [
+        json!({ "description": "Coffee", "amount": 0.1, "paid_by": alice, "split_between": [bob] }),
+        json!({ "description": "Tea", "amount": 0.2, "paid_by": alice, "split_between": [bob] }),
+        json!({ "description": "Payback", "amount": 0.3, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice, "split_between": [] }),
+    ] {
+        let (status, created) = app.post("/groups/current/expenses", body, &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", created);
+    }
+
+    let (_, balances) = app.get("/groups/current/balances", &token).await;
+    for balance in balances.as_array().unwrap() {
+        assert_zero(&balance["balance"], &format!("balance of {}", balance["user_name"]));
+    }
+    let (_, statement) = app.get(&format!("/groups/current/members/{}/statement", bob), &token).await;
+    assert_zero(&statement["closing_balance"], "closing balance");
+    let (_, history) = app.get(&format!("/groups/current/members/{}/balance-history", bob), &token).await;
+    assert_zero(&history[0]["balance"], "balance history");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn members_who_broke_even_and_lone_expenses_report_clean_zeros() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+
+    // A single expense has no spread to measure outliers against
+    app.create_expense(&token, json!({ "description": "Dinner", "amount": 20.0, "paid_by": alice, "split_between": [alice, bob] }))
+        .await;
+    let (_, anomalies) = app.get("/groups/current/stats/anomalies", &token).await;
+    assert_eq!(anomalies["mean"], 20.0);
+    assert_zero(&anomalies["std_dev"], "std_dev of one expense");
+    assert_eq!(anomalies["anomalies"], json!([]));
+
+    // Bob pays his share back, Carol only ever pays for herself, and an empty trip has nothing in it
+    app.create_expense(
+        &token,
+        json!({ "description": "Payback", "amount": 10.0, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice, "split_between": [] }),
+    )
+    .await;
+    app.create_expense(&token, json!({ "description": "Snack", "amount": 5.0, "paid_by": carol, "split_between": [carol] })).await;
+    let today = chrono::Utc::now().date_naive().to_string();
+    let (_, trip) = app.post("/groups/current/trips", json!({ "name": "Rome", "start_date": today }), &token).await;
+
+    let (_, balances) = app.get("/groups/current/balances?amounts=minor", &token).await;
+    let (_, trip_balances) = app.get(&format!("/groups/current/trips/{}/balances", trip["id"].as_str().unwrap()), &token).await;
+    for balance in balances.as_array().unwrap().iter().chain(trip_balances.as_array().unwrap()) {
+        assert_zero(&balance["balance"], &format!("balance of {}", balance["user_name"]));
+    }
+    assert!(balances.as_array().unwrap().iter().all(|b| b["balance_minor"] == 0));
+    let (_, stats) = app.get("/groups/current/stats/members", &token).await;
+    for member in stats.as_array().unwrap() {
+        assert_zero(&member["balance"], &format!("stats balance of {}", member["name"]));
+    }
+    let (_, breakdown) = app.get(&format!("/groups/current/members/{}/breakdown", carol), &token).await;
+    assert_zero(&breakdown["net"], "Carol's net");
+    let (_, pair) = app.get(&format!("/groups/current/pairs/{}/{}/expenses", alice, carol), &token).await;
+    assert_zero(&pair["net"], "Alice and Carol's net");
+    let (_, debtors) = app.get("/groups/current/debtors", &token).await;
+    assert_eq!(debtors, json!([]));
+    let (_, requests) = app.get("/groups/current/payment-requests", &token).await;
+    assert_eq!(requests["requests"], json!([]));
+
+    // A debt that was paid back in full leaves nothing remaining
+    let (_, progress) = app.get("/groups/current/settlement-progress", &token).await;
+    assert_eq!(progress.as_array().unwrap().len(), 1, "{}", progress);
+    assert_eq!(progress[0]["from"], bob.as_str());
+    assert_eq!((&progress[0]["owed"], &progress[0]["settled"]), (&json!(10.0), &json!(10.0)));
+    assert_zero(&progress[0]["remaining"], "remaining debt");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn reordering_members_changes_their_listing_order() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let names = |group: &serde_json::Value| -> Vec<String> {
+        group["members"].as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap().to_string()).collect()
+    };
+    let (_, group) = app.get("/groups/current", &token).await;
+    assert_eq!(names(&group), ["Alice", "Bob", "Carol"]);
+
+    let reorder = |ids: serde_json::Value| app.request(Method::PUT, "/groups/current/members/order", Some(json!({ "member_ids": ids })), Some(&token));
+    let (status, reordered) = reorder(json!([members["Carol"], members["Alice"], members["Bob"]])).await;
+    assert_eq!(status, StatusCode::OK, "{}", reordered);
+    assert_eq!(names(&reordered), ["Carol", "Alice", "Bob"]);
+    let (_, group) = app.get("/groups/current", &token).await;
+    assert_eq!(names(&group), ["Carol", "Alice", "Bob"]);
+    let (_, balances) = app.get("/groups/current/balances", &token).await;
+    let balance_names: Vec<&str> = balances.as_array().unwrap().iter().map(|b| b["user_name"].as_str().unwrap()).collect();
+    assert_eq!(balance_names, ["Carol", "Alice", "Bob"]);
+
+    // New members go last
+    let (_, group) = app.post("/groups/current/members", json!({ "name": "Dave" }), &token).await;
+    assert_eq!(names(&group), ["Carol", "Alice", "Bob", "Dave"]);
+
+    // The list must name every member exactly once
+    let dave = group["members"][3]["id"].clone();
+    let unknown = uuid::Uuid::new_v4().to_string();
+    for ids in [
+        json!([members["Carol"], members["Alice"], members["Bob"]]),
+        json!([members["Carol"], members["Alice"], members["Bob"], dave, members["Bob"]]),
+        json!([members["Carol"], members["Alice"], members["Bob"], unknown]),
+    ] {
+        let (status, error) = reorder(ids).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", error);
+    }
+    let (_, group) = app.get("/groups/current", &token).await;
+    assert_eq!(names(&group), ["Carol", "Alice", "Bob", "Dave"]);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn member_order_invalidates_caches_survives_merges_and_needs_manage_members() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+    let names = |group: &serde_json::Value| -> Vec<String> {
+        group["members"].as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap().to_string()).collect()
+    };
+    let reorder = |ids: serde_json::Value, token: &str| {
+        let (app, token) = (&app, token.to_string());
+        async move { app.request(Method::PUT, "/groups/current/members/order", Some(json!({ "member_ids": ids })), Some(&token)).await }
+    };
+    let (alice, bob, carol, dave) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Dave"]);
+
+    // Only the order changes what clients have cached; the same order again is a no-op
+    let (_, etag, _) = app.get_conditional("/groups/current", &token, None).await;
+    let etag = etag.expect("ETag header");
+    let (status, _) = reorder(json!([alice, bob, carol, dave]), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.get_conditional("/groups/current", &token, Some(&etag)).await.0, StatusCode::NOT_MODIFIED);
+    let (status, _) = reorder(json!([dave, carol, bob, alice]), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, group) = app.get_conditional("/groups/current", &token, Some(&etag)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(names(&group), ["Dave", "Carol", "Bob", "Alice"]);
+
+    // Every listing of members follows it
+    let (_, stats) = app.get("/groups/current/stats/members", &token).await;
+    let stat_names: Vec<&str> = stats.as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap()).collect();
+    assert_eq!(stat_names, ["Dave", "Carol", "Bob", "Alice"]);
+    let (_, overview) = app.get("/groups/current/overview", &token).await;
+    assert_eq!(names(&overview["group"]), ["Dave", "Carol", "Bob", "Alice"]);
+
+    // Merging a member away keeps the others in place, and newcomers still go last
+    let (status, merged) = app.post("/groups/current/members/merge", json!({ "source_id": carol, "target_id": alice }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", merged);
+    let (_, group) = app.post("/groups/current/members", json!({ "name": "Erin" }), &token).await;
+    assert_eq!(names(&group), ["Dave", "Bob", "Alice", "Erin"]);
+    let erin = group["members"][3]["id"].clone();
+    let (status, _) = reorder(json!([dave, carol, bob, alice, erin]), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Nothing listed, or something that isn't a member id, is rejected
+    for ids in [json!([]), json!([dave, bob, alice, "not-a-uuid"]), json!(null)] {
+        let (status, _) = reorder(ids.clone(), &token).await;
+        assert!(status.is_client_error(), "{}: {}", ids, status);
+    }
+
+    // It's member management, and a locked group keeps its order
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_manage_members": false }), &token).await;
+    let (status, _) = reorder(json!([erin, alice, bob, dave]), scoped["token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    app.request(Method::POST, "/groups/current/lock", None, Some(&token)).await;
+    let (status, _) = reorder(json!([erin, alice, bob, dave]), &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, group) = app.get("/groups/current", &token).await;
+    assert_eq!(names(&group), ["Dave", "Bob", "Alice", "Erin"]);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn unbalanced_balances_are_reported_instead_of_settled() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (_, strangers) = app.create_group(&["Mallory"]).await;
+    let (status, expense) = app
+        .post(
+            "/groups/current/expenses",
+            json!({
+                "description": "Dinner",
+                "amount": 30.0,
+                "paid_by": members["Alice"],
+                "split_between": [members["Alice"], members["Bob"], members["Carol"]],
+            }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", expense);
+    // Corrupt the data: a member of another group takes part in the split, so
+    // Alice is owed a share nobody in the group owes her
+    app.execute(&format!(
+        "INSERT INTO expense_splits (expense_id, member_id) VALUES ('{}', '{}')",
+        expense["id"].as_str().unwrap(),
+        strangers["Mallory"]
+    ))
+    .await;
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (22.5, -7.5, -7.5));
+
+    let (status, overview) = app.get("/groups/current/overview", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", overview);
+    assert_eq!(overview["settlements"], json!([]));
+    assert_eq!(
+        overview["settlement_warning"],
+        json!({
+            "unaccounted": 7.5,
+            "members": [{ "user_id": members["Alice"], "user_name": "Alice", "amount": 7.5 }],
+        })
+    );
+
+    let (status, error) = app.post("/groups/current/settle-all", json!({}), &token).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", error);
+    assert!(error["error"].as_str().unwrap().contains("7.50 unaccounted"), "{}", error);
+    let path = format!("/groups/current/settle-up?from={}&to={}", members["Bob"], members["Alice"]);
+    assert_eq!(app.get(&path, &token).await.0, StatusCode::CONFLICT);
+    assert_eq!(app.get("/groups/current/payment-requests", &token).await.0, StatusCode::CONFLICT);
+    // Nothing was recorded
+    assert_eq!(app.balances(&token).await, balances);
+
+    // A consistent group still gets its plan
+    app.execute(&format!("DELETE FROM expense_splits WHERE member_id = '{}'", strangers["Mallory"])).await;
+    let (_, overview) = app.get("/groups/current/overview", &token).await;
+    assert_eq!(overview["settlements"].as_array().unwrap().len(), 2);
+    assert!(overview.get("settlement_warning").is_none());
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn member_breakdown_adds_up_to_the_balance() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let everyone = [&members["Alice"], &members["Bob"], &members["Carol"]];
+    let mut ids = Vec::new();
+    for body in [
+        json!({ "description": "Groceries", "amount": 10.0, "paid_by": members["Alice"], "split_between": everyone }),
+        json!({ "description": "Taxi", "amount": 25.0, "paid_by": members["Bob"], "split_between": [members["Bob"], members["Carol"]], "split_type": "shares",
+                "splits": [{ "member_id": members["Bob"], "share": 1.0 }, { "member_id": members["Carol"], "share": 2.0 }] }),
+        json!({ "description": "Deposit back", "amount": 7.0, "paid_by": members["Carol"], "expense_type": "income", "split_between": everyone }),
+        json!({ "description": "Payback", "amount": 4.5, "paid_by": members["Carol"], "expense_type": "transfer", "transfer_to": members["Bob"], "split_between": [] }),
+        json!({ "description": "Museum", "amount": 12.0, "paid_by": members["Carol"], "split_between": [members["Alice"], members["Carol"]], "currency": "USD", "exchange_rate": 0.9 }),
+    ] {
+        let (status, created) = app.post("/groups/current/expenses", body, &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", created);
+        ids.push(created["id"].as_str().unwrap().to_string());
+    }
+    // A share settled directly still counts, as in the default balances
+    let path = format!("/groups/current/expenses/{}/splits/{}/settled", ids[0], members["Bob"]);
+    let (status, settled) = app.request(Method::PUT, &path, Some(json!({ "settled": true })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", settled);
+
+    let balances = app.balances(&token).await;
+    for (name, id) in &members {
+        let (status, breakdown) = app.get(&format!("/groups/current/members/{}/breakdown", id), &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", breakdown);
+        assert_eq!(breakdown["member_name"], name.as_str());
+        let entries = breakdown["entries"].as_array().unwrap();
+        let cents = |v: &serde_json::Value| (v.as_f64().unwrap() * 100.0).round() as i64;
+        let sum: i64 = entries.iter().map(|e| cents(&e["they_paid"]) - cents(&e["their_share"])).sum();
+        assert_eq!(sum, cents(&breakdown["net"]), "{}: {}", name, breakdown);
+        assert_eq!(breakdown["net"].as_f64().unwrap(), balances[name], "{}: {}", name, breakdown);
+    }
+
+    // Alice: paid the groceries and has a share of three expenses, but not the taxi
+    let (_, alice) = app.get(&format!("/groups/current/members/{}/breakdown", members["Alice"]), &token).await;
+    let entries = alice["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0]["description"], "Groceries");
+    assert_eq!(entries[0]["they_paid"], 10.0);
+    // Alice was created first, so she gets the odd cent
+    assert_eq!(entries[0]["their_share"], 3.34);
+    assert_eq!(entries[1]["expense_type"], "income");
+    assert_eq!(entries[1]["they_paid"], 0.0);
+    assert_eq!(entries[1]["their_share"], -2.34);
+    assert_eq!(entries[2]["their_share"], 5.4);
+
+    let unknown = format!("/groups/current/members/{}/breakdown", uuid::Uuid::new_v4());
+    assert_eq!(app.get(&unknown, &token).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn member_breakdown_matches_both_engines_through_transfers_refunds_and_merges() {
+    for sql_threshold in ["0", "1000"] {
+        let app = TestApp::spawn_with(&[("SQL_BALANCES_THRESHOLD", sql_threshold)]).await;
+        let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dan"]).await;
+        let (alice, bob, carol, dan) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Dan"]);
+        let yesterday = (chrono::Utc::now().date_naive() - chrono::Duration::days(1)).to_string();
+        let dinner = app
+            .create_expense(&token, json!({ "description": "Dinner", "amount": 100.0, "paid_by": alice, "split_between": [alice, bob, carol] }))
+            .await;
+        for body in [
+            json!({ "description": "Refund", "amount": 10.0, "paid_by": alice, "refund_of": dinner["id"] }),
+            json!({ "description": "Payback", "amount": 20.0, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice, "split_between": [] }),
+            json!({ "description": "Tickets", "amount": 10.0, "paid_by": dan, "split_between": [bob, carol, dan], "currency": "USD", "exchange_rate": 0.333333 }),
+            // Recorded last, but it happened first
+            json!({ "description": "Breakfast", "amount": 1.0, "paid_by": carol, "split_between": [alice, bob, carol], "expense_date": yesterday }),
+        ] {
+            app.create_expense(&token, body).await;
+        }
+
+        let breakdown_matches = |stage: &'static str| {
+            let (app, token, members) = (&app, &token, &members);
+            async move {
+                let balances = app.balances(token).await;
+                let (_, group) = app.get("/groups/current", token).await;
+                for member in group["members"].as_array().unwrap() {
+                    let name = member["name"].as_str().unwrap();
+                    let (_, breakdown) = app.get(&format!("/groups/current/members/{}/breakdown", member["id"].as_str().unwrap()), token).await;
+                    let cents = |v: &serde_json::Value| (v.as_f64().unwrap() * 100.0).round() as i64;
+                    let sum: i64 = breakdown["entries"].as_array().unwrap().iter().map(|e| cents(&e["they_paid"]) - cents(&e["their_share"])).sum();
+                    assert_eq!(sum, cents(&breakdown["net"]), "{} {}: {}", stage, name, breakdown);
+                    assert_eq!(breakdown["net"].as_f64().unwrap(), balances[name], "{} {} with SQL_BALANCES_THRESHOLD={}", stage, name, sql_threshold);
+                }
+                assert_eq!(group["members"].as_array().unwrap().len(), members.len() - usize::from(stage == "merged"));
+            }
+        };
+        breakdown_matches("recorded").await;
+
+        // Entries go by date; the odd cents go to the members created first
+        let (_, bob_breakdown) = app.get(&format!("/groups/current/members/{}/breakdown", bob), &token).await;
+        let entries: Vec<(&str, f64, f64)> = bob_breakdown["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["description"].as_str().unwrap(), e["they_paid"].as_f64().unwrap(), e["their_share"].as_f64().unwrap()))
+            .collect();
+        assert_eq!(
+            entries,
+            [("Breakfast", 0.0, 0.33), ("Dinner", 0.0, 33.33), ("Refund", 0.0, -3.33), ("Payback", 20.0, 0.0), ("Tickets", 0.0, 1.11)]
+        );
+        let (_, alice_breakdown) = app.get(&format!("/groups/current/members/{}/breakdown", alice), &token).await;
+        let payback = alice_breakdown["entries"].as_array().unwrap().iter().find(|e| e["description"] == "Payback").unwrap().clone();
+        assert_eq!((&payback["they_paid"], &payback["their_share"]), (&json!(0.0), &json!(20.0)));
+        assert_eq!(alice_breakdown["entries"][0]["their_share"], 0.34);
+
+        // Merging Carol into Dan hands Dan her entries
+        let (status, merged) = app.post("/groups/current/members/merge", json!({ "source_id": carol, "target_id": dan }), &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", merged);
+        breakdown_matches("merged").await;
+        assert_eq!(app.get(&format!("/groups/current/members/{}/breakdown", carol), &token).await.0, StatusCode::NOT_FOUND);
+    }
+
+    let app = TestApp::spawn().await;
+    let (token, _) = app.create_group(&["Alice"]).await;
+    assert_eq!(app.get("/groups/current/members/not-a-uuid/breakdown", &token).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn income_can_be_split_unevenly() {
+    for sql_threshold in ["200", "0"] {
+        // Both balance computations: expense by expense and in one SQL query
+        let app = TestApp::spawn_with(&[("SQL_BALANCES_THRESHOLD", sql_threshold)]).await;
+        let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+        let split = |entries: &[(&str, f64)]| -> Vec<serde_json::Value> {
+            entries.iter().map(|(name, share)| json!({ "member_id": members[*name], "share": share })).collect()
+        };
+        let everyone = [&members["Alice"], &members["Bob"], &members["Carol"]];
+        // Carol collects each payout and owes it out to the recipients
+        for (split_type, splits, expected) in [
+            ("percentage", split(&[("Alice", 50.0), ("Bob", 30.0), ("Carol", 20.0)]), [50.0, 30.0, -80.0]),
+            ("shares", split(&[("Alice", 1.0), ("Bob", 3.0), ("Carol", 0.0)]), [25.0, 75.0, -100.0]),
+            ("exact", split(&[("Alice", 10.0), ("Bob", 60.0), ("Carol", 30.0)]), [10.0, 60.0, -70.0]),
+            ("adjustment", split(&[("Alice", 40.0)]), [60.0, 20.0, -80.0]),
+        ] {
+            let (status, income) = app
+                .post(
+                    "/groups/current/expenses",
+                    json!({
+                        "description": "Prize",
+                        "amount": 100.0,
+                        "paid_by": members["Carol"],
+                        "expense_type": "income",
+                        "split_between": everyone,
+                        "split_type": split_type,
+                        "splits": splits,
+                    }),
+                    &token,
+                )
+                .await;
+            assert_eq!(status, StatusCode::OK, "{}: {}", split_type, income);
+            assert_eq!(income["split_type"], split_type);
+
+            let balances = app.balances(&token).await;
+            let got = [balances["Alice"], balances["Bob"], balances["Carol"]];
+            assert_eq!(got, expected, "{} split with SQL_BALANCES_THRESHOLD={}", split_type, sql_threshold);
+            let (status, _) = app
+                .request(Method::DELETE, &format!("/groups/current/expenses/{}", income["id"].as_str().unwrap()), None, Some(&token))
+                .await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+        }
+    }
+
+    // An uneven split must hand out the whole payout
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let income = |split_type: &str, shares: [f64; 3]| {
+        json!({
+            "description": "Prize",
+            "amount": 100.0,
+            "paid_by": members["Carol"],
+            "expense_type": "income",
+            "split_between": [members["Alice"], members["Bob"], members["Carol"]],
+            "split_type": split_type,
+            "splits": [
+                { "member_id": members["Alice"], "share": shares[0] },
+                { "member_id": members["Bob"], "share": shares[1] },
+                { "member_id": members["Carol"], "share": shares[2] },
+            ],
+        })
+    };
+    for (split_type, shares) in [("percentage", [50.0, 30.0, 0.0]), ("exact", [10.0, 20.0, 30.0]), ("shares", [0.0, 0.0, 0.0])] {
+        let (status, error) = app.post("/groups/current/expenses", income(split_type, shares), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", split_type, error);
+    }
+    // Percentages that only miss by rounding are fine
+    let (status, created) = app.post("/groups/current/expenses", income("percentage", [33.33, 33.33, 33.33]), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let balances = app.balances(&token).await;
+    assert_eq!(balances.values().map(|b| (b * 100.0).round() as i64).sum::<i64>(), 0);
+
+    // Edits are held to the same rule
+    let (_, exact) = app.post("/groups/current/expenses", income("exact", [10.0, 60.0, 30.0]), &token).await;
+    let path = format!("/groups/current/expenses/{}", exact["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "amount": 120.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn uneven_income_keeps_its_split_through_odd_cents_currencies_and_copies() {
+    for sql_threshold in ["1000", "0"] {
+        let app = TestApp::spawn_with(&[("SQL_BALANCES_THRESHOLD", sql_threshold)]).await;
+        let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+        let income = |amount: f64, split_type: &str, shares: &[(&str, f64)]| {
+            json!({
+                "description": "Prize",
+                "amount": amount,
+                "paid_by": members["Carol"],
+                "expense_type": "income",
+                "split_between": shares.iter().map(|(name, _)| &members[*name]).collect::<Vec<_>>(),
+                "split_type": split_type,
+                "splits": shares.iter().map(|(name, share)| json!({ "member_id": members[*name], "share": share })).collect::<Vec<_>>(),
+            })
+        };
+        let balances_in_cents = || {
+            let (app, token) = (&app, &token);
+            async move {
+                let balances = app.balances(token).await;
+                ["Alice", "Bob", "Carol"].map(|name| (balances[name] * 100.0).round() as i64)
+            }
+        };
+        let what = format!("SQL_BALANCES_THRESHOLD={}", sql_threshold);
+
+        // Equal shares of 1.00 leave an odd cent, which goes to Alice like for an expense
+        let (status, preview) =
+            app.post("/groups/current/expenses/preview", income(1.0, "shares", &[("Alice", 1.0), ("Bob", 1.0), ("Carol", 1.0)]), &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", preview);
+        let first = app.create_expense(&token, income(1.0, "shares", &[("Alice", 1.0), ("Bob", 1.0), ("Carol", 1.0)])).await;
+        assert_eq!(balances_in_cents().await, [34, 33, -67], "{}", what);
+        let previewed: i64 = preview.as_array().unwrap().iter().map(|c| (c["delta"].as_f64().unwrap() * 100.0).round() as i64).sum();
+        assert_eq!(previewed, 0);
+
+        // Paid out in dollars, split among the two others only, in the group currency
+        let mut dollars = income(10.0, "exact", &[("Alice", 7.0), ("Bob", 3.0)]);
+        dollars["currency"] = json!("USD");
+        dollars["exchange_rate"] = json!(0.9);
+        app.create_expense(&token, dollars).await;
+        assert_eq!(balances_in_cents().await, [34 + 630, 33 + 270, -67 - 900], "{}", what);
+
+        // A copy is split like the original, and editing the amount scales shares but not exact amounts
+        let (status, copy) = app
+            .request(Method::POST, &format!("/groups/current/expenses/{}/duplicate", first["id"].as_str().unwrap()), None, Some(&token))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", copy);
+        assert_eq!((&copy["split_type"], &copy["expense_type"]), (&json!("shares"), &json!("income")));
+        let copy_path = format!("/groups/current/expenses/{}", copy["id"].as_str().unwrap());
+        let (status, _) = app.request(Method::PATCH, &copy_path, Some(json!({ "amount": 3.0 })), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(balances_in_cents().await, [34 + 630 + 100, 33 + 270 + 100, -67 - 900 - 200], "{}", what);
+
+        // Exact amounts may miss the payout by a cent each, which still hands out all of it
+        let exact = income(1.0, "exact", &[("Alice", 0.33), ("Bob", 0.33), ("Carol", 0.33)]);
+        app.create_expense(&token, exact).await;
+        assert_eq!(balances_in_cents().await.iter().sum::<i64>(), 0, "{}", what);
+        let exact = income(1.0, "exact", &[("Alice", 0.32), ("Bob", 0.32), ("Carol", 0.32)]);
+        assert_eq!(app.post("/groups/current/expenses", exact, &token).await.0, StatusCode::BAD_REQUEST);
+        // Adjustments can't hand out more than the payout either
+        let adjusted = income(10.0, "adjustment", &[("Alice", 11.0)]);
+        assert_eq!(app.post("/groups/current/expenses", adjusted, &token).await.0, StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn issued_tokens_are_listed_capped_and_revocable() {
+    let app = TestApp::spawn_with(&[("MAX_GROUP_TOKENS", "3")]).await;
+    let (token, _) = app.create_group(&["Alice", "Bob"]).await;
+    let (_, read_only) = app
+        .post("/groups/current/scoped-token", json!({ "can_add_expenses": false, "can_settle": false }), &token)
+        .await;
+    let read_only = read_only["token"].as_str().unwrap();
+    let (_, link) = app.post("/groups/current/share", json!({}), &token).await;
+    let (status, redeemed) = app
+        .request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", redeemed);
+
+    let (status, tokens) = app.get("/groups/current/tokens", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", tokens);
+    let tokens = tokens.as_array().unwrap();
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens.iter().filter(|t| t["current"] == true).count(), 1);
+    let read_only_entry = tokens
+        .iter()
+        .find(|t| t["permissions"]["can_add_expenses"] == false)
+        .expect("scoped token is listed");
+    assert_eq!(read_only_entry["permissions"]["can_delete_group"], true);
+    assert!(read_only_entry["issued_at"].is_string());
+    let (status, _) = app.get("/groups/current/tokens", read_only).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // The group is full: no new tokens, and redeeming doesn't use up the link
+    let (status, error) = app.post("/groups/current/scoped-token", json!({}), &token).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(error["error"].as_str().unwrap().contains("at most 3"), "{}", error);
+    let (_, single_use) = app.post("/groups/current/share", json!({ "max_uses": 1 }), &token).await;
+    let redeem = json!({ "code": single_use["code"] });
+    let (status, _) = app.request(Method::POST, "/share/redeem", Some(redeem.clone()), None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Revoking one makes room and ends that token
+    let jti = read_only_entry["jti"].as_str().unwrap();
+    let path = format!("/groups/current/tokens/{}", jti);
+    let (status, _) = app.request(Method::DELETE, &path, None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = app.request(Method::DELETE, &path, None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.get("/groups/current", read_only).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.request(Method::POST, "/share/redeem", Some(redeem), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, tokens) = app.get("/groups/current/tokens", &token).await;
+    assert_eq!(tokens.as_array().unwrap().len(), 3);
+    assert!(tokens.as_array().unwrap().iter().all(|t| t["jti"] != jti));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn token_cap_counts_only_live_tokens_of_the_group_and_owners_can_start_over() {
+    let app = TestApp::spawn_with(&[("MAX_GROUP_TOKENS", "2")]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let listed = || {
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, tokens) = app.get("/groups/current/tokens", token).await;
+            assert_eq!(status, StatusCode::OK, "{}", tokens);
+            tokens.as_array().unwrap().clone()
+        }
+    };
+    let scoped = |token: &str| {
+        let (app, token) = (&app, token.to_string());
+        async move { app.post("/groups/current/scoped-token", json!({ "can_delete_group": false }), &token).await }
+    };
+
+    // A member-bound token shows who it acts as; it has never been used yet
+    let (_, link) = app.post("/groups/current/share", json!({ "member_id": members["Bob"] }), &token).await;
+    let (status, bob) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", bob);
+    let bob = bob["token"].as_str().unwrap().to_string();
+    let tokens = listed().await;
+    let bob_entry = tokens.iter().find(|t| t["member_id"] == members["Bob"].as_str()).expect("Bob's token is listed").clone();
+    assert!(bob_entry["last_used_at"].is_null(), "{}", bob_entry);
+    assert!(bob_entry["expires_at"].as_str().unwrap() > bob_entry["issued_at"].as_str().unwrap());
+    assert_eq!(app.get("/groups/current", &bob).await.0, StatusCode::OK);
+    let mut used = false;
+    for _ in 0..20 {
+        let tokens = listed().await;
+        if tokens.iter().any(|t| t["jti"] == bob_entry["jti"] && t["last_used_at"].is_string()) {
+            used = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(used, "last use of Bob's token was never recorded");
+
+    // The group is full, but that's no concern of other groups
+    assert_eq!(scoped(&token).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = app.post("/groups/current/merge-token", json!({ "other_token": bob }), &token).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (other, _) = app.create_group(&["Carol"]).await;
+    assert_eq!(scoped(&other).await.0, StatusCode::OK);
+    assert_eq!(listed().await.len(), 2);
+
+    // Expired tokens neither count nor show up
+    app.execute(&format!("UPDATE issued_tokens SET expires_at = NOW() - INTERVAL '1 second' WHERE jti = '{}'", bob_entry["jti"].as_str().unwrap()))
+        .await;
+    assert_eq!(listed().await.len(), 1);
+    let (status, _) = scoped(&token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The owner can always get a fresh token, even past the cap, and start over with only that one
+    let (status, fresh) = app.post("/groups/current/new-owner-token", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", fresh);
+    assert_eq!(listed().await.len(), 3);
+    let (status, reset) = app.post("/groups/current/new-owner-token", json!({ "revoke_existing": true }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", reset);
+    let reset = reset["token"].as_str().unwrap();
+    let (_, tokens) = app.get("/groups/current/tokens", reset).await;
+    assert_eq!(tokens.as_array().unwrap().len(), 1, "{}", tokens);
+    assert_eq!(tokens[0]["current"], true);
+    // A token issued in the same second as the reset isn't caught by it
+    let (status, after_reset) = scoped(reset).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.get("/groups/current", after_reset["token"].as_str().unwrap()).await.0, StatusCode::OK);
+    assert_eq!(scoped(reset).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn locked_group_rejects_edits_but_stays_readable() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let dinner = json!({ "description": "Dinner", "amount": 40.0, "paid_by": members["Alice"] });
+    assert_eq!(app.post("/groups/current/expenses", dinner.clone(), &token).await.0, StatusCode::OK);
+
+    // Only a token with every permission may lock
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_delete_group": false }), &token).await;
+    let scoped = scoped["token"].as_str().unwrap();
+    let (status, _) = app.request(Method::POST, "/groups/current/lock", None, Some(scoped)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.request(Method::POST, "/groups/current/lock", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, error) = app.post("/groups/current/expenses", dinner.clone(), &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(error["error"].as_str().unwrap().contains("locked"), "{}", error);
+    let (status, _) = app.post("/groups/current/members", json!({ "name": "Carol" }), &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app
+        .post("/groups/current/webhooks", json!({ "url": "https://example.org/hook", "events": ["expense.created"] }), &token)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.request(Method::DELETE, "/groups/current", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Previewing changes nothing, so it still works
+    let (status, preview) = app.post("/groups/current/expenses/preview", dinner.clone(), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", preview);
+
+    let (status, group) = app.get("/groups/current", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(group["locked"], true);
+    let (status, expenses) = app.get("/groups/current/expenses", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", expenses);
+    assert_eq!(app.balances(&token).await["Bob"], -20.0);
+    let (status, overview) = app.get("/groups/current/overview", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(overview["settlements"].as_array().unwrap().len(), 1);
+    let (status, _, _) = app.get_text("/groups/current/report.pdf", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.request(Method::POST, "/groups/current/unlock", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(app.post("/groups/current/expenses", dinner, &token).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn every_mutating_route_respects_the_lock_while_access_can_still_be_shared() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (alice, bob) = (&members["Alice"], &members["Bob"]);
+    let expense = app.create_expense(&token, json!({ "description": "Dinner", "amount": 40.0, "paid_by": alice })).await;
+    let expense = format!("/groups/current/expenses/{}", expense["id"].as_str().unwrap());
+    let today = chrono::Utc::now().date_naive().to_string();
+    let (_, trip) = app.post("/groups/current/trips", json!({ "name": "Rome", "start_date": today }), &token).await;
+    let trip = format!("/groups/current/trips/{}", trip["id"].as_str().unwrap());
+    let (_, hook) = app
+        .post("/groups/current/webhooks", json!({ "url": "https://example.org/hook", "events": ["expense.created"] }), &token)
+        .await;
+    let hook = format!("/groups/current/webhooks/{}", hook["webhook"]["id"].as_str().unwrap());
+    let (_, before) = app.get("/groups/current/expenses", &token).await;
+    assert_eq!(app.request(Method::POST, "/groups/current/lock", None, Some(&token)).await.0, StatusCode::NO_CONTENT);
+    // Locking again is harmless
+    assert_eq!(app.request(Method::POST, "/groups/current/lock", None, Some(&token)).await.0, StatusCode::NO_CONTENT);
+
+    // The lock is checked before anything else, so even incomplete requests are told why
+    let member = |suffix: &str| format!("/groups/current/members/{}{}", alice, suffix);
+    let attempts = [
+        (Method::POST, "/groups/current/members".to_string()),
+        (Method::POST, "/groups/current/members/merge".to_string()),
+        (Method::PUT, "/groups/current/members/order".to_string()),
+        (Method::POST, member(&format!("/reassign?to={}", bob))),
+        (Method::PUT, member("/payment")),
+        (Method::PUT, member("/notifications")),
+        (Method::POST, member("/invite")),
+        (Method::POST, "/groups/current/expenses".to_string()),
+        (Method::PUT, expense.clone()),
+        (Method::PATCH, expense.clone()),
+        (Method::DELETE, expense.clone()),
+        (Method::POST, format!("{}/duplicate", expense)),
+        (Method::POST, format!("{}/receipt", expense)),
+        (Method::DELETE, format!("{}/receipt", expense)),
+        (Method::PUT, format!("{}/splits/{}/settled", expense, bob)),
+        (Method::POST, "/groups/current/trips".to_string()),
+        (Method::PUT, trip.clone()),
+        (Method::DELETE, trip.clone()),
+        (Method::POST, "/groups/current/undo".to_string()),
+        (Method::POST, "/groups/current/settle-all".to_string()),
+        (Method::POST, "/groups/current/webhooks".to_string()),
+        (Method::DELETE, hook.clone()),
+        (Method::PUT, "/groups/current/name".to_string()),
+        (Method::PUT, "/groups/current/default-split".to_string()),
+        (Method::PUT, "/groups/current/default-share-permissions".to_string()),
+        (Method::DELETE, "/groups/current".to_string()),
+    ];
+    for (method, path) in attempts {
+        let (status, error) = app.request(method.clone(), &path, Some(json!({})), Some(&token)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{} {}", method, path);
+        assert!(error["error"].as_str().unwrap_or_default().contains("locked"), "{} {}: {}", method, path, error);
+    }
+    let (_, after) = app.get("/groups/current/expenses", &token).await;
+    assert_eq!(after, before);
+
+    // Handing out and revoking access doesn't change the group's data
+    let (status, link) = app.post("/groups/current/share", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", link);
+    let (status, redeemed) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", redeemed);
+    let guest = redeemed["token"].as_str().unwrap();
+    assert_eq!(app.get("/groups/current/balances", guest).await.0, StatusCode::OK);
+    let dinner = json!({ "description": "Dinner", "amount": 40.0, "paid_by": alice });
+    assert_eq!(app.post("/groups/current/expenses", dinner.clone(), guest).await.0, StatusCode::FORBIDDEN);
+
+    // A locked group can also be archived; it stays read-only until it is both unlocked and unarchived
+    assert_eq!(app.request(Method::POST, "/groups/current/archive", None, Some(&token)).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(app.post("/groups/current/expenses", dinner.clone(), &token).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(app.request(Method::POST, "/groups/current/unlock", None, Some(&token)).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(app.post("/groups/current/expenses", dinner.clone(), &token).await.0, StatusCode::CONFLICT);
+    assert_eq!(app.request(Method::POST, "/groups/current/unarchive", None, Some(&token)).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(app.post("/groups/current/expenses", dinner, guest).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn archived_group_rejects_mutations_but_stays_readable() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let dinner = json!({ "description": "Dinner", "amount": 40.0, "paid_by": members["Alice"] });
+    let expense = app.create_expense(&token, dinner.clone()).await;
+    let expense_path = format!("/groups/current/expenses/{}", expense["id"].as_str().unwrap());
+
+    // Archiving needs the delete permission
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_delete_group": false }), &token).await;
+    let scoped = scoped["token"].as_str().unwrap();
+    let (status, _) = app.request(Method::POST, "/groups/current/archive", None, Some(scoped)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.request(Method::POST, "/groups/current/archive", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = app.post("/groups/current/expenses", dinner.clone(), &token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app.request(Method::PATCH, &expense_path, Some(json!({ "amount": 10.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app.request(Method::DELETE, &expense_path, None, Some(&token)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app.post("/groups/current/members", json!({ "name": "Carol" }), &token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app.request(Method::DELETE, "/groups/current", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, group) = app.get("/groups/current", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(group["archived_at"].is_string(), "{}", group);
+    assert_eq!(app.expenses(&token).await.len(), 1);
+    assert_eq!(app.balances(&token).await["Bob"], -20.0);
+    let (status, content_type, _) = app.get_text("/groups/current/report.pdf", &token).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/pdf"));
+
+    let (status, _) = app.request(Method::POST, "/groups/current/unarchive", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, group) = app.get("/groups/current", &token).await;
+    assert!(group["archived_at"].is_null(), "{}", group);
+    app.create_expense(&token, dinner).await;
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn locking_changes_the_group_etag() {
+    let app = TestApp::spawn().await;
+    let (token, _) = app.create_group(&["Alice", "Bob"]).await;
+    for path in ["/groups/current", "/groups/current/overview"] {
+        let (status, etag, _) = app.get_conditional(path, &token, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let etag = etag.expect("ETag header");
+        let (status, _, _) = app.get_conditional(path, &token, Some(&etag)).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        app.request(Method::POST, "/groups/current/lock", None, Some(&token)).await;
+        let (status, _, body) = app.get_conditional(path, &token, Some(&etag)).await;
+        assert_eq!(status, StatusCode::OK, "{} still cached after locking", path);
+        let group = if path == "/groups/current" { &body } else { &body["group"] };
+        assert_eq!(group["locked"], true);
+        app.request(Method::POST, "/groups/current/unlock", None, Some(&token)).await;
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn receipt_scans_accept_photos_up_to_their_own_limit() {
+    let limit = 2 * 1024 * 1024;
+    let app = TestApp::spawn_with(&[
+        ("MAX_SCAN_BYTES", "2097152"),
+        // Nothing listens there, so accepted scans fail fast after the size check
+        ("OLLAMA_URL", "http://127.0.0.1:9"),
+    ])
+    .await;
+    let (token, _) = app.create_group(&["Alice"]).await;
+    let scan = |size: usize| {
+        let envelope = r#"{"image":"","language":"en"}"#.len();
+        format!(r#"{{"image":"{}","language":"en"}}"#, "A".repeat(size - envelope))
+    };
+
+    // Well over the 1 MiB JSON limit of other routes, just under the scan limit
+    let status = app.post_raw("/receipt/scan", &scan(limit - 64), &token).await;
+    assert_ne!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    let status = app.post_raw("/receipt/scan", &scan(limit + 64), &token).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Other routes keep the JSON limit
+    let expense = format!(r#"{{"description":"{}","amount":1.0}}"#, "A".repeat(limit - 64));
+    let status = app.post_raw("/groups/current/expenses", &expense, &token).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn sql_and_rust_balances_agree() {
+    let mut results = Vec::new();
+    for sql_threshold in ["0", "1000000"] {
+        let app = TestApp::spawn_with(&[("SQL_BALANCES_THRESHOLD", sql_threshold)]).await;
+        // Added one by one, so the members created first (who get odd cents) are the same in both apps
+        let (token, mut members) = app.create_group(&["Alice"]).await;
+        for name in ["Bob", "Carol", "Dave"] {
+            let (_, group) = app.post("/groups/current/members", json!({ "name": name }), &token).await;
+            let member = group["members"].as_array().unwrap().iter().find(|m| m["name"] == name).unwrap();
+            members.insert(name.to_string(), member["id"].as_str().unwrap().to_string());
+        }
+        let ids = |names: &[&str]| names.iter().map(|n| members[*n].clone()).collect::<Vec<_>>();
+        let expenses = [
+            json!({ "description": "Taxi", "amount": 10.0, "paid_by": members["Dave"], "split_between": ids(&["Alice", "Bob", "Carol"]) }),
+            json!({ "description": "Taxi back", "amount": 10.0, "paid_by": members["Dave"], "split_between": ids(&["Alice", "Bob", "Carol"]) }),
+            json!({
+                "description": "Groceries", "amount": 20.0, "paid_by": members["Alice"], "split_type": "shares",
+                "split_between": ids(&["Bob", "Carol", "Dave"]),
+                "splits": [
+                    { "member_id": members["Bob"], "share": 1.0 },
+                    { "member_id": members["Carol"], "share": 1.0 },
+                    { "member_id": members["Dave"], "share": 1.0 },
+                ],
+            }),
+            json!({ "description": "Refund", "amount": 7.0, "paid_by": members["Carol"], "expense_type": "income", "split_between": ids(&["Alice", "Bob", "Carol"]) }),
+            json!({
+                "description": "Museum", "amount": 9.99, "currency": "USD", "exchange_rate": 0.9137,
+                "paid_by": members["Bob"], "split_between": ids(&["Alice", "Bob", "Carol", "Dave"]),
+            }),
+            json!({ "description": "Payback", "amount": 5.55, "paid_by": members["Alice"], "expense_type": "transfer", "transfer_to": members["Dave"] }),
+        ];
+        let mut created = Vec::new();
+        for expense in expenses {
+            let (status, body) = app.post("/groups/current/expenses", expense, &token).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            created.push(body["id"].as_str().unwrap().to_string());
+        }
+        let path = format!("/groups/current/expenses/{}/splits/{}/settled", created[0], members["Bob"]);
+        let (status, _) = app.request(Method::PUT, &path, Some(json!({ "settled": true })), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let mut balances = Vec::new();
+        for query in ["", "?include_settled=false"] {
+            let (_, list) = app.get(&format!("/groups/current/balances{}", query), &token).await;
+            let mut list: Vec<(String, f64)> = list
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|b| (b["user_name"].as_str().unwrap().to_string(), b["balance"].as_f64().unwrap()))
+                .collect();
+            list.sort_by(|a, b| a.0.cmp(&b.0));
+            balances.push(list);
+        }
+        results.push(balances);
+    }
+    assert_eq!(results[0], results[1], "SQL (left) and Rust (right) balances differ");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn revoking_tokens_covers_the_current_second() {
+    // With RS256 there are no per-group key versions, so only the issue times
+    // tell the revoked tokens apart from the new owner token
+    let fixture = |name: &str| format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let (private_key, public_key) = (fixture("jwt_private.pem"), fixture("jwt_public.pem"));
+    let app = TestApp::spawn_with(&[
+        ("JWT_ALG", "RS256"),
+        ("JWT_PRIVATE_KEY", &private_key),
+        ("JWT_PUBLIC_KEY", &public_key),
+    ])
+    .await;
+    let (first, _) = app.create_group(&["Alice"]).await;
+    let (status, body) = app.post("/groups/current/new-owner-token", json!({}), &first).await;
+    assert_eq!(status, StatusCode::OK);
+    let second = body["token"].as_str().unwrap().to_string();
+
+    // Usually all within one second: the tokens issued just before must still go
+    let revoke = json!({ "revoke_existing": true });
+    let (status, body) = app.post("/groups/current/new-owner-token", revoke.clone(), &second).await;
+    assert_eq!(status, StatusCode::OK);
+    let third = body["token"].as_str().unwrap().to_string();
+    for token in [&first, &second] {
+        assert_eq!(app.get("/groups/current", token).await.0, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(app.get("/groups/current", &third).await.0, StatusCode::OK);
+
+    // Revoking again right away also revokes the owner token of the last revocation
+    let (status, body) = app.post("/groups/current/new-owner-token", revoke, &third).await;
+    assert_eq!(status, StatusCode::OK);
+    let fourth = body["token"].as_str().unwrap().to_string();
+    assert_eq!(app.get("/groups/current", &third).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/groups/current", &fourth).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn failed_split_rewrite_keeps_the_original_splits() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let mut everyone = vec![members["Alice"].clone(), members["Bob"].clone(), members["Carol"].clone()];
+    everyone.sort();
+    let split_of = |expense: &serde_json::Value| {
+        let mut ids: Vec<String> =
+            expense["split_between"].as_array().unwrap().iter().map(|id| id.as_str().unwrap().to_string()).collect();
+        ids.sort();
+        ids
+    };
+    let created = app
+        .create_expense(
+            &token,
+            json!({ "description": "Dinner", "amount": 30.0, "paid_by": members["Alice"], "split_between": everyone }),
+        )
+        .await;
+    let path = format!("/groups/current/expenses/{}", created["id"].as_str().unwrap());
+
+    // Carol passes the membership check, but her new split row fails on insert,
+    // after the old splits were already deleted inside the transaction
+    app.execute(&format!(
+        "CREATE FUNCTION fail_split() RETURNS trigger AS $$
+         BEGIN RAISE EXCEPTION 'simulated split failure'; END $$ LANGUAGE plpgsql;
+         CREATE TRIGGER fail_split BEFORE INSERT ON expense_splits
+         FOR EACH ROW WHEN (NEW.member_id = '{}') EXECUTE FUNCTION fail_split();",
+        members["Carol"]
+    ))
+    .await;
+    let update = json!({
+        "description": "Dinner for two",
+        "amount": 40.0,
+        "paid_by": members["Bob"],
+        "split_between": [members["Alice"], members["Carol"]],
+    });
+    let (status, _) = app.request(Method::PUT, &path, Some(update), Some(&token)).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let expenses = app.expenses(&token).await;
+    assert_eq!(expenses.len(), 1);
+    assert_eq!(expenses[0]["description"], "Dinner");
+    assert_eq!(expenses[0]["amount"], 30.0);
+    assert_eq!(split_of(&expenses[0]), everyone);
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (20.0, -10.0, -10.0));
+
+    // Splits that fail validation are rejected before anything is touched
+    let (_, other) = app.create_group(&["Zed"]).await;
+    let foreign = json!({
+        "description": "Dinner", "amount": 30.0, "paid_by": members["Alice"],
+        "split_between": [members["Alice"], other["Zed"]],
+    });
+    let (status, _) = app.request(Method::PUT, &path, Some(foreign), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let empty = json!({ "description": "Dinner", "amount": 30.0, "paid_by": members["Alice"], "split_between": [] });
+    let (status, _) = app.request(Method::PUT, &path, Some(empty), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(split_of(&app.expenses(&token).await[0]), everyone);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn itemized_expenses_split_by_item_and_save_atomically() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let groceries = |items: serde_json::Value| {
+        json!({ "description": "Groceries", "amount": 0.0, "paid_by": alice, "items": items })
+    };
+
+    let created = app
+        .create_expense(
+            &token,
+            groceries(json!([
+                { "description": "Wine", "amount": 12.0, "member_ids": [alice, bob] },
+                { "description": "Bread", "amount": 3.0, "member_ids": [carol, carol] },
+                { "description": "Cheese", "amount": 6.0, "member_ids": [alice, bob, carol] },
+            ])),
+        )
+        .await;
+    // The total comes from the items, not the request's amount
+    assert_eq!(created["amount"], 21.0);
+    assert_eq!(created["items"].as_array().unwrap().len(), 3);
+    assert_eq!(created["items"][1]["member_ids"], json!([carol]));
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (13.0, -8.0, -5.0));
+
+    let (_, other) = app.create_group(&["Zed"]).await;
+    for items in [
+        json!([]),
+        json!([{ "description": "Wine", "amount": 12.0, "member_ids": [] }]),
+        json!([{ "description": "Wine", "amount": -1.0, "member_ids": [alice] }]),
+        json!([{ "description": "Wine", "amount": 12.0, "member_ids": [other["Zed"]] }]),
+    ] {
+        let (status, _) = app.post("/groups/current/expenses", groceries(items.clone()), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", items);
+    }
+
+    // An item that fails to save takes the whole expense with it
+    app.execute(
+        "CREATE FUNCTION fail_item() RETURNS trigger AS $$
+         BEGIN RAISE EXCEPTION 'simulated item failure'; END $$ LANGUAGE plpgsql;
+         CREATE TRIGGER fail_item BEFORE INSERT ON expense_items
+         FOR EACH ROW WHEN (NEW.description = 'Broken') EXECUTE FUNCTION fail_item();",
+    )
+    .await;
+    let partial = groceries(json!([
+        { "description": "Wine", "amount": 12.0, "member_ids": [bob] },
+        { "description": "Broken", "amount": 3.0, "member_ids": [carol] },
+    ]));
+    let (status, _) = app.post("/groups/current/expenses", partial, &token).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(app.expenses(&token).await.len(), 1);
+    assert_eq!(app.balances(&token).await, balances);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn tags_are_normalized_filtered_and_saved_with_the_expense() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let dinner = |tags: serde_json::Value| {
+        json!({ "description": "Dinner", "amount": 20.0, "paid_by": members["Alice"], "tags": tags })
+    };
+
+    let created = app.create_expense(&token, dinner(json!([" #Food", "trip", "food", ""]))).await;
+    assert_eq!(created["tags"], json!(["food", "trip"]));
+    app.create_expense(&token, dinner(json!(["FOOD"]))).await;
+    app.create_expense(&token, dinner(json!([]))).await;
+
+    let (status, food) = app.get("/groups/current/expenses?tag=%23Food", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(food.as_array().unwrap().len(), 2);
+    let (_, tags) = app.get("/groups/current/tags", &token).await;
+    assert_eq!(tags, json!([{ "tag": "food", "count": 2 }, { "tag": "trip", "count": 1 }]));
+
+    let too_long = "x".repeat(51);
+    let (status, _) = app.post("/groups/current/expenses", dinner(json!([too_long])), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A tag that fails to save takes the whole expense with it
+    app.execute(
+        "CREATE FUNCTION fail_tag() RETURNS trigger AS $$
+         BEGIN RAISE EXCEPTION 'simulated tag failure'; END $$ LANGUAGE plpgsql;
+         CREATE TRIGGER fail_tag BEFORE INSERT ON expense_tags
+         FOR EACH ROW WHEN (NEW.tag = 'broken') EXECUTE FUNCTION fail_tag();",
+    )
+    .await;
+    let balances = app.balances(&token).await;
+    let (status, _) = app.post("/groups/current/expenses", dinner(json!(["food", "broken"])), &token).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(app.expenses(&token).await.len(), 3);
+    assert_eq!(app.balances(&token).await, balances);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn initial_expenses_are_validated_like_new_expenses() {
+    let app = TestApp::spawn().await;
+    let create = |name: &str, expenses: serde_json::Value| {
+        json!({ "name": name, "member_names": ["Alice", "Bob", "Carol"], "expenses": expenses })
+    };
+
+    let (status, created) = app
+        .request(
+            Method::POST,
+            "/groups",
+            Some(create(
+                "Imported",
+                json!([
+                    { "description": "Hotel", "amount": 90.0, "paid_by": 0 },
+                    { "description": "Taxi", "amount": 30.0, "paid_by": 1, "split_type": "adjustment",
+                      "split_between": [0, 1, 2], "splits": [{ "member": 2, "share": 6.0 }] },
+                    { "description": "Payback", "amount": 10.0, "paid_by": 2,
+                      "expense_type": "transfer", "transfer_to": 0 },
+                ]),
+            )),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    assert_eq!(created["expenses"].as_array().unwrap().len(), 3);
+    let token = created["token"].as_str().unwrap();
+    assert_eq!(app.expenses(token).await.len(), 3);
+    let balances = app.balances(token).await;
+    // Hotel 30 each; taxi (paid by Bob) 8 each plus Carol's 6 extra; Carol paid Alice back 10
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (42.0, -8.0, -34.0));
+
+    // One invalid expense rejects the whole group, whichever check it fails
+    let hotel = json!({ "description": "Hotel", "amount": 90.0, "paid_by": 0 });
+    for invalid in [
+        json!({ "description": "Taxi", "amount": 30.0, "paid_by": 1, "split_type": "bogus" }),
+        json!({ "description": "Payback", "amount": 10.0, "paid_by": 2, "expense_type": "transfer" }),
+        json!({ "description": "Taxi", "amount": 30.0, "paid_by": 1, "split_type": "adjustment",
+                "splits": [{ "member": 2, "share": 40.0 }] }),
+        json!({ "description": "Taxi", "amount": 30.0, "paid_by": 1, "split_type": "adjustment",
+                "splits": [{ "member": 2, "share": -1.0 }] }),
+        json!({ "description": "Salary", "amount": 30.0, "paid_by": 1, "expense_type": "income",
+                "split_type": "exact", "splits": [{ "member": 0, "share": 1.0 }, { "member": 1, "share": 1.0 },
+                { "member": 2, "share": 1.0 }] }),
+        json!({ "description": "Taxi", "amount": 30.0, "paid_by": 1, "split_between": [] }),
+        json!({ "description": "Taxi", "amount": 30.0, "paid_by": 1, "expense_date": "2999-01-01" }),
+        json!({ "description": "Taxi", "amount": 30.0, "paid_by": 5 }),
+    ] {
+        let (status, body) = app
+            .request(Method::POST, "/groups", Some(create("Rejected", json!([hotel, invalid]))), None)
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", invalid);
+        assert!(body["error"].as_str().unwrap_or_default().starts_with("expenses[1]: "), "{}", body);
+    }
+    assert_eq!(app.count("SELECT COUNT(*) FROM groups WHERE name = 'Rejected'").await, 0);
+    assert_eq!(app.count("SELECT COUNT(*) FROM expenses").await, 3);
+
+    // New expenses go through the same checks
+    let ids: Vec<&str> = created["group"]["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap())
+        .collect();
+    for invalid in [
+        json!({ "description": "Taxi", "amount": 30.0, "paid_by": ids[1], "split_type": "bogus" }),
+        json!({ "description": "Payback", "amount": 10.0, "paid_by": ids[2], "expense_type": "transfer" }),
+    ] {
+        let (status, _) = app.post("/groups/current/expenses", invalid.clone(), token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", invalid);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn members_created_with_the_group_get_odd_cents_in_listed_order() {
+    let app = TestApp::spawn().await;
+    // Created in one statement batch with the same created_at, so only their
+    // insertion order tells them apart. Several groups, since random ids would
+    // sometimes happen to sort in the right order.
+    for _ in 0..5 {
+        let names = ["Zed", "Alice", "Mia", "Bob"];
+        let (token, members) = app.create_group(&names).await;
+        let ids: Vec<&String> = names.iter().map(|n| &members[*n]).collect();
+        let reversed: Vec<&String> = ids.iter().rev().copied().collect();
+
+        // Defaulting to everyone and listing them backwards store the same order
+        let everyone = app
+            .create_expense(&token, json!({ "description": "Odd", "amount": 0.03, "paid_by": ids[3] }))
+            .await;
+        assert_eq!(everyone["split_between"], json!(ids));
+        let listed = app
+            .create_expense(
+                &token,
+                json!({ "description": "Odd", "amount": 10.02, "paid_by": ids[3], "split_between": reversed }),
+            )
+            .await;
+        assert_eq!(listed["split_between"], json!(ids));
+
+        // 0.03 gives a cent to the first three; 10.02 gives 2.51 to the first two
+        let balances = app.balances(&token).await;
+        let owed: Vec<i64> = names.iter().take(3).map(|n| (-balances[*n] * 100.0).round() as i64).collect();
+        assert_eq!(owed, vec![252, 252, 251], "{:?}", balances);
+        let (_, expenses) = app.get("/groups/current/expenses?amounts=minor", &token).await;
+        for expense in expenses.as_array().unwrap() {
+            assert_eq!(expense["split_between"], json!(ids));
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn receipts_are_stored_served_and_removed() {
+    let app = TestApp::spawn_with(&[("MAX_RECEIPT_BYTES", "1024")]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let expense = app
+        .create_expense(&token, json!({ "description": "Dinner", "amount": 20.0, "paid_by": members["Alice"] }))
+        .await;
+    let id = expense["id"].as_str().unwrap();
+    let path = format!("/groups/current/expenses/{}/receipt", id);
+
+    let (status, _, _) = app.get_text(&path, &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let pdf = b"%PDF-1.4 dinner receipt";
+    let (status, info) = app.upload_receipt(id, pdf, &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", info);
+    assert_eq!(info["content_type"], "application/pdf");
+    assert_eq!(info["size_bytes"], pdf.len());
+    let (status, content_type, body) = app.get_text(&path, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/pdf");
+    assert_eq!(body.as_bytes(), pdf);
+
+    // The type comes from the content, not the client, and is checked before the
+    // old receipt is touched
+    let (status, _) = app.upload_receipt(id, b"just some text", &token).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let mut oversized = b"%PDF-".to_vec();
+    oversized.resize(1025, b' ');
+    let (status, _) = app.upload_receipt(id, &oversized, &token).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(app.get_text(&path, &token).await.2.as_bytes(), pdf);
+    let mut at_limit = b"%PDF-".to_vec();
+    at_limit.resize(1024, b' ');
+    let (status, info) = app.upload_receipt(id, &at_limit, &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", info);
+    assert_eq!(app.get_text(&path, &token).await.2.len(), 1024);
+
+    // Another group sees neither the expense nor its receipt
+    let (other, _) = app.create_group(&["Zed"]).await;
+    assert_eq!(app.get_text(&path, &other).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.upload_receipt(id, pdf, &other).await.0, StatusCode::NOT_FOUND);
+    let (status, _) = app.request(Method::DELETE, &path, None, Some(&other)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = app.request(Method::DELETE, &path, None, Some(&token)).await;
+    assert!(status.is_success(), "{}", status);
+    assert_eq!(app.get_text(&path, &token).await.0, StatusCode::NOT_FOUND);
+    let (status, _) = app.request(Method::DELETE, &path, None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn slow_statements_time_out_with_503() {
+    let app = TestApp::spawn_with(&[("DB_TIMEOUT_MS", "300")]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    app.execute(
+        "CREATE FUNCTION slow_expense() RETURNS trigger AS $$
+         BEGIN PERFORM pg_sleep(2); RETURN NEW; END $$ LANGUAGE plpgsql;
+         CREATE TRIGGER slow_expense BEFORE INSERT ON expenses
+         FOR EACH ROW WHEN (NEW.description = 'Slow') EXECUTE FUNCTION slow_expense();",
+    )
+    .await;
+    let expense = |description: &str| json!({ "description": description, "amount": 10.0, "paid_by": members["Alice"] });
+
+    let started = std::time::Instant::now();
+    let (status, _) = app.post("/groups/current/expenses", expense("Slow"), &token).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(started.elapsed() < std::time::Duration::from_secs(2), "{:?}", started.elapsed());
+
+    // The cancelled statement left nothing behind, and the connection is usable again
+    app.create_expense(&token, expense("Fast")).await;
+    let expenses = app.expenses(&token).await;
+    assert_eq!(expenses.len(), 1);
+    assert_eq!(expenses[0]["description"], "Fast");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn undo_reverts_this_tokens_last_change_within_the_window() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (status, other) = app.post("/groups/current/scoped-token", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", other);
+    let other = other["token"].as_str().unwrap();
+    let dinner = json!({ "description": "Dinner", "amount": 20.0, "paid_by": members["Alice"] });
+
+    // Nothing to undo yet
+    let (status, _) = app.post("/groups/current/undo", json!({}), &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Undo a create: the expense is gone again
+    let created = app.create_expense(&token, dinner.clone()).await;
+    let (status, _) = app.post("/groups/current/undo", json!({}), other).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "another token can't undo it");
+    let (status, undone) = app.post("/groups/current/undo", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", undone);
+    assert_eq!(undone["action"], "expense_created");
+    assert_eq!(undone["expense_id"], created["id"]);
+    assert!(app.expenses(&token).await.is_empty());
+    assert_eq!(app.balances(&token).await["Alice"], 0.0);
+    // An undone action isn't undone twice
+    let (status, _) = app.post("/groups/current/undo", json!({}), &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Undo a delete: the expense comes back with its id and splits
+    let kept = app.create_expense(&token, dinner.clone()).await;
+    let path = format!("/groups/current/expenses/{}", kept["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::DELETE, &path, None, Some(&token)).await;
+    assert!(status.is_success(), "{}", status);
+    assert!(app.expenses(&token).await.is_empty());
+    let (status, undone) = app.post("/groups/current/undo", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", undone);
+    assert_eq!(undone["action"], "expense_deleted");
+    let expenses = app.expenses(&token).await;
+    assert_eq!(expenses.len(), 1);
+    assert_eq!(expenses[0]["id"], kept["id"]);
+    assert_eq!(expenses[0]["split_between"], kept["split_between"]);
+    assert_eq!(app.balances(&token).await["Alice"], 10.0);
+
+    // A change by someone else since then blocks the undo
+    let (status, patched) = app.request(Method::PATCH, &path, Some(json!({ "amount": 30.0 })), Some(other)).await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    let (status, _) = app.post("/groups/current/undo", json!({}), &token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(app.expenses(&token).await[0]["amount"], 30.0);
+
+    // Outside the window nothing is undone
+    app.create_expense(&token, dinner).await;
+    app.execute("UPDATE activity_log SET created_at = NOW() - INTERVAL '1 hour'").await;
+    let (status, _) = app.post("/groups/current/undo", json!({}), &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(app.expenses(&token).await.len(), 2);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn merging_members_keeps_every_balance() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Bobby"]).await;
+    let (alice, bob, carol, bobby) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Bobby"]);
+    let split = |entries: &[(&String, f64)]| {
+        entries.iter().map(|(id, share)| json!({ "member_id": id, "share": share })).collect::<Vec<_>>()
+    };
+    // Bob and Bobby share most of these splits, so they have to be folded together
+    for expense in [
+        json!({ "description": "Equal", "amount": 30.0, "paid_by": alice, "split_between": [alice, bob, bobby] }),
+        json!({ "description": "Percent", "amount": 100.0, "paid_by": carol, "split_between": [bob, bobby, carol],
+                "split_type": "percentage", "splits": split(&[(bob, 50.0), (bobby, 30.0), (carol, 20.0)]) }),
+        json!({ "description": "Exact", "amount": 15.0, "paid_by": bobby, "split_between": [alice, bobby],
+                "split_type": "exact", "splits": split(&[(alice, 5.0), (bobby, 10.0)]) }),
+        json!({ "description": "Adjusted", "amount": 40.0, "paid_by": alice, "split_between": [bob, bobby, carol],
+                "adjustments": [{ "member_id": bobby, "amount": 4.0 }] }),
+        json!({ "description": "Shares", "amount": 12.0, "paid_by": bob, "split_between": [alice, bob, bobby],
+                "split_type": "shares", "splits": split(&[(alice, 1.0), (bob, 1.0), (bobby, 2.0)]) }),
+        json!({ "description": "Payback", "amount": 7.0, "paid_by": bobby, "expense_type": "transfer", "transfer_to": carol }),
+    ] {
+        app.create_expense(&token, expense).await;
+    }
+    let before = app.balances(&token).await;
+
+    let (status, _) = app.post("/groups/current/members/merge", json!({ "source_id": bob, "target_id": bob }), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, other) = app.create_group(&["Zed"]).await;
+    let (status, _) =
+        app.post("/groups/current/members/merge", json!({ "source_id": other["Zed"], "target_id": bob }), &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, group) =
+        app.post("/groups/current/members/merge", json!({ "source_id": bobby, "target_id": bob }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", group);
+    assert_eq!(group["members"].as_array().unwrap().len(), 3);
+
+    let after = app.balances(&token).await;
+    let cents = |v: f64| (v * 100.0).round() as i64;
+    assert!(!after.contains_key("Bobby"));
+    assert_eq!(cents(after["Bob"]), cents(before["Bob"] + before["Bobby"]), "{:?} -> {:?}", before, after);
+    for name in ["Alice", "Carol"] {
+        assert_eq!(cents(after[name]), cents(before[name]), "{}", name);
+    }
+    // Every split had Bob or Bobby and now has Bob exactly once; the transfer moved to Bob
+    for expense in app.expenses(&token).await {
+        if expense["expense_type"] == "transfer" {
+            assert_eq!(expense["paid_by"], json!(bob));
+            continue;
+        }
+        let ids = expense["split_between"].as_array().unwrap();
+        assert!(!ids.contains(&json!(bobby)), "{}", expense);
+        assert_eq!(ids.iter().filter(|id| *id == &json!(bob)).count(), 1, "{}", expense);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn patching_one_field_leaves_the_others_alone() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let created = app
+        .create_expense(
+            &token,
+            json!({
+                "description": "Museum",
+                "amount": 40.0,
+                "paid_by": alice,
+                "split_between": [alice, bob, carol],
+                "split_type": "percentage",
+                "splits": [
+                    { "member_id": alice, "share": 50.0 },
+                    { "member_id": bob, "share": 30.0 },
+                    { "member_id": carol, "share": 20.0 },
+                ],
+                "currency": "USD",
+                "exchange_rate": 0.5,
+                "notes": "Tickets",
+                "tags": ["culture"],
+            }),
+        )
+        .await;
+    let path = format!("/groups/current/expenses/{}", created["id"].as_str().unwrap());
+    let unchanged = [
+        "description", "paid_by", "split_between", "split_type", "splits", "currency",
+        "exchange_rate", "expense_date", "notes", "tags", "expense_type",
+    ];
+    let patch = |body: serde_json::Value| app.request(Method::PATCH, &path, Some(body), Some(&token));
+
+    let (status, patched) = patch(json!({ "amount": 60.0 })).await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert_eq!(patched["amount"], 60.0);
+    for field in unchanged {
+        assert_eq!(patched[field], created[field], "{}", field);
+    }
+    // The stored splits still apply, to the new amount (60 USD = 30 in the group currency)
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (15.0, -9.0, -6.0));
+
+    // An explicit null clears a nullable field; other omitted fields stay
+    let (status, patched) = patch(json!({ "notes": null })).await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert!(patched["notes"].is_null());
+    assert_eq!(patched["amount"], 60.0);
+    assert_eq!(patched["splits"], created["splits"]);
+
+    // A rejected patch changes nothing, and unknown expenses are not found
+    let (status, _) = patch(json!({ "amount": 1e15, "description": "Changed" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = patch(json!({ "split_between": [] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let current = app.expenses(&token).await.remove(0);
+    assert_eq!(current["description"], "Museum");
+    assert_eq!(current["split_between"], created["split_between"]);
+    let missing = format!("/groups/current/expenses/{}", uuid::Uuid::new_v4());
+    let (status, _) = app.request(Method::PATCH, &missing, Some(json!({ "amount": 1.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn delete_dry_run_counts_what_the_delete_removes() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    // Another group whose rows must survive
+    let (other, other_members) = app.create_group(&["Zed", "Yan"]).await;
+    app.create_expense(&other, json!({ "description": "Other", "amount": 5.0, "paid_by": other_members["Zed"] })).await;
+
+    app.create_expense(&token, json!({ "description": "Dinner", "amount": 30.0, "paid_by": alice })).await;
+    let itemized = app
+        .create_expense(
+            &token,
+            json!({ "description": "Shop", "amount": 0.0, "paid_by": bob, "items": [
+                { "description": "Milk", "amount": 2.0, "member_ids": [alice] },
+                { "description": "Bread", "amount": 3.0, "member_ids": [bob, carol] },
+            ] }),
+        )
+        .await;
+    app.create_expense(&token, json!({ "description": "Payback", "amount": 5.0, "paid_by": carol, "expense_type": "transfer", "transfer_to": alice })).await;
+    let (status, _) = app.upload_receipt(itemized["id"].as_str().unwrap(), b"%PDF-1.4 shop", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let tables = ["members", "expenses", "expense_splits", "expense_items", "expense_receipts"];
+    let mut before = Vec::new();
+    for table in tables {
+        before.push(app.count(&format!("SELECT COUNT(*) FROM {}", table)).await);
+    }
+    let dry_run = "/groups/current?dry_run=true";
+
+    // Previewing works on locked and archived groups, deleting doesn't
+    app.request(Method::POST, "/groups/current/lock", None, Some(&token)).await;
+    let (status, preview) = app.request(Method::DELETE, dry_run, None, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", preview);
+    let (status, error) = app.request(Method::DELETE, "/groups/current", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(error["error"].as_str().unwrap_or_default().contains("locked"), "{}", error);
+    app.request(Method::POST, "/groups/current/unlock", None, Some(&token)).await;
+    app.request(Method::POST, "/groups/current/archive", None, Some(&token)).await;
+    assert_eq!(app.request(Method::DELETE, dry_run, None, Some(&token)).await.0, StatusCode::OK);
+    let (status, _) = app.request(Method::DELETE, "/groups/current", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    app.request(Method::POST, "/groups/current/unarchive", None, Some(&token)).await;
+
+    // The dry run itself removes nothing
+    assert_eq!(app.expenses(&token).await.len(), 3);
+    let (status, _) = app.request(Method::DELETE, "/groups/current", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    for ((table, count), key) in tables.iter().zip(before).zip(["members", "expenses", "splits", "items", "receipts"]) {
+        let after = app.count(&format!("SELECT COUNT(*) FROM {}", table)).await;
+        assert_eq!(count - after, preview[key].as_i64().unwrap(), "{}", table);
+    }
+    assert_eq!(preview["members"], 3);
+    assert_eq!(app.expenses(&other).await.len(), 1);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn json_bodies_over_the_limit_are_rejected() {
+    let limit = 4096;
+    let app = TestApp::spawn_with(&[("MAX_JSON_BYTES", "4096")]).await;
+    let (token, members) = app.create_group(&["Alice"]).await;
+    // Padded with whitespace, so only the size differs
+    let expense = |size: usize| {
+        let body = json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"] }).to_string();
+        format!("{}{}", body, " ".repeat(size - body.len()))
+    };
+
+    assert_eq!(app.post_raw("/groups/current/expenses", &expense(limit), &token).await, StatusCode::OK);
+    assert_eq!(
+        app.post_raw("/groups/current/expenses", &expense(limit + 1), &token).await,
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+    assert_eq!(app.expenses(&token).await.len(), 1);
+
+    // Unauthenticated routes too, e.g. a new group with a large import
+    let group = json!({ "name": "Big", "member_names": ["A"], "expenses": [] }).to_string();
+    let group = format!("{}{}", group, " ".repeat(limit));
+    assert_eq!(app.post_raw("/groups", &group, "").await, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn omitted_split_defaults_to_the_members_at_creation_time() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let dinner = json!({ "description": "Dinner", "amount": 30.0, "paid_by": members["Alice"] });
+
+    let first = app.create_expense(&token, dinner.clone()).await;
+    assert_eq!(first["split_between"], json!([members["Alice"], members["Bob"], members["Carol"]]));
+
+    let (status, group) = app.post("/groups/current/members", json!({ "name": "Dave" }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", group);
+    let dave = group["members"].as_array().unwrap().last().unwrap()["id"].clone();
+    let second = app.create_expense(&token, dinner.clone()).await;
+    assert_eq!(second["split_between"].as_array().unwrap().len(), 4);
+    assert!(second["split_between"].as_array().unwrap().contains(&dave));
+
+    // The earlier expense keeps its three members; Dave only owes for the second
+    let expenses = app.expenses(&token).await;
+    let stored = expenses.iter().find(|e| e["id"] == first["id"]).unwrap();
+    assert_eq!(stored["split_between"], first["split_between"]);
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Bob"], balances["Dave"]), (-17.5, -7.5));
+
+    // An explicit empty list is still an error, and transfers aren't split
+    let mut empty = dinner.clone();
+    empty["split_between"] = json!([]);
+    let (status, _) = app.post("/groups/current/expenses", empty, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let transfer = app
+        .create_expense(
+            &token,
+            json!({ "description": "Payback", "amount": 5.0, "paid_by": members["Bob"],
+                    "expense_type": "transfer", "transfer_to": members["Alice"] }),
+        )
+        .await;
+    assert_eq!(transfer["split_between"], json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn exclude_payer_leaves_the_payer_out_of_the_split() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+
+    // With the default split: Alice treats the other two
+    let treat = app
+        .create_expense(&token, json!({ "description": "Treat", "amount": 30.0, "paid_by": alice, "exclude_payer": true }))
+        .await;
+    assert_eq!(treat["split_between"], json!([bob, carol]));
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (30.0, -15.0, -15.0));
+
+    // With an explicit split, and `false` changes nothing
+    let listed = app
+        .create_expense(
+            &token,
+            json!({ "description": "Taxi", "amount": 10.0, "paid_by": bob, "split_between": [alice, bob], "exclude_payer": true }),
+        )
+        .await;
+    assert_eq!(listed["split_between"], json!([alice]));
+    let kept = app
+        .create_expense(
+            &token,
+            json!({ "description": "Taxi", "amount": 10.0, "paid_by": bob, "split_between": [alice, bob], "exclude_payer": false }),
+        )
+        .await;
+    assert_eq!(kept["split_between"], json!([alice, bob]));
+
+    // Edits take the flag too
+    let path = format!("/groups/current/expenses/{}", kept["id"].as_str().unwrap());
+    let (status, updated) = app
+        .request(
+            Method::PUT,
+            &path,
+            Some(json!({ "description": "Taxi", "amount": 10.0, "paid_by": bob, "split_between": [alice, bob, carol], "exclude_payer": true })),
+            Some(&token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    assert_eq!(updated["split_between"], json!([alice, carol]));
+
+    // Nobody left to split with
+    let (status, _) = app
+        .post(
+            "/groups/current/expenses",
+            json!({ "description": "Solo", "amount": 10.0, "paid_by": bob, "split_between": [bob], "exclude_payer": true }),
+            &token,
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn debtors_lists_only_negative_balances_most_negative_first() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+    let (alice, bob, carol, dave) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Dave"]);
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            &format!("/groups/current/members/{}/payment", carol),
+            Some(json!({ "paypal_email": "carol@example.org", "iban": "DE89370400440532013000" })),
+            Some(&token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, debtors) = app.get("/groups/current/debtors", &token).await;
+    assert_eq!(debtors, json!([]));
+
+    // Alice is owed 50; Carol owes 30, Bob 20; Dave is even
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 40.0, "paid_by": alice, "split_between": [bob, carol] })).await;
+    app.create_expense(&token, json!({ "description": "Taxi", "amount": 10.0, "paid_by": alice, "split_between": [carol] })).await;
+    app.create_expense(&token, json!({ "description": "Snack", "amount": 4.0, "paid_by": dave, "split_between": [dave] })).await;
+
+    let (status, debtors) = app.get("/groups/current/debtors", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", debtors);
+    let debtors = debtors.as_array().unwrap();
+    let listed: Vec<(&str, f64)> = debtors
+        .iter()
+        .map(|d| (d["user_name"].as_str().unwrap(), d["balance"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(listed, vec![("Carol", -30.0), ("Bob", -20.0)]);
+    assert_eq!(debtors[0]["paypal_email"], "carol@example.org");
+    assert_eq!(debtors[0]["iban"], "DE89370400440532013000");
+    assert!(debtors[1]["paypal_email"].is_null());
+
+    // Settled debtors drop off the list
+    app.create_expense(&token, json!({ "description": "Payback", "amount": 20.0, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice })).await;
+    let (_, debtors) = app.get("/groups/current/debtors", &token).await;
+    assert_eq!(debtors.as_array().unwrap().len(), 1);
+    assert_eq!(debtors[0]["user_name"], "Carol");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn rs256_tokens_verify_with_the_public_key_only() {
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+
+    let fixture = |name: &str| format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let (private_key, public_key) = (fixture("jwt_private.pem"), fixture("jwt_public.pem"));
+    let app = TestApp::spawn_with(&[
+        ("JWT_ALG", "RS256"),
+        ("JWT_PRIVATE_KEY", &private_key),
+        ("JWT_PUBLIC_KEY", &public_key),
+    ])
+    .await;
+    let (token, _) = app.create_group(&["Alice"]).await;
+    assert_eq!(app.get("/groups/current", &token).await.0, StatusCode::OK);
+
+    // Issued tokens are RS256 and verify with the public key alone
+    let header = jsonwebtoken::decode_header(&token).unwrap();
+    assert_eq!(header.alg, Algorithm::RS256);
+    assert_eq!(header.kid.as_deref(), Some("1"));
+    let public_pem = std::fs::read(&public_key).unwrap();
+    let claims = jsonwebtoken::decode::<serde_json::Value>(
+        &token,
+        &DecodingKey::from_rsa_pem(&public_pem).unwrap(),
+        &Validation::new(Algorithm::RS256),
+    )
+    .unwrap()
+    .claims;
+
+    // The same claims HMAC-signed with the (public) key as secret are no token
+    let mut forged = Header::new(Algorithm::HS256);
+    forged.kid = header.kid.clone();
+    let forged = jsonwebtoken::encode(&forged, &claims, &EncodingKey::from_secret(&public_pem)).unwrap();
+    assert_eq!(app.get("/groups/current", &forged).await.0, StatusCode::UNAUTHORIZED);
+    // Re-signed with the private key they are
+    let private_pem = std::fs::read(&private_key).unwrap();
+    let resigned = jsonwebtoken::encode(&header, &claims, &EncodingKey::from_rsa_pem(&private_pem).unwrap()).unwrap();
+    assert_eq!(app.get("/groups/current", &resigned).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn tokens_signed_with_a_previous_key_keep_working() {
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, Validation};
+
+    let app = TestApp::spawn_with(&[
+        ("JWT_SECRET", "current-secret"),
+        ("JWT_KEY_ID", "2"),
+        ("JWT_PREVIOUS_KEYS", "1=previous-secret"),
+    ])
+    .await;
+    let (token, _) = app.create_group(&["Alice"]).await;
+    // New tokens use the current key (derived per group, hence the suffix)
+    let kid = jsonwebtoken::decode_header(&token).unwrap().kid.unwrap();
+    assert!(kid.starts_with("2+g"), "{}", kid);
+
+    // The same claims as a token issued with the previous secret before the rotation
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.insecure_disable_signature_validation();
+    let claims = jsonwebtoken::decode::<serde_json::Value>(
+        &token,
+        &jsonwebtoken::DecodingKey::from_secret(b""),
+        &validation,
+    )
+    .unwrap()
+    .claims;
+    let sign = |kid: &str, secret: &[u8]| {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(kid.to_string());
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    };
+    assert_eq!(app.get("/groups/current", &sign("1", b"previous-secret")).await.0, StatusCode::OK);
+    // Only with the key its kid names
+    assert_eq!(app.get("/groups/current", &sign("1", b"current-secret")).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/groups/current", &sign("3", b"previous-secret")).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/groups/current", &token).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn group_creation_is_rate_limited_per_client_ip() {
+    let app = TestApp::spawn_with(&[("CREATE_GROUP_RATE_LIMIT_PER_MINUTE", "3")]).await;
+    let client = reqwest::Client::new();
+    // Behind the proxy, the client IP comes from X-Real-IP (see Rocket.toml)
+    let create = |ip: &'static str| {
+        client
+            .post(format!("{}/groups", app.base))
+            .header("X-Real-IP", ip)
+            .json(&json!({ "name": "Trip", "member_names": ["Alice"] }))
+            .send()
+    };
+
+    for _ in 0..3 {
+        assert_eq!(create("203.0.113.1").await.unwrap().status(), StatusCode::OK);
+    }
+    assert_eq!(create("203.0.113.1").await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    // Other clients have their own budget
+    assert_eq!(create("203.0.113.2").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(app.count("SELECT COUNT(*) FROM groups").await, 4);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn debt_matrix_keeps_each_pairwise_debt() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    app.create_expense(&token, json!({ "description": "Dinner", "amount": 30.0, "paid_by": alice })).await;
+    app.create_expense(&token, json!({ "description": "Taxi", "amount": 20.0, "paid_by": bob, "split_between": [alice, bob] })).await;
+
+    let (status, matrix) = app.get("/groups/current/debt-matrix", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", matrix);
+    let mut entries: Vec<(String, String, f64)> = matrix
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| (d["from"].as_str().unwrap().to_string(), d["to"].as_str().unwrap().to_string(), d["amount"].as_f64().unwrap()))
+        .collect();
+    entries.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut expected = vec![
+        (bob.clone(), alice.clone(), 10.0),
+        (carol.clone(), alice.clone(), 10.0),
+        // Not netted against Bob's debt to Alice, unlike the settlement plan
+        (alice.clone(), bob.clone(), 10.0),
+    ];
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(entries, expected);
+
+    // The settlement plan nets the same debts down to Carol paying Alice
+    let (_, overview) = app.get("/groups/current/overview", &token).await;
+    let plan = &overview["settlements"];
+    assert_eq!(plan.as_array().map(Vec::len), Some(1), "{}", plan);
+    assert_eq!((&plan[0]["from"], &plan[0]["to"], &plan[0]["amount"]), (&json!(carol), &json!(alice), &json!(10.0)));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn notes_round_trip_and_updates_keep_created_at() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let dinner = |notes: serde_json::Value| {
+        json!({ "description": "Dinner", "amount": 20.0, "paid_by": members["Alice"],
+                "split_between": [members["Alice"], members["Bob"]], "notes": notes })
+    };
+
+    let created = app.create_expense(&token, dinner(json!("Split excludes drinks"))).await;
+    assert_eq!(created["notes"], "Split excludes drinks");
+    let stored = app.expenses(&token).await.remove(0);
+    assert_eq!(stored["notes"], "Split excludes drinks");
+
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    let path = format!("/groups/current/expenses/{}", created["id"].as_str().unwrap());
+    let (status, updated) = app.request(Method::PUT, &path, Some(dinner(json!("Drinks paid separately"))), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    assert_eq!(updated["notes"], "Drinks paid separately");
+    let listed = app.expenses(&token).await.remove(0);
+    assert_eq!(listed["notes"], "Drinks paid separately");
+    assert_eq!(listed["created_at"], stored["created_at"]);
+    assert_eq!(updated["created_at"], stored["created_at"]);
+
+    // Leaving notes out of a full update clears them
+    let (_, cleared) = app.request(Method::PUT, &path, Some(dinner(serde_json::Value::Null)), Some(&token)).await;
+    assert!(cleared["notes"].is_null(), "{}", cleared);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn duplicating_copies_the_splits_under_a_new_id() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let source = app
+        .create_expense(
+            &token,
+            json!({
+                "description": "Groceries", "amount": 50.0, "paid_by": bob,
+                "split_between": [alice, bob, carol], "split_type": "shares",
+                "splits": [{ "member_id": alice, "share": 2.0 }, { "member_id": bob, "share": 2.0 }, { "member_id": carol, "share": 1.0 }],
+                "tags": ["food"],
+            }),
+        )
+        .await;
+    let today = source["expense_date"].clone();
+    let path = format!("/groups/current/expenses/{}/duplicate", source["id"].as_str().unwrap());
+
+    let (status, copy) = app.post(&path, json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", copy);
+    assert_ne!(copy["id"], source["id"]);
+    for field in ["description", "amount", "paid_by", "split_between", "split_type", "splits", "tags", "currency"] {
+        assert_eq!(copy[field], source[field], "{}", field);
+    }
+    assert_eq!(copy["expense_date"], today);
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (-40.0, 60.0, -20.0));
+
+    // A date from the body, and no body at all
+    let (status, dated) = app.post(&path, json!({ "expense_date": today }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", dated);
+    let status = app.post_raw(&path, "", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.expenses(&token).await.len(), 4);
+
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_add_expenses": false, "can_settle": false }), &token).await;
+    let (status, _) = app.post(&path, json!({}), scoped["token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (other, _) = app.create_group(&["Zed"]).await;
+    let (status, _) = app.post(&path, json!({}), &other).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn degenerate_group_and_member_names_are_rejected() {
+    let app = TestApp::spawn().await;
+    let long_group = "g".repeat(101);
+    let long_member = "m".repeat(61);
+    for (name, member_names) in [
+        ("", json!(["Alice"])),
+        ("   ", json!(["Alice"])),
+        (long_group.as_str(), json!(["Alice"])),
+        ("Trip", json!([])),
+        ("Trip", json!(["Alice", " "])),
+        ("Trip", json!(["Alice", long_member])),
+        ("Trip", json!(["Alice", "ALICE"])),
+    ] {
+        let body = json!({ "name": name, "member_names": member_names });
+        let (status, error) = app.request(Method::POST, "/groups", Some(body.clone()), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        assert!(error["error"].is_string(), "{}", error);
+    }
+    assert_eq!(app.count("SELECT COUNT(*) FROM groups").await, 0);
+
+    // Members added later follow the same rules
+    let (token, _) = app.create_group(&["Alice"]).await;
+    for name in ["  ", long_member.as_str()] {
+        let (status, _) = app.post("/groups/current/members", json!({ "name": name }), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", name);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn report_is_a_pdf_download() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 120.0, "paid_by": members["Alice"] })).await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/groups/current/report.pdf", app.base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let header = |name: &str| response.headers()[name].to_str().unwrap().to_string();
+    assert_eq!(header("content-type"), "application/pdf");
+    assert_eq!(header("content-disposition"), "attachment; filename=\"Test-group-report.pdf\"");
+    let pdf = response.bytes().await.unwrap();
+    assert!(pdf.starts_with(b"%PDF-"), "{:?}", &pdf[..pdf.len().min(16)]);
+    assert!(pdf.len() > 500);
+
+    // Unknown locales are rejected rather than silently ignored
+    let (status, _, _) = app.get_text("/groups/current/report.pdf?locale=xx-nope", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _, _) = app.get_text("/groups/current/report.pdf?locale=de-DE", &token).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn statement_running_balance_ends_at_the_balance() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let day = |ago: i64| (chrono::Utc::now().date_naive() - chrono::Duration::days(ago)).to_string();
+    for expense in [
+        json!({ "description": "Hotel", "amount": 30.0, "paid_by": alice, "expense_date": day(10) }),
+        json!({ "description": "Taxi", "amount": 12.0, "paid_by": bob, "split_between": [alice, bob], "expense_date": day(5) }),
+        json!({ "description": "Payback", "amount": 5.0, "paid_by": carol, "expense_type": "transfer", "transfer_to": alice, "expense_date": day(3) }),
+        json!({ "description": "Refund", "amount": 9.0, "paid_by": alice, "expense_type": "income", "expense_date": day(2) }),
+        // Doesn't involve Alice
+        json!({ "description": "Snack", "amount": 4.0, "paid_by": bob, "split_between": [carol], "expense_date": day(1) }),
+    ] {
+        app.create_expense(&token, expense).await;
+    }
+    let balance = app.balances(&token).await["Alice"];
+    assert_eq!(balance, 3.0);
+
+    let path = format!("/groups/current/members/{}/statement", alice);
+    let (status, statement) = app.get(&path, &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", statement);
+    assert_eq!(statement["opening_balance"], 0.0);
+    let entries = statement["entries"].as_array().unwrap();
+    let walked: Vec<(&str, f64, f64)> = entries
+        .iter()
+        .map(|e| (e["expense"]["description"].as_str().unwrap(), e["change"].as_f64().unwrap(), e["balance"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(
+        walked,
+        vec![("Hotel", 20.0, 20.0), ("Taxi", -6.0, 14.0), ("Payback", -5.0, 9.0), ("Refund", -6.0, 3.0)]
+    );
+    assert_eq!(statement["closing_balance"], balance);
+
+    // A window starts from what came before it
+    let (status, window) = app.get(&format!("{}?from={}&to={}", path, day(5), day(3)), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", window);
+    assert_eq!(window["opening_balance"], 20.0);
+    assert_eq!(window["entries"].as_array().unwrap().len(), 2);
+    assert_eq!(window["closing_balance"], 9.0);
+
+    let (status, _) = app.get(&format!("{}?from=yesterday", path), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app
+        .get(&format!("/groups/current/members/{}/statement", uuid::Uuid::new_v4()), &token)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn oversized_split_lists_are_rejected_and_repeats_charged_once() {
+    let app = TestApp::spawn_with(&[("MAX_SPLIT_MEMBERS", "4")]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob) = (&members["Alice"], &members["Bob"]);
+    let stranger = uuid::Uuid::new_v4().to_string();
+    let post = |split_between: serde_json::Value| {
+        let body = json!({ "description": "Dinner", "amount": 10.0, "paid_by": alice, "split_between": split_between });
+        app.post("/groups/current/expenses", body, &token)
+    };
+
+    // Over the absolute cap, even though the repeats would dedupe away
+    let (status, _) = post(json!([alice, alice, bob, bob, alice])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // Within the cap, but more distinct ids than the group has members
+    let (status, _) = post(json!([alice, bob, &members["Carol"], stranger])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(app.expenses(&token).await.is_empty());
+
+    let (status, created) = post(json!([bob, alice, bob, alice])).await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    assert_eq!(created["split_between"], json!([alice, bob]));
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (5.0, -5.0, 0.0));
+
+    // Updates dedupe the same way
+    let path = format!("/groups/current/expenses/{}", created["id"].as_str().unwrap());
+    let body = json!({ "description": "Dinner", "amount": 12.0, "paid_by": alice, "split_between": [bob, bob, bob] });
+    let (status, updated) = app.request(Method::PUT, &path, Some(body), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    assert_eq!(updated["split_between"], json!([bob]));
+    assert_eq!(app.balances(&token).await["Bob"], -12.0);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn expenses_without_a_split_use_the_group_default() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+    let (alice, bob, carol, dave) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Dave"]);
+    let set_default = |default_split: serde_json::Value| {
+        app.request(Method::PUT, "/groups/current/default-split", Some(json!({ "default_split": default_split })), Some(&token))
+    };
+
+    let (status, _) = set_default(json!({ "split_type": "shares", "splits": [{ "member_id": uuid::Uuid::new_v4(), "share": 1.0 }] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = set_default(json!({ "split_type": "halves", "splits": [{ "member_id": alice, "share": 1.0 }] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, group) = set_default(json!({
+        "split_type": "shares",
+        "splits": [{ "member_id": alice, "share": 2.0 }, { "member_id": bob, "share": 1.0 }, { "member_id": dave, "share": 1.0 }],
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", group);
+    assert_eq!(group["default_split"]["split_type"], "shares");
+
+    let expense = app.create_expense(&token, json!({ "description": "Groceries", "amount": 40.0, "paid_by": carol })).await;
+    assert_eq!(expense["split_type"], "shares");
+    assert_eq!(expense["split_between"], json!([alice, bob, dave]));
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"], balances["Dave"]), (-20.0, -10.0, 40.0, -10.0));
+
+    // An explicit split still wins over the default
+    let expense = app
+        .create_expense(&token, json!({ "description": "Taxi", "amount": 10.0, "paid_by": carol, "split_between": [carol] }))
+        .await;
+    assert_eq!(expense["split_type"], "equal");
+    assert_eq!(expense["split_between"], json!([carol]));
+
+    // Members merged away since the default was saved are skipped
+    let (status, _) = app.post("/groups/current/members/merge", json!({ "source_id": dave, "target_id": carol }), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let expense = app.create_expense(&token, json!({ "description": "Lunch", "amount": 9.0, "paid_by": carol })).await;
+    assert_eq!(expense["split_between"], json!([alice, bob]));
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"]), (-26.0, -13.0));
+
+    // Clearing the default goes back to everyone
+    let (status, _) = set_default(serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let expense = app.create_expense(&token, json!({ "description": "Coffee", "amount": 3.0, "paid_by": carol })).await;
+    assert_eq!(expense["split_type"], "equal");
+    assert_eq!(expense["split_between"], json!([alice, bob, carol]));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn list_queries_are_backed_by_indexes() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    app.create_expense(&token, json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"] })).await;
+
+    for (name, table) in [
+        ("idx_expenses_group_date", "expenses"),
+        ("idx_expense_splits_expense_id", "expense_splits"),
+        ("idx_expense_splits_member_id", "expense_splits"),
+        ("idx_members_group_id", "members"),
+    ] {
+        let sql = format!("SELECT COUNT(*) FROM pg_indexes WHERE indexname = '{}' AND tablename = '{}'", name, table);
+        assert_eq!(app.count(&sql).await, 1, "{} is missing", name);
+    }
+    // Covered as a prefix of idx_expenses_group_date
+    assert_eq!(app.count("SELECT COUNT(*) FROM pg_indexes WHERE indexname = 'idx_expenses_group_id'").await, 0);
+
+    // Any index leading with the filtered column will do; members also has
+    // idx_members_display_order on (group_id, ...), which the planner may prefer
+    let group = uuid::Uuid::new_v4();
+    for (sql, column) in [
+        (format!("SELECT * FROM expenses WHERE group_id = '{}' ORDER BY expense_date DESC, created_at DESC", group), "group_id"),
+        (format!("SELECT * FROM expense_splits WHERE expense_id = '{}'", group), "expense_id"),
+        (format!("SELECT * FROM expense_splits WHERE member_id = '{}'", group), "member_id"),
+        (format!("SELECT * FROM members WHERE group_id = '{}'", group), "group_id"),
+    ] {
+        let plan = app.explain(&sql).await;
+        assert!(!plan.contains("Seq Scan"), "{} scans the table:\n{}", sql, plan);
+        assert!(plan.contains(&format!("Index Cond: ({} =", column)), "{} doesn't use an index:\n{}", sql, plan);
+        assert!(!plan.contains("Sort"), "{} sorts instead of reading the index in order:\n{}", sql, plan);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn sigterm_finishes_in_flight_requests_and_closes_the_pool() {
+    let mut app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    app.execute(
+        "CREATE FUNCTION slow_expense() RETURNS trigger AS $$
+         BEGIN PERFORM pg_sleep(1); RETURN NEW; END $$ LANGUAGE plpgsql;
+         CREATE TRIGGER slow_expense BEFORE INSERT ON expenses
+         FOR EACH ROW EXECUTE FUNCTION slow_expense();",
+    )
+    .await;
+
+    let body = json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"] });
+    let (status, _) = tokio::join!(app.post("/groups/current/expenses", body, &token), async {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        app.terminate();
+    })
+    .0;
+    assert_eq!(status, StatusCode::OK, "the in-flight request was cut off");
+
+    let exit = app.wait_for_exit(std::time::Duration::from_secs(10)).await;
+    assert!(exit.success(), "{:?}", exit);
+    assert!(app.server_log().contains("Shutdown: database pool closed"), "{}", app.server_log());
+    assert_eq!(app.count("SELECT COUNT(*) FROM expenses").await, 1);
+    let connections = "SELECT COUNT(*) FROM pg_stat_activity WHERE datname = current_database() AND pid <> pg_backend_pid()";
+    assert_eq!(app.count(connections).await, 0, "pooled connections were left open");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn balances_convert_to_a_requested_currency() {
+    let (rates_url, requests) =
+        common::serve_json(json!({ "amount": 1.0, "base": "EUR", "date": "2026-10-14", "rates": { "USD": 1.1, "JPY": 161.234 } }));
+    let app = TestApp::spawn_with(&[("EXCHANGE_RATE_API_URL", &rates_url)]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 30.0, "paid_by": members["Alice"] })).await;
+
+    // Without the parameter the balances stay a plain list in the group currency
+    let (status, plain) = app.get("/groups/current/balances", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(plain.is_array(), "{}", plain);
+    let converted = |body: &serde_json::Value| -> Vec<f64> {
+        body["balances"].as_array().unwrap().iter().map(|b| b["balance"].as_f64().unwrap()).collect()
+    };
+
+    let (status, usd) = app.get("/groups/current/balances?currency=usd", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", usd);
+    assert_eq!((usd["currency"].as_str(), usd["group_currency"].as_str()), (Some("USD"), Some("EUR")));
+    assert_eq!(usd["rate"], 1.1);
+    assert_eq!(usd["rate_date"], "2026-10-14");
+    assert!(usd["fetched_at"].is_string());
+    assert_eq!(converted(&usd), vec![22.0, -11.0, -11.0]);
+    assert!(requests.lock().unwrap().iter().any(|p| p == "/latest?from=EUR&to=USD"), "{:?}", requests.lock().unwrap());
+
+    // Rounded to the target currency's minor units
+    let (status, jpy) = app.get("/groups/current/balances?currency=JPY", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", jpy);
+    assert_eq!(converted(&jpy), vec![3225.0, -1612.0, -1612.0]);
+
+    // The group currency itself needs no rate
+    let asked = requests.lock().unwrap().len();
+    let (status, eur) = app.get("/groups/current/balances?currency=EUR", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((eur["rate"].as_f64(), converted(&eur)), (Some(1.0), vec![20.0, -10.0, -10.0]));
+    assert_eq!(requests.lock().unwrap().len(), asked);
+
+    let (status, _) = app.get("/groups/current/balances?currency=US", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // The provider has no GBP rate
+    let (status, _) = app.get("/groups/current/balances?currency=GBP", &token).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn payment_preferences_round_trip_and_pick_the_payment_method() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let path = format!("/groups/current/members/{}/payment", members["Alice"]);
+    let put = |body: serde_json::Value| app.request(Method::PUT, &path, Some(body), Some(&token));
+
+    let (status, member) = put(json!({
+        "paypal_email": "alice@example.com",
+        "iban": "DE89370400440532013000",
+        "preferred_payment_method": "venmo",
+        "venmo_handle": "  @alice-w ",
+        "payment_note": "  Alice Wonder  ",
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", member);
+    assert_eq!(member["preferred_payment_method"], "venmo");
+    assert_eq!(member["venmo_handle"], "alice-w");
+    assert_eq!(member["payment_note"], "Alice Wonder");
+    let (_, group) = app.get("/groups/current", &token).await;
+    let listed = group["members"].as_array().unwrap().iter().find(|m| m["id"] == members["Alice"].as_str()).unwrap().clone();
+    for field in ["paypal_email", "iban", "preferred_payment_method", "venmo_handle", "payment_note"] {
+        assert_eq!(listed[field], member[field], "{}", field);
+    }
+
+    // Bob owes Alice, who wants Venmo
+    app.create_expense(&token, json!({ "description": "Dinner", "amount": 20.0, "paid_by": members["Alice"] })).await;
+    let method = || async {
+        let (status, batch) = app.get("/groups/current/payment-requests", &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", batch);
+        batch["requests"][0]["method"].clone()
+    };
+    assert_eq!(method().await, "venmo");
+
+    // Older clients sending only paypal_email/iban leave the preferences alone
+    let (status, member) = put(json!({ "paypal_email": "alice@example.com", "iban": null })).await;
+    assert_eq!(status, StatusCode::OK, "{}", member);
+    assert!(member["iban"].is_null());
+    assert_eq!((member["preferred_payment_method"].as_str(), member["venmo_handle"].as_str()), (Some("venmo"), Some("alice-w")));
+
+    // Without Venmo details the next available method is used
+    let (status, member) = put(json!({ "paypal_email": "alice@example.com", "venmo_handle": null, "payment_note": null })).await;
+    assert_eq!(status, StatusCode::OK, "{}", member);
+    assert!(member["venmo_handle"].is_null() && member["payment_note"].is_null());
+    assert_eq!(method().await, "paypal");
+
+    let (status, _) = put(json!({ "preferred_payment_method": "cash" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn reassigning_moves_every_reference_and_keeps_the_member() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+    let (alice, bob, carol, dave) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Dave"]);
+    for expense in [
+        json!({ "description": "Hotel", "amount": 30.0, "paid_by": bob, "split_between": [alice, bob, carol] }),
+        json!({ "description": "Taxi", "amount": 12.0, "paid_by": alice, "split_between": [bob, carol] }),
+        json!({ "description": "Wine", "amount": 9.0, "paid_by": dave, "split_between": [bob, carol], "split_type": "adjustment",
+                "splits": [{ "member_id": bob, "share": 3.0 }] }),
+        json!({ "description": "Payback", "amount": 5.0, "paid_by": bob, "expense_type": "transfer", "transfer_to": carol }),
+        json!({ "description": "Coffee", "amount": 4.0, "paid_by": carol, "split_between": [bob] }),
+    ] {
+        app.create_expense(&token, expense).await;
+    }
+    let before = app.balances(&token).await;
+    assert_eq!((before["Alice"], before["Bob"], before["Carol"], before["Dave"]), (2.0, 9.0, -20.0, 9.0));
+
+    let reassign = |from: &str, to: &str| {
+        let path = format!("/groups/current/members/{}/reassign?to={}", from, to);
+        let (app, token) = (&app, &token);
+        async move { app.post(&path, json!({}), token).await }
+    };
+    let (status, _) = reassign(bob, bob).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = reassign(bob, "not-a-member").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = reassign(bob, &uuid::Uuid::new_v4().to_string()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(app.balances(&token).await, before);
+
+    let (status, group) = reassign(bob, carol).await;
+    assert_eq!(status, StatusCode::OK, "{}", group);
+    assert!(group["members"].as_array().unwrap().iter().any(|m| m["id"] == bob.as_str()), "Bob was deleted");
+
+    let references = format!(
+        "SELECT (SELECT COUNT(*) FROM expenses WHERE paid_by = '{0}' OR transfer_to = '{0}')
+              + (SELECT COUNT(*) FROM expense_splits WHERE member_id = '{0}')",
+        bob
+    );
+    assert_eq!(app.count(&references).await, 0);
+    let after = app.balances(&token).await;
+    assert_eq!((after["Alice"], after["Bob"], after["Carol"], after["Dave"]), (2.0, 0.0, -11.0, 9.0));
+    // The transfer between the two is now a no-op and is dropped
+    let descriptions: Vec<String> = app.expenses(&token).await.iter().map(|e| e["description"].as_str().unwrap().to_string()).collect();
+    assert!(!descriptions.contains(&"Payback".to_string()), "{:?}", descriptions);
+    assert_eq!(descriptions.len(), 4);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn maintenance_purges_only_eligible_rows() {
+    let app = TestApp::spawn_with(&[("ADMIN_TOKEN", "admin-secret")]).await;
+    let trigger = |token: &'static str| app.request(Method::POST, "/admin/maintenance", None, Some(token));
+    let (status, _) = trigger("wrong-secret").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    // Let the run the server starts with finish first, so it can't eat the rows below
+    loop {
+        let (status, report) = trigger("admin-secret").await;
+        assert_eq!(status, StatusCode::OK, "{}", report);
+        if report["ran"] == true {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    let (token, _) = app.create_group(&["Alice"]).await;
+    let (revoked_token, _) = app.create_group(&["Bob"]).await;
+    let (_, group) = app.get("/groups/current", &token).await;
+    let (_, revoked_group) = app.get("/groups/current", &revoked_token).await;
+    let (group, revoked_group) = (group["id"].as_str().unwrap(), revoked_group["id"].as_str().unwrap());
+    let (stale, archived, webhook) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    let token_row = |jti: &str, expires: &str, revoked: &str| {
+        format!(
+            "INSERT INTO issued_tokens (jti, group_id, can_delete_group, can_manage_members, can_update_payment, can_add_expenses,
+                                        can_edit_expenses, can_settle, issued_at, expires_at, revoked_at)
+             VALUES ('{}', '{}', true, true, true, true, true, true, NOW() - INTERVAL '1 day', {}, {});",
+            jti, group, expires, revoked
+        )
+    };
+    app.execute(&format!(
+        "INSERT INTO groups (id, name, last_activity_at) VALUES ('{stale}', 'Stale', NOW() - INTERVAL '7 months');
+         INSERT INTO groups (id, name, last_activity_at, archived_at) VALUES ('{archived}', 'Archived', NOW() - INTERVAL '7 months', NOW());
+         INSERT INTO webhooks (id, group_id, url, secret, events) VALUES ('{webhook}', '{group}', 'https://example.com', 's', '{{}}');
+         INSERT INTO webhook_deliveries (webhook_id, event, attempt, success, created_at) VALUES
+             ('{webhook}', 'expense.created', 1, true, NOW() - INTERVAL '31 days'),
+             ('{webhook}', 'expense.created', 1, true, NOW() - INTERVAL '29 days');
+         UPDATE groups SET tokens_revoked_before = NOW() WHERE id = '{revoked_group}';
+         {}{}{}",
+        token_row("expired", "NOW() - INTERVAL '1 minute'", "NULL"),
+        token_row("revoked", "NOW() + INTERVAL '1 day'", "NOW()"),
+        token_row("live", "NOW() + INTERVAL '1 day'", "NULL"),
+    ))
+    .await;
+    let tokens = |group: &str| format!("SELECT COUNT(*) FROM issued_tokens WHERE group_id = '{}'", group);
+    let live_tokens = app.count(&tokens(group)).await;
+    let revoked_group_tokens = app.count(&tokens(revoked_group)).await;
+    assert!(revoked_group_tokens > 0);
+
+    let (status, report) = trigger("admin-secret").await;
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    assert_eq!(report["ran"], true);
+    assert_eq!(report["inactive_groups_deleted"], 1);
+    assert_eq!(report["webhook_deliveries_deleted"], 1);
+    assert_eq!(report["stale_tokens_deleted"], revoked_group_tokens + 1);
+
+    let groups = format!("SELECT COUNT(*) FROM groups WHERE id IN ('{}', '{}', '{}', '{}')", stale, archived, group, revoked_group);
+    assert_eq!(app.count(&groups).await, 3);
+    assert_eq!(app.count(&format!("SELECT COUNT(*) FROM groups WHERE id = '{}'", stale)).await, 0);
+    assert_eq!(app.count("SELECT COUNT(*) FROM webhook_deliveries").await, 1);
+    assert_eq!(app.count(&tokens(group)).await, live_tokens - 1);
+    assert_eq!(app.count("SELECT COUNT(*) FROM issued_tokens WHERE jti IN ('revoked', 'live')").await, 2);
+    assert_eq!(app.count(&tokens(revoked_group)).await, 0);
+    // The group's own token is still good
+    let (status, _) = app.get("/groups/current", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, report) = trigger("admin-secret").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((report["inactive_groups_deleted"].as_u64(), report["stale_tokens_deleted"].as_u64()), (Some(0), Some(0)));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn token_info_describes_the_token_and_warns_before_expiry() {
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Validation};
+
+    let fixture = |name: &str| format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let (private_key, public_key) = (fixture("jwt_private.pem"), fixture("jwt_public.pem"));
+    let app = TestApp::spawn_with(&[
+        ("JWT_ALG", "RS256"),
+        ("JWT_PRIVATE_KEY", &private_key),
+        ("JWT_PUBLIC_KEY", &public_key),
+    ])
+    .await;
+    let (token, _) = app.create_group(&["Alice"]).await;
+    let (_, group) = app.get("/groups/current", &token).await;
+    let header = jsonwebtoken::decode_header(&token).unwrap();
+    let mut claims = jsonwebtoken::decode::<serde_json::Value>(
+        &token,
+        &DecodingKey::from_rsa_pem(&std::fs::read(&public_key).unwrap()).unwrap(),
+        &Validation::new(Algorithm::RS256),
+    )
+    .unwrap()
+    .claims;
+
+    let (status, info) = app.get("/groups/current/token-info", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", info);
+    assert_eq!(info["group_id"], group["id"]);
+    assert_eq!(info["jti"], claims["jti"]);
+    assert!(info.get("member_id").is_none());
+    assert_eq!(info["expiring_soon"], false);
+    let expires_at = chrono::DateTime::parse_from_rfc3339(info["expires_at"].as_str().unwrap()).unwrap();
+    assert_eq!(expires_at.timestamp(), claims["exp"].as_i64().unwrap());
+    let (_, permissions) = app.get("/groups/current/permissions", &token).await;
+    assert_eq!(info["permissions"], permissions);
+    assert!(!info.to_string().contains(&token), "the token is echoed back");
+
+    // The same token, but due to expire in two days
+    let exp = (chrono::Utc::now() + chrono::Duration::days(2)).timestamp();
+    claims["exp"] = json!(exp);
+    let private_key = EncodingKey::from_rsa_pem(&std::fs::read(&private_key).unwrap()).unwrap();
+    let expiring = jsonwebtoken::encode(&header, &claims, &private_key).unwrap();
+    let (status, info) = app.get("/groups/current/token-info", &expiring).await;
+    assert_eq!(status, StatusCode::OK, "{}", info);
+    assert_eq!(info["expiring_soon"], true);
+    let expires_at = chrono::DateTime::parse_from_rfc3339(info["expires_at"].as_str().unwrap()).unwrap();
+    assert_eq!(expires_at.timestamp(), exp);
+
+    // Scoped tokens report their own permissions
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_settle": false }), &token).await;
+    let (status, info) = app.get("/groups/current/token-info", scoped["token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(info["permissions"], scoped["permissions"]);
+    assert_ne!(info["jti"], claims["jti"]);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn partial_repayments_reduce_the_remaining_debt() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let transfer = |from: &str, to: &str, amount: f64| {
+        json!({ "description": "Payback", "amount": amount, "paid_by": from, "expense_type": "transfer", "transfer_to": to })
+    };
+    let progress = || async {
+        let (status, progress) = app.get("/groups/current/settlement-progress", &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", progress);
+        progress
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| {
+                let name = |key: &str| p[key].as_str().unwrap().to_string();
+                let amount = |key: &str| p[key].as_f64().unwrap();
+                (name("from_name"), name("to_name"), amount("owed"), amount("settled"), amount("remaining"))
+            })
+            .collect::<Vec<_>>()
+    };
+    let row = |from: &str, to: &str, owed: f64, settled: f64, remaining: f64| {
+        (from.to_string(), to.to_string(), owed, settled, remaining)
+    };
+
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 30.0, "paid_by": alice })).await;
+    // Nets against what Bob owes Alice
+    app.create_expense(&token, json!({ "description": "Taxi", "amount": 6.0, "paid_by": bob, "split_between": [alice, bob] })).await;
+    assert_eq!(progress().await, vec![row("Carol", "Alice", 10.0, 0.0, 10.0), row("Bob", "Alice", 7.0, 0.0, 7.0)]);
+
+    app.create_expense(&token, transfer(bob, alice, 4.0)).await;
+    assert_eq!(progress().await, vec![row("Carol", "Alice", 10.0, 0.0, 10.0), row("Bob", "Alice", 7.0, 4.0, 3.0)]);
+
+    app.create_expense(&token, transfer(carol, alice, 10.0)).await;
+    app.create_expense(&token, transfer(bob, alice, 5.0)).await;
+    assert_eq!(progress().await, vec![row("Carol", "Alice", 10.0, 10.0, 0.0), row("Bob", "Alice", 7.0, 9.0, -2.0)]);
+
+    // A transfer between members who owed each other nothing shows as overpaid
+    app.create_expense(&token, transfer(carol, bob, 2.0)).await;
+    assert!(progress().await.contains(&row("Carol", "Bob", 0.0, 2.0, -2.0)));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn receipt_links_are_validated_and_stored() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let expense = |receipt_url: serde_json::Value| {
+        json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"], "split_between": [members["Bob"]], "receipt_url": receipt_url })
+    };
+    let too_long = format!("https://example.com/{}", "r".repeat(2048));
+    for bad in ["not a url", "/receipts/1.jpg", "ftp://example.com/r.jpg", "javascript:alert(1)", "http://", too_long.as_str()] {
+        let (status, _) = app.post("/groups/current/expenses", expense(json!(bad)), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", bad);
+    }
+    assert!(app.expenses(&token).await.is_empty());
+
+    let created = app.create_expense(&token, expense(json!("  https://photos.example.com/r/1.jpg?size=large "))).await;
+    assert_eq!(created["receipt_url"], "https://photos.example.com/r/1.jpg?size=large");
+    assert_eq!(app.expenses(&token).await[0]["receipt_url"], created["receipt_url"]);
+    let path = format!("/groups/current/expenses/{}", created["id"].as_str().unwrap());
+
+    // A PUT without the field keeps the link; a malformed one is rejected and changes nothing
+    let mut body = expense(serde_json::Value::Null);
+    body.as_object_mut().unwrap().remove("receipt_url");
+    let (status, updated) = app.request(Method::PUT, &path, Some(body), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    assert_eq!(updated["receipt_url"], created["receipt_url"]);
+    let (status, _) = app.request(Method::PUT, &path, Some(expense(json!("mailto:a@example.com"))), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "receipt_url": "http//typo.example.com" })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.expenses(&token).await[0]["receipt_url"], created["receipt_url"]);
+
+    let (status, patched) = app.request(Method::PATCH, &path, Some(json!({ "receipt_url": "http://example.com/2.png" })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert_eq!(patched["receipt_url"], "http://example.com/2.png");
+    let (status, patched) = app.request(Method::PATCH, &path, Some(json!({ "receipt_url": null })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert!(app.expenses(&token).await[0]["receipt_url"].is_null());
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn share_links_stop_working_once_used_up_or_expired() {
+    let app = TestApp::spawn().await;
+    let (token, _) = app.create_group(&["Alice", "Bob"]).await;
+    let redeem = |code: &serde_json::Value| {
+        app.request(Method::POST, "/share/redeem", Some(json!({ "code": code })), None)
+    };
+
+    for invalid in [json!({ "max_uses": 0 }), json!({ "expires_in_hours": 0 }), json!({ "expires_in_hours": -5 })] {
+        let (status, _) = app.post("/groups/current/share", invalid.clone(), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", invalid);
+    }
+
+    let (status, once) = app.post("/groups/current/share", json!({ "max_uses": 1 }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", once);
+    // Limited links are never shared between requests
+    let (_, again) = app.post("/groups/current/share", json!({ "max_uses": 1 }), &token).await;
+    assert_ne!(once["code"], again["code"]);
+
+    let (status, redeemed) = redeem(&once["code"]).await;
+    assert_eq!(status, StatusCode::OK, "{}", redeemed);
+    let (status, _) = app.get("/groups/current", redeemed["token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = redeem(&once["code"]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    // The token it handed out keeps working
+    let (status, _) = app.get("/groups/current", redeemed["token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, twice) = app.post("/groups/current/share", json!({ "max_uses": 2, "expires_in_hours": 1 }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", twice);
+    assert_eq!(redeem(&twice["code"]).await.0, StatusCode::OK);
+    app.execute(&format!(
+        "UPDATE share_links SET expires_at = NOW() - INTERVAL '1 second' WHERE code = '{}'",
+        twice["code"].as_str().unwrap()
+    ))
+    .await;
+    assert_eq!(redeem(&twice["code"]).await.0, StatusCode::UNAUTHORIZED);
+
+    assert_eq!(redeem(&json!("no-such-code")).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn member_bound_links_attribute_changes_to_the_member() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (_, other) = app.create_group(&["Zed"]).await;
+    let bound_token = |member: String| {
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, link) = app.post("/groups/current/share", json!({ "member_id": member }), token).await;
+            assert_eq!(status, StatusCode::OK, "{}", link);
+            let (status, redeemed) =
+                app.request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None).await;
+            assert_eq!(status, StatusCode::OK, "{}", redeemed);
+            redeemed["token"].as_str().unwrap().to_string()
+        }
+    };
+
+    let (status, _) = app.post("/groups/current/share", json!({ "member_id": other["Zed"] }), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let bob = bound_token(members["Bob"].clone()).await;
+    let carol = bound_token(members["Carol"].clone()).await;
+    let (_, info) = app.get("/groups/current/token-info", &bob).await;
+    assert_eq!(info["member_id"], members["Bob"].as_str());
+
+    let body = json!({ "description": "Dinner", "amount": 12.0, "paid_by": members["Alice"], "split_between": [members["Bob"]] });
+    let created = app.create_expense(&bob, body.clone()).await;
+    assert_eq!(created["created_by"], members["Bob"].as_str());
+    assert!(created["updated_by"].is_null());
+
+    let path = format!("/groups/current/expenses/{}", created["id"].as_str().unwrap());
+    let (status, updated) = app.request(Method::PATCH, &path, Some(json!({ "amount": 15.0 })), Some(&carol)).await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    let listed = app.expenses(&token).await;
+    assert_eq!(listed[0]["created_by"], members["Bob"].as_str());
+    assert_eq!(listed[0]["updated_by"], members["Carol"].as_str());
+
+    // Unbound tokens stay anonymous
+    let anonymous = app.create_expense(&token, body).await;
+    assert!(anonymous["created_by"].is_null());
+
+    // A bound token can only share links for its own member
+    let (status, _) = app.post("/groups/current/share", json!({ "member_id": members["Carol"] }), &bob).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.post("/groups/current/share", json!({ "member_id": members["Bob"] }), &bob).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn member_expenses_cover_every_role_and_page() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let day = |ago: i64| (chrono::Utc::now().date_naive() - chrono::Duration::days(ago)).to_string();
+    for expense in [
+        json!({ "description": "Paid", "amount": 12.0, "paid_by": bob, "split_between": [alice, carol], "expense_date": day(5) }),
+        json!({ "description": "Shared", "amount": 9.0, "paid_by": alice, "expense_date": day(4) }),
+        json!({ "description": "Received", "amount": 5.0, "paid_by": carol, "expense_type": "transfer", "transfer_to": bob, "expense_date": day(3) }),
+        json!({ "description": "Sent", "amount": 2.0, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice, "expense_date": day(2) }),
+        json!({ "description": "Elsewhere", "amount": 4.0, "paid_by": alice, "split_between": [carol], "expense_date": day(1) }),
+    ] {
+        app.create_expense(&token, expense).await;
+    }
+    let page = |query: String| {
+        let (app, token) = (&app, &token);
+        async move { app.get(&format!("/groups/current/members/{}/expenses{}", bob, query), token).await }
+    };
+    let roles = |page: &serde_json::Value| -> Vec<(String, bool, bool, f64, f64)> {
+        page["expenses"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| {
+                (
+                    e["expense"]["description"].as_str().unwrap().to_string(),
+                    e["paid"].as_bool().unwrap(),
+                    e["received"].as_bool().unwrap(),
+                    e["share"].as_f64().unwrap(),
+                    e["change"].as_f64().unwrap(),
+                )
+            })
+            .collect()
+    };
+    let role = |description: &str, paid: bool, received: bool, share: f64, change: f64| {
+        (description.to_string(), paid, received, share, change)
+    };
+
+    let (status, all) = page(String::new()).await;
+    assert_eq!(status, StatusCode::OK, "{}", all);
+    assert_eq!(all["total"], 4);
+    assert_eq!(
+        roles(&all),
+        vec![
+            role("Sent", true, false, 0.0, 2.0),
+            role("Received", false, true, 0.0, -5.0),
+            role("Shared", false, false, 3.0, -3.0),
+            role("Paid", true, false, 0.0, 12.0),
+        ]
+    );
+    let change: f64 = roles(&all).iter().map(|r| r.4).sum();
+    assert_eq!(change, app.balances(&token).await["Bob"]);
+
+    let (status, first) = page("?limit=3".to_string()).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, rest) = page("?limit=3&offset=3".to_string()).await;
+    assert_eq!((first["total"].as_i64(), rest["total"].as_i64()), (Some(4), Some(4)));
+    assert_eq!([roles(&first), roles(&rest)].concat(), roles(&all));
+
+    for query in ["?limit=0", "?offset=-1"] {
+        assert_eq!(page(query.to_string()).await.0, StatusCode::BAD_REQUEST, "{}", query);
+    }
+    let (status, _) = app
+        .get(&format!("/groups/current/members/{}/expenses", uuid::Uuid::new_v4()), &token)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn cached_balances_follow_every_change() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (alice, bob) = (&members["Alice"], &members["Bob"]);
+    let dinner = app.create_expense(&token, json!({ "description": "Dinner", "amount": 10.0, "paid_by": alice })).await;
+    assert_eq!(app.balances(&token).await["Alice"], 5.0);
+
+    // Sneak a change past the version counter: the cached balances are served
+    // until the next read with ?fresh=true
+    app.execute(&format!(
+        "UPDATE expenses SET amount = 20 WHERE id = '{}';
+         UPDATE groups SET version = version - 1 WHERE id = (SELECT group_id FROM expenses WHERE id = '{0}');",
+        dinner["id"].as_str().unwrap()
+    ))
+    .await;
+    assert_eq!(app.balances(&token).await["Alice"], 5.0);
+    let (status, fresh) = app.get("/groups/current/balances?fresh=true", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let alice_balance = fresh.as_array().unwrap().iter().find(|b| b["user_name"] == "Alice").unwrap()["balance"].clone();
+    assert_eq!(alice_balance, 10.0);
+
+    // Every change through the API shows up on the next read
+    let taxi = app.create_expense(&token, json!({ "description": "Taxi", "amount": 4.0, "paid_by": bob })).await;
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"]), (8.0, -8.0));
+
+    let path = format!("/groups/current/expenses/{}", taxi["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "amount": 8.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.balances(&token).await["Alice"], 6.0);
+
+    let (status, _) = app.post("/groups/current/members", json!({ "name": "Carol" }), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.balances(&token).await.get("Carol"), Some(&0.0));
+
+    let (status, _) = app.request(Method::DELETE, &path, None, Some(&token)).await;
+    assert!(status.is_success(), "{}", status);
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"], balances["Carol"]), (10.0, -10.0, 0.0));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn unknown_expense_types_are_rejected_everywhere() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (alice, bob) = (&members["Alice"], &members["Bob"]);
+    let expense = |expense_type: &str| {
+        json!({ "description": "Payback", "amount": 5.0, "paid_by": alice, "transfer_to": bob, "split_between": [bob], "expense_type": expense_type })
+    };
+    let invalid = ["Transfer", "INCOME", "donation", "", " transfer"];
+
+    for expense_type in invalid {
+        let (status, _) = app.post("/groups/current/expenses", expense(expense_type), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "create {:?}", expense_type);
+        let group = json!({
+            "name": "Trip", "member_names": ["Alice", "Bob"],
+            "expenses": [{ "description": "Payback", "amount": 5.0, "paid_by": 0, "transfer_to": 1, "expense_type": expense_type }],
+        });
+        let (status, _) = app.request(Method::POST, "/groups", Some(group), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "initial {:?}", expense_type);
+    }
+    assert!(app.expenses(&token).await.is_empty());
+
+    // Omitted means a regular expense; valid types are stored as sent
+    let mut body = expense("expense");
+    body.as_object_mut().unwrap().remove("expense_type");
+    body.as_object_mut().unwrap().remove("transfer_to");
+    let regular = app.create_expense(&token, body).await;
+    assert_eq!(regular["expense_type"], "expense");
+    let transfer = app.create_expense(&token, expense("transfer")).await;
+    assert_eq!(transfer["expense_type"], "transfer");
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"]), (10.0, -10.0));
+
+    let path = format!("/groups/current/expenses/{}", transfer["id"].as_str().unwrap());
+    for expense_type in invalid {
+        let (status, _) = app.request(Method::PUT, &path, Some(expense(expense_type)), Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "update {:?}", expense_type);
+        let (status, _) =
+            app.request(Method::PATCH, &path, Some(json!({ "expense_type": expense_type })), Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "patch {:?}", expense_type);
+    }
+    assert_eq!(app.count("SELECT COUNT(*) FROM expenses WHERE expense_type NOT IN ('expense', 'transfer', 'income')").await, 0);
+    assert_eq!(app.balances(&token).await["Bob"], -10.0);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn cloned_groups_keep_the_members_but_no_expenses() {
+    let app = TestApp::spawn().await;
+    let group = json!({ "name": "Ski trip", "member_names": ["Alice", "Bob", "Carol"], "currency": "CHF" });
+    let (status, created) = app.request(Method::POST, "/groups", Some(group), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let token = created["token"].as_str().unwrap();
+    let members = &created["group"]["members"];
+    let path = format!("/groups/current/members/{}/payment", members[0]["id"].as_str().unwrap());
+    let payment = json!({ "paypal_email": "alice@example.com", "iban": null, "payment_note": "Alice W." });
+    assert_eq!(app.request(Method::PUT, &path, Some(payment), Some(token)).await.0, StatusCode::OK);
+    app.create_expense(token, json!({ "description": "Lift pass", "amount": 90.0, "paid_by": members[0]["id"] })).await;
+    let names = |group: &serde_json::Value| -> Vec<String> {
+        group["members"].as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap().to_string()).collect()
+    };
+
+    let (status, _) = app.post("/groups/current/clone", json!({ "name": "   " }), token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, clone) = app.post("/groups/current/clone", json!({ "name": "Ski trip 2027" }), token).await;
+    assert_eq!(status, StatusCode::OK, "{}", clone);
+    let clone_token = clone["token"].as_str().unwrap();
+    let (_, cloned) = app.get("/groups/current", clone_token).await;
+    assert_eq!(cloned["name"], "Ski trip 2027");
+    assert_eq!(cloned["currency"], "CHF");
+    assert_ne!(cloned["id"], created["group"]["id"]);
+    assert_eq!(names(&cloned), ["Alice", "Bob", "Carol"]);
+    let source_ids: Vec<&serde_json::Value> = members.as_array().unwrap().iter().map(|m| &m["id"]).collect();
+    assert!(cloned["members"].as_array().unwrap().iter().all(|m| !source_ids.contains(&&m["id"])));
+    assert!(cloned["members"][0]["paypal_email"].is_null() && cloned["members"][0]["payment_note"].is_null());
+    assert!(app.expenses(clone_token).await.is_empty());
+    assert!(app.balances(clone_token).await.values().all(|b| *b == 0.0));
+    // The source group is untouched
+    assert_eq!(app.expenses(token).await.len(), 1);
+    assert_eq!(app.balances(token).await["Alice"], 60.0);
+
+    let (status, clone) = app.post("/groups/current/clone", json!({ "copy_payment_info": true }), token).await;
+    assert_eq!(status, StatusCode::OK, "{}", clone);
+    assert_eq!(clone["group"]["name"], "Ski trip");
+    assert_eq!(clone["group"]["members"][0]["paypal_email"], "alice@example.com");
+    assert_eq!(clone["group"]["members"][0]["payment_note"], "Alice W.");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn settle_up_reports_the_amount_and_direction_between_two_members() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 30.0, "paid_by": alice })).await;
+    let settle_up = |from: &str, to: &str| {
+        let path = format!("/groups/current/settle-up?from={}&to={}", from, to);
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, body) = app.get(&path, token).await;
+            (status, body["amount"].as_f64(), body["direction"].as_str().map(str::to_string))
+        }
+    };
+    let suggestion = |amount: f64, direction: &str| (StatusCode::OK, Some(amount), Some(direction.to_string()));
+
+    assert_eq!(settle_up(bob, alice).await, suggestion(10.0, "from_pays_to"));
+    assert_eq!(settle_up(alice, bob).await, suggestion(10.0, "to_pays_from"));
+    // Bob and Carol both owe Alice, not each other
+    assert_eq!(settle_up(bob, carol).await, suggestion(0.0, "none"));
+
+    // A partial repayment lowers the amount, a full one clears it
+    let payback = |amount: f64| json!({ "description": "Payback", "amount": amount, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice });
+    app.create_expense(&token, payback(4.0)).await;
+    assert_eq!(settle_up(bob, alice).await, suggestion(6.0, "from_pays_to"));
+    app.create_expense(&token, payback(6.0)).await;
+    assert_eq!(settle_up(bob, alice).await, suggestion(0.0, "none"));
+    assert_eq!(settle_up(alice, carol).await, suggestion(10.0, "to_pays_from"));
+
+    assert_eq!(settle_up(bob, bob).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(settle_up(bob, "carol").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(settle_up(bob, &uuid::Uuid::new_v4().to_string()).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn rates_come_from_stored_rows_before_the_provider() {
+    let (rates_url, requests) =
+        common::serve_json(json!({ "amount": 1.0, "base": "SEK", "date": "2020-01-02", "rates": { "GBP": 0.5 } }));
+    let app = TestApp::spawn_with(&[("EXCHANGE_RATE_API_URL", &rates_url)]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let day = |offset: i64| chrono::Utc::now().date_naive() - chrono::Duration::days(30) + chrono::Duration::days(offset);
+    app.execute(&format!(
+        "INSERT INTO exchange_rates (base, quote, date, rate) VALUES
+             ('EUR', 'USD', '{}', 1.25), ('EUR', 'USD', '{}', 2.0), ('EUR', 'JPY', '{}', 150.0)",
+        day(-2),
+        day(1),
+        day(-1)
+    ))
+    .await;
+    let rate = |from: &str, to: &str, date: String| {
+        let path = format!("/rates?from={}&to={}&date={}", from, to, date);
+        let app = &app;
+        async move { app.request(Method::GET, &path, None, None).await }
+    };
+    let asked_for = |date: String| requests.lock().unwrap().iter().filter(|p| p.contains(&date)).count();
+
+    // The pair itself, the latest row on or before the date
+    let (status, direct) = rate("eur", "usd", day(0).to_string()).await;
+    assert_eq!(status, StatusCode::OK, "{}", direct);
+    assert_eq!((direct["from"].as_str(), direct["to"].as_str()), (Some("EUR"), Some("USD")));
+    assert_eq!((direct["rate"].as_f64(), direct["date"].as_str()), (Some(1.25), Some(day(-2).to_string().as_str())));
+    // Its inverse, and a cross rate through EUR
+    let (_, inverse) = rate("USD", "EUR", day(0).to_string()).await;
+    assert_eq!(inverse["rate"], 0.8);
+    let (_, cross) = rate("USD", "JPY", day(0).to_string()).await;
+    assert_eq!(cross["rate"], 120.0);
+    assert_eq!(cross["date"], day(-2).to_string());
+    assert_eq!(asked_for(day(0).to_string()), 0);
+
+    // Foreign expenses without a rate use the stored one for their date
+    let expense = app
+        .create_expense(
+            &token,
+            json!({ "description": "Museum", "amount": 10.0, "currency": "USD", "paid_by": members["Alice"], "expense_date": day(0) }),
+        )
+        .await;
+    assert_eq!(expense["exchange_rate"], 0.8);
+
+    // Rows more than a week old don't count; the provider is asked and its answer stored
+    let (status, _) = rate("EUR", "USD", day(-10).to_string()).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "the provider has no USD rate");
+    assert_eq!(asked_for(day(-10).to_string()), 1);
+    let (status, fetched) = rate("SEK", "GBP", day(0).to_string()).await;
+    assert_eq!(status, StatusCode::OK, "{}", fetched);
+    assert_eq!((fetched["rate"].as_f64(), fetched["date"].as_str()), (Some(0.5), Some("2020-01-02")));
+    assert!(requests.lock().unwrap().contains(&format!("/{}?from=SEK&to=GBP", day(0))));
+    assert_eq!(app.count("SELECT COUNT(*) FROM exchange_rates WHERE base = 'SEK' AND quote = 'GBP' AND rate = 0.5").await, 1);
+
+    assert_eq!(rate("EURO", "USD", day(0).to_string()).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(rate("EUR", "USD", "yesterday".to_string()).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn repeated_split_members_pay_once_and_rates_must_be_positive() {
+    let app = TestApp::spawn().await;
+    let group = |expense: serde_json::Value| json!({ "name": "Trip", "member_names": ["Alice", "Bob"], "expenses": [expense] });
+
+    // Group creation dedupes repeated indices like the expense endpoints dedupe ids
+    let (status, created) = app
+        .request(Method::POST, "/groups", Some(group(json!({ "description": "Dinner", "amount": 10.0, "paid_by": 0, "split_between": [1, 1, 1] }))), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let token = created["token"].as_str().unwrap().to_string();
+    let balances = app.balances(&token).await;
+    assert_eq!((balances["Alice"], balances["Bob"]), (10.0, -10.0));
+    for rate in [0.0, -0.5] {
+        let expense = json!({ "description": "Museum", "amount": 10.0, "paid_by": 0, "currency": "USD", "exchange_rate": rate });
+        let (status, error) = app.request(Method::POST, "/groups", Some(group(expense)), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "rate {}", rate);
+        assert!(error["error"].as_str().unwrap().starts_with("expenses[0]: "), "{}", error);
+    }
+
+    let members: Vec<&str> = created["group"]["members"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap()).collect();
+    let (alice, bob) = (members[0], members[1]);
+    let body = |rate: f64| {
+        json!({ "description": "Museum", "amount": 10.0, "paid_by": alice, "split_between": [bob, alice, bob], "currency": "USD", "exchange_rate": rate })
+    };
+    let museum = app.create_expense(&token, body(0.5)).await;
+    assert_eq!(museum["split_between"], json!([alice, bob]));
+    assert_eq!(app.balances(&token).await["Bob"], -12.5);
+
+    let path = format!("/groups/current/expenses/{}", museum["id"].as_str().unwrap());
+    for rate in [0.0, -2.0] {
+        let (status, _) = app.request(Method::PUT, &path, Some(body(rate)), Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "PUT rate {}", rate);
+        let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "exchange_rate": rate })), Some(&token)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "PATCH rate {}", rate);
+    }
+    let (status, patched) =
+        app.request(Method::PATCH, &path, Some(json!({ "split_between": [bob, bob] })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert_eq!(patched["split_between"], json!([bob]));
+    assert_eq!(patched["exchange_rate"], 0.5);
+    assert_eq!(app.balances(&token).await["Bob"], -15.0);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn previews_match_the_change_once_saved() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 31.0, "paid_by": carol })).await;
+
+    for body in [
+        json!({ "description": "Odd cents", "amount": 10.0, "paid_by": alice }),
+        json!({ "description": "Shares", "amount": 9.99, "paid_by": bob, "split_type": "shares",
+                "split_between": [alice, bob], "splits": [{ "member_id": alice, "share": 2.0 }, { "member_id": bob, "share": 1.0 }] }),
+        json!({ "description": "Foreign", "amount": 7.0, "paid_by": carol, "currency": "USD", "exchange_rate": 0.9137 }),
+        json!({ "description": "Adjusted", "amount": 20.0, "paid_by": alice, "split_type": "adjustment",
+                "split_between": [alice, bob, carol], "splits": [{ "member_id": carol, "share": 5.0 }] }),
+        json!({ "description": "Refund", "amount": 6.0, "paid_by": bob, "expense_type": "income" }),
+        json!({ "description": "Payback", "amount": 4.5, "paid_by": bob, "expense_type": "transfer", "transfer_to": carol }),
+    ] {
+        let before = app.balances(&token).await;
+        let (status, preview) = app.post("/groups/current/expenses/preview", body.clone(), &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", preview);
+        // Nothing is saved by a preview
+        assert_eq!(app.balances(&token).await, before);
+
+        app.create_expense(&token, body.clone()).await;
+        let after = app.balances(&token).await;
+        for change in preview.as_array().unwrap() {
+            let name = change["user_name"].as_str().unwrap();
+            let delta = change["delta"].as_f64().unwrap();
+            assert_eq!(change["balance_before"], before[name], "{} {}", body["description"], name);
+            assert_eq!(change["balance_after"], after[name], "{} {}", body["description"], name);
+            assert!((after[name] - before[name] - delta).abs() < 1e-9, "{} {}: {} vs {}", body["description"], name, after[name] - before[name], delta);
+        }
+    }
+    assert_eq!(app.expenses(&token).await.len(), 7);
+
+    // Validated like a real create
+    let (status, _) = app.post("/groups/current/expenses/preview", json!({ "description": "Huge", "amount": 1e15, "paid_by": alice }), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app
+        .post("/groups/current/expenses/preview", json!({ "description": "Stranger", "amount": 5.0, "paid_by": uuid::Uuid::new_v4() }), &token)
+        .await;
+    assert!(status.is_client_error(), "{}", status);
+    let (_, read_only) = app.post("/groups/current/scoped-token", json!({ "can_add_expenses": false }), &token).await;
+    let (status, _) = app
+        .post("/groups/current/expenses/preview", json!({ "description": "Lunch", "amount": 5.0, "paid_by": alice }), read_only["token"].as_str().unwrap())
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn pages_stay_stable_when_timestamps_tie() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    for i in 0..25 {
+        app.create_expense(&token, json!({ "description": format!("Expense {}", i), "amount": 1.0, "paid_by": members["Alice"] })).await;
+    }
+    // Same instant for all but two, which sort before and after the tied block
+    app.execute(
+        "UPDATE expenses SET expense_date = CURRENT_DATE - 1, created_at = '2026-01-01 12:00:00+00';
+         UPDATE expenses SET expense_date = CURRENT_DATE WHERE description = 'Expense 3';
+         UPDATE expenses SET expense_date = CURRENT_DATE - 2 WHERE description = 'Expense 7';",
+    )
+    .await;
+
+    let walk = || async {
+        let mut ids = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let path = match &cursor {
+                Some(c) => format!("/groups/current/expenses?limit=4&cursor={}", c),
+                None => "/groups/current/expenses?limit=4".to_string(),
+            };
+            let (status, page) = app.get(&path, &token).await;
+            assert_eq!(status, StatusCode::OK, "{}", page);
+            let expenses = page["expenses"].as_array().unwrap();
+            assert!(expenses.len() <= 4);
+            ids.extend(expenses.iter().map(|e| e["id"].as_str().unwrap().to_string()));
+            match page["next_cursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
+            }
+        }
+        ids
+    };
+
+    let first = walk().await;
+    assert_eq!(first.len(), 25);
+    let mut unique = first.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), 25, "pages overlap");
+    assert_eq!(walk().await, first);
+
+    let listed: Vec<String> = app.expenses(&token).await.iter().map(|e| e["id"].as_str().unwrap().to_string()).collect();
+    assert_eq!(listed, first);
+    let descriptions: Vec<String> = app.expenses(&token).await.iter().map(|e| e["description"].as_str().unwrap().to_string()).collect();
+    assert_eq!((descriptions[0].as_str(), descriptions[24].as_str()), ("Expense 3", "Expense 7"));
+    // Ties are broken by id, newest-first like the rest of the order
+    let tied = &first[1..24];
+    assert!(tied.windows(2).all(|w| w[0] > w[1]), "{:?}", tied);
+
+    let (status, _) = app.get("/groups/current/expenses?limit=4&cursor=bm90LWEtY3Vyc29y", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.get("/groups/current/expenses?limit=0", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn overview_matches_the_individual_endpoints() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+    for (payer, amount) in [("Alice", 40.0), ("Bob", 13.0), ("Alice", 7.77)] {
+        app.create_expense(&token, json!({ "description": "Groceries", "amount": amount, "paid_by": members[payer] })).await;
+    }
+
+    let (status, etag, overview) = app.get_conditional("/groups/current/overview", &token, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", overview);
+    let (_, group) = app.get("/groups/current", &token).await;
+    assert_eq!(overview["group"], group);
+    let (_, balances) = app.get("/groups/current/balances", &token).await;
+    assert_eq!(overview["balances"], balances);
+
+    // The settlement plan is the one payment requests are made from, and it settles everyone
+    let (_, payments) = app.get("/groups/current/payment-requests", &token).await;
+    let settlements = overview["settlements"].as_array().unwrap();
+    assert!(!settlements.is_empty());
+    assert_eq!(settlements.len(), payments["requests"].as_array().unwrap().len());
+    let mut remaining = app.balances(&token).await;
+    for (settlement, request) in settlements.iter().zip(payments["requests"].as_array().unwrap()) {
+        assert_eq!(
+            (&settlement["from"], &settlement["to"], &settlement["amount"]),
+            (&request["payer_id"], &request["payee_id"], &request["amount"])
+        );
+        let amount = settlement["amount"].as_f64().unwrap();
+        *remaining.get_mut(settlement["from_name"].as_str().unwrap()).unwrap() += amount;
+        *remaining.get_mut(settlement["to_name"].as_str().unwrap()).unwrap() -= amount;
+    }
+    assert!(remaining.values().all(|b| b.abs() < 0.005), "{:?}", remaining);
+    assert!(overview.get("settlement_warning").is_none());
+
+    // Cached until something changes
+    let (status, _, _) = app.get_conditional("/groups/current/overview", &token, etag.as_deref()).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    app.create_expense(&token, json!({ "description": "Taxi", "amount": 5.0, "paid_by": members["Dave"] })).await;
+    let (status, _, changed) = app.get_conditional("/groups/current/overview", &token, etag.as_deref()).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, balances) = app.get("/groups/current/balances", &token).await;
+    assert_eq!(changed["balances"], balances);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn members_sort_by_balance_both_ways() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Erin", "Carol", "Alice", "Dave", "Bob"]).await;
+    let (alice, bob, carol, dave) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Dave"]);
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 30.0, "paid_by": alice, "split_between": [alice, bob, carol] })).await;
+    // Dave pays for himself only, Erin has no expenses: both end at zero
+    app.create_expense(&token, json!({ "description": "Snack", "amount": 5.0, "paid_by": dave, "split_between": [dave] })).await;
+    let ranking = |query: &'static str| {
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, body) = app.get(&format!("/groups/current/members/by-balance{}", query), token).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|m| {
+                    assert!(m["id"].is_string() && m.get("paypal_email").is_some(), "{}", m);
+                    (m["name"].as_str().unwrap().to_string(), m["balance"].as_f64().unwrap())
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+    let entry = |name: &str, balance: f64| (name.to_string(), balance);
+
+    // Equal balances fall back to the name, whichever the direction
+    let descending = vec![entry("Alice", 20.0), entry("Dave", 0.0), entry("Erin", 0.0), entry("Bob", -10.0), entry("Carol", -10.0)];
+    assert_eq!(ranking("").await, descending);
+    assert_eq!(ranking("?order=desc").await, descending);
+    assert_eq!(
+        ranking("?order=asc").await,
+        vec![entry("Bob", -10.0), entry("Carol", -10.0), entry("Dave", 0.0), entry("Erin", 0.0), entry("Alice", 20.0)]
+    );
+
+    let (status, _) = app.get("/groups/current/members/by-balance?order=up", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn settle_all_brings_everyone_to_zero_once() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+    for (payer, amount) in [("Alice", 100.0), ("Bob", 33.33), ("Carol", 7.01)] {
+        app.create_expense(&token, json!({ "description": "Groceries", "amount": amount, "paid_by": members[payer] })).await;
+    }
+    let (_, no_settle) = app.post("/groups/current/scoped-token", json!({ "can_settle": false }), &token).await;
+    let (status, _) = app.post("/groups/current/settle-all", json!({}), no_settle["token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(app.expenses(&token).await.len(), 3);
+
+    let (status, settled) = app.post("/groups/current/settle-all", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", settled);
+    let transfers = settled["transfers"].as_array().unwrap();
+    assert!(!transfers.is_empty());
+    let today = chrono::Utc::now().date_naive().to_string();
+    for transfer in transfers {
+        assert_eq!(transfer["expense_type"], "transfer");
+        assert_eq!(transfer["expense_date"], today.as_str());
+        assert!(transfer["amount"].as_f64().unwrap() > 0.0);
+    }
+    for balance in settled["balances"].as_array().unwrap() {
+        assert!(balance["balance"].as_f64().unwrap().abs() < 0.005, "{}", balance);
+    }
+    assert!(app.balances(&token).await.values().all(|b| b.abs() < 0.005));
+    let saved: Vec<serde_json::Value> = app.expenses(&token).await.into_iter().filter(|e| e["expense_type"] == "transfer").collect();
+    assert_eq!(saved.len(), transfers.len());
+
+    // Already settled: nothing more is recorded
+    let (status, again) = app.post("/groups/current/settle-all", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(again["transfers"], json!([]));
+    assert_eq!(app.expenses(&token).await.len(), 3 + transfers.len());
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn expense_dates_must_be_near_the_group() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let day = |offset: i64| (chrono::Utc::now().date_naive() + chrono::Duration::days(offset)).to_string();
+    let expense = |date: String| json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"], "expense_date": date });
+
+    // Up to a week ahead, and a year before the group was created
+    for offset in [-1, 7, -365] {
+        let (status, created) = app.post("/groups/current/expenses", expense(day(offset)), &token).await;
+        assert_eq!(status, StatusCode::OK, "{} days: {}", offset, created);
+    }
+    for offset in [8, 3650, -366, -3650] {
+        let (status, _) = app.post("/groups/current/expenses", expense(day(offset)), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{} days", offset);
+    }
+
+    let recent = app.create_expense(&token, expense(day(-2))).await;
+    let path = format!("/groups/current/expenses/{}", recent["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "expense_date": day(30) })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let mut body = expense(day(-400));
+    body["split_between"] = json!([members["Alice"], members["Bob"]]);
+    let (status, _) = app.request(Method::PUT, &path, Some(body), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Only a set or changed date is checked, so an old expense stays editable
+    app.execute(&format!("UPDATE expenses SET expense_date = '2000-01-01' WHERE id = '{}'", recent["id"].as_str().unwrap())).await;
+    let (status, patched) = app.request(Method::PATCH, &path, Some(json!({ "description": "Old dinner" })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", patched);
+    assert_eq!(patched["expense_date"], "2000-01-01");
+
+    let strict = TestApp::spawn_with(&[("EXPENSE_DATE_MAX_FUTURE_DAYS", "0"), ("EXPENSE_DATE_MAX_PAST_DAYS", "10")]).await;
+    let (token, members) = strict.create_group(&["Alice"]).await;
+    let expense = |date: String| json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"], "expense_date": date });
+    assert_eq!(strict.post("/groups/current/expenses", expense(day(0)), &token).await.0, StatusCode::OK);
+    assert_eq!(strict.post("/groups/current/expenses", expense(day(-10)), &token).await.0, StatusCode::OK);
+    assert_eq!(strict.post("/groups/current/expenses", expense(day(1)), &token).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(strict.post("/groups/current/expenses", expense(day(-11)), &token).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn trip_balances_only_count_that_trips_expenses() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let today = chrono::Utc::now().date_naive().to_string();
+    let trip = |name: &str| json!({ "name": name, "start_date": today });
+    let (status, rome) = app.post("/groups/current/trips", trip("Rome"), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", rome);
+    let (_, oslo) = app.post("/groups/current/trips", trip("Oslo"), &token).await;
+    let (rome_id, oslo_id) = (rome["id"].as_str().unwrap(), oslo["id"].as_str().unwrap());
+
+    let expense = |payer: &str, amount: f64, trip_id: Option<&str>| {
+        json!({ "description": "Dinner", "amount": amount, "paid_by": members[payer], "trip_id": trip_id })
+    };
+    app.create_expense(&token, expense("Alice", 40.0, Some(rome_id))).await;
+    let oslo_dinner = app.create_expense(&token, expense("Bob", 10.0, Some(oslo_id))).await;
+    app.create_expense(&token, expense("Bob", 100.0, None)).await;
+
+    let trip_balances = |trip_id: &str| {
+        let (app, token, path) = (&app, &token, format!("/groups/current/trips/{}/balances", trip_id));
+        async move {
+            let (status, balances) = app.get(&path, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", balances);
+            balances
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|b| (b["user_name"].as_str().unwrap().to_string(), b["balance"].as_f64().unwrap()))
+                .collect::<std::collections::HashMap<_, _>>()
+        }
+    };
+    let rome_balances = trip_balances(rome_id).await;
+    assert!((rome_balances["Alice"] - 20.0).abs() < 0.005, "{:?}", rome_balances);
+    assert!((rome_balances["Bob"] + 20.0).abs() < 0.005, "{:?}", rome_balances);
+    let oslo_balances = trip_balances(oslo_id).await;
+    assert!((oslo_balances["Bob"] - 5.0).abs() < 0.005, "{:?}", oslo_balances);
+    let all = app.balances(&token).await;
+    assert!((all["Bob"] - 35.0).abs() < 0.005, "{:?}", all);
+
+    // Moving an expense to another trip moves it out of the first trip's balances
+    let path = format!("/groups/current/expenses/{}", oslo_dinner["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "trip_id": rome_id })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(trip_balances(oslo_id).await.values().all(|b| b.abs() < 0.005));
+    assert!((trip_balances(rome_id).await["Alice"] - 15.0).abs() < 0.005);
+
+    // Trips of other groups can't be used or read
+    let (other_token, other_members) = app.create_group(&["Carol"]).await;
+    let (_, foreign) = app.post("/groups/current/trips", trip("Paris"), &other_token).await;
+    let foreign_id = foreign["id"].as_str().unwrap();
+    let (status, _) = app.post("/groups/current/expenses", expense("Alice", 5.0, Some(foreign_id)), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let unknown = uuid::Uuid::new_v4().to_string();
+    let (status, _) = app.post("/groups/current/expenses", expense("Alice", 5.0, Some(&unknown)), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.get(&format!("/groups/current/trips/{}/balances", foreign_id), &token).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.get(&format!("/groups/current/trips/{}/balances", unknown), &token).await.0, StatusCode::NOT_FOUND);
+    let carol_expense = json!({ "description": "Lunch", "amount": 5.0, "paid_by": other_members["Carol"], "trip_id": foreign_id });
+    app.create_expense(&other_token, carol_expense).await;
+    let rome_balances = trip_balances(rome_id).await;
+    assert!(!rome_balances.contains_key("Carol"));
+
+    // Deleting a trip keeps its expenses in the group
+    let (status, _) = app.request(Method::DELETE, &format!("/groups/current/trips/{}", rome_id), None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(app.get(&format!("/groups/current/trips/{}/balances", rome_id), &token).await.0, StatusCode::NOT_FOUND);
+    let expenses = app.expenses(&token).await;
+    assert_eq!(expenses.len(), 3);
+    assert!(expenses.iter().all(|e| e["trip_id"].is_null()), "{:?}", expenses);
+    assert_eq!(app.balances(&token).await, all);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn minor_amounts_are_exact_integers_in_each_currency() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let expense = |description: &str, amount: f64, currency: &str| {
+        json!({
+            "description": description, "amount": amount, "paid_by": members["Alice"],
+            "split_between": [members["Alice"], members["Bob"], members["Carol"]],
+            "currency": currency, "exchange_rate": 1.0,
+        })
+    };
+    app.create_expense(&token, expense("Dinner", 10.0, "EUR")).await;
+    // 0.29 * 100 is 28.999999999999996 in floating point
+    app.create_expense(&token, expense("Gum", 0.29, "EUR")).await;
+    app.create_expense(&token, expense("Ramen", 1234.0, "JPY")).await;
+    app.create_expense(&token, expense("Tea", 1.25, "KWD")).await;
+
+    let (status, plain) = app.get("/groups/current/expenses", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(plain.as_array().unwrap().iter().all(|e| e.get("amount_minor").is_none()), "{}", plain);
+    let (status, minor) = app.get("/groups/current/expenses?amounts=minor", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let by_description: std::collections::HashMap<&str, &serde_json::Value> =
+        minor.as_array().unwrap().iter().map(|e| (e["description"].as_str().unwrap(), &e["amount_minor"])).collect();
+    assert_eq!(by_description["Dinner"], &json!(1000));
+    assert_eq!(by_description["Gum"], &json!(29));
+    assert_eq!(by_description["Ramen"], &json!(1234));
+    assert_eq!(by_description["Tea"], &json!(1250));
+    let (status, paged) = app.get("/groups/current/expenses?amounts=minor&limit=10", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(paged["expenses"].as_array().unwrap().iter().all(|e| e["amount_minor"].is_i64()), "{}", paged);
+
+    // Balances in the group currency: minor units match the rounded float exactly
+    let (status, balances) = app.get("/groups/current/balances?amounts=minor", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    for balance in balances.as_array().unwrap() {
+        let cents = (balance["balance"].as_f64().unwrap() * 100.0).round() as i64;
+        assert_eq!(balance["balance_minor"], json!(cents), "{}", balance);
+    }
+    let (_, plain) = app.get("/groups/current/balances", &token).await;
+    assert!(plain.as_array().unwrap().iter().all(|b| b.get("balance_minor").is_none()), "{}", plain);
+
+    for query in ["amounts=cents", "amounts=MINOR", "amounts="] {
+        assert_eq!(app.get(&format!("/groups/current/expenses?{}", query), &token).await.0, StatusCode::BAD_REQUEST, "{}", query);
+        assert_eq!(app.get(&format!("/groups/current/balances?{}", query), &token).await.0, StatusCode::BAD_REQUEST, "{}", query);
+    }
+    assert_eq!(app.get("/groups/current/expenses?amounts=decimal", &token).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn readiness_reports_missing_and_modified_migrations() {
+    let app = TestApp::spawn().await;
+    let (status, ready) = app.request(Method::GET, "/ready", None, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", ready);
+    assert_eq!(ready["ready"], true);
+    assert_eq!(ready["database"], true);
+    let latest = ready["migrations"]["expected_version"].as_i64().unwrap();
+    assert_eq!(ready["migrations"]["applied_version"], latest);
+    assert_eq!(ready["migrations"]["pending"], json!([]));
+    assert_eq!(ready["migrations"]["modified"], json!([]));
+
+    // The latest migration is missing from the history
+    app.execute(&format!("DELETE FROM refinery_schema_history WHERE version = {}", latest)).await;
+    let (status, ready) = app.request(Method::GET, "/ready", None, None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(ready["ready"], false);
+    assert_eq!(ready["database"], true);
+    assert_eq!(ready["migrations"]["applied_version"], latest - 1);
+    let pending = ready["migrations"]["pending"].as_array().unwrap();
+    assert_eq!(pending.len(), 1, "{}", ready);
+    assert!(pending[0].as_str().unwrap().contains(&format!("V{}", latest)), "{}", ready);
+
+    // An older migration was applied from a different file
+    app.execute("UPDATE refinery_schema_history SET checksum = '1' WHERE version = 1").await;
+    let (status, ready) = app.request(Method::GET, "/ready", None, None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let modified = ready["migrations"]["modified"].as_array().unwrap();
+    assert_eq!(modified.len(), 1, "{}", ready);
+    assert!(modified[0].as_str().unwrap().starts_with("V1_"), "{}", ready);
+
+    // No history at all: every migration is pending
+    app.execute("DROP TABLE refinery_schema_history").await;
+    let (status, ready) = app.request(Method::GET, "/ready", None, None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(ready["migrations"]["applied_version"].is_null(), "{}", ready);
+    assert_eq!(ready["migrations"]["pending"].as_array().unwrap().len() as i64, latest);
+
+    // Liveness doesn't depend on migrations
+    let response = reqwest::get(format!("{}/health", app.base)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn merging_tokens_only_adds_permissions_the_other_token_states() {
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Validation};
+
+    let fixture = |name: &str| format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let (private_key, public_key) = (fixture("jwt_private.pem"), fixture("jwt_public.pem"));
+    let app = TestApp::spawn_with(&[
+        ("JWT_ALG", "RS256"),
+        ("JWT_PRIVATE_KEY", &private_key),
+        ("JWT_PUBLIC_KEY", &public_key),
+    ])
+    .await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let scoped = |permissions: serde_json::Value| {
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, scoped) = app.post("/groups/current/scoped-token", permissions, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", scoped);
+            scoped["token"].as_str().unwrap().to_string()
+        }
+    };
+    let none = json!({
+        "can_delete_group": false, "can_manage_members": false, "can_update_payment": false,
+        "can_add_expenses": false, "can_edit_expenses": false, "can_settle": false,
+    });
+    let read_only = scoped(none.clone()).await;
+    let mut adding = none.clone();
+    adding["can_add_expenses"] = json!(true);
+    let adder = scoped(adding).await;
+    let granted = |merged: &serde_json::Value| {
+        merged["permissions"].as_object().unwrap().iter().filter(|(_, v)| **v == json!(true)).map(|(k, _)| k.clone()).collect::<Vec<_>>()
+    };
+
+    let (status, merged) = app.post("/groups/current/merge-token", json!({ "other_token": adder }), &read_only).await;
+    assert_eq!(status, StatusCode::OK, "{}", merged);
+    assert_eq!(granted(&merged), ["can_add_expenses"]);
+    let (status, _) = app.get("/groups/current", merged["token"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    // Merging two restricted tokens never yields more than either holds
+    let (_, merged) = app.post("/groups/current/merge-token", json!({ "other_token": read_only }), &read_only).await;
+    assert!(granted(&merged).is_empty(), "{}", merged);
+
+    // Re-sign the creator's claims the way older tokens looked
+    let header = jsonwebtoken::decode_header(&token).unwrap();
+    let claims = jsonwebtoken::decode::<serde_json::Value>(
+        &token,
+        &DecodingKey::from_rsa_pem(&std::fs::read(&public_key).unwrap()).unwrap(),
+        &Validation::new(Algorithm::RS256),
+    )
+    .unwrap()
+    .claims;
+    let private_key = EncodingKey::from_rsa_pem(&std::fs::read(&private_key).unwrap()).unwrap();
+    let resign = |permissions: Option<serde_json::Value>| {
+        let mut claims = claims.clone();
+        match permissions {
+            Some(permissions) => claims["p"] = permissions,
+            None => {
+                claims.as_object_mut().unwrap().remove("p");
+            }
+        }
+        jsonwebtoken::encode(&header, &claims, &private_key).unwrap()
+    };
+
+    // No permission claim at all: works on its own, but grants nothing when merged
+    let legacy = resign(None);
+    assert_eq!(app.get("/groups/current", &legacy).await.0, StatusCode::OK);
+    let (status, merged) = app.post("/groups/current/merge-token", json!({ "other_token": legacy }), &read_only).await;
+    assert_eq!(status, StatusCode::OK, "{}", merged);
+    assert!(granted(&merged).is_empty(), "{}", merged);
+    let (status, _) = app.request(Method::DELETE, "/groups/current", None, merged["token"].as_str()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Only the stated fields count; settling follows adding expenses in old tokens
+    let partial = resign(Some(json!({ "ae": true })));
+    let (_, merged) = app.post("/groups/current/merge-token", json!({ "other_token": partial }), &read_only).await;
+    assert_eq!(granted(&merged), ["can_add_expenses", "can_settle"]);
+
+    // Tokens of other groups, revoked ones, garbage, and other members' tokens are refused
+    let (other_group, _) = app.create_group(&["Zed"]).await;
+    let revoked = scoped(json!({})).await;
+    let (_, info) = app.get("/groups/current/token-info", &revoked).await;
+    let (status, _) = app.request(Method::DELETE, &format!("/groups/current/tokens/{}", info["jti"].as_str().unwrap()), None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    for other in [other_group, revoked, "not-a-token".to_string()] {
+        let (status, _) = app.post("/groups/current/merge-token", json!({ "other_token": other }), &read_only).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    let as_member = |member: &str| {
+        let mut claims = claims.clone();
+        claims["sub"] = json!(members[member]);
+        jsonwebtoken::encode(&header, &claims, &private_key).unwrap()
+    };
+    let (status, _) = app.post("/groups/current/merge-token", json!({ "other_token": as_member("Bob") }), &as_member("Alice")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn category_stats_total_each_tag_in_the_group_currency() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let day = |offset: i64| (chrono::Utc::now().date_naive() + chrono::Duration::days(offset)).to_string();
+    let expense = |amount: f64, tags: serde_json::Value, offset: i64| {
+        json!({ "description": "Spend", "amount": amount, "paid_by": members["Alice"], "tags": tags, "expense_date": day(offset) })
+    };
+    app.create_expense(&token, expense(20.0, json!(["food"]), -10)).await;
+    app.create_expense(&token, expense(5.5, json!(["Food"]), -1)).await;
+    // Tagged twice: counted under both tags
+    app.create_expense(&token, expense(30.0, json!(["food", "travel"]), -1)).await;
+    app.create_expense(&token, expense(7.0, json!([]), -1)).await;
+    let mut foreign = expense(10.0, json!(["travel"]), -1);
+    foreign["currency"] = json!("USD");
+    foreign["exchange_rate"] = json!(0.9);
+    app.create_expense(&token, foreign).await;
+    let mut income = expense(12.0, json!(["food"]), -1);
+    income["expense_type"] = json!("income");
+    app.create_expense(&token, income).await;
+    // Transfers aren't spending
+    let transfer = json!({ "description": "Payback", "amount": 50.0, "paid_by": members["Bob"], "expense_type": "transfer", "transfer_to": members["Alice"], "tags": ["food"] });
+    app.create_expense(&token, transfer).await;
+
+    let totals = |query: String| {
+        let app = &app;
+        let token = &token;
+        async move {
+            let (status, totals) = app.get(&format!("/groups/current/stats/by-category{}", query), token).await;
+            assert_eq!(status, StatusCode::OK, "{}", totals);
+            totals.as_array().unwrap().clone()
+        }
+    };
+    let all = totals(String::new()).await;
+    assert_eq!(
+        all,
+        [
+            json!({ "name": "food", "total": 55.5, "expense_count": 3, "income": 12.0, "income_count": 1 }),
+            json!({ "name": "travel", "total": 39.0, "expense_count": 2, "income": 0.0, "income_count": 0 }),
+            json!({ "name": null, "total": 7.0, "expense_count": 1, "income": 0.0, "income_count": 0 }),
+        ]
+    );
+
+    // The range is inclusive on both ends
+    let recent = totals(format!("?from={}", day(-1))).await;
+    assert_eq!(recent[0]["name"], "travel");
+    assert_eq!(recent[1]["name"], "food");
+    assert_eq!(recent[1]["total"], 35.5);
+    let old = totals(format!("?from={}&to={}", day(-10), day(-10))).await;
+    assert_eq!(old, [json!({ "name": "food", "total": 20.0, "expense_count": 1, "income": 0.0, "income_count": 0 })]);
+    assert!(totals(format!("?to={}", day(-11))).await.is_empty());
+
+    for query in [format!("?from={}&to={}", day(0), day(-1)), "?from=yesterday".to_string(), "?to=2024-02-30".to_string()] {
+        let (status, _) = app.get(&format!("/groups/current/stats/by-category{}", query), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn group_limits_reject_members_and_expenses_past_the_cap() {
+    let app = TestApp::spawn_with(&[("MAX_GROUP_MEMBERS", "3"), ("MAX_GROUP_EXPENSES", "2")]).await;
+    let group = |names: &[&str], expenses: serde_json::Value| json!({ "name": "Trip", "member_names": names, "expenses": expenses });
+    let (status, error) = app.request(Method::POST, "/groups", Some(group(&["A", "B", "C", "D"], json!([]))), None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error["error"], "A group can have at most 3 members");
+    let payback = json!({ "description": "Payback", "amount": 5.0, "paid_by": 0 });
+    let (status, error) = app
+        .request(Method::POST, "/groups", Some(group(&["A"], json!([payback, payback, payback]))), None)
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error["error"], "A group can have at most 2 expenses");
+    assert_eq!(app.count("SELECT COUNT(*) FROM groups").await, 0);
+
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (status, _) = app.post("/groups/current/members", json!({ "name": "Carol" }), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, error) = app.post("/groups/current/members", json!({ "name": "Dave" }), &token).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error["error"], "A group can have at most 3 members");
+
+    let expense = json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"] });
+    let first = app.create_expense(&token, expense.clone()).await;
+    app.create_expense(&token, expense.clone()).await;
+    let (status, error) = app.post("/groups/current/expenses", expense.clone(), &token).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(error["error"], "A group can have at most 2 expenses");
+    let path = format!("/groups/current/expenses/{}/duplicate", first["id"].as_str().unwrap());
+    assert_eq!(app.post(&path, json!({}), &token).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // A deletion frees a slot, and a full group can still be settled
+    let (status, _) = app.request(Method::DELETE, &format!("/groups/current/expenses/{}", first["id"].as_str().unwrap()), None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    app.create_expense(&token, expense.clone()).await;
+    let (status, settled) = app.post("/groups/current/settle-all", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", settled);
+    assert!(!settled["transfers"].as_array().unwrap().is_empty());
+    assert!(app.count("SELECT COUNT(*) FROM expenses").await > 2);
+
+    // Other groups have their own budget
+    let (other, other_members) = app.create_group(&["Zed"]).await;
+    let zed = json!({ "description": "Lunch", "amount": 4.0, "paid_by": other_members["Zed"] });
+    app.create_expense(&other, zed).await;
+
+    // Zero or garbage means unlimited
+    for limit in ["0", "-1", "lots"] {
+        let unlimited = TestApp::spawn_with(&[("MAX_GROUP_MEMBERS", limit), ("MAX_GROUP_EXPENSES", limit)]).await;
+        let (token, members) = unlimited.create_group(&["A", "B", "C", "D", "E"]).await;
+        for _ in 0..3 {
+            unlimited.create_expense(&token, json!({ "description": "Dinner", "amount": 1.0, "paid_by": members["A"] })).await;
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn inspecting_a_token_reports_its_permissions_for_this_group_only() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let redeem = |link: serde_json::Value| {
+        let (app, token) = (&app, &token);
+        async move {
+            let (status, link) = app.post("/groups/current/share", link, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", link);
+            let (_, redeemed) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None).await;
+            redeemed["token"].as_str().unwrap().to_string()
+        }
+    };
+    let viewer = redeem(json!({
+        "can_delete_group": false, "can_manage_members": false, "can_update_payment": false,
+        "can_add_expenses": false, "can_edit_expenses": false, "can_settle": false,
+    }))
+    .await;
+    let bob = redeem(json!({ "member_id": members["Bob"], "can_delete_group": false })).await;
+    let inspect = |other: &str, auth: &str| {
+        let (app, body, auth) = (&app, json!({ "token": other }), auth.to_string());
+        async move { app.post("/groups/current/inspect-token", body, &auth).await }
+    };
+
+    // A read-only caller can look at a more powerful token without it being used
+    let (status, info) = inspect(&bob, &viewer).await;
+    assert_eq!(status, StatusCode::OK, "{}", info);
+    let (_, group) = app.get("/groups/current", &token).await;
+    assert_eq!(info["group_id"], group["id"]);
+    assert_eq!(info["member_id"], members["Bob"].as_str());
+    assert_eq!(info["permissions"]["can_delete_group"], false);
+    assert_eq!(info["permissions"]["can_add_expenses"], true);
+    assert_eq!(info["expiring_soon"], false);
+    let (_, own) = app.get("/groups/current/token-info", &bob).await;
+    assert_eq!(info, own);
+
+    let (status, info) = inspect(&format!("  {}\n", viewer), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(info["permissions"].as_object().unwrap().values().all(|v| *v == json!(false)), "{}", info);
+    assert!(info.get("member_id").is_none());
+
+    let (foreign, _) = app.create_group(&["Zed"]).await;
+    assert_eq!(inspect(&foreign, &token).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(inspect("not-a-token", &token).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(inspect("", &token).await.0, StatusCode::BAD_REQUEST);
+    let flip = bob.len() - 10;
+    let replacement = if &bob[flip..flip + 1] == "A" { "B" } else { "A" };
+    let tampered = format!("{}{}{}", &bob[..flip], replacement, &bob[flip + 1..]);
+    assert_eq!(inspect(&tampered, &token).await.0, StatusCode::BAD_REQUEST);
+    app.request(Method::DELETE, &format!("/groups/current/tokens/{}", own["jti"].as_str().unwrap()), None, Some(&token)).await;
+    assert_eq!(inspect(&bob, &token).await.0, StatusCode::BAD_REQUEST);
+    // The caller still needs a valid token of its own
+    let (status, _) = app
+        .request(Method::POST, "/groups/current/inspect-token", Some(json!({ "token": viewer })), None)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn deleting_a_group_or_expense_removes_every_descendant_row() {
+    let app = TestApp::spawn().await;
+    let (other, other_members) = app.create_group(&["Zed", "Yan"]).await;
+    let zed = json!({ "description": "Lunch", "amount": 4.0, "paid_by": other_members["Zed"], "tags": ["food"] });
+    let kept = app.create_expense(&other, zed).await;
+    // Row counts of every table while only the other group exists
+    let rows = |table: &str| {
+        format!(
+            "(xpath('/row/n/text()', query_to_xml(format('SELECT COUNT(*) AS n FROM %I', {}), false, true, '')))[1]::text::bigint",
+            table
+        )
+    };
+    app.execute(&format!(
+        "CREATE TABLE row_counts AS SELECT table_name::text AS name, {} AS n FROM information_schema.tables
+         WHERE table_schema = 'public' AND table_type = 'BASE TABLE'",
+        rows("table_name")
+    ))
+    .await;
+    let changed_tables = format!("SELECT COUNT(*) FROM row_counts WHERE name <> 'row_counts' AND n <> {}", rows("name"));
+
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let today = chrono::Utc::now().date_naive().to_string();
+    let (_, trip) = app.post("/groups/current/trips", json!({ "name": "Rome", "start_date": today }), &token).await;
+    let items = json!([{ "description": "Pasta", "amount": 12.0, "member_ids": [members["Alice"], members["Bob"]] }]);
+    let dinner = app
+        .create_expense(
+            &token,
+            json!({ "description": "Dinner", "amount": 0.0, "paid_by": members["Alice"], "items": items, "tags": ["food"], "trip_id": trip["id"] }),
+        )
+        .await;
+    let taxi = app
+        .create_expense(
+            &token,
+            json!({ "description": "Taxi", "amount": 9.0, "paid_by": members["Bob"], "split_between": [members["Bob"], members["Carol"]], "tags": ["travel"] }),
+        )
+        .await;
+    assert_eq!(app.upload_receipt(dinner["id"].as_str().unwrap(), b"%PDF-1.4 dinner", &token).await.0, StatusCode::OK);
+    assert_eq!(app.upload_receipt(taxi["id"].as_str().unwrap(), b"%PDF-1.4 taxi", &token).await.0, StatusCode::OK);
+    let (_, link) = app.post("/groups/current/share", json!({ "member_id": members["Bob"] }), &token).await;
+    let (_, redeemed) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None).await;
+    let bob = redeemed["token"].as_str().unwrap();
+    let settled_path = format!("/groups/current/expenses/{}/splits/{}/settled", taxi["id"].as_str().unwrap(), members["Carol"]);
+    assert_eq!(app.request(Method::PUT, &settled_path, Some(json!({ "settled": true })), Some(bob)).await.0, StatusCode::OK);
+    assert!(app.count(&changed_tables).await >= 10, "only {} tables got rows", app.count(&changed_tables).await);
+
+    // Deleting an expense removes its splits, items, tags and receipt
+    let taxi_id = taxi["id"].as_str().unwrap();
+    let (status, _) = app.request(Method::DELETE, &format!("/groups/current/expenses/{}", taxi_id), None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    for table in ["expense_splits", "expense_items", "expense_tags", "expense_receipts"] {
+        assert_eq!(app.count(&format!("SELECT COUNT(*) FROM {} WHERE expense_id = '{}'", table, taxi_id)).await, 0, "{}", table);
+    }
+    let dinner_id = dinner["id"].as_str().unwrap();
+    assert_eq!(app.count(&format!("SELECT COUNT(*) FROM expense_splits WHERE expense_id = '{}'", dinner_id)).await, 2);
+
+    // Deleting the group leaves every table as it was before the group existed
+    let (status, _) = app.request(Method::DELETE, "/groups/current", None, Some(&token)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(app.count(&changed_tables).await, 0);
+    assert_eq!(app.get("/groups/current", &token).await.0, StatusCode::NOT_FOUND);
+    let (status, expenses) = app.get("/groups/current/expenses", &other).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(expenses[0]["id"], kept["id"]);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn balance_history_ends_at_the_current_balance() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+    let day = |offset: i64| (chrono::Utc::now().date_naive() + chrono::Duration::days(offset)).to_string();
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let trio = json!([alice, bob, carol]);
+    // Created out of date order; thirds that don't divide evenly
+    app.create_expense(&token, json!({ "description": "Taxi", "amount": 10.0, "paid_by": bob, "split_between": trio, "expense_date": day(-1) })).await;
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 100.0, "paid_by": alice, "split_between": trio, "expense_date": day(-5) })).await;
+    app.create_expense(&token, json!({ "description": "Museum", "amount": 20.0, "paid_by": carol, "split_between": trio, "currency": "USD", "exchange_rate": 0.9, "expense_date": day(-5) })).await;
+    app.create_expense(&token, json!({ "description": "Refund", "amount": 6.0, "paid_by": bob, "split_between": trio, "expense_type": "income", "expense_date": day(-3) })).await;
+    app.create_expense(&token, json!({ "description": "Payback", "amount": 25.0, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice, "expense_date": day(-2) })).await;
+
+    let history = |member: &str| {
+        let (app, token, path) = (&app, &token, format!("/groups/current/members/{}/balance-history", member));
+        async move {
+            let (status, history) = app.get(&path, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", history);
+            history.as_array().unwrap().clone()
+        }
+    };
+    let balances = app.balances(&token).await;
+    for name in ["Alice", "Bob", "Carol"] {
+        let points = history(&members[name]).await;
+        let last = points.last().unwrap()["balance"].as_f64().unwrap();
+        assert!((last - balances[name]).abs() < 1e-9, "{}: {:?} vs {}", name, points, balances[name]);
+        let dates: Vec<&str> = points.iter().map(|p| p["date"].as_str().unwrap()).collect();
+        let mut sorted = dates.clone();
+        sorted.dedup();
+        sorted.sort();
+        assert_eq!(dates, sorted, "one point per date, in order");
+    }
+    // Both expenses of the first day end up in one point
+    let alice_points = history(alice).await;
+    assert_eq!(alice_points.len(), 4);
+    assert_eq!(alice_points[0]["date"], day(-5).as_str());
+    assert!((alice_points[0]["balance"].as_f64().unwrap() - (100.0 - 100.0 / 3.0 - 6.0)).abs() < 0.01, "{:?}", alice_points);
+
+    // Members without expenses have an empty history
+    assert!(history(&members["Dave"]).await.is_empty());
+    let (_, other) = app.create_group(&["Zed"]).await;
+    for (member, expected) in [(other["Zed"].clone(), StatusCode::NOT_FOUND), (uuid::Uuid::new_v4().to_string(), StatusCode::NOT_FOUND), ("nope".to_string(), StatusCode::BAD_REQUEST)] {
+        let (status, _) = app.get(&format!("/groups/current/members/{}/balance-history", member), &token).await;
+        assert_eq!(status, expected, "{}", member);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn settled_shares_drop_out_of_the_balances_when_excluded() {
+    let mut results = Vec::new();
+    // Both the in-memory and the SQL balance computation
+    for threshold in ["1000", "0"] {
+        let app = TestApp::spawn_with(&[("SQL_BALANCES_THRESHOLD", threshold)]).await;
+        let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+        let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+        let dinner = app
+            .create_expense(&token, json!({ "description": "Dinner", "amount": 30.0, "paid_by": alice, "split_between": [alice, bob, carol] }))
+            .await;
+        let taxi = app
+            .create_expense(&token, json!({ "description": "Taxi", "amount": 10.0, "paid_by": carol, "split_between": [alice, bob, carol] }))
+            .await;
+        let settle = |expense: &serde_json::Value, member: &str, settled: bool| {
+            let path = format!("/groups/current/expenses/{}/splits/{}/settled", expense["id"].as_str().unwrap(), member);
+            let (app, token) = (&app, &token);
+            async move { app.request(Method::PUT, &path, Some(json!({ "settled": settled })), Some(token)).await }
+        };
+        let excluding = || {
+            let (app, token) = (&app, &token);
+            async move {
+                let (status, balances) = app.get("/groups/current/balances?include_settled=false", token).await;
+                assert_eq!(status, StatusCode::OK, "{}", balances);
+                balances
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|b| (b["user_name"].as_str().unwrap().to_string(), b["balance"].as_f64().unwrap()))
+                    .collect::<std::collections::HashMap<_, _>>()
+            }
+        };
+        let all = app.balances(&token).await;
+
+        let (status, first) = settle(&dinner, bob, true).await;
+        assert_eq!(status, StatusCode::OK, "{}", first);
+        assert_eq!(first["settled"], true);
+        let without = excluding().await;
+        // Bob no longer owes Alice his 10.00 of the dinner
+        assert!((without["Bob"] - (all["Bob"] + 10.0)).abs() < 0.005, "{:?}", without);
+        assert!((without["Alice"] - (all["Alice"] - 10.0)).abs() < 0.005, "{:?}", without);
+        assert!((without["Carol"] - all["Carol"]).abs() < 0.005, "{:?}", without);
+        assert_eq!(app.balances(&token).await, all, "included by default");
+        // Marking it again keeps the first settled_at
+        let (_, again) = settle(&dinner, bob, true).await;
+        assert_eq!(again["settled_at"], first["settled_at"]);
+
+        // The payer's own share and an uneven share
+        assert_eq!(settle(&dinner, alice, true).await.0, StatusCode::OK);
+        assert_eq!(settle(&taxi, alice, true).await.0, StatusCode::OK);
+        results.push(excluding().await);
+        let listed = app.expenses(&token).await;
+        let dinner_listed = listed.iter().find(|e| e["id"] == dinner["id"]).unwrap();
+        assert_eq!(dinner_listed["settled_shares"].as_array().unwrap().len(), 2);
+
+        // Unsettling restores the debt; changing the amount resets every share
+        assert_eq!(settle(&taxi, alice, false).await.0, StatusCode::OK);
+        let path = format!("/groups/current/expenses/{}", dinner["id"].as_str().unwrap());
+        let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "amount": 33.0 })), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(excluding().await, app.balances(&token).await);
+
+        // Only split members of this group's expenses
+        let (_, other) = app.create_group(&["Zed"]).await;
+        assert_eq!(settle(&dinner, &other["Zed"], true).await.0, StatusCode::NOT_FOUND);
+        let (other_token, _) = app.create_group(&["Yan"]).await;
+        let path = format!("/groups/current/expenses/{}/splits/{}/settled", dinner["id"].as_str().unwrap(), bob);
+        assert_eq!(app.request(Method::PUT, &path, Some(json!({ "settled": true })), Some(&other_token)).await.0, StatusCode::NOT_FOUND);
+    }
+    assert_eq!(results[0], results[1]);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn pair_history_covers_every_way_two_members_are_involved() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let day = |offset: i64| (chrono::Utc::now().date_naive() + chrono::Duration::days(offset)).to_string();
+    let cases = [
+        // (description, body, expected direction for (Alice, Bob), amount)
+        ("Alice paid, Bob shares", json!({ "amount": 30.0, "paid_by": alice, "split_between": [alice, bob, carol] }), "b_owes_a", 10.0),
+        ("Bob paid, Alice shares", json!({ "amount": 12.0, "paid_by": bob, "split_between": [alice, bob] }), "a_owes_b", 6.0),
+        ("Both only share", json!({ "amount": 9.0, "paid_by": carol, "split_between": [alice, bob, carol] }), "none", 0.0),
+        ("Alice pays Bob", json!({ "amount": 5.0, "paid_by": alice, "expense_type": "transfer", "transfer_to": bob }), "b_owes_a", 5.0),
+        ("Bob pays Alice", json!({ "amount": 3.0, "paid_by": bob, "expense_type": "transfer", "transfer_to": alice }), "a_owes_b", 3.0),
+        ("Alice receives, both share", json!({ "amount": 8.0, "paid_by": alice, "split_between": [alice, bob], "expense_type": "income" }), "a_owes_b", 4.0),
+    ];
+    for (i, (description, body, _, _)) in cases.iter().enumerate() {
+        let mut body = body.clone();
+        body["description"] = json!(description);
+        body["expense_date"] = json!(day(-(i as i64) - 1));
+        app.create_expense(&token, body).await;
+    }
+    // Not involving both of them
+    let unrelated = [
+        json!({ "description": "Alice alone", "amount": 4.0, "paid_by": alice, "split_between": [alice] }),
+        json!({ "description": "Alice and Carol", "amount": 4.0, "paid_by": alice, "split_between": [carol] }),
+        json!({ "description": "Carol pays Bob", "amount": 4.0, "paid_by": carol, "expense_type": "transfer", "transfer_to": bob }),
+    ];
+    for body in unrelated {
+        app.create_expense(&token, body).await;
+    }
+
+    let (status, pair) = app.get(&format!("/groups/current/pairs/{}/{}/expenses", alice, bob), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", pair);
+    assert_eq!((pair["a_name"].as_str(), pair["b_name"].as_str()), (Some("Alice"), Some("Bob")));
+    let listed = pair["expenses"].as_array().unwrap();
+    assert_eq!(listed.len(), cases.len(), "{}", pair);
+    for (entry, (description, _, direction, amount)) in listed.iter().zip(cases.iter()) {
+        assert_eq!(entry["expense"]["description"], *description);
+        assert_eq!(entry["direction"], *direction, "{}", description);
+        assert!((entry["amount"].as_f64().unwrap() - amount).abs() < 0.005, "{}: {}", description, entry);
+    }
+    assert!((pair["net"].as_f64().unwrap() - 2.0).abs() < 0.005, "{}", pair);
+
+    // Swapping the pair flips every direction and the net
+    let (_, swapped) = app.get(&format!("/groups/current/pairs/{}/{}/expenses", bob, alice), &token).await;
+    assert!((swapped["net"].as_f64().unwrap() + 2.0).abs() < 0.005, "{}", swapped);
+    for (entry, original) in swapped["expenses"].as_array().unwrap().iter().zip(listed) {
+        let flipped = match original["direction"].as_str().unwrap() {
+            "a_owes_b" => "b_owes_a",
+            "b_owes_a" => "a_owes_b",
+            other => other,
+        };
+        assert_eq!(entry["direction"], flipped);
+        assert_eq!(entry["amount"], original["amount"]);
+    }
+
+    let (_, other) = app.create_group(&["Zed"]).await;
+    for (a, b, expected) in [
+        (alice.clone(), alice.clone(), StatusCode::BAD_REQUEST),
+        (alice.clone(), "nope".to_string(), StatusCode::BAD_REQUEST),
+        (alice.clone(), other["Zed"].clone(), StatusCode::NOT_FOUND),
+        (uuid::Uuid::new_v4().to_string(), bob.clone(), StatusCode::NOT_FOUND),
+    ] {
+        let (status, _) = app.get(&format!("/groups/current/pairs/{}/{}/expenses", a, b), &token).await;
+        assert_eq!(status, expected, "{} {}", a, b);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn invites_hand_out_single_use_links_bound_to_the_member() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let invite = |member: &str, body: serde_json::Value, auth: &str| {
+        let (app, path, auth) = (&app, format!("/groups/current/members/{}/invite", member), auth.to_string());
+        async move { app.post(&path, body, &auth).await }
+    };
+    let redeem = |code: &str| app.request(Method::POST, "/share/redeem", Some(json!({ "code": code })), None);
+
+    // Without SMTP the link comes back in the response
+    let (status, invited) = invite(&members["Bob"], json!({ "email": " bob@example.com ", "can_delete_group": false, "expires_in_hours": 2 }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", invited);
+    assert_eq!(invited["sent"], false);
+    assert_eq!(invited["email"], "bob@example.com");
+    assert_eq!(invited["permissions"]["can_delete_group"], false);
+    let expires_at: chrono::DateTime<chrono::Utc> = invited["expires_at"].as_str().unwrap().parse().unwrap();
+    assert!((expires_at - chrono::Utc::now() - chrono::Duration::hours(2)).num_seconds().abs() < 60);
+    let link = invited["link"].as_str().unwrap();
+    let code = link.split("#join=").nth(1).expect("join link");
+
+    let (status, redeemed) = redeem(code).await;
+    assert_eq!(status, StatusCode::OK, "{}", redeemed);
+    let bob = redeemed["token"].as_str().unwrap();
+    let (_, info) = app.get("/groups/current/token-info", bob).await;
+    assert_eq!(info["member_id"], members["Bob"].as_str());
+    assert_eq!(info["permissions"]["can_delete_group"], false);
+    assert_eq!(info["permissions"]["can_add_expenses"], true);
+    assert_eq!(redeem(code).await.0, StatusCode::UNAUTHORIZED, "links are single use");
+
+    // The email is kept when a later invite leaves it out
+    let (_, again) = invite(&members["Bob"], json!({}), &token).await;
+    assert_eq!(again["email"], "bob@example.com");
+    assert_ne!(again["link"], invited["link"]);
+
+    // A member-bound token may only invite its own member, and never widens its permissions
+    let (status, own) = invite(&members["Bob"], json!({ "can_delete_group": true }), bob).await;
+    assert_eq!(status, StatusCode::OK, "{}", own);
+    assert_eq!(own["permissions"]["can_delete_group"], false);
+    assert_eq!(invite(&members["Alice"], json!({}), bob).await.0, StatusCode::FORBIDDEN);
+    let (_, scoped) = app.post("/groups/current/scoped-token", json!({ "can_manage_members": false }), &token).await;
+    assert_eq!(invite(&members["Alice"], json!({}), scoped["token"].as_str().unwrap()).await.0, StatusCode::FORBIDDEN);
+
+    for body in [json!({ "email": "not-an-email" }), json!({ "expires_in_hours": 0 }), json!({ "email": format!("{}@example.com", "a".repeat(250)) })] {
+        assert_eq!(invite(&members["Alice"], body.clone(), &token).await.0, StatusCode::BAD_REQUEST, "{}", body);
+    }
+    let (_, other) = app.create_group(&["Zed"]).await;
+    assert_eq!(invite(&other["Zed"], json!({}), &token).await.0, StatusCode::NOT_FOUND);
+
+    // With SMTP the link is only in the email
+    let (port, messages) = common::serve_smtp();
+    let port = port.to_string();
+    let mailing = TestApp::spawn_with(&[
+        ("SMTP_HOST", "127.0.0.1"),
+        ("SMTP_PORT", &port),
+        ("SMTP_TLS", "none"),
+        ("APP_BASE_URL", "http://app.test/"),
+    ])
+    .await;
+    let (token, members) = mailing.create_group(&["Alice", "Bob"]).await;
+    let path = format!("/groups/current/members/{}/invite", members["Bob"]);
+    let (status, _) = mailing.post(&path, json!({}), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "no email address to send to");
+    let (status, sent) = mailing.post(&path, json!({ "email": "bob@example.com" }), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", sent);
+    assert_eq!(sent["sent"], true);
+    assert!(sent.get("link").is_none());
+    let message = messages.lock().unwrap().last().cloned().expect("an email");
+    assert!(message.contains("bob@example.com"), "{}", message);
+    let code = message.split("http://app.test/#join=").nth(1).expect("join link in the email");
+    let code: String = code.chars().take_while(char::is_ascii_alphanumeric).collect();
+    let (status, redeemed) =
+        mailing.request(Method::POST, "/share/redeem", Some(json!({ "code": code })), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", redeemed);
+
+    // A failed send leaves no working link behind
+    let unreachable = TestApp::spawn_with(&[("SMTP_HOST", "127.0.0.1"), ("SMTP_PORT", "1"), ("SMTP_TLS", "none")]).await;
+    let (token, members) = unreachable.create_group(&["Alice", "Bob"]).await;
+    let path = format!("/groups/current/members/{}/invite", members["Bob"]);
+    let (status, _) = unreachable.post(&path, json!({ "email": "bob@example.com" }), &token).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(unreachable.count("SELECT COUNT(*) FROM share_links WHERE member_id IS NOT NULL").await, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn share_links_fall_back_to_the_group_default_and_stay_capped() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let share = |body: serde_json::Value, auth: &str| {
+        let (app, auth) = (&app, auth.to_string());
+        async move {
+            let (status, link) = app.post("/groups/current/share", body, &auth).await;
+            assert_eq!(status, StatusCode::OK, "{}", link);
+            link["permissions"].clone()
+        }
+    };
+    let all = json!({
+        "can_delete_group": true, "can_manage_members": true, "can_update_payment": true,
+        "can_add_expenses": true, "can_edit_expenses": true, "can_settle": true,
+    });
+    assert_eq!(share(json!({}), &token).await, all, "everything the caller has without a default");
+
+    let contributor = json!({
+        "can_delete_group": false, "can_manage_members": false, "can_update_payment": false,
+        "can_add_expenses": true, "can_edit_expenses": false, "can_settle": true,
+    });
+    let path = "/groups/current/default-share-permissions";
+    let (status, group) = app.request(Method::PUT, path, Some(json!({ "default_share_permissions": contributor })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", group);
+    assert_eq!(group["default_share_permissions"], contributor);
+    assert_eq!(share(json!({}), &token).await, contributor);
+    // Stated fields win over the default, in both directions
+    let mut expected = contributor.clone();
+    expected["can_edit_expenses"] = json!(true);
+    expected["can_settle"] = json!(false);
+    assert_eq!(share(json!({ "can_edit_expenses": true, "can_settle": false }), &token).await, expected);
+    // Invites use the default too
+    let (_, invited) = app.post(&format!("/groups/current/members/{}/invite", members["Bob"]), json!({}), &token).await;
+    assert_eq!(invited["permissions"], contributor);
+
+    // The default never lifts a caller's own limits
+    let (_, scoped) = app
+        .post("/groups/current/scoped-token", json!({ "can_add_expenses": false, "can_delete_group": false }), &token)
+        .await;
+    let limited = scoped["token"].as_str().unwrap();
+    let mut capped = contributor.clone();
+    capped["can_add_expenses"] = json!(false);
+    assert_eq!(share(json!({}), limited).await, capped);
+    assert_eq!(share(json!({ "can_delete_group": true }), limited).await, capped);
+    // Only callers who may delete the group change the default
+    let (status, _) = app.request(Method::PUT, path, Some(json!({ "default_share_permissions": all })), Some(limited)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Older clients may leave out can_settle, which is then granted; other fields are required
+    let mut without_settle = contributor.clone();
+    without_settle.as_object_mut().unwrap().remove("can_settle");
+    let (_, group) = app.request(Method::PUT, path, Some(json!({ "default_share_permissions": without_settle })), Some(&token)).await;
+    assert_eq!(group["default_share_permissions"]["can_settle"], true);
+    let mut incomplete = contributor.clone();
+    incomplete.as_object_mut().unwrap().remove("can_add_expenses");
+    let (status, _) = app.request(Method::PUT, path, Some(json!({ "default_share_permissions": incomplete })), Some(&token)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Clearing it restores the old behaviour
+    let (status, group) = app.request(Method::PUT, path, Some(json!({ "default_share_permissions": null })), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(group["default_share_permissions"].is_null());
+    assert_eq!(share(json!({}), &token).await, all);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn income_counts_as_received_not_spent() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob", "Carol"]).await;
+    let (alice, bob, carol) = (&members["Alice"], &members["Bob"], &members["Carol"]);
+    let split = |entries: &[(&String, f64)]| {
+        entries.iter().map(|(id, share)| json!({ "member_id": id, "share": share })).collect::<Vec<_>>()
+    };
+    // Alice collects a 30.00 deposit refund for everyone
+    app.create_expense(&token, json!({ "description": "Deposit", "amount": 30.0, "paid_by": alice, "split_between": [alice, bob, carol], "expense_type": "income" })).await;
+    app.create_expense(&token, json!({ "description": "Cashback", "amount": 10.0, "paid_by": bob, "split_between": [alice, bob],
+        "expense_type": "income", "split_type": "percentage", "splits": split(&[(alice, 75.0), (bob, 25.0)]) })).await;
+    app.create_expense(&token, json!({ "description": "Taxi", "amount": 12.0, "paid_by": bob, "split_between": [alice, bob] })).await;
+    app.create_expense(&token, json!({ "description": "Payback", "amount": 5.0, "paid_by": carol, "expense_type": "transfer", "transfer_to": alice })).await;
+
+    let (status, stats) = app.get("/groups/current/stats/members", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", stats);
+    let stats: std::collections::HashMap<&str, &serde_json::Value> =
+        stats.as_array().unwrap().iter().map(|s| (s["name"].as_str().unwrap(), s)).collect();
+    let field = |name: &str, field: &str| stats[name][field].as_f64().unwrap();
+    // Income never shows up as paid or spent
+    assert_eq!((field("Alice", "paid"), field("Alice", "spent")), (0.0, 6.0));
+    assert_eq!((field("Bob", "paid"), field("Bob", "spent")), (12.0, 6.0));
+    assert_eq!((field("Carol", "paid"), field("Carol", "spent")), (0.0, 0.0));
+    assert_eq!((field("Alice", "income_collected"), field("Alice", "income_share")), (30.0, 17.5));
+    assert_eq!((field("Bob", "income_collected"), field("Bob", "income_share")), (10.0, 12.5));
+    assert_eq!((field("Carol", "income_collected"), field("Carol", "income_share")), (0.0, 10.0));
+    assert_eq!((field("Carol", "transfers_sent"), field("Alice", "transfers_received")), (5.0, 5.0));
+
+    // The breakdown adds up to the balance, and the balance to the balances endpoint
+    let balances = app.balances(&token).await;
+    for name in ["Alice", "Bob", "Carol"] {
+        let sum = field(name, "paid") - field(name, "spent") - field(name, "income_collected") + field(name, "income_share")
+            + field(name, "transfers_sent")
+            - field(name, "transfers_received");
+        assert!((sum - field(name, "balance")).abs() < 0.005, "{}: {:?}", name, stats[name]);
+        assert_eq!(field(name, "balance"), balances[name], "{}", name);
+    }
+    let (_, categories) = app.get("/groups/current/stats/by-category", &token).await;
+    assert_eq!(categories, json!([{ "name": null, "total": 12.0, "expense_count": 1, "income": 40.0, "income_count": 2 }]));
+
+    // Income has to hand out a positive amount in full
+    let income = |extra: serde_json::Value| {
+        let mut body = json!({ "description": "Refund", "amount": 9.0, "paid_by": alice, "split_between": [alice, bob], "expense_type": "income" });
+        body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        body
+    };
+    for extra in [
+        json!({ "amount": 0.0 }),
+        json!({ "amount": -9.0 }),
+        json!({ "split_type": "percentage", "splits": split(&[(alice, 50.0), (bob, 30.0)]) }),
+        json!({ "split_type": "exact", "splits": split(&[(alice, 2.0), (bob, 3.0)]) }),
+        json!({ "split_type": "shares", "splits": split(&[(alice, 0.0), (bob, 0.0)]) }),
+    ] {
+        let (status, _) = app.post("/groups/current/expenses", income(extra.clone()), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", extra);
+    }
+    let refund = app.create_expense(&token, income(json!({}))).await;
+    let path = format!("/groups/current/expenses/{}", refund["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "amount": 0.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // Turning an expense into income checks the same
+    let taxi = app.expenses(&token).await.into_iter().find(|e| e["description"] == "Taxi").unwrap();
+    let path = format!("/groups/current/expenses/{}", taxi["id"].as_str().unwrap());
+    let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "expense_type": "income", "amount": -1.0 })), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // Nothing invalid was saved
+    assert_eq!(app.expenses(&token).await.len(), 5);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn payment_request_batches_settle_every_balance() {
+    let app = TestApp::spawn().await;
+    let (status, created) = app
+        .request(Method::POST, "/groups", Some(json!({ "name": "Tokyo", "member_names": ["Alice", "Bob", "Carol", "Dave"], "currency": "JPY" })), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let token = created["token"].as_str().unwrap().to_string();
+    let members: std::collections::HashMap<&str, &str> = created["group"]["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["name"].as_str().unwrap(), m["id"].as_str().unwrap()))
+        .collect();
+    let (alice, bob, carol, dave) = (members["Alice"], members["Bob"], members["Carol"], members["Dave"]);
+
+    let (_, empty) = app.get("/groups/current/payment-requests", &token).await;
+    assert_eq!(empty["requests"], json!([]));
+
+    let path = format!("/groups/current/members/{}/payment", alice);
+    let details = json!({ "iban": "DE89370400440532013000", "payment_note": "A. Wonder" });
+    assert_eq!(app.request(Method::PUT, &path, Some(details), Some(&token)).await.0, StatusCode::OK);
+    // 3001 yen split three ways doesn't divide evenly
+    app.create_expense(&token, json!({ "description": "Hotel", "amount": 3001.0, "paid_by": alice, "split_between": [alice, bob, dave] })).await;
+    app.create_expense(&token, json!({ "description": "Ramen", "amount": 500.0, "paid_by": carol, "split_between": [bob, carol] })).await;
+
+    let (status, batch) = app.get("/groups/current/payment-requests", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", batch);
+    assert_eq!(batch["group_name"], "Tokyo");
+    assert_eq!(batch["currency"], "JPY");
+    let requests = batch["requests"].as_array().unwrap();
+    assert!(!requests.is_empty());
+    let mut settled = app.balances(&token).await;
+    for request in requests {
+        let amount = request["amount"].as_f64().unwrap();
+        assert!(amount > 0.0 && amount.fract() == 0.0, "whole yen: {}", request);
+        assert_eq!(request["amount_minor"].as_i64().unwrap() as f64, amount);
+        assert_eq!(request["currency"], "JPY");
+        assert_eq!(request["reference"], format!("Tokyo: {}", request["payer_name"].as_str().unwrap()));
+        *settled.get_mut(request["payer_name"].as_str().unwrap()).unwrap() += amount;
+        *settled.get_mut(request["payee_name"].as_str().unwrap()).unwrap() -= amount;
+        if request["payee_id"] == alice {
+            assert_eq!(request["payable"], true);
+            assert_eq!(request["method"], "iban");
+            assert_eq!(request["iban"], "DE89370400440532013000");
+            assert_eq!(request["payment_note"], "A. Wonder");
+        } else {
+            // Carol has no payment details: still listed, but flagged
+            assert_eq!(request["payee_id"], carol);
+            assert_eq!(request["payable"], false);
+            assert!(request["method"].is_null() && request["iban"].is_null() && request["paypal_email"].is_null());
+        }
+    }
+    assert!(settled.values().all(|b| b.abs() < 0.5), "the batch settles everyone: {:?}", settled);
+    assert!(requests.iter().all(|r| r["payer_id"] != alice && r["payer_id"] != carol));
+
+    // Once paid, nothing is left to request
+    let (status, _) = app.post("/groups/current/settle-all", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, batch) = app.get("/groups/current/payment-requests", &token).await;
+    assert_eq!(batch["requests"], json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn adjusted_shares_add_up_to_the_expense_amount() {
+    let mut results = Vec::new();
+    for threshold in ["1000", "0"] {
+        let app = TestApp::spawn_with(&[("SQL_BALANCES_THRESHOLD", threshold)]).await;
+        let (token, members) = app.create_group(&["Alice", "Bob", "Carol", "Dave"]).await;
+        let (alice, bob, carol, dave) = (&members["Alice"], &members["Bob"], &members["Carol"], &members["Dave"]);
+        // Dave pays, so everyone else's balance is exactly minus their share
+        let adjusted = |amount: f64, adjustments: serde_json::Value| {
+            json!({ "description": "Dinner", "amount": amount, "paid_by": dave, "split_between": [alice, bob, carol], "adjustments": adjustments })
+        };
+        let shares = || {
+            let (app, token) = (&app, &token);
+            async move {
+                let balances = app.balances(token).await;
+                ["Alice", "Bob", "Carol"].map(|name| (-balances[name] * 100.0).round() as i64)
+            }
+        };
+
+        // 16.00 left after Bob's dessert doesn't divide by three
+        let dinner = app.create_expense(&token, adjusted(20.0, json!([{ "member_id": bob, "amount": 4.0 }]))).await;
+        assert_eq!(dinner["split_type"], "adjustment");
+        let cents = shares().await;
+        assert_eq!(cents.iter().sum::<i64>(), 2000, "{:?}", cents);
+        assert_eq!(cents, [534, 933, 533]);
+
+        // The adjustments are kept when the amount changes, only the equal part moves
+        let path = format!("/groups/current/expenses/{}", dinner["id"].as_str().unwrap());
+        let (status, _) = app.request(Method::PATCH, &path, Some(json!({ "amount": 25.0 })), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(shares().await, [700, 1100, 700]);
+
+        // Adjustments may use up the whole amount, leaving nothing to split
+        let (status, _) = app.request(Method::DELETE, &path, None, Some(&token)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        app.create_expense(&token, adjusted(10.0, json!([{ "member_id": alice, "amount": 6.5 }, { "member_id": carol, "amount": 3.5 }]))).await;
+        assert_eq!(shares().await, [650, 0, 350]);
+        results.push(app.balances(&token).await);
+
+        for adjustments in [
+            json!([{ "member_id": bob, "amount": 10.01 }]),
+            json!([{ "member_id": bob, "amount": 6.0 }, { "member_id": carol, "amount": 5.0 }]),
+            json!([{ "member_id": bob, "amount": -1.0 }]),
+            json!([{ "member_id": dave, "amount": 1.0 }]),
+            json!([{ "member_id": bob, "amount": 1.0 }, { "member_id": bob, "amount": 1.0 }]),
+        ] {
+            let (status, _) = app.post("/groups/current/expenses", adjusted(10.0, adjustments.clone()), &token).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", adjustments);
+        }
+        assert_eq!(app.expenses(&token).await.len(), 1);
+    }
+    assert_eq!(results[0], results[1]);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn test_apps_are_isolated_and_clean_up_after_themselves() {
+    let (first, second) = tokio::join!(TestApp::spawn(), TestApp::spawn());
+    assert_ne!(first.base, second.base);
+    assert_ne!(first.database(), second.database());
+    // Every migration ran on each fresh database
+    for app in [&first, &second] {
+        let (status, ready) = app.request(Method::GET, "/ready", None, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", ready);
+        assert_eq!(ready["migrations"]["pending"], json!([]));
+    }
+
+    let (token, members) = first.create_group(&["Alice", "Bob"]).await;
+    let expense = first.create_expense(&token, json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"] })).await;
+    assert_eq!(first.upload_receipt(expense["id"].as_str().unwrap(), b"%PDF-1.4 receipt", &token).await.0, StatusCode::OK);
+    assert_eq!(second.count("SELECT COUNT(*) FROM groups").await, 0);
+    // Both servers sign with the same default key, but the group only exists in one database
+    assert_eq!(second.get("/groups/current", &token).await.0, StatusCode::NOT_FOUND);
+
+    let receipts = std::env::temp_dir().join(first.database());
+    assert!(receipts.exists());
+    let (database, base) = (first.database().to_string(), first.base.clone());
+    drop(first);
+    let exists = format!("SELECT COUNT(*) FROM pg_database WHERE datname = '{}'", database);
+    assert_eq!(second.count(&exists).await, 0);
+    assert!(!receipts.exists());
+    assert!(reqwest::get(format!("{}/health", base)).await.is_err(), "the server was stopped");
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn amount_and_rate_limits_hold_at_their_boundaries() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let expense = |amount: f64, rate: f64| {
+        json!({ "description": "Edge", "amount": amount, "currency": "USD", "exchange_rate": rate, "paid_by": members["Alice"] })
+    };
+    let create = |body: serde_json::Value| app.post("/groups/current/expenses", body, &token);
+
+    // The largest amount the column holds, in both directions
+    for amount in [9_999_999_999.99, -9_999_999_999.99] {
+        let (status, created) = create(expense(amount, 1.0)).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", amount, created);
+        assert_eq!(created["amount"], amount);
+    }
+    for amount in [10_000_000_000.0, -10_000_000_000.0, f64::MAX] {
+        let (status, error) = create(expense(amount, 1.0)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", amount);
+        assert!(error["error"].as_str().unwrap().contains("amount"), "{}", error);
+    }
+    // Rounded to cents before storing
+    let (_, created) = create(expense(12.3456, 1.0)).await;
+    assert_eq!(created["amount"], 12.35);
+
+    // Rates between one millionth and the column's maximum
+    for rate in [0.000001, 0.0000006, 999_999.999_999] {
+        let (status, created) = create(expense(1.0, rate)).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", rate, created);
+        assert!(created["exchange_rate"].as_f64().unwrap() >= 0.000001);
+    }
+    for rate in [0.0000004, 1_000_000.0, f64::MIN_POSITIVE, -0.0] {
+        let (status, error) = create(expense(1.0, rate)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", rate);
+        assert!(error["error"].as_str().unwrap().contains("exchange_rate"), "{}", error);
+    }
+
+    // Every way in checks the same
+    let huge = expense(1e12, 1.0);
+    assert_eq!(app.post("/groups/current/expenses/preview", huge.clone(), &token).await.0, StatusCode::BAD_REQUEST);
+    let (_, first) = create(expense(1.0, 1.0)).await;
+    let path = format!("/groups/current/expenses/{}", first["id"].as_str().unwrap());
+    let mut put = huge.clone();
+    put["split_between"] = json!([members["Alice"], members["Bob"]]);
+    assert_eq!(app.request(Method::PUT, &path, Some(put), Some(&token)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.request(Method::PATCH, &path, Some(json!({ "exchange_rate": 0.0 })), Some(&token)).await.0, StatusCode::BAD_REQUEST);
+    let group = json!({ "name": "Big", "member_names": ["A"], "expenses": [{ "description": "Yacht", "amount": 1e12, "paid_by": 0 }] });
+    assert_eq!(app.request(Method::POST, "/groups", Some(group), None).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.expenses(&token).await.len(), 7);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn scoped_tokens_keep_the_member_and_count_towards_the_cap() {
+    let app = TestApp::spawn_with(&[("MAX_GROUP_TOKENS", "3")]).await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let scope = |body: serde_json::Value, auth: &str| {
+        let (app, auth) = (&app, auth.to_string());
+        async move { app.post("/groups/current/scoped-token", body, &auth).await }
+    };
+
+    // Nothing requested: the same permissions as the caller
+    let (_, link) = app.post("/groups/current/share", json!({ "member_id": members["Bob"], "can_delete_group": false }), &token).await;
+    let (_, redeemed) = app.request(Method::POST, "/share/redeem", Some(json!({ "code": link["code"] })), None).await;
+    let bob = redeemed["token"].as_str().unwrap();
+    let (status, same) = scope(json!({}), bob).await;
+    assert_eq!(status, StatusCode::OK, "{}", same);
+    assert_eq!(same["permissions"], redeemed["permissions"]);
+    // Still acting as Bob
+    let (_, info) = app.get("/groups/current/token-info", same["token"].as_str().unwrap()).await;
+    assert_eq!(info["member_id"], members["Bob"].as_str());
+    assert_ne!(same["token"], bob);
+
+    // With the creator, Bob's share link token and this one, the group is full
+    let (status, error) = scope(json!({ "can_delete_group": false }), &token).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(error["error"].as_str().unwrap().contains("active tokens"), "{}", error);
+    let (_, info) = app.get("/groups/current/token-info", same["token"].as_str().unwrap()).await;
+    let path = format!("/groups/current/tokens/{}", info["jti"].as_str().unwrap());
+    assert_eq!(app.request(Method::DELETE, &path, None, Some(&token)).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(app.get("/groups/current", same["token"].as_str().unwrap()).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/groups/current", bob).await.0, StatusCode::OK, "revoking the scoped token leaves its parent alone");
+
+    // Everything off: read-only
+    let none = json!({
+        "can_delete_group": false, "can_manage_members": false, "can_update_payment": false,
+        "can_add_expenses": false, "can_edit_expenses": false, "can_settle": false,
+    });
+    let (status, read_only) = scope(none.clone(), &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", read_only);
+    assert_eq!(read_only["permissions"], none);
+    let read_only = read_only["token"].as_str().unwrap();
+    assert_eq!(app.get("/groups/current/expenses", read_only).await.0, StatusCode::OK);
+    let expense = json!({ "description": "Dinner", "amount": 10.0, "paid_by": members["Alice"] });
+    assert_eq!(app.post("/groups/current/expenses", expense, read_only).await.0, StatusCode::FORBIDDEN);
+    let transfer = json!({ "description": "Payback", "amount": 1.0, "paid_by": members["Bob"], "expense_type": "transfer", "transfer_to": members["Alice"] });
+    assert_eq!(app.post("/groups/current/expenses", transfer, read_only).await.0, StatusCode::FORBIDDEN);
+
+    assert_eq!(scope(json!({ "can_delete_group": "no" }), &token).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = app.request(Method::POST, "/groups/current/scoped-token", Some(json!({})), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn anomalies_need_a_spread_and_use_the_group_currency() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let spend = |amount: f64| json!({ "description": format!("Spend {}", amount), "amount": amount, "paid_by": members["Alice"] });
+    let report = |query: &str| {
+        let (app, token, path) = (&app, &token, format!("/groups/current/stats/anomalies{}", query));
+        async move {
+            let (status, report) = app.get(&path, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", report);
+            report
+        }
+    };
+
+    // One expense, then identical ones: no spread, so nothing stands out even at k = 0
+    app.create_expense(&token, spend(20.0)).await;
+    assert_eq!(report("?k=0").await["anomalies"], json!([]));
+    for _ in 0..4 {
+        app.create_expense(&token, spend(20.0)).await;
+    }
+    let flat = report("?k=0").await;
+    assert_eq!((flat["std_dev"].as_f64(), flat["anomalies"].clone()), (Some(0.0), json!([])));
+
+    // Income is not spending, however large
+    let mut income = spend(10_000.0);
+    income["expense_type"] = json!("income");
+    app.create_expense(&token, income).await;
+    assert_eq!(report("").await["expense_count"], 5);
+
+    // 100 USD at 12.5 is 1250 in the group currency, the 30.00 expense is not an outlier
+    let mut foreign = spend(100.0);
+    foreign["currency"] = json!("USD");
+    foreign["exchange_rate"] = json!(12.5);
+    app.create_expense(&token, foreign).await;
+    app.create_expense(&token, spend(30.0)).await;
+    let flagged = report("").await;
+    let anomalies = flagged["anomalies"].as_array().unwrap();
+    assert_eq!(anomalies.len(), 1, "{}", flagged);
+    assert_eq!(anomalies[0]["amount"], 1250.0);
+    assert_eq!(anomalies[0]["expense"]["amount"], 100.0);
+
+    // k = 0 flags everything strictly above the mean, largest first
+    let everything = report("?k=0").await;
+    let amounts: Vec<f64> = everything["anomalies"].as_array().unwrap().iter().map(|a| a["amount"].as_f64().unwrap()).collect();
+    assert_eq!(amounts, [1250.0]);
+    let mean = everything["mean"].as_f64().unwrap();
+    assert!(mean > 30.0, "the outlier pulls the mean past the 30.00 expense: {}", mean);
+    assert_eq!(everything["threshold"], everything["mean"]);
+
+    for k in ["NaN", "inf", "-0.5"] {
+        let (status, _) = app.get(&format!("/groups/current/stats/anomalies?k={}", k), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "k={}", k);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn activity_pages_are_stable_and_date_bounds_inclusive() {
+    let app = TestApp::spawn().await;
+    let (token, members) = app.create_group(&["Alice", "Bob"]).await;
+    let (other, other_members) = app.create_group(&["Zed"]).await;
+    app.create_expense(&other, json!({ "description": "Elsewhere", "amount": 1.0, "paid_by": other_members["Zed"] })).await;
+    for i in 0..5 {
+        app.create_expense(&token, json!({ "description": format!("Spend {}", i), "amount": 10.0, "paid_by": members["Alice"] })).await;
+    }
+    let activity = |query: &str| {
+        let (app, token, path) = (&app, &token, format!("/groups/current/activity{}", query));
+        async move {
+            let (status, page) = app.get(&path, token).await;
+            assert_eq!(status, StatusCode::OK, "{}", page);
+            page
+        }
+    };
+    let ids = |page: &serde_json::Value| page["entries"].as_array().unwrap().iter().map(|e| e["id"].as_i64().unwrap()).collect::<Vec<_>>();
+
+    // Entries logged in the same instant still page without gaps or repeats
+    app.execute("UPDATE activity_log SET created_at = '2026-03-01 12:00:00+00'").await;
+    let all = ids(&activity("").await);
+    assert_eq!(all.len(), 5, "only this group's entries");
+    let mut paged = Vec::new();
+    for offset in 0..5 {
+        let page = activity(&format!("?limit=1&offset={}", offset)).await;
+        assert_eq!(page["total"], 5);
+        paged.extend(ids(&page));
+    }
+    assert_eq!(paged, all);
+    assert!(all.windows(2).all(|w| w[0] > w[1]));
+    let past_the_end = activity("?offset=5").await;
+    assert_eq!((past_the_end["total"].as_i64(), past_the_end["entries"].clone()), (Some(5), json!([])));
+
+    // Dates are whole UTC days, inclusive at both ends
+    app.execute(&format!("UPDATE activity_log SET created_at = '2026-03-01 23:59:59.999+00' WHERE id = {}", all[0])).await;
+    app.execute(&format!("UPDATE activity_log SET created_at = '2026-03-02 00:00:00+00' WHERE id = {}", all[1])).await;
+    app.execute(&format!("UPDATE activity_log SET created_at = '2026-02-28 23:59:59+00' WHERE id = {}", all[4])).await;
+    assert_eq!(activity("?from=2026-03-01&to=2026-03-01").await["total"], 3);
+    assert_eq!(ids(&activity("?from=2026-03-02").await), [all[1]]);
+    assert_eq!(ids(&activity("?to=2026-02-28").await), [all[4]]);
+    assert_eq!(activity("?from=2026-02-28&to=2026-03-02").await["total"], 5);
+    // Filters and paging combine: the total counts the filtered entries
+    let filtered = activity("?action=expense_created&from=2026-03-01&limit=2").await;
+    assert_eq!((filtered["total"].as_i64(), ids(&filtered).len()), (Some(4), 2));
+    assert_eq!(activity("?action=expense_deleted").await["total"], 0);
+
+    assert_eq!(activity("?limit=200").await["entries"].as_array().unwrap().len(), 5);
+    for query in ["?limit=0", "?limit=201", "?offset=-1", "?from=2026-03-02&to=2026-03-01", "?from=2026-13-01", "?action="] {
+        let (status, _) = app.get(&format!("/groups/current/activity{}", query), &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
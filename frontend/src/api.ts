@@ -5,6 +5,9 @@ export interface Member {
   name: string;
   paypal_email: string | null;
   iban: string | null;
+  preferred_payment_method?: 'paypal' | 'iban' | 'venmo' | null;
+  venmo_handle?: string | null;
+  payment_note?: string | null;
 }
 
 export interface Group {
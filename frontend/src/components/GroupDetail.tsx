@@ -1344,6 +1344,13 @@ export function GroupDetail({ group, token, onGroupUpdated, onGroupDeleted }: Gr
                     <Stack gap={4}>
                       {owes.map((o, i) => {
                         const recipient = getMember(o.id);
+                        // Only show the recipient's preferred method if they have one set up
+                        const preferred = recipient?.preferred_payment_method;
+                        const hasPreferred =
+                          (preferred === 'paypal' && !!recipient?.paypal_email) ||
+                          (preferred === 'iban' && !!recipient?.iban) ||
+                          (preferred === 'venmo' && !!recipient?.venmo_handle);
+                        const showMethod = (method: string) => !hasPreferred || preferred === method;
                         return (
                           <div key={`owe-${i}`}>
                             <MGroup gap="xs">
@@ -1351,7 +1358,7 @@ export function GroupDetail({ group, token, onGroupUpdated, onGroupDeleted }: Gr
                               <Text size="sm" fw={500}>{o.name}</Text>
                               <Text size="sm" fw={600} c="red">{fmtAmt(o.amount, group.currency)}</Text>
                             </MGroup>
-                            {recipient?.paypal_email && (
+                            {recipient?.paypal_email && showMethod('paypal') && (
                               <MGroup gap="xs" ml="md" mt={2}>
                                 <Badge size="xs" color="indigo" variant="filled">PayPal</Badge>
                                 <Anchor
@@ -1366,7 +1373,7 @@ export function GroupDetail({ group, token, onGroupUpdated, onGroupDeleted }: Gr
                                 </Anchor>
                               </MGroup>
                             )}
-                            {recipient?.iban && (
+                            {recipient?.iban && showMethod('iban') && (
                               <MGroup gap="xs" ml="md" mt={2}>
                                 <Badge size="xs" color="gray" variant="light">IBAN</Badge>
                                 <Text size="xs" ff="monospace">{recipient.iban}</Text>
@@ -1381,6 +1388,23 @@ export function GroupDetail({ group, token, onGroupUpdated, onGroupDeleted }: Gr
                                 </CopyButton>
                               </MGroup>
                             )}
+                            {recipient?.venmo_handle && showMethod('venmo') && (
+                              <MGroup gap="xs" ml="md" mt={2}>
+                                <Badge size="xs" color="cyan" variant="filled">Venmo</Badge>
+                                <Anchor
+                                  size="xs"
+                                  fw={600}
+                                  href={`https://venmo.com/${encodeURIComponent(recipient.venmo_handle)}?txn=pay&amount=${o.amount.toFixed(2)}`}
+                                  target="_blank"
+                                  rel="noopener noreferrer"
+                                >
+                                  @{recipient.venmo_handle}
+                                </Anchor>
+                              </MGroup>
+                            )}
+                            {recipient?.payment_note && (
+                              <Text size="xs" c="dimmed" ml="md" mt={2}>{recipient.payment_note}</Text>
+                            )}
                             {otherGroups.length > 0 && (
                               crossGroupTransfer?.fromId === balance.user_id && crossGroupTransfer?.toId === o.id ? (
                                 <Paper p="xs" ml="md" mt={4} withBorder radius="sm" onClick={(e: React.MouseEvent) => e.stopPropagation()}>